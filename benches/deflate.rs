@@ -0,0 +1,61 @@
+use bencher::{benchmark_group, benchmark_main};
+
+use std::io::{Cursor, Read, Write};
+
+use bencher::Bencher;
+use zip::{write::SimpleFileOptions, CompressionMethod, ZipArchive, ZipWriter};
+
+// Deflate goes through whichever flate2 backend the enabled features select. Compare backends by
+// running this with e.g. `--no-default-features --features deflate` (miniz_oxide) and then with
+// `--no-default-features --features deflate-zlib-ng`.
+
+const SIZE: usize = 1024 * 1024;
+
+fn generate_text() -> Vec<u8> {
+    (0u64..)
+        .flat_map(|i| format!("line {i}: {}\n", i * i % 9973).into_bytes())
+        .take(SIZE)
+        .collect()
+}
+
+fn write_deflated(contents: &[u8]) -> Vec<u8> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    writer
+        .start_file(
+            "text.txt",
+            SimpleFileOptions::default()
+                .compression_method(CompressionMethod::DEFLATE)
+                .compression_level(Some(6)),
+        )
+        .unwrap();
+    writer.write_all(contents).unwrap();
+    writer.finish().unwrap().into_inner()
+}
+
+fn compress_deflate(bench: &mut Bencher) {
+    let contents = generate_text();
+
+    bench.iter(|| write_deflated(&contents));
+
+    bench.bytes = SIZE as u64;
+}
+
+fn decompress_deflate(bench: &mut Bencher) {
+    let bytes = write_deflated(&generate_text());
+    let mut archive = ZipArchive::new(Cursor::new(bytes.as_slice())).unwrap();
+
+    bench.iter(|| {
+        let mut contents = Vec::with_capacity(SIZE);
+        archive
+            .by_name("text.txt")
+            .unwrap()
+            .read_to_end(&mut contents)
+            .unwrap();
+        contents
+    });
+
+    bench.bytes = SIZE as u64;
+}
+
+benchmark_group!(benches, compress_deflate, decompress_deflate);
+benchmark_main!(benches);
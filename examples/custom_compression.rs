@@ -0,0 +1,54 @@
+//! Writes and reads back an archive whose file is "compressed" by a codec defined here, which
+//! copies the data unchanged but stores it under a compression method ID of its own.
+
+use std::io::{self, Read, Write};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionStream, Compressor, Decompressor, ZipArchive, ZipWriter};
+
+/// A compression method ID that no real method uses
+const IDENTITY_METHOD: u16 = 0xC0DE;
+
+struct Identity;
+
+impl Compressor for Identity {
+    fn start(&self, _level: Option<i64>) -> io::Result<Box<dyn CompressionStream>> {
+        Ok(Box::new(IdentityStream))
+    }
+}
+
+struct IdentityStream;
+
+impl CompressionStream for IdentityStream {
+    fn compress(&mut self, input: &[u8], output: &mut dyn Write) -> io::Result<()> {
+        output.write_all(input)
+    }
+
+    fn finish(self: Box<Self>, _output: &mut dyn Write) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Decompressor for Identity {
+    fn decompress<'a>(&self, compressed: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(compressed)
+    }
+}
+
+fn main() -> zip::result::ZipResult<()> {
+    let options = SimpleFileOptions::default().with_custom_compressor(IDENTITY_METHOD, &Identity);
+    let mut zip = ZipWriter::new(io::Cursor::new(Vec::new()));
+    zip.start_file("hello.txt", options)?;
+    zip.write_all(b"Hello, World!\n")?;
+    let bytes = zip.finish()?.into_inner();
+
+    let mut archive = ZipArchive::new(io::Cursor::new(bytes))?;
+    // Without a decompressor for its method, the file can't be read
+    assert!(archive.by_name("hello.txt").is_err());
+    archive.register_decompressor(IDENTITY_METHOD, Identity);
+    let mut contents = String::new();
+    archive
+        .by_name("hello.txt")?
+        .read_to_string(&mut contents)?;
+    println!("hello.txt reads back as {contents:?}");
+    Ok(())
+}
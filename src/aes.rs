@@ -12,14 +12,22 @@ use hmac::{Hmac, Mac};
 use rand::RngCore;
 use sha1::Sha1;
 use std::io::{self, Error, ErrorKind, Read, Write};
-use zeroize::{Zeroize, Zeroizing};
+use zeroize::Zeroizing;
 
 /// The length of the password verifcation value in bytes
 const PWD_VERIFY_LENGTH: usize = 2;
 /// The length of the authentication code in bytes
 const AUTH_CODE_LENGTH: usize = 10;
-/// The number of iterations used with PBKDF2
-const ITERATION_COUNT: u32 = 1000;
+/// The number of iterations used with PBKDF2 by the WinZip AES specification
+pub(crate) const DEFAULT_ITERATION_COUNT: u32 = 1000;
+/// The most iterations that [`ZipArchive`](crate::ZipArchive) will run when the count comes from
+/// the archive rather than the caller, so that a hostile archive can't make decryption take
+/// arbitrarily long
+pub(crate) const MAX_RECORDED_ITERATION_COUNT: u32 = 1_000_000;
+
+/// The most data that [`AesWriter`] encrypts at once, which bounds the memory it uses however
+/// much is written in one call
+const WRITE_BUFFER_SIZE: usize = 8192;
 
 enum Cipher {
     Aes128(Box<aes_ctr::AesCtrZipKeyStream<aes_ctr::Aes128>>),
@@ -64,10 +72,16 @@ pub struct AesReader<R> {
     reader: R,
     aes_mode: AesMode,
     data_length: u64,
+    iterations: u32,
 }
 
 impl<R: Read> AesReader<R> {
-    pub const fn new(reader: R, aes_mode: AesMode, compressed_size: u64) -> AesReader<R> {
+    pub const fn new(
+        reader: R,
+        aes_mode: AesMode,
+        compressed_size: u64,
+        iterations: u32,
+    ) -> AesReader<R> {
         let data_length = compressed_size
             - (PWD_VERIFY_LENGTH + AUTH_CODE_LENGTH + aes_mode.salt_length()) as u64;
 
@@ -75,16 +89,18 @@ impl<R: Read> AesReader<R> {
             reader,
             aes_mode,
             data_length,
+            iterations,
         }
     }
 
-    /// Read the AES header bytes and validate the password.
+    /// Read the AES header bytes and validate each of `passwords` against them, keeping the first
+    /// that passes, or fail with [`ZipError::InvalidPassword`] if none does.
     ///
     /// Even if the validation succeeds, there is still a 1 in 65536 chance that an incorrect
     /// password was provided.
     /// It isn't possible to check the authentication code in this step. This will be done after
     /// reading and decrypting the file.
-    pub fn validate(mut self, password: &[u8]) -> Result<AesReaderValid<R>, ZipError> {
+    pub fn validate(mut self, passwords: &[&[u8]]) -> Result<AesReaderValid<R>, ZipError> {
         let salt_length = self.aes_mode.salt_length();
         let key_length = self.aes_mode.key_length();
 
@@ -100,29 +116,32 @@ impl<R: Read> AesReader<R> {
         let derived_key_len = 2 * key_length + PWD_VERIFY_LENGTH;
         let mut derived_key: Box<[u8]> = vec![0; derived_key_len].into_boxed_slice();
 
-        // use PBKDF2 with HMAC-Sha1 to derive the key
-        pbkdf2::pbkdf2::<Hmac<Sha1>>(password, &salt, ITERATION_COUNT, &mut derived_key)
-            .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
-        let decrypt_key = &derived_key[0..key_length];
-        let hmac_key = &derived_key[key_length..key_length * 2];
-        let pwd_verify = &derived_key[derived_key_len - 2..];
+        for password in passwords {
+            // use PBKDF2 with HMAC-Sha1 to derive the key
+            pbkdf2::pbkdf2::<Hmac<Sha1>>(password, &salt, self.iterations, &mut derived_key)
+                .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
+            let decrypt_key = &derived_key[0..key_length];
+            let hmac_key = &derived_key[key_length..key_length * 2];
+            let pwd_verify = &derived_key[derived_key_len - 2..];
+
+            // the last 2 bytes should equal the password verification value
+            if pwd_verification_value != pwd_verify {
+                // wrong password
+                continue;
+            }
 
-        // the last 2 bytes should equal the password verification value
-        if pwd_verification_value != pwd_verify {
-            // wrong password
-            return Err(ZipError::InvalidPassword);
-        }
+            let cipher = Cipher::from_mode(self.aes_mode, decrypt_key);
+            let hmac = Hmac::<Sha1>::new_from_slice(hmac_key).unwrap();
 
-        let cipher = Cipher::from_mode(self.aes_mode, decrypt_key);
-        let hmac = Hmac::<Sha1>::new_from_slice(hmac_key).unwrap();
-
-        Ok(AesReaderValid {
-            reader: self.reader,
-            data_remaining: self.data_length,
-            cipher,
-            hmac,
-            finalized: false,
-        })
+            return Ok(AesReaderValid {
+                reader: self.reader,
+                data_remaining: self.data_length,
+                cipher,
+                hmac,
+                finalized: false,
+            });
+        }
+        Err(ZipError::InvalidPassword)
     }
 }
 
@@ -158,6 +177,13 @@ impl<R: Read> Read for AesReaderValid<R> {
         // 2^32 bytes even on 32 bit systems.
         let bytes_to_read = self.data_remaining.min(buf.len() as u64) as usize;
         let read = self.reader.read(&mut buf[0..bytes_to_read])?;
+        if read == 0 && bytes_to_read > 0 {
+            // Otherwise the truncated data would look complete without being authenticated
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "AES encrypted data ended before its authentication code",
+            ));
+        }
         self.data_remaining -= read as u64;
 
         // Update the hmac with the encrypted data
@@ -206,12 +232,13 @@ pub struct AesWriter<W> {
     writer: W,
     cipher: Cipher,
     hmac: Hmac<Sha1>,
-    buffer: Zeroizing<Vec<u8>>,
+    /// Where each chunk is encrypted before it's written, so the caller's data isn't modified
+    buffer: Box<[u8]>,
     encrypted_file_header: Option<Vec<u8>>,
 }
 
 impl<W: Write> AesWriter<W> {
-    pub fn new(writer: W, aes_mode: AesMode, password: &[u8]) -> io::Result<Self> {
+    pub fn new(writer: W, aes_mode: AesMode, password: &[u8], iterations: u32) -> io::Result<Self> {
         let salt_length = aes_mode.salt_length();
         let key_length = aes_mode.key_length();
 
@@ -226,7 +253,7 @@ impl<W: Write> AesWriter<W> {
         let mut derived_key: Zeroizing<Vec<u8>> = Zeroizing::new(vec![0; derived_key_len]);
 
         // Use PBKDF2 with HMAC-Sha1 to derive the key.
-        pbkdf2::pbkdf2::<Hmac<Sha1>>(password, &salt, ITERATION_COUNT, &mut derived_key)
+        pbkdf2::pbkdf2::<Hmac<Sha1>>(password, &salt, iterations, &mut derived_key)
             .map_err(|e| Error::new(ErrorKind::InvalidInput, e))?;
         let encryption_key = &derived_key[0..key_length];
         let hmac_key = &derived_key[key_length..key_length * 2];
@@ -241,20 +268,24 @@ impl<W: Write> AesWriter<W> {
             writer,
             cipher,
             hmac,
-            buffer: Default::default(),
+            buffer: vec![0; WRITE_BUFFER_SIZE].into_boxed_slice(),
             encrypted_file_header: Some(encrypted_file_header),
         })
     }
 
-    pub fn finish(mut self) -> io::Result<W> {
+    /// Writes the authentication code that ends the file's data. The writer is kept rather than
+    /// returned, so that it isn't lost if this fails.
+    pub fn finish(&mut self) -> io::Result<()> {
         self.write_encrypted_file_header()?;
 
         // Zip uses HMAC-Sha1-80, which only uses the first half of the hash
         // see https://www.winzip.com/win/en/aes_info.html#auth-faq
         let computed_auth_code = &self.hmac.finalize_reset().into_bytes()[0..AUTH_CODE_LENGTH];
-        self.writer.write_all(computed_auth_code)?;
+        self.writer.write_all(computed_auth_code)
+    }
 
-        Ok(self.writer)
+    pub fn into_inner(self) -> W {
+        self.writer
     }
 
     /// The AES encryption specification requires some metadata being written at the start of the
@@ -273,24 +304,22 @@ impl<W: Write> Write for AesWriter<W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         self.write_encrypted_file_header()?;
 
-        // Fill the internal buffer and encrypt it in-place.
-        self.buffer.extend_from_slice(buf);
-        self.cipher.crypt_in_place(&mut self.buffer[..]);
+        // Copy at most one buffer's worth and encrypt it in-place, leaving the rest for the next
+        // call, as `write_all` makes.
+        let len = buf.len().min(self.buffer.len());
+        let chunk = &mut self.buffer[..len];
+        chunk.copy_from_slice(&buf[..len]);
+        self.cipher.crypt_in_place(chunk);
 
         // Update the hmac with the encrypted data.
-        self.hmac.update(&self.buffer[..]);
+        self.hmac.update(chunk);
 
-        // Write the encrypted buffer to the inner writer.  We need to use `write_all` here as if
+        // Write the encrypted chunk to the inner writer.  We need to use `write_all` here as if
         // we only write parts of the data we can't easily reverse the keystream in the cipher
-        // implementation.
-        self.writer.write_all(&self.buffer[..])?;
-
-        // Zeroize the backing memory before clearing the buffer to prevent cleartext data from
-        // being left in memory.
-        self.buffer.zeroize();
-        self.buffer.clear();
+        // implementation. The buffer now only holds encrypted data, so it needn't be zeroized.
+        self.writer.write_all(chunk)?;
 
-        Ok(buf.len())
+        Ok(len)
     }
 
     fn flush(&mut self) -> io::Result<()> {
@@ -303,7 +332,7 @@ mod tests {
     use std::io::{self, Read, Write};
 
     use crate::{
-        aes::{AesReader, AesWriter},
+        aes::{AesReader, AesWriter, DEFAULT_ITERATION_COUNT, WRITE_BUFFER_SIZE},
         result::ZipError,
         types::AesMode,
     };
@@ -314,7 +343,7 @@ mod tests {
         let mut read_buffer = vec![];
 
         {
-            let mut writer = AesWriter::new(&mut buf, aes_mode, password)?;
+            let mut writer = AesWriter::new(&mut buf, aes_mode, password, DEFAULT_ITERATION_COUNT)?;
             writer.write_all(plaintext)?;
             writer.finish()?;
         }
@@ -324,14 +353,69 @@ mod tests {
 
         {
             let compressed_length = buf.get_ref().len() as u64;
-            let mut reader =
-                AesReader::new(&mut buf, aes_mode, compressed_length).validate(password)?;
+            let mut reader = AesReader::new(
+                &mut buf,
+                aes_mode,
+                compressed_length,
+                DEFAULT_ITERATION_COUNT,
+            )
+            .validate(&[password])?;
             reader.read_to_end(&mut read_buffer)?;
         }
 
         Ok(plaintext == read_buffer)
     }
 
+    #[test]
+    fn large_writes_are_chunked() -> Result<(), ZipError> {
+        let plaintext: Vec<u8> = (0..3 * WRITE_BUFFER_SIZE + 5).map(|i| i as u8).collect();
+        let password = b"some super secret password";
+        let mut writer = AesWriter::new(
+            io::Cursor::new(vec![]),
+            AesMode::Aes256,
+            password,
+            DEFAULT_ITERATION_COUNT,
+        )?;
+        assert_eq!(writer.write(&plaintext)?, WRITE_BUFFER_SIZE);
+        writer.write_all(&plaintext[WRITE_BUFFER_SIZE..])?;
+        assert_eq!(writer.buffer.len(), WRITE_BUFFER_SIZE);
+        writer.finish()?;
+        let encrypted = writer.into_inner().into_inner();
+        assert_eq!(encrypted.len(), 16 + 2 + plaintext.len() + 10);
+
+        // The HMAC is checked incrementally however the data is read
+        let open = |encrypted: &[u8]| {
+            AesReader::new(
+                io::Cursor::new(encrypted.to_vec()),
+                AesMode::Aes256,
+                encrypted.len() as u64,
+                DEFAULT_ITERATION_COUNT,
+            )
+            .validate(&[password])
+        };
+        let mut reader = open(&encrypted)?;
+        let mut decrypted = vec![];
+        let mut buf = [0; 1000];
+        loop {
+            let read = reader.read(&mut buf)?;
+            if read == 0 {
+                break;
+            }
+            decrypted.extend_from_slice(&buf[..read]);
+        }
+        assert_eq!(decrypted, plaintext);
+
+        let mut tampered = encrypted.clone();
+        tampered[100] ^= 1;
+        assert!(open(&tampered)?.read_to_end(&mut vec![]).is_err());
+        // Claiming the whole length, so that the data runs out before the authentication code
+        let mut truncated = open(&encrypted)?;
+        truncated.reader.get_mut().truncate(encrypted.len() - 20);
+        let error = truncated.read_to_end(&mut vec![]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::UnexpectedEof);
+        Ok(())
+    }
+
     #[test]
     fn crypt_aes_256_0_byte() {
         let plaintext = &[];
@@ -1,6 +1,7 @@
 //! Possible ZIP compression methods.
 
 use std::fmt;
+use std::io::{self, Read, Write};
 
 #[allow(deprecated)]
 /// Identifies the storage format used to compress a file within a ZIP archive.
@@ -38,6 +39,34 @@ pub enum CompressionMethod {
     /// Compress the file using LZMA
     #[cfg(feature = "lzma")]
     Lzma,
+    /// Compress the file using Brotli
+    ///
+    /// Brotli isn't one of the methods defined by the ZIP specification. This crate stores it under
+    /// method ID 121, which some other tools use for it too, but stock ZIP tools such as Info-ZIP,
+    /// 7-Zip and the ones built into operating systems won't be able to decompress these files.
+    #[cfg(feature = "brotli")]
+    Brotli,
+    /// The file is compressed using Shrink, an LZW variant from PKZIP 1.x
+    ///
+    /// Only decompression is supported.
+    #[cfg(feature = "legacy")]
+    Shrink,
+    /// The file is compressed using Implode, a sliding window with Shannon-Fano coding from PKZIP
+    /// 1.x
+    ///
+    /// Only decompression is supported.
+    #[cfg(feature = "legacy")]
+    Implode,
+    /// Compress the file using PPMd
+    ///
+    /// Only decompression is supported.
+    #[cfg(feature = "ppmd")]
+    Ppmd,
+    /// Compress the file using XZ, which wraps LZMA2 in the `.xz` container format
+    ///
+    /// Only decompression is supported.
+    #[cfg(feature = "xz")]
+    Xz,
     /// Unsupported compression method
     #[cfg_attr(
         not(fuzzing),
@@ -49,11 +78,17 @@ pub enum CompressionMethod {
 /// All compression methods defined for the ZIP format
 impl CompressionMethod {
     pub const STORE: Self = CompressionMethod::Stored;
+    #[cfg(feature = "legacy")]
+    pub const SHRINK: Self = CompressionMethod::Shrink;
+    #[cfg(not(feature = "legacy"))]
     pub const SHRINK: Self = CompressionMethod::Unsupported(1);
     pub const REDUCE_1: Self = CompressionMethod::Unsupported(2);
     pub const REDUCE_2: Self = CompressionMethod::Unsupported(3);
     pub const REDUCE_3: Self = CompressionMethod::Unsupported(4);
     pub const REDUCE_4: Self = CompressionMethod::Unsupported(5);
+    #[cfg(feature = "legacy")]
+    pub const IMPLODE: Self = CompressionMethod::Implode;
+    #[cfg(not(feature = "legacy"))]
     pub const IMPLODE: Self = CompressionMethod::Unsupported(6);
     #[cfg(feature = "_deflate-any")]
     pub const DEFLATE: Self = CompressionMethod::Deflated;
@@ -80,14 +115,26 @@ impl CompressionMethod {
     #[cfg(not(feature = "zstd"))]
     pub const ZSTD: Self = CompressionMethod::Unsupported(93);
     pub const MP3: Self = CompressionMethod::Unsupported(94);
+    #[cfg(feature = "xz")]
+    pub const XZ: Self = CompressionMethod::Xz;
+    #[cfg(not(feature = "xz"))]
     pub const XZ: Self = CompressionMethod::Unsupported(95);
     pub const JPEG: Self = CompressionMethod::Unsupported(96);
     pub const WAVPACK: Self = CompressionMethod::Unsupported(97);
+    #[cfg(feature = "ppmd")]
+    pub const PPMD: Self = CompressionMethod::Ppmd;
+    #[cfg(not(feature = "ppmd"))]
     pub const PPMD: Self = CompressionMethod::Unsupported(98);
     #[cfg(feature = "aes-crypto")]
     pub const AES: Self = CompressionMethod::Aes;
     #[cfg(not(feature = "aes-crypto"))]
     pub const AES: Self = CompressionMethod::Unsupported(99);
+    /// Not defined by the ZIP specification; see [`CompressionMethod::Brotli`]
+    #[cfg(feature = "brotli")]
+    pub const BROTLI: Self = CompressionMethod::Brotli;
+    /// Not defined by the ZIP specification; see [`CompressionMethod::Brotli`]
+    #[cfg(not(feature = "brotli"))]
+    pub const BROTLI: Self = CompressionMethod::Unsupported(121);
 }
 impl CompressionMethod {
     /// Converts an u16 to its corresponding CompressionMethod
@@ -99,6 +146,10 @@ impl CompressionMethod {
         #[allow(deprecated)]
         match val {
             0 => CompressionMethod::Stored,
+            #[cfg(feature = "legacy")]
+            1 => CompressionMethod::Shrink,
+            #[cfg(feature = "legacy")]
+            6 => CompressionMethod::Implode,
             #[cfg(feature = "_deflate-any")]
             8 => CompressionMethod::Deflated,
             #[cfg(feature = "deflate64")]
@@ -109,8 +160,14 @@ impl CompressionMethod {
             14 => CompressionMethod::Lzma,
             #[cfg(feature = "zstd")]
             93 => CompressionMethod::Zstd,
+            #[cfg(feature = "xz")]
+            95 => CompressionMethod::Xz,
+            #[cfg(feature = "ppmd")]
+            98 => CompressionMethod::Ppmd,
             #[cfg(feature = "aes-crypto")]
             99 => CompressionMethod::Aes,
+            #[cfg(feature = "brotli")]
+            121 => CompressionMethod::Brotli,
 
             v => CompressionMethod::Unsupported(v),
         }
@@ -125,6 +182,10 @@ impl CompressionMethod {
         #[allow(deprecated)]
         match self {
             CompressionMethod::Stored => 0,
+            #[cfg(feature = "legacy")]
+            CompressionMethod::Shrink => 1,
+            #[cfg(feature = "legacy")]
+            CompressionMethod::Implode => 6,
             #[cfg(feature = "_deflate-any")]
             CompressionMethod::Deflated => 8,
             #[cfg(feature = "deflate64")]
@@ -137,6 +198,12 @@ impl CompressionMethod {
             CompressionMethod::Zstd => 93,
             #[cfg(feature = "lzma")]
             CompressionMethod::Lzma => 14,
+            #[cfg(feature = "xz")]
+            CompressionMethod::Xz => 95,
+            #[cfg(feature = "ppmd")]
+            CompressionMethod::Ppmd => 98,
+            #[cfg(feature = "brotli")]
+            CompressionMethod::Brotli => 121,
 
             CompressionMethod::Unsupported(v) => v,
         }
@@ -171,8 +238,97 @@ pub const SUPPORTED_COMPRESSION_METHODS: &[CompressionMethod] = &[
     CompressionMethod::Bzip2,
     #[cfg(feature = "zstd")]
     CompressionMethod::Zstd,
+    #[cfg(feature = "brotli")]
+    CompressionMethod::Brotli,
 ];
 
+/// Compresses files with a method this crate doesn't implement, for
+/// [`FileOptions::with_custom_compressor`](crate::write::FileOptions::with_custom_compressor)
+///
+/// ```
+/// # fn main() -> zip::result::ZipResult<()> {
+/// use std::io::{self, Read, Write};
+/// use zip::write::SimpleFileOptions;
+/// use zip::{CompressionStream, Compressor, Decompressor};
+///
+/// /// Stores each byte plus one, under method ID 0xC0DE
+/// struct AddOne;
+///
+/// struct AddOneStream;
+///
+/// impl Compressor for AddOne {
+///     fn start(&self, _level: Option<i64>) -> io::Result<Box<dyn CompressionStream>> {
+///         Ok(Box::new(AddOneStream))
+///     }
+/// }
+///
+/// impl CompressionStream for AddOneStream {
+///     fn compress(&mut self, input: &[u8], output: &mut dyn Write) -> io::Result<()> {
+///         let added: Vec<u8> = input.iter().map(|byte| byte.wrapping_add(1)).collect();
+///         output.write_all(&added)
+///     }
+///
+///     fn finish(self: Box<Self>, _output: &mut dyn Write) -> io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// impl Decompressor for AddOne {
+///     fn decompress<'a>(&self, compressed: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+///         Ok(Box::new(SubtractOne(compressed)))
+///     }
+/// }
+///
+/// struct SubtractOne<'a>(Box<dyn Read + 'a>);
+///
+/// impl Read for SubtractOne<'_> {
+///     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+///         let count = self.0.read(buf)?;
+///         buf[..count].iter_mut().for_each(|byte| *byte = byte.wrapping_sub(1));
+///         Ok(count)
+///     }
+/// }
+///
+/// let mut zip = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+/// zip.start_file("a.txt", SimpleFileOptions::default().with_custom_compressor(0xC0DE, &AddOne))?;
+/// zip.write_all(b"Hello")?;
+/// let mut archive = zip.finish_into_readable()?;
+/// archive.register_decompressor(0xC0DE, AddOne);
+/// let mut contents = String::new();
+/// archive.by_name("a.txt")?.read_to_string(&mut contents)?;
+/// assert_eq!(contents, "Hello");
+/// # Ok(())
+/// # }
+/// ```
+pub trait Compressor: Send + Sync {
+    /// Start compressing a file, at the level set with
+    /// [`FileOptions::compression_level`](crate::write::FileOptions::compression_level) if any
+    fn start(&self, level: Option<i64>) -> io::Result<Box<dyn CompressionStream>>;
+}
+
+/// The state of a [`Compressor`] while it compresses one file
+pub trait CompressionStream: Send {
+    /// Compress the next part of the file, writing any output that's ready to `output`
+    fn compress(&mut self, input: &[u8], output: &mut dyn Write) -> io::Result<()>;
+
+    /// Write the rest of the output to `output`, once the whole file has been passed to
+    /// [`CompressionStream::compress`]
+    fn finish(self: Box<Self>, output: &mut dyn Write) -> io::Result<()>;
+}
+
+/// Decompresses files with a method this crate doesn't implement, for
+/// [`ZipArchive::register_decompressor`](crate::ZipArchive::register_decompressor)
+///
+/// See [`Compressor`] for an example.
+pub trait Decompressor: Send + Sync {
+    /// Wrap `compressed`, which reads the file's data as stored in the archive, in a reader that
+    /// returns it decompressed
+    ///
+    /// The data has already been decrypted if the file was encrypted, and the CRC-32 of what the
+    /// returned reader reads is checked as for any other method.
+    fn decompress<'a>(&self, compressed: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>>;
+}
+
 #[cfg(test)]
 mod test {
     use super::{CompressionMethod, SUPPORTED_COMPRESSION_METHODS};
@@ -36,6 +36,18 @@ impl FromCp437 for Box<[u8]> {
     }
 }
 
+/// Encodes a string in IBM codepage 437, or returns `None` if it has a character that codepage 437
+/// can't represent.
+pub(crate) fn to_cp437(input: &str) -> Option<Box<[u8]>> {
+    input
+        .chars()
+        .map(|c| match u8::try_from(c) {
+            Ok(ascii @ 0x00..=0x7f) => Some(ascii),
+            _ => (0x80..=0xff).find(|&byte| to_char(byte) == c),
+        })
+        .collect()
+}
+
 fn to_char(input: u8) -> char {
     let output = match input {
         0x00..=0x7f => input as u32,
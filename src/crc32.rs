@@ -5,30 +5,42 @@ use std::io::prelude::*;
 
 use crc32fast::Hasher;
 
+use crate::result::ZipError;
+
 /// Reader that validates the CRC32 when it reaches the EOF.
 pub struct Crc32Reader<R> {
     inner: R,
     hasher: Hasher,
     check: u32,
-    /// Signals if `inner` stores aes encrypted data.
-    /// AE-2 encrypted data doesn't use crc and sets the value to 0.
-    ae2_encrypted: bool,
+    /// Whether the checksum is validated at all. This is false for AE-2 encrypted data, which
+    /// doesn't use crc and sets the value to 0, and when the caller has opted out.
+    enabled: bool,
 }
 
 impl<R> Crc32Reader<R> {
     /// Get a new Crc32Reader which checks the inner reader against checksum.
-    /// The check is disabled if `ae2_encrypted == true`.
-    pub(crate) fn new(inner: R, checksum: u32, ae2_encrypted: bool) -> Crc32Reader<R> {
+    /// The check is skipped if `enabled == false`.
+    pub(crate) fn new(inner: R, checksum: u32, enabled: bool) -> Crc32Reader<R> {
         Crc32Reader {
             inner,
             hasher: Hasher::new(),
             check: checksum,
-            ae2_encrypted,
+            enabled,
         }
     }
 
-    fn check_matches(&self) -> bool {
-        self.check == self.hasher.clone().finalize()
+    fn checksum_error(&self) -> Option<io::Error> {
+        let actual = self.hasher.clone().finalize();
+        if !self.enabled || self.check == actual {
+            return None;
+        }
+        Some(
+            ZipError::InvalidChecksum {
+                expected: self.check,
+                actual,
+            }
+            .into(),
+        )
     }
 
     pub fn into_inner(self) -> R {
@@ -38,20 +50,39 @@ impl<R> Crc32Reader<R> {
 
 impl<R: Read> Read for Crc32Reader<R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let invalid_check = !buf.is_empty() && !self.check_matches() && !self.ae2_encrypted;
-
-        let count = match self.inner.read(buf) {
-            Ok(0) if invalid_check => {
-                return Err(io::Error::new(io::ErrorKind::Other, "Invalid checksum"))
+        let count = self.inner.read(buf)?;
+        if count == 0 && !buf.is_empty() {
+            if let Some(err) = self.checksum_error() {
+                return Err(err);
             }
-            Ok(n) => n,
-            Err(e) => return Err(e),
-        };
+        }
         self.hasher.update(&buf[0..count]);
         Ok(count)
     }
 }
 
+#[cfg(feature = "tokio")]
+impl<R: tokio::io::AsyncRead + Unpin> tokio::io::AsyncRead for Crc32Reader<R> {
+    fn poll_read(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<io::Result<()>> {
+        let wants_data = buf.remaining() > 0;
+        let start = buf.filled().len();
+
+        std::task::ready!(std::pin::Pin::new(&mut self.inner).poll_read(cx, buf))?;
+        let read = &buf.filled()[start..];
+        if read.is_empty() && wants_data {
+            if let Some(err) = self.checksum_error() {
+                return std::task::Poll::Ready(Err(err));
+            }
+        }
+        self.hasher.update(read);
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -61,15 +92,18 @@ mod test {
         let data: &[u8] = b"";
         let mut buf = [0; 1];
 
-        let mut reader = Crc32Reader::new(data, 0, false);
+        let mut reader = Crc32Reader::new(data, 0, true);
         assert_eq!(reader.read(&mut buf).unwrap(), 0);
 
-        let mut reader = Crc32Reader::new(data, 1, false);
+        let mut reader = Crc32Reader::new(data, 1, true);
         assert!(reader
             .read(&mut buf)
             .unwrap_err()
             .to_string()
             .contains("Invalid checksum"));
+
+        let mut reader = Crc32Reader::new(data, 1, false);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
     }
 
     #[test]
@@ -77,7 +111,7 @@ mod test {
         let data: &[u8] = b"1234";
         let mut buf = [0; 1];
 
-        let mut reader = Crc32Reader::new(data, 0x9be3e0a3, false);
+        let mut reader = Crc32Reader::new(data, 0x9be3e0a3, true);
         assert_eq!(reader.read(&mut buf).unwrap(), 1);
         assert_eq!(reader.read(&mut buf).unwrap(), 1);
         assert_eq!(reader.read(&mut buf).unwrap(), 1);
@@ -92,8 +126,50 @@ mod test {
         let data: &[u8] = b"1234";
         let mut buf = [0; 5];
 
-        let mut reader = Crc32Reader::new(data, 0x9be3e0a3, false);
+        let mut reader = Crc32Reader::new(data, 0x9be3e0a3, true);
         assert_eq!(reader.read(&mut buf[..0]).unwrap(), 0);
         assert_eq!(reader.read(&mut buf).unwrap(), 4);
     }
+
+    /// The bitwise CRC-32 from the ZIP specification, to check the hasher against whichever
+    /// implementation it picked
+    fn reference_crc32(data: &[u8]) -> u32 {
+        let mut crc = !0u32;
+        for &byte in data {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+            }
+        }
+        !crc
+    }
+
+    #[test]
+    fn matches_reference() {
+        assert_eq!(reference_crc32(b"123456789"), 0xcbf4_3926);
+        let mut data = vec![0; 70_000];
+        getrandom::getrandom(&mut data).unwrap();
+        // Whether `Hasher::new` picks a SIMD implementation depends on this CPU and the
+        // `crc-simd` feature, so it's checked across lengths and alignments, fed in chunks
+        for (start, len) in [
+            (0, 0),
+            (1, 1),
+            (3, 15),
+            (0, 64),
+            (7, 129),
+            (5, 4099),
+            (0, 70_000),
+        ] {
+            let data = &data[start..start + len];
+            let expected = reference_crc32(data);
+            let mut hasher = Hasher::new();
+            for chunk in data.chunks(1000) {
+                hasher.update(chunk);
+            }
+            assert_eq!(hasher.finalize(), expected, "{start}..+{len}");
+
+            let mut reader = Crc32Reader::new(data, expected, true);
+            io::copy(&mut reader, &mut io::sink()).unwrap();
+        }
+    }
 }
@@ -1,10 +1,10 @@
 use crate::result::{ZipError, ZipResult};
-use crate::unstable::LittleEndianReadExt;
+use crate::unstable::{LittleEndianReadExt, LittleEndianWriteExt};
 use std::io::Read;
 
 /// extended timestamp, as described in <https://libzip.org/specifications/extrafld.txt>
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Copy)]
 pub struct ExtendedTimestamp {
     mod_time: Option<u32>,
     ac_time: Option<u32>,
@@ -12,6 +12,57 @@ pub struct ExtendedTimestamp {
 }
 
 impl ExtendedTimestamp {
+    /// The header ID of this extra field
+    pub(crate) const HEADER_ID: u16 = 0x5455;
+
+    pub(crate) const fn new(mod_time: u32, ac_time: Option<u32>, cr_time: Option<u32>) -> Self {
+        Self {
+            mod_time: Some(mod_time),
+            ac_time,
+            cr_time,
+        }
+    }
+
+    fn flags(&self) -> u8 {
+        self.mod_time.is_some() as u8
+            | (self.ac_time.is_some() as u8) << 1
+            | (self.cr_time.is_some() as u8) << 2
+    }
+
+    /// Serializes the field with its header as it appears in a local header, which holds every
+    /// timestamp indicated by the flags.
+    pub(crate) fn local_header_bytes(&self) -> Vec<u8> {
+        let times = [self.mod_time, self.ac_time, self.cr_time];
+        let mut out = Vec::with_capacity(17);
+        out.write_u16_le(Self::HEADER_ID).unwrap();
+        out.write_u16_le(1 + 4 * self.flags().count_ones() as u16)
+            .unwrap();
+        out.push(self.flags());
+        for time in times.into_iter().flatten() {
+            out.write_u32_le(time).unwrap();
+        }
+        out
+    }
+
+    /// Serializes the field with its header as it appears in the central directory, which keeps
+    /// the local header's flags but only ever holds the modification time.
+    pub(crate) fn central_header_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(9);
+        out.write_u16_le(Self::HEADER_ID).unwrap();
+        match self.mod_time {
+            Some(mod_time) => {
+                out.write_u16_le(5).unwrap();
+                out.push(self.flags());
+                out.write_u32_le(mod_time).unwrap();
+            }
+            None => {
+                out.write_u16_le(1).unwrap();
+                out.push(self.flags());
+            }
+        }
+        out
+    }
+
     /// creates an extended timestamp struct by reading the required bytes from the reader.
     ///
     /// This method assumes that the length has already been read, therefore
@@ -17,12 +17,30 @@ impl ExtraFieldVersion for LocalHeaderVersion {}
 impl ExtraFieldVersion for CentralHeaderVersion {}
 
 mod extended_timestamp;
+mod ntfs;
+mod raw;
+mod strong_encryption;
+mod unix_ownership;
 
 pub use extended_timestamp::*;
+pub use ntfs::*;
+pub use raw::*;
+pub use strong_encryption::*;
+pub use unix_ownership::*;
 
 /// contains one extra field
 #[derive(Debug, Clone)]
+#[non_exhaustive]
 pub enum ExtraField {
     /// extended timestamp, as described in <https://libzip.org/specifications/extrafld.txt>
     ExtendedTimestamp(ExtendedTimestamp),
+
+    /// NTFS timestamps, as described in <https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT>
+    Ntfs(Ntfs),
+
+    /// PKWARE strong encryption header, as described in <https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT>
+    StrongEncryption(StrongEncryption),
+
+    /// Unix owner and group IDs, as described in <https://libzip.org/specifications/extrafld.txt>
+    UnixOwnership(UnixOwnership),
 }
@@ -0,0 +1,186 @@
+use crate::result::{ZipError, ZipResult};
+use crate::unstable::{LittleEndianReadExt, LittleEndianWriteExt};
+use std::io::Read;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Seconds between the Windows FILETIME epoch (1601-01-01) and the Unix epoch
+const FILETIME_UNIX_EPOCH_SECS: u64 = 11_644_473_600;
+
+/// Number of 100-nanosecond FILETIME intervals in a second
+const FILETIME_TICKS_PER_SEC: u64 = 10_000_000;
+
+/// NTFS timestamps, as described in the "NTFS Extra Field" section of
+/// <https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT>
+///
+/// Each time is a Windows FILETIME: the number of 100-nanosecond intervals since 1601-01-01 UTC.
+#[derive(Debug, Clone, Copy)]
+pub struct Ntfs {
+    mtime: u64,
+    atime: u64,
+    ctime: u64,
+}
+
+impl Ntfs {
+    /// The header ID of this extra field
+    pub(crate) const HEADER_ID: u16 = 0x000a;
+
+    /// The tag of the attribute that holds the three timestamps
+    const TIMES_TAG: u16 = 0x0001;
+
+    pub(crate) fn new(mtime: SystemTime, atime: SystemTime, ctime: SystemTime) -> Self {
+        Self {
+            mtime: system_time_to_filetime(mtime),
+            atime: system_time_to_filetime(atime),
+            ctime: system_time_to_filetime(ctime),
+        }
+    }
+
+    /// Serializes the field with its header. The local header and central directory hold the
+    /// same data.
+    pub(crate) fn to_bytes(self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(36);
+        out.write_u16_le(Self::HEADER_ID).unwrap();
+        out.write_u16_le(32).unwrap();
+        // reserved
+        out.write_u32_le(0).unwrap();
+        out.write_u16_le(Self::TIMES_TAG).unwrap();
+        out.write_u16_le(24).unwrap();
+        out.write_u64_le(self.mtime).unwrap();
+        out.write_u64_le(self.atime).unwrap();
+        out.write_u64_le(self.ctime).unwrap();
+        out
+    }
+
+    /// creates an NTFS timestamps struct by reading the required bytes from the reader.
+    ///
+    /// This method assumes that the length has already been read, therefore
+    /// it must be passed as an argument. Exactly `len` bytes are consumed.
+    pub fn try_from_reader<R>(reader: &mut R, len: u16) -> ZipResult<Self>
+    where
+        R: Read,
+    {
+        let mut data = vec![0; len as usize];
+        reader.read_exact(&mut data)?;
+        // skip the reserved field
+        let mut attributes = data
+            .get(4..)
+            .ok_or(ZipError::InvalidArchive("NTFS extra field is too short"))?;
+        let mut times = None;
+        while attributes.len() >= 4 {
+            let tag = attributes.read_u16_le()?;
+            let size = attributes.read_u16_le()? as usize;
+            if size > attributes.len() {
+                return Err(ZipError::InvalidArchive(
+                    "NTFS extra field attribute is too long",
+                ));
+            }
+            let (mut attribute, rest) = attributes.split_at(size);
+            attributes = rest;
+            if tag == Self::TIMES_TAG && size == 24 {
+                times = Some(Self {
+                    mtime: attribute.read_u64_le()?,
+                    atime: attribute.read_u64_le()?,
+                    ctime: attribute.read_u64_le()?,
+                });
+            }
+        }
+        times.ok_or(ZipError::UnsupportedArchive(
+            "NTFS extra field doesn't contain timestamps",
+        ))
+    }
+
+    /// returns the last modification time as a FILETIME
+    pub const fn mtime(&self) -> u64 {
+        self.mtime
+    }
+
+    /// returns the last access time as a FILETIME
+    pub const fn atime(&self) -> u64 {
+        self.atime
+    }
+
+    /// returns the creation time as a FILETIME
+    pub const fn ctime(&self) -> u64 {
+        self.ctime
+    }
+
+    /// returns the last modification time, or `None` if this platform can't represent it
+    pub fn modified(&self) -> Option<SystemTime> {
+        filetime_to_system_time(self.mtime)
+    }
+
+    /// returns the last access time, or `None` if this platform can't represent it
+    pub fn accessed(&self) -> Option<SystemTime> {
+        filetime_to_system_time(self.atime)
+    }
+
+    /// returns the creation time, or `None` if this platform can't represent it
+    pub fn created(&self) -> Option<SystemTime> {
+        filetime_to_system_time(self.ctime)
+    }
+}
+
+fn filetime_to_system_time(filetime: u64) -> Option<SystemTime> {
+    let since_1601 = Duration::new(
+        filetime / FILETIME_TICKS_PER_SEC,
+        (filetime % FILETIME_TICKS_PER_SEC) as u32 * 100,
+    );
+    let unix_epoch = Duration::from_secs(FILETIME_UNIX_EPOCH_SECS);
+    match since_1601.checked_sub(unix_epoch) {
+        Some(since_unix_epoch) => UNIX_EPOCH.checked_add(since_unix_epoch),
+        None => UNIX_EPOCH.checked_sub(unix_epoch - since_1601),
+    }
+}
+
+/// Converts to a FILETIME, saturating times outside the range it can represent.
+fn system_time_to_filetime(time: SystemTime) -> u64 {
+    let unix_epoch = FILETIME_UNIX_EPOCH_SECS as u128 * FILETIME_TICKS_PER_SEC as u128;
+    let ticks = match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => unix_epoch + since.as_nanos() / 100,
+        Err(before) => unix_epoch.saturating_sub(before.duration().as_nanos() / 100),
+    };
+    ticks.min(u64::MAX as u128) as u64
+}
+
+#[cfg(test)]
+mod test {
+    use super::Ntfs;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn round_trip() {
+        let mtime = UNIX_EPOCH + Duration::new(1_714_635_025, 123_456_700);
+        // past the 32-bit Unix time limit
+        let atime = UNIX_EPOCH + Duration::from_secs(5_000_000_000);
+        let ctime = UNIX_EPOCH - Duration::from_secs(86_400 * 365);
+        let ntfs = Ntfs::new(mtime, atime, ctime);
+        assert_eq!(ntfs.mtime(), 133_591_086_251_234_567);
+
+        let bytes = ntfs.to_bytes();
+        assert_eq!(bytes[..4], [0x0a, 0x00, 32, 0]);
+        let parsed = Ntfs::try_from_reader(&mut &bytes[4..], 32).unwrap();
+        assert_eq!(parsed.modified(), Some(mtime));
+        assert_eq!(parsed.accessed(), Some(atime));
+        assert_eq!(parsed.created(), Some(ctime));
+    }
+
+    #[test]
+    fn filetime_epoch() {
+        let ntfs = Ntfs::new(UNIX_EPOCH, UNIX_EPOCH, UNIX_EPOCH);
+        assert_eq!(ntfs.mtime(), 116_444_736_000_000_000);
+        let before_1601 = UNIX_EPOCH - Duration::from_secs(12_000_000_000);
+        assert_eq!(Ntfs::new(before_1601, UNIX_EPOCH, UNIX_EPOCH).mtime(), 0);
+    }
+
+    #[test]
+    fn skips_unknown_attributes() {
+        let bytes = [
+            0, 0, 0, 0, // reserved
+            0x02, 0x00, 0x02, 0x00, 0xaa, 0xbb, // unknown tag
+            0x01, 0x00, 24, 0x00, // timestamps
+            1, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        let ntfs = Ntfs::try_from_reader(&mut &bytes[..], bytes.len() as u16).unwrap();
+        assert_eq!((ntfs.mtime(), ntfs.atime(), ntfs.ctime()), (1, 2, 3));
+    }
+}
@@ -0,0 +1,59 @@
+use crate::unstable::LittleEndianReadExt;
+
+/// an extra field that hasn't been interpreted, as it is stored in the archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RawExtraField {
+    header_id: u16,
+    data: Vec<u8>,
+}
+
+impl RawExtraField {
+    /// The header ID identifying what kind of extra field this is
+    pub const fn header_id(&self) -> u16 {
+        self.header_id
+    }
+
+    /// The contents of the field, without the header ID and length
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Splits a block of extra data into its fields. Iteration stops at the first field whose
+    /// header or declared length doesn't fit in what remains of the block.
+    pub(crate) fn parse_all(mut extra_data: &[u8]) -> impl Iterator<Item = RawExtraField> + '_ {
+        std::iter::from_fn(move || {
+            let header_id = extra_data.read_u16_le().ok()?;
+            let len = extra_data.read_u16_le().ok()? as usize;
+            if len > extra_data.len() {
+                extra_data = &[];
+                return None;
+            }
+            let (data, rest) = extra_data.split_at(len);
+            extra_data = rest;
+            Some(RawExtraField {
+                header_id,
+                data: data.to_vec(),
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RawExtraField;
+
+    #[test]
+    fn parse_all() {
+        let extra_data = [
+            0x4d, 0x33, 0x02, 0x00, 0xaa, 0xbb, // 0x334d with 2 bytes
+            0x34, 0x12, 0x00, 0x00, // 0x1234 with no data
+            0x01, 0x00, 0x08, 0x00, 0x01, // truncated
+        ];
+        let fields: Vec<_> = RawExtraField::parse_all(&extra_data).collect();
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0].header_id(), 0x334d);
+        assert_eq!(fields[0].data(), [0xaa, 0xbb]);
+        assert_eq!(fields[1].header_id(), 0x1234);
+        assert!(fields[1].data().is_empty());
+    }
+}
@@ -0,0 +1,128 @@
+use crate::result::{ZipError, ZipResult};
+use crate::unstable::LittleEndianReadExt;
+use std::fmt;
+use std::io::Read;
+
+/// The Strong Encryption Header of a file encrypted with PKWARE's strong encryption, as described
+/// in the "Strong Encryption Header" section of
+/// <https://pkware.cachefly.net/webdocs/casestudies/APPNOTE.TXT>
+///
+/// Such files can't be decrypted by this crate, but this says which algorithm they need.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StrongEncryption {
+    algorithm: StrongEncryptionAlgorithm,
+    bit_length: u16,
+    flags: u16,
+}
+
+impl StrongEncryption {
+    /// The header ID of this extra field
+    pub(crate) const HEADER_ID: u16 = 0x0017;
+
+    /// creates a Strong Encryption Header by reading the required bytes from the reader.
+    ///
+    /// This method assumes that the length has already been read, therefore
+    /// it must be passed as an argument. Exactly `len` bytes are consumed.
+    pub fn try_from_reader<R>(reader: &mut R, len: u16) -> ZipResult<Self>
+    where
+        R: Read,
+    {
+        let mut data = vec![0; len as usize];
+        reader.read_exact(&mut data)?;
+        if data.len() < 8 {
+            return Err(ZipError::InvalidArchive(
+                "Strong encryption extra field is too short",
+            ));
+        }
+        // skip the format, which is always 2; any certificate data follows the flags
+        let mut fields = &data[2..];
+        Ok(Self {
+            algorithm: fields.read_u16_le()?.into(),
+            bit_length: fields.read_u16_le()?,
+            flags: fields.read_u16_le()?,
+        })
+    }
+
+    /// The algorithm the file is encrypted with
+    pub const fn algorithm(&self) -> StrongEncryptionAlgorithm {
+        self.algorithm
+    }
+
+    /// The length of the key, in bits
+    pub const fn bit_length(&self) -> u16 {
+        self.bit_length
+    }
+
+    /// How the key is protected: 1 for a password, 2 for certificates, or 3 for either
+    pub const fn flags(&self) -> u16 {
+        self.flags
+    }
+}
+
+/// An algorithm used by PKWARE's strong encryption, identified as in APPNOTE 7.2.3.2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StrongEncryptionAlgorithm {
+    /// DES
+    Des,
+    /// RC2, as written before PKZIP 5.2
+    Rc2Legacy,
+    /// Triple DES with a 168-bit key
+    TripleDes168,
+    /// Triple DES with a 112-bit key
+    TripleDes112,
+    /// AES with a 128-bit key
+    Aes128,
+    /// AES with a 192-bit key
+    Aes192,
+    /// AES with a 256-bit key
+    Aes256,
+    /// RC2
+    Rc2,
+    /// Blowfish
+    Blowfish,
+    /// Twofish
+    Twofish,
+    /// RC4
+    Rc4,
+    /// An algorithm with any other ID, or `0xFFFF` when the archive doesn't say which it is
+    Unknown(u16),
+}
+
+impl From<u16> for StrongEncryptionAlgorithm {
+    fn from(id: u16) -> Self {
+        match id {
+            0x6601 => Self::Des,
+            0x6602 => Self::Rc2Legacy,
+            0x6603 => Self::TripleDes168,
+            0x6609 => Self::TripleDes112,
+            0x660E => Self::Aes128,
+            0x660F => Self::Aes192,
+            0x6610 => Self::Aes256,
+            0x6702 => Self::Rc2,
+            0x6720 => Self::Blowfish,
+            0x6721 => Self::Twofish,
+            0x6801 => Self::Rc4,
+            id => Self::Unknown(id),
+        }
+    }
+}
+
+impl fmt::Display for StrongEncryptionAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Des => f.write_str("DES"),
+            Self::Rc2Legacy | Self::Rc2 => f.write_str("RC2"),
+            Self::TripleDes168 => f.write_str("3DES-168"),
+            Self::TripleDes112 => f.write_str("3DES-112"),
+            Self::Aes128 => f.write_str("AES-128"),
+            Self::Aes192 => f.write_str("AES-192"),
+            Self::Aes256 => f.write_str("AES-256"),
+            Self::Blowfish => f.write_str("Blowfish"),
+            Self::Twofish => f.write_str("Twofish"),
+            Self::Rc4 => f.write_str("RC4"),
+            Self::Unknown(0xFFFF) => f.write_str("an unknown algorithm"),
+            Self::Unknown(id) => write!(f, "algorithm {id:#06x}"),
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use crate::result::{ZipError, ZipResult};
+use crate::unstable::LittleEndianWriteExt;
+use std::io::Read;
+
+/// The owner of a file on Unix, as stored in the Info-ZIP new Unix extra field described in
+/// <https://libzip.org/specifications/extrafld.txt>
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UnixOwnership {
+    uid: u32,
+    gid: u32,
+}
+
+impl UnixOwnership {
+    /// The header ID of this extra field
+    pub(crate) const HEADER_ID: u16 = 0x7875;
+
+    /// The only version of the field defined so far
+    const VERSION: u8 = 1;
+
+    pub(crate) const fn new(uid: u32, gid: u32) -> Self {
+        Self { uid, gid }
+    }
+
+    /// Serializes the field with its header, with 4-byte IDs as Info-ZIP writes them. The local
+    /// header and central directory hold the same data.
+    pub(crate) fn to_bytes(self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(15);
+        out.write_u16_le(Self::HEADER_ID).unwrap();
+        out.write_u16_le(11).unwrap();
+        out.push(Self::VERSION);
+        out.push(4);
+        out.write_u32_le(self.uid).unwrap();
+        out.push(4);
+        out.write_u32_le(self.gid).unwrap();
+        out
+    }
+
+    /// creates a Unix ownership struct by reading the required bytes from the reader.
+    ///
+    /// This method assumes that the length has already been read, therefore
+    /// it must be passed as an argument. Exactly `len` bytes are consumed.
+    ///
+    /// The UID and GID may each be stored in any number of bytes, but this returns
+    /// [`ZipError::UnsupportedArchive`] if either doesn't fit in a `u32`, or if the field has a
+    /// version other than 1.
+    pub fn try_from_reader<R>(reader: &mut R, len: u16) -> ZipResult<Self>
+    where
+        R: Read,
+    {
+        let mut data = vec![0; len as usize];
+        reader.read_exact(&mut data)?;
+        let (&version, mut ids) = data.split_first().ok_or(ZipError::InvalidArchive(
+            "Unix ownership extra field is too short",
+        ))?;
+        if version != Self::VERSION {
+            return Err(ZipError::UnsupportedArchive(
+                "Unix ownership extra field has an unknown version",
+            ));
+        }
+        let uid = read_id(&mut ids)?;
+        let gid = read_id(&mut ids)?;
+        Ok(Self { uid, gid })
+    }
+
+    /// returns the user ID of the file's owner
+    pub const fn uid(&self) -> u32 {
+        self.uid
+    }
+
+    /// returns the ID of the file's group
+    pub const fn gid(&self) -> u32 {
+        self.gid
+    }
+}
+
+/// Reads a little-endian ID preceded by its size in bytes
+fn read_id(data: &mut &[u8]) -> ZipResult<u32> {
+    let too_short = || ZipError::InvalidArchive("Unix ownership extra field is too short");
+    let (&size, rest) = data.split_first().ok_or_else(too_short)?;
+    if rest.len() < size as usize {
+        return Err(too_short());
+    }
+    let (id, rest) = rest.split_at(size as usize);
+    *data = rest;
+    if id.iter().skip(4).any(|&byte| byte != 0) {
+        return Err(ZipError::UnsupportedArchive(
+            "Unix ownership extra field has an ID that doesn't fit in 32 bits",
+        ));
+    }
+    let mut bytes = [0; 4];
+    let used = id.len().min(4);
+    bytes[..used].copy_from_slice(&id[..used]);
+    Ok(u32::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod test {
+    use super::UnixOwnership;
+    use crate::result::ZipError;
+
+    #[test]
+    fn round_trip() {
+        let ownership = UnixOwnership::new(1000, 0x8765_4321);
+        let bytes = ownership.to_bytes();
+        assert_eq!(bytes[..4], [0x75, 0x78, 11, 0]);
+        let parsed = UnixOwnership::try_from_reader(&mut &bytes[4..], 11).unwrap();
+        assert_eq!(parsed, ownership);
+    }
+
+    #[test]
+    fn variable_width_ids() {
+        // a 2-byte UID and an 8-byte GID
+        let bytes = [1, 2, 0xe8, 0x03, 8, 100, 0, 0, 0, 0, 0, 0, 0];
+        let parsed = UnixOwnership::try_from_reader(&mut &bytes[..], bytes.len() as u16).unwrap();
+        assert_eq!((parsed.uid(), parsed.gid()), (1000, 100));
+
+        // 1-byte and empty IDs
+        let bytes = [1, 1, 7, 0];
+        let parsed = UnixOwnership::try_from_reader(&mut &bytes[..], bytes.len() as u16).unwrap();
+        assert_eq!((parsed.uid(), parsed.gid()), (7, 0));
+
+        let too_wide = [1, 8, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0];
+        assert!(matches!(
+            UnixOwnership::try_from_reader(&mut &too_wide[..], too_wide.len() as u16),
+            Err(ZipError::UnsupportedArchive(_))
+        ));
+        let unknown_version = [2, 1, 0, 1, 0];
+        assert!(matches!(
+            UnixOwnership::try_from_reader(&mut &unknown_version[..], 5),
+            Err(ZipError::UnsupportedArchive(_))
+        ));
+        let truncated = [1, 4, 0, 0];
+        assert!(matches!(
+            UnixOwnership::try_from_reader(&mut &truncated[..], 4),
+            Err(ZipError::InvalidArchive(_))
+        ));
+    }
+}
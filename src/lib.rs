@@ -21,15 +21,20 @@
 //! | Deflate64 | ✅ | |
 //! | Bzip2 | ✅ | ✅ |
 //! | LZMA | ✅ | |
+//! | Shrink and Implode | ✅ | |
+//! | PPMd | ✅ | |
+//! | XZ | ✅ | |
 //! | AES encryption | ✅ | ✅ |
 //! | ZipCrypto deprecated encryption | ✅ | ✅ |
 //!
 //!
 #![warn(missing_docs)]
 #![allow(unexpected_cfgs)] // Needed for cfg(fuzzing) on nightly as of 2024-05-06
-pub use crate::compression::{CompressionMethod, SUPPORTED_COMPRESSION_METHODS};
+pub use crate::compression::{
+    CompressionMethod, CompressionStream, Compressor, Decompressor, SUPPORTED_COMPRESSION_METHODS,
+};
 pub use crate::read::ZipArchive;
-pub use crate::types::{AesMode, DateTime};
+pub use crate::types::{AesMode, AesVendorVersion, DateTime, DosAttributes, NameEncoding, System};
 pub use crate::write::ZipWriter;
 
 #[cfg(feature = "aes-crypto")]
@@ -2,22 +2,32 @@
 
 #[cfg(feature = "aes-crypto")]
 use crate::aes::{AesReader, AesReaderValid};
-use crate::compression::CompressionMethod;
+use crate::compression::{CompressionMethod, Decompressor};
 use crate::cp437::FromCp437;
 use crate::crc32::Crc32Reader;
-use crate::extra_fields::{ExtendedTimestamp, ExtraField};
-use crate::read::zip_archive::Shared;
+use crate::extra_fields::{
+    ExtendedTimestamp, ExtraField, Ntfs, RawExtraField, StrongEncryption,
+    StrongEncryptionAlgorithm, UnixOwnership,
+};
+use crate::read::zip_archive::{PendingDirectory, Shared};
 use crate::result::{ZipError, ZipResult};
 use crate::spec;
-use crate::types::{AesMode, AesVendorVersion, DateTime, System, ZipFileData};
+use crate::types::{
+    AesMode, AesVendorVersion, DateTime, DosAttributes, NameEncoding, System, ZipFileData,
+    AES_ITERATIONS_EXTRA_FIELD_ID,
+};
 use crate::zipcrypto::{ZipCryptoReader, ZipCryptoReaderValid, ZipCryptoValidator};
 use indexmap::IndexMap;
 use std::borrow::Cow;
+use std::fmt;
 use std::fs::create_dir_all;
 use std::io::{self, copy, prelude::*, sink};
+use std::mem;
 use std::ops::Deref;
 use std::path::{Path, PathBuf};
-use std::sync::{Arc, OnceLock};
+use std::slice;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[cfg(any(
     feature = "deflate",
@@ -41,16 +51,62 @@ pub(crate) mod stream;
 #[cfg(feature = "lzma")]
 pub(crate) mod lzma;
 
+#[cfg(feature = "legacy")]
+pub(crate) mod legacy;
+
+#[cfg(feature = "ppmd")]
+pub(crate) mod ppmd;
+
+#[cfg(feature = "xz")]
+pub(crate) mod xz;
+
+mod glob;
+
+pub(crate) mod builder;
+pub use builder::ZipArchiveBuilder;
+
+pub(crate) mod seekable;
+pub use seekable::SeekableZipFile;
+
+pub(crate) mod spanned;
+pub use spanned::SpannedReader;
+
+#[cfg(feature = "tokio")]
+pub(crate) mod async_archive;
+#[cfg(feature = "tokio")]
+pub use async_archive::{AsyncZipArchive, AsyncZipFile};
+
 // Put the struct declaration in a private module to convince rustdoc to display ZipArchive nicely
 pub(crate) mod zip_archive {
     use std::sync::Arc;
 
     /// Extract immutable data from `ZipArchive` to make it cheap to clone
-    #[derive(Debug)]
+    #[derive(Clone, Debug)]
     pub(crate) struct Shared {
         pub(crate) files: super::IndexMap<Box<str>, super::ZipFileData>,
         pub(super) offset: u64,
         pub(super) dir_start: u64,
+        /// Central directory records that haven't been parsed yet, for archives opened with
+        /// `ZipArchive::new_lazy`
+        pub(super) pending: Option<PendingDirectory>,
+        /// The indices of the files with each name in ASCII lowercase, built the first time
+        /// `ZipArchive::by_name_case_insensitive` needs it
+        pub(super) lowercase_names:
+            std::sync::OnceLock<std::collections::HashMap<Box<str>, Vec<usize>>>,
+        /// Whether the central directory was found through a ZIP64 end of central directory
+        /// record
+        pub(super) zip64: bool,
+        /// The number of entries the end of central directory record says there are
+        pub(super) total_entries: u64,
+        /// The number of bytes after the end of central directory record
+        pub(super) trailing_data_len: u64,
+    }
+
+    /// Where to resume parsing a lazily-read central directory
+    #[derive(Clone, Debug)]
+    pub(crate) struct PendingDirectory {
+        pub(super) next_header: u64,
+        pub(super) remaining: usize,
     }
 
     /// ZIP archive reader
@@ -78,23 +134,108 @@ pub(crate) mod zip_archive {
         pub(super) reader: R,
         pub(super) shared: Arc<Shared>,
         pub(super) comment: Arc<[u8]>,
+        pub(super) limits: super::DecompressionLimits,
+        pub(super) verify_crc: bool,
+        #[cfg_attr(not(feature = "aes-crypto"), allow(dead_code))]
+        pub(super) aes_iterations: Option<u32>,
+        pub(super) strictness: super::Strictness,
+        pub(super) decompressors: super::Decompressors,
+    }
+}
+
+/// The decompressors registered with [`ZipArchive::register_decompressor`], by method ID
+#[derive(Clone, Default)]
+pub(crate) struct Decompressors(Arc<std::collections::HashMap<u16, Arc<dyn Decompressor>>>);
+
+impl Decompressors {
+    fn get(&self, data: &ZipFileData) -> Option<Arc<dyn Decompressor>> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let method = match data.aes_mode {
+            Some((_, _, method)) => method,
+            None => data.compression_method,
+        };
+        #[allow(deprecated)]
+        self.0.get(&method.to_u16()).cloned()
+    }
+}
+
+impl fmt::Debug for Decompressors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_set().entries(self.0.keys()).finish()
     }
 }
 
+#[cfg(feature = "legacy")]
+use crate::read::legacy::{ImplodeDecoder, ShrinkDecoder};
 #[cfg(feature = "lzma")]
 use crate::read::lzma::LzmaDecoder;
+#[cfg(feature = "ppmd")]
+use crate::read::ppmd::PpmdDecoder;
+#[cfg(feature = "xz")]
+use crate::read::xz::XzDecoder;
 use crate::result::ZipError::{InvalidPassword, UnsupportedArchive};
 use crate::spec::path_to_string;
 use crate::unstable::LittleEndianReadExt;
 pub use zip_archive::ZipArchive;
 
+/// A reader that can seek, as one trait object
+pub(crate) trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+/// Where the raw data of a [`ZipFile`] comes from
+pub(crate) enum EntryReader<'a> {
+    /// The archive's own reader, which can go back to where the data starts
+    Seekable {
+        reader: &'a mut dyn ReadSeek,
+        data_start: u64,
+    },
+    /// The reader of a stream
+    Borrowed(&'a mut dyn Read),
+    /// A copy of the data, for entries read from a stream whose length wasn't known until after
+    /// reading them
+    Buffered(io::Cursor<Vec<u8>>),
+    /// Data the caller already has, given to [`decode_entry`]
+    Slice(io::Cursor<&'a [u8]>),
+}
+
+impl Read for EntryReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self {
+            EntryReader::Seekable { reader, .. } => reader.read(buf),
+            EntryReader::Borrowed(r) => r.read(buf),
+            EntryReader::Buffered(r) => r.read(buf),
+            EntryReader::Slice(r) => r.read(buf),
+        }
+    }
+}
+
+impl EntryReader<'_> {
+    /// Go back to the start of the data
+    fn rewind(&mut self) -> ZipResult<()> {
+        match self {
+            EntryReader::Seekable { reader, data_start } => {
+                reader.seek(io::SeekFrom::Start(*data_start))?;
+            }
+            EntryReader::Borrowed(_) => {
+                return unsupported_zip_error("Files read from a stream can't be rewound")
+            }
+            EntryReader::Buffered(r) => r.set_position(0),
+            EntryReader::Slice(r) => r.set_position(0),
+        }
+        Ok(())
+    }
+}
+
 #[allow(clippy::large_enum_variant)]
 pub(crate) enum CryptoReader<'a> {
-    Plaintext(io::Take<&'a mut dyn Read>),
-    ZipCrypto(ZipCryptoReaderValid<io::Take<&'a mut dyn Read>>),
+    Plaintext(io::Take<EntryReader<'a>>),
+    ZipCrypto(ZipCryptoReaderValid<io::Take<EntryReader<'a>>>),
     #[cfg(feature = "aes-crypto")]
     Aes {
-        reader: AesReaderValid<io::Take<&'a mut dyn Read>>,
+        reader: AesReaderValid<io::Take<EntryReader<'a>>>,
         vendor_version: AesVendorVersion,
     },
 }
@@ -112,7 +253,7 @@ impl<'a> Read for CryptoReader<'a> {
 
 impl<'a> CryptoReader<'a> {
     /// Consumes this decoder, returning the underlying reader.
-    pub fn into_inner(self) -> io::Take<&'a mut dyn Read> {
+    pub fn into_inner(self) -> io::Take<EntryReader<'a>> {
         match self {
             CryptoReader::Plaintext(r) => r,
             CryptoReader::ZipCrypto(r) => r.into_inner(),
@@ -138,7 +279,9 @@ impl<'a> CryptoReader<'a> {
 
 pub(crate) enum ZipFileReader<'a> {
     NoReader,
-    Raw(io::Take<&'a mut dyn Read>),
+    Raw(io::Take<EntryReader<'a>>),
+    /// Decrypted but still compressed
+    Decrypted(CryptoReader<'a>),
     Stored(Crc32Reader<CryptoReader<'a>>),
     #[cfg(feature = "_deflate-any")]
     Deflated(Crc32Reader<DeflateDecoder<CryptoReader<'a>>>),
@@ -150,6 +293,18 @@ pub(crate) enum ZipFileReader<'a> {
     Zstd(Crc32Reader<ZstdDecoder<'a, io::BufReader<CryptoReader<'a>>>>),
     #[cfg(feature = "lzma")]
     Lzma(Crc32Reader<Box<LzmaDecoder<CryptoReader<'a>>>>),
+    #[cfg(feature = "brotli")]
+    Brotli(Crc32Reader<Box<brotli::Decompressor<CryptoReader<'a>>>>),
+    #[cfg(feature = "legacy")]
+    Shrink(Crc32Reader<Box<ShrinkDecoder<CryptoReader<'a>>>>),
+    #[cfg(feature = "legacy")]
+    Implode(Crc32Reader<Box<ImplodeDecoder<CryptoReader<'a>>>>),
+    #[cfg(feature = "ppmd")]
+    Ppmd(Crc32Reader<Box<PpmdDecoder<CryptoReader<'a>>>>),
+    #[cfg(feature = "xz")]
+    Xz(Crc32Reader<Box<XzDecoder<CryptoReader<'a>>>>),
+    /// Decompressed by a [`Decompressor`] registered with [`ZipArchive::register_decompressor`]
+    Custom(Crc32Reader<Box<dyn Read + 'a>>),
 }
 
 impl<'a> Read for ZipFileReader<'a> {
@@ -157,6 +312,7 @@ impl<'a> Read for ZipFileReader<'a> {
         match self {
             ZipFileReader::NoReader => panic!("ZipFileReader was in an invalid state"),
             ZipFileReader::Raw(r) => r.read(buf),
+            ZipFileReader::Decrypted(r) => r.read(buf),
             ZipFileReader::Stored(r) => r.read(buf),
             #[cfg(feature = "_deflate-any")]
             ZipFileReader::Deflated(r) => r.read(buf),
@@ -168,16 +324,36 @@ impl<'a> Read for ZipFileReader<'a> {
             ZipFileReader::Zstd(r) => r.read(buf),
             #[cfg(feature = "lzma")]
             ZipFileReader::Lzma(r) => r.read(buf),
+            #[cfg(feature = "brotli")]
+            ZipFileReader::Brotli(r) => r.read(buf),
+            #[cfg(feature = "legacy")]
+            ZipFileReader::Shrink(r) => r.read(buf),
+            #[cfg(feature = "legacy")]
+            ZipFileReader::Implode(r) => r.read(buf),
+            #[cfg(feature = "ppmd")]
+            ZipFileReader::Ppmd(r) => r.read(buf),
+            #[cfg(feature = "xz")]
+            ZipFileReader::Xz(r) => r.read(buf),
+            ZipFileReader::Custom(r) => r.read(buf),
         }
     }
 }
 
 impl<'a> ZipFileReader<'a> {
-    /// Consumes this decoder, returning the underlying reader.
+    /// Consumes this decoder, reading the rest of the compressed data without decompressing it.
     pub fn drain(self) {
-        let mut inner = match self {
+        if let Some(mut inner) = self.into_entry_reader() {
+            let _ = copy(&mut inner, &mut sink());
+        }
+    }
+
+    /// Consumes this decoder, returning the reader of the compressed data, unless it's held by a
+    /// custom decompressor
+    fn into_entry_reader(self) -> Option<io::Take<EntryReader<'a>>> {
+        Some(match self {
             ZipFileReader::NoReader => panic!("ZipFileReader was in an invalid state"),
             ZipFileReader::Raw(r) => r,
+            ZipFileReader::Decrypted(r) => r.into_inner(),
             ZipFileReader::Stored(r) => r.into_inner().into_inner(),
             #[cfg(feature = "_deflate-any")]
             ZipFileReader::Deflated(r) => r.into_inner().into_inner().into_inner(),
@@ -188,37 +364,127 @@ impl<'a> ZipFileReader<'a> {
             #[cfg(feature = "zstd")]
             ZipFileReader::Zstd(r) => r.into_inner().finish().into_inner().into_inner(),
             #[cfg(feature = "lzma")]
-            ZipFileReader::Lzma(r) => {
-                // Lzma reader owns its buffer rather than mutably borrowing it, so we have to drop
-                // it separately
-                if let Ok(mut remaining) = r.into_inner().finish() {
-                    let _ = copy(&mut remaining, &mut sink());
-                }
-                return;
-            }
-        };
-        let _ = copy(&mut inner, &mut sink());
+            ZipFileReader::Lzma(r) => r.into_inner().into_inner().into_inner(),
+            #[cfg(feature = "brotli")]
+            ZipFileReader::Brotli(r) => r.into_inner().into_inner().into_inner(),
+            #[cfg(feature = "legacy")]
+            ZipFileReader::Shrink(r) => r.into_inner().into_inner().into_inner(),
+            #[cfg(feature = "legacy")]
+            ZipFileReader::Implode(r) => r.into_inner().into_inner().into_inner(),
+            #[cfg(feature = "ppmd")]
+            ZipFileReader::Ppmd(r) => r.into_inner().into_inner().into_inner(),
+            #[cfg(feature = "xz")]
+            ZipFileReader::Xz(r) => r.into_inner().into_inner().into_inner(),
+            // Only files from a `ZipArchive` use these, and it doesn't need the rest of the data
+            // to be read to find the next file
+            ZipFileReader::Custom(_) => return None,
+        })
     }
 }
 
+/// The most end of central directory records that [`ZipArchive`] tries to open an archive from
+/// before giving up
+const MAX_FOOTER_CANDIDATES: usize = 16;
+
+/// Whether an end of central directory record found at `cde_start_pos` could describe an archive,
+/// judging only by the record itself. One whose sizes need a ZIP64 record can't be checked this
+/// way, so it's always plausible.
+fn footer_is_plausible(footer: &spec::CentralDirectoryEnd, cde_start_pos: u64) -> bool {
+    if footer.number_of_files == u16::MAX
+        || footer.central_directory_size == u32::MAX
+        || footer.central_directory_offset == u32::MAX
+    {
+        return true;
+    }
+    // Each central directory header takes at least 46 bytes
+    footer.number_of_files_on_this_disk <= footer.number_of_files
+        && footer.number_of_files as u64 * 46 <= footer.central_directory_size as u64
+        && footer.central_directory_offset as u64 + footer.central_directory_size as u64
+            <= cde_start_pos
+}
+
 /// A struct for reading a zip file
 pub struct ZipFile<'a> {
     pub(crate) data: Cow<'a, ZipFileData>,
     pub(crate) crypto_reader: Option<CryptoReader<'a>>,
     pub(crate) reader: ZipFileReader<'a>,
+    pub(crate) limits: DecompressionLimits,
+    pub(crate) bytes_decompressed: u64,
+    pub(crate) verify_crc: bool,
+    pub(crate) decompressor: Option<Arc<dyn Decompressor>>,
+    /// The dictionary given to [`ZipArchive::by_index_with_zstd_dictionary`]
+    #[cfg(feature = "zstd")]
+    pub(crate) zstd_dictionary: Option<&'a [u8]>,
+    /// Whether the data can be read again from the start, which it can't when it comes straight
+    /// from a stream
+    pub(crate) rewindable: bool,
+}
+
+/// How [`ZipArchive`] treats a file whose local header disagrees with its central directory
+/// record, as set with [`ZipArchive::set_strictness`]
+///
+/// The compression method, name, CRC-32 and sizes are compared. The CRC-32 and sizes in a local
+/// header that leaves them for a data descriptor aren't.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Strictness {
+    /// Opening the file fails with [`ZipError::InconsistentMetadata`]
+    Strict,
+    /// The central directory is used, and the local header is only read to find the data
+    #[default]
+    Lenient,
+}
+
+/// Caps on how much data reading a single [`ZipFile`] may produce, to guard against zip bombs
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct DecompressionLimits {
+    max_decompressed_size: Option<u64>,
+    max_compression_ratio: Option<f64>,
+}
+
+impl DecompressionLimits {
+    fn check(&self, bytes_decompressed: u64, compressed_size: u64) -> io::Result<()> {
+        if self
+            .max_decompressed_size
+            .is_some_and(|max| bytes_decompressed > max)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "Decompressed size limit exceeded",
+            ));
+        }
+        if self
+            .max_compression_ratio
+            .is_some_and(|max| bytes_decompressed as f64 > max * compressed_size as f64)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                "Compression ratio limit exceeded",
+            ));
+        }
+        Ok(())
+    }
 }
 
 pub(crate) fn find_content<'a>(
     data: &ZipFileData,
     reader: &'a mut (impl Read + Seek),
-) -> ZipResult<io::Take<&'a mut dyn Read>> {
+) -> ZipResult<io::Take<EntryReader<'a>>> {
+    let data_start = find_data_start(data, reader)?;
+    reader.seek(io::SeekFrom::Start(data_start))?;
+    Ok(EntryReader::Seekable { reader, data_start }.take(data.compressed_size))
+}
+
+/// Check the local header of `data` and find where its compressed data starts, reading the local
+/// header's name and extra field lengths if that isn't known yet.
+fn find_data_start(data: &ZipFileData, reader: &mut (impl Read + Seek)) -> ZipResult<u64> {
     // Parse local header
     reader.seek(io::SeekFrom::Start(data.header_start))?;
     let signature = reader.read_u32_le()?;
     if signature != spec::LOCAL_FILE_HEADER_SIGNATURE {
         return Err(ZipError::InvalidArchive("Invalid local file header"));
     }
-    let data_start = match data.data_start.get() {
+    Ok(match data.data_start.get() {
         None => {
             reader.seek(io::SeekFrom::Current(22))?;
             let file_name_length = reader.read_u16_le()? as u64;
@@ -230,68 +496,105 @@ pub(crate) fn find_content<'a>(
             data_start
         }
         Some(start) => *start,
-    };
+    })
+}
 
-    reader.seek(io::SeekFrom::Start(data_start))?;
-    Ok((reader as &mut dyn Read).take(data.compressed_size))
+/// Compare a file's local header with its central directory record, for [`Strictness::Strict`]
+fn check_local_header(data: &ZipFileData, reader: &mut (impl Read + Seek)) -> ZipResult<()> {
+    reader.seek(io::SeekFrom::Start(data.header_start))?;
+    if reader.read_u32_le()? != spec::LOCAL_FILE_HEADER_SIGNATURE {
+        return Err(ZipError::InvalidArchive("Invalid local file header"));
+    }
+    let local = local_header_to_zip_file_data(reader)?;
+    // The method in the header of an AES encrypted file is 99, and the real one is in its extra
+    // field, which both records have
+    if local.compression_method != data.compression_method
+        || local.aes_mode.is_some() != data.aes_mode.is_some()
+    {
+        return Err(ZipError::InconsistentMetadata("compression method"));
+    }
+    if local.file_name_raw != data.file_name_raw {
+        return Err(ZipError::InconsistentMetadata("file name"));
+    }
+    if !local.using_data_descriptor {
+        if local.crc32 != data.crc32 {
+            return Err(ZipError::InconsistentMetadata("CRC-32"));
+        }
+        if local.compressed_size != data.compressed_size {
+            return Err(ZipError::InconsistentMetadata("compressed size"));
+        }
+        if local.uncompressed_size != data.uncompressed_size {
+            return Err(ZipError::InconsistentMetadata("uncompressed size"));
+        }
+    }
+    Ok(())
+}
+
+/// Fails for compression methods this crate doesn't know
+pub(crate) fn check_compression_supported(compression_method: CompressionMethod) -> ZipResult<()> {
+    #[allow(deprecated)]
+    if let CompressionMethod::Unsupported(_) = compression_method {
+        return unsupported_zip_error("Compression method not supported");
+    }
+    Ok(())
 }
 
 #[allow(clippy::too_many_arguments)]
 pub(crate) fn make_crypto_reader<'a>(
-    compression_method: CompressionMethod,
     crc32: u32,
     last_modified_time: DateTime,
     using_data_descriptor: bool,
-    reader: io::Take<&'a mut dyn Read>,
-    password: Option<&[u8]>,
+    reader: io::Take<EntryReader<'a>>,
+    passwords: &[&[u8]],
     aes_info: Option<(AesMode, AesVendorVersion, CompressionMethod)>,
     #[cfg(feature = "aes-crypto")] compressed_size: u64,
+    #[cfg(feature = "aes-crypto")] aes_iterations: u32,
 ) -> ZipResult<CryptoReader<'a>> {
-    #[allow(deprecated)]
-    {
-        if let CompressionMethod::Unsupported(_) = compression_method {
-            return unsupported_zip_error("Compression method not supported");
-        }
-    }
-
-    let reader = match (password, aes_info) {
+    let reader = match (passwords, aes_info) {
         #[cfg(not(feature = "aes-crypto"))]
-        (Some(_), Some(_)) => {
+        ([_, ..], Some(_)) => {
             return Err(ZipError::UnsupportedArchive(
                 "AES encrypted files cannot be decrypted without the aes-crypto feature.",
             ))
         }
         #[cfg(feature = "aes-crypto")]
-        (Some(password), Some((aes_mode, vendor_version, _))) => CryptoReader::Aes {
-            reader: AesReader::new(reader, aes_mode, compressed_size).validate(password)?,
+        ([_, ..], Some((aes_mode, vendor_version, _))) => CryptoReader::Aes {
+            reader: AesReader::new(reader, aes_mode, compressed_size, aes_iterations)
+                .validate(passwords)?,
             vendor_version,
         },
-        (Some(password), None) => {
+        ([_, ..], None) => {
             let validator = if using_data_descriptor {
                 ZipCryptoValidator::InfoZipMsdosTime(last_modified_time.timepart())
             } else {
                 ZipCryptoValidator::PkzipCrc32(crc32)
             };
-            CryptoReader::ZipCrypto(ZipCryptoReader::new(reader, password).validate(validator)?)
+            CryptoReader::ZipCrypto(ZipCryptoReader::validate(reader, passwords, validator)?)
         }
-        (None, Some(_)) => return Err(InvalidPassword),
-        (None, None) => CryptoReader::Plaintext(reader),
+        ([], Some(_)) => return Err(InvalidPassword),
+        ([], None) => CryptoReader::Plaintext(reader),
     };
     Ok(reader)
 }
 
-pub(crate) fn make_reader(
+pub(crate) fn make_reader<'a>(
     compression_method: CompressionMethod,
     crc32: u32,
-    reader: CryptoReader,
-) -> ZipResult<ZipFileReader> {
-    let ae2_encrypted = reader.is_ae2_encrypted();
+    #[cfg_attr(
+        not(any(feature = "lzma", feature = "legacy", feature = "ppmd", feature = "xz")),
+        allow(unused_variables)
+    )]
+    uncompressed_size: u64,
+    reader: CryptoReader<'a>,
+    verify_crc: bool,
+    #[cfg(feature = "zstd")] zstd_dictionary: Option<&[u8]>,
+    #[cfg(feature = "legacy")] compression_flags: u16,
+) -> ZipResult<ZipFileReader<'a>> {
+    let verify_crc = verify_crc && !reader.is_ae2_encrypted();
 
     match compression_method {
         CompressionMethod::Stored => Ok(ZipFileReader::Stored(Crc32Reader::new(
-            reader,
-            crc32,
-            ae2_encrypted,
+            reader, crc32, verify_crc,
         ))),
         #[cfg(feature = "_deflate-any")]
         CompressionMethod::Deflated => {
@@ -299,7 +602,7 @@ pub(crate) fn make_reader(
             Ok(ZipFileReader::Deflated(Crc32Reader::new(
                 deflate_reader,
                 crc32,
-                ae2_encrypted,
+                verify_crc,
             )))
         }
         #[cfg(feature = "deflate64")]
@@ -308,7 +611,7 @@ pub(crate) fn make_reader(
             Ok(ZipFileReader::Deflate64(Crc32Reader::new(
                 deflate64_reader,
                 crc32,
-                ae2_encrypted,
+                verify_crc,
             )))
         }
         #[cfg(feature = "bzip2")]
@@ -317,37 +620,93 @@ pub(crate) fn make_reader(
             Ok(ZipFileReader::Bzip2(Crc32Reader::new(
                 bzip2_reader,
                 crc32,
-                ae2_encrypted,
+                verify_crc,
             )))
         }
         #[cfg(feature = "zstd")]
         CompressionMethod::Zstd => {
-            let zstd_reader = ZstdDecoder::new(reader).unwrap();
+            let zstd_reader = match zstd_dictionary {
+                Some(dictionary) => {
+                    ZstdDecoder::with_dictionary(io::BufReader::new(reader), dictionary)?
+                }
+                None => ZstdDecoder::new(reader).unwrap(),
+            };
             Ok(ZipFileReader::Zstd(Crc32Reader::new(
                 zstd_reader,
                 crc32,
-                ae2_encrypted,
+                verify_crc,
             )))
         }
         #[cfg(feature = "lzma")]
         CompressionMethod::Lzma => {
-            let reader = LzmaDecoder::new(reader);
+            let reader = LzmaDecoder::new(reader, uncompressed_size)?;
             Ok(ZipFileReader::Lzma(Crc32Reader::new(
                 Box::new(reader),
                 crc32,
-                ae2_encrypted,
+                verify_crc,
+            )))
+        }
+        #[cfg(feature = "brotli")]
+        CompressionMethod::Brotli => {
+            let brotli_reader = brotli::Decompressor::new(reader, BROTLI_BUFFER_SIZE);
+            Ok(ZipFileReader::Brotli(Crc32Reader::new(
+                Box::new(brotli_reader),
+                crc32,
+                verify_crc,
+            )))
+        }
+        #[cfg(feature = "legacy")]
+        CompressionMethod::Shrink => {
+            let reader = ShrinkDecoder::new(reader, uncompressed_size);
+            Ok(ZipFileReader::Shrink(Crc32Reader::new(
+                Box::new(reader),
+                crc32,
+                verify_crc,
+            )))
+        }
+        #[cfg(feature = "legacy")]
+        CompressionMethod::Implode => {
+            let reader = ImplodeDecoder::new(reader, uncompressed_size, compression_flags)?;
+            Ok(ZipFileReader::Implode(Crc32Reader::new(
+                Box::new(reader),
+                crc32,
+                verify_crc,
+            )))
+        }
+        #[cfg(feature = "ppmd")]
+        CompressionMethod::Ppmd => {
+            let reader = PpmdDecoder::new(reader, uncompressed_size)?;
+            Ok(ZipFileReader::Ppmd(Crc32Reader::new(
+                Box::new(reader),
+                crc32,
+                verify_crc,
+            )))
+        }
+        #[cfg(feature = "xz")]
+        CompressionMethod::Xz => {
+            let reader = XzDecoder::new(reader, uncompressed_size);
+            Ok(ZipFileReader::Xz(Crc32Reader::new(
+                Box::new(reader),
+                crc32,
+                verify_crc,
             )))
         }
         _ => Err(UnsupportedArchive("Compression method not supported")),
     }
 }
 
+/// Size of the buffer between the Brotli decoder and the compressed data
+#[cfg(feature = "brotli")]
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
 pub(crate) struct CentralDirectoryInfo {
     pub(crate) archive_offset: u64,
     pub(crate) directory_start: u64,
+    pub(crate) directory_size: u64,
     pub(crate) number_of_files: usize,
     pub(crate) disk_number: u32,
     pub(crate) disk_with_central_directory: u32,
+    pub(crate) is_zip64: bool,
 }
 
 impl<R> ZipArchive<R> {
@@ -361,21 +720,38 @@ impl<R> ZipArchive<R> {
             Some((_, file)) => file.header_start,
             None => 0,
         };
+        // The writer only adds the ZIP64 records when the entry count or offsets need them
+        let zip64 = files.len() > spec::ZIP64_ENTRY_THR || central_start > spec::ZIP64_BYTES_THR;
+        let total_entries = files.len() as u64;
         let shared = Arc::new(zip_archive::Shared {
             files,
             offset: initial_offset,
             dir_start: central_start,
+            pending: None,
+            lowercase_names: OnceLock::new(),
+            zip64,
+            total_entries,
+            trailing_data_len: 0,
         });
         Ok(Self {
             reader,
             shared,
             comment: comment.into(),
+            limits: DecompressionLimits::default(),
+            verify_crc: true,
+            aes_iterations: None,
+            strictness: Strictness::Lenient,
+            decompressors: Default::default(),
         })
     }
 
     /// Total size of the files in the archive, if it can be known. Doesn't include directories or
-    /// metadata.
+    /// metadata. Always `None` for an archive opened with [`ZipArchive::new_lazy`] until every
+    /// record has been parsed.
     pub fn decompressed_size(&self) -> Option<u128> {
+        if self.shared.pending.is_some() {
+            return None;
+        }
         let mut total = 0u128;
         for file in self.shared.files.values() {
             if file.using_data_descriptor {
@@ -385,13 +761,34 @@ impl<R> ZipArchive<R> {
         }
         Some(total)
     }
+
+    /// Total size of the files' compressed (and possibly encrypted) data in the archive, if it can
+    /// be known. Like [`ZipArchive::decompressed_size`], this only adds up the sizes recorded in
+    /// the central directory, without reading any data, and doesn't include headers. Always
+    /// `None` for an archive opened with [`ZipArchive::new_lazy`] until every record has been
+    /// parsed.
+    pub fn compressed_size(&self) -> Option<u128> {
+        if self.shared.pending.is_some() {
+            return None;
+        }
+        let mut total = 0u128;
+        for file in self.shared.files.values() {
+            if file.using_data_descriptor {
+                return None;
+            }
+            total = total.checked_add(file.compressed_size as u128)?;
+        }
+        Some(total)
+    }
 }
 
 impl<R: Read + Seek> ZipArchive<R> {
     pub(crate) fn merge_contents<W: Write + io::Seek>(
         &mut self,
         mut w: W,
+        buffer_size: usize,
     ) -> ZipResult<IndexMap<Box<str>, ZipFileData>> {
+        self.parse_pending(|_| false)?;
         if self.shared.files.is_empty() {
             return Ok(IndexMap::new());
         }
@@ -443,7 +840,7 @@ impl<R: Read + Seek> ZipArchive<R> {
          * handle, which it really shouldn't need to anyway. */
         let mut limited_raw = (&mut self.reader as &mut dyn Read).take(length_to_read);
         /* Copy over file data from source archive directly. */
-        io::copy(&mut limited_raw, &mut w)?;
+        copy_with_progress(&mut limited_raw, &mut w, &mut vec![0; buffer_size], |_| {})?;
 
         /* Return the files we've just written to the data stream. */
         Ok(new_files)
@@ -469,24 +866,25 @@ impl<R: Read + Seek> ZipArchive<R> {
         Ok(CentralDirectoryInfo {
             archive_offset,
             directory_start,
+            directory_size: footer.central_directory_size as u64,
             number_of_files,
             disk_number: footer.disk_number as u32,
             disk_with_central_directory: footer.disk_with_central_directory as u32,
+            is_zip64: false,
         })
     }
 
     fn get_directory_info_zip64(
         reader: &mut R,
-        footer: &spec::CentralDirectoryEnd,
         cde_start_pos: u64,
     ) -> ZipResult<Vec<ZipResult<CentralDirectoryInfo>>> {
         // See if there's a ZIP64 footer. The ZIP64 locator if present will
-        // have its signature 20 bytes in front of the standard footer. The
-        // standard footer, in turn, is 22+N bytes large, where N is the
-        // comment length. Therefore:
-        reader.seek(io::SeekFrom::End(
-            -(20 + 22 + footer.zip_file_comment.len() as i64),
-        ))?;
+        // have its signature 20 bytes in front of the standard footer. That
+        // isn't necessarily 22+N bytes from the end of the file, where N is
+        // the comment length, since data may have been appended. Therefore:
+        reader.seek(io::SeekFrom::Start(cde_start_pos.checked_sub(20).ok_or(
+            ZipError::InvalidArchive("File cannot contain ZIP64 central directory end"),
+        )?))?;
         let locator64 = spec::Zip64CentralDirectoryEndLocator::parse(reader)?;
 
         // We need to reassess `archive_offset`. We know where the ZIP64
@@ -536,9 +934,11 @@ impl<R: Read + Seek> ZipArchive<R> {
                         Ok(CentralDirectoryInfo {
                             archive_offset,
                             directory_start,
+                            directory_size: footer64.central_directory_size,
                             number_of_files: footer64.number_of_files as usize,
                             disk_number: footer64.disk_number,
                             disk_with_central_directory: footer64.disk_with_central_directory,
+                            is_zip64: true,
                         })
                     }
                 })
@@ -547,20 +947,16 @@ impl<R: Read + Seek> ZipArchive<R> {
         Ok(results)
     }
 
-    /// Get the directory start offset and number of files. This is done in a
-    /// separate function to ease the control flow design.
-    pub(crate) fn get_metadata(
+    /// Find the candidate central directories described by the ZIP32 and any ZIP64 footers.
+    fn get_directory_infos(
         reader: &mut R,
         footer: &spec::CentralDirectoryEnd,
         cde_start_pos: u64,
-    ) -> ZipResult<Shared> {
+    ) -> Vec<ZipResult<CentralDirectoryInfo>> {
         // Check if file has a zip64 footer
-        let mut results = Self::get_directory_info_zip64(reader, footer, cde_start_pos)
-            .unwrap_or_else(|e| vec![Err(e)]);
+        let mut results =
+            Self::get_directory_info_zip64(reader, cde_start_pos).unwrap_or_else(|e| vec![Err(e)]);
         let zip32_result = Self::get_directory_info_zip32(footer, cde_start_pos);
-        let mut invalid_errors = Vec::new();
-        let mut unsupported_errors = Vec::new();
-        let mut ok_results = Vec::new();
         results.iter_mut().for_each(|result| {
             if let Ok(central_dir) = result {
                 if let Ok(zip32_central_dir) = &zip32_result {
@@ -594,6 +990,83 @@ impl<R: Read + Seek> ZipArchive<R> {
         });
         results.push(zip32_result);
         results
+    }
+
+    /// Count the bytes after the end of central directory record, such as a signature or padding
+    /// that was appended to the archive
+    pub(crate) fn measure_trailing_data(
+        reader: &mut R,
+        footer: &spec::CentralDirectoryEnd,
+        cde_start_pos: u64,
+    ) -> ZipResult<u64> {
+        let file_length = reader.seek(io::SeekFrom::End(0))?;
+        Ok(file_length.saturating_sub(cde_start_pos + footer.record_len()))
+    }
+
+    /// Find the end of central directory record within the last `max_bytes` bytes of `reader`
+    /// that `open` accepts, and return it along with what `open` made of it
+    ///
+    /// The search starts from the end, so usually the first record found is the right one. But
+    /// data appended to an archive can contain something that looks like a record, whose offsets
+    /// only make sense if all the data before it were prepended data. So when `open` finds that a
+    /// record doesn't describe a valid archive, the search goes on to the one before it, and the
+    /// first error is returned if none of them do. Earlier records whose sizes can't fit before
+    /// them are skipped without being opened, and at most [`MAX_FOOTER_CANDIDATES`] are opened, so
+    /// appended data full of signatures can't make this parse the central directory over and
+    /// over.
+    fn find_archive<T>(
+        reader: &mut R,
+        max_bytes: u64,
+        mut open: impl FnMut(&mut R, &spec::CentralDirectoryEnd, u64) -> ZipResult<T>,
+    ) -> ZipResult<(spec::CentralDirectoryEnd, T)> {
+        let (mut footer, mut cde_start_pos) =
+            spec::CentralDirectoryEnd::find_and_parse_within(reader, max_bytes)?;
+        let search_upper_bound = reader.seek(io::SeekFrom::End(0))?.saturating_sub(max_bytes);
+        let mut first_error = None;
+        let mut candidates_opened = 0;
+        loop {
+            candidates_opened += 1;
+            match open(reader, &footer, cde_start_pos) {
+                Ok(opened) => return Ok((footer, opened)),
+                // An archive was found, but this crate can't read it
+                Err(e @ (ZipError::UnsupportedArchive(_) | ZipError::UnsupportedEncryption(_))) => {
+                    return Err(first_error.unwrap_or(e))
+                }
+                Err(e) => {
+                    first_error.get_or_insert(e);
+                }
+            }
+            loop {
+                if candidates_opened == MAX_FOOTER_CANDIDATES {
+                    return Err(first_error.unwrap());
+                }
+                match spec::CentralDirectoryEnd::find_and_parse_before(
+                    reader,
+                    cde_start_pos,
+                    search_upper_bound,
+                )? {
+                    Some(found) => (footer, cde_start_pos) = found,
+                    None => return Err(first_error.unwrap()),
+                }
+                if footer_is_plausible(&footer, cde_start_pos) {
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Get the directory start offset and number of files. This is done in a
+    /// separate function to ease the control flow design.
+    pub(crate) fn get_metadata(
+        reader: &mut R,
+        footer: &spec::CentralDirectoryEnd,
+        cde_start_pos: u64,
+    ) -> ZipResult<Shared> {
+        let trailing_data_len = Self::measure_trailing_data(reader, footer, cde_start_pos)?;
+        let mut invalid_errors = Vec::new();
+        let mut unsupported_errors = Vec::new();
+        let mut ok_results = Vec::new();
+        Self::get_directory_infos(reader, footer, cde_start_pos)
             .into_iter()
             .map(|result| {
                 result.and_then(|dir_info| {
@@ -618,13 +1091,18 @@ impl<R: Read + Seek> ZipArchive<R> {
                             files,
                             offset: dir_info.archive_offset,
                             dir_start: dir_info.directory_start,
+                            pending: None,
+                            lowercase_names: OnceLock::new(),
+                            zip64: dir_info.is_zip64,
+                            total_entries: dir_info.number_of_files as u64,
+                            trailing_data_len,
                         })
                     }
                 })
             })
             .for_each(|result| match result {
-                Err(ZipError::UnsupportedArchive(e)) => {
-                    unsupported_errors.push(ZipError::UnsupportedArchive(e))
+                Err(e @ (ZipError::UnsupportedArchive(_) | ZipError::UnsupportedEncryption(_))) => {
+                    unsupported_errors.push(e)
                 }
                 Err(e) => invalid_errors.push(e),
                 Ok(o) => ok_results.push(o),
@@ -643,72 +1121,748 @@ impl<R: Read + Seek> ZipArchive<R> {
         Ok(shared)
     }
 
+    /// Choose the central directory that [`ZipArchive::new_lazy`] trusts without parsing it: the
+    /// one pointing at the later central directory when both ZIP32 and ZIP64 footers are present.
+    /// Only candidates that start with a central directory header, or have no entries, count.
+    pub(crate) fn find_directory_info(
+        reader: &mut R,
+        footer: &spec::CentralDirectoryEnd,
+        cde_start_pos: u64,
+    ) -> ZipResult<CentralDirectoryInfo> {
+        let mut invalid_errors = Vec::new();
+        let mut unsupported_errors = Vec::new();
+        let mut ok_results = Vec::new();
+        for result in Self::get_directory_infos(reader, footer, cde_start_pos) {
+            match result {
+                Ok(dir_info) if dir_info.disk_number != dir_info.disk_with_central_directory => {
+                    unsupported_errors.push(ZipError::UnsupportedArchive(
+                        "Support for multi-disk files is not implemented",
+                    ))
+                }
+                Ok(dir_info) => {
+                    // When the ZIP64 records are present, the offset the ZIP32 footer implies is
+                    // past them, so it usually misses the directory by their length
+                    let signature = if dir_info.number_of_files == 0 {
+                        Some(spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE)
+                    } else {
+                        reader
+                            .seek(io::SeekFrom::Start(dir_info.directory_start))
+                            .ok()
+                            .and_then(|_| reader.read_u32_le().ok())
+                    };
+                    if signature == Some(spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE) {
+                        ok_results.push(dir_info);
+                    } else if signature == Some(spec::ARCHIVE_EXTRA_DATA_SIGNATURE) {
+                        unsupported_errors.push(encrypted_directory_error(reader));
+                    } else {
+                        invalid_errors
+                            .push(ZipError::InvalidArchive("Invalid Central Directory header"));
+                    }
+                }
+                Err(e @ (ZipError::UnsupportedArchive(_) | ZipError::UnsupportedEncryption(_))) => {
+                    unsupported_errors.push(e)
+                }
+                Err(e) => invalid_errors.push(e),
+            }
+        }
+        ok_results
+            .into_iter()
+            .max_by_key(|dir_info| dir_info.directory_start)
+            .ok_or_else(|| {
+                unsupported_errors
+                    .into_iter()
+                    .next()
+                    .unwrap_or_else(|| invalid_errors.into_iter().next().unwrap())
+            })
+    }
+
     /// Read a ZIP archive, collecting the files it contains
     ///
-    /// This uses the central directory record of the ZIP file, and ignores local file headers
-    pub fn new(mut reader: R) -> ZipResult<ZipArchive<R>> {
-        let (footer, cde_start_pos) = spec::CentralDirectoryEnd::find_and_parse(&mut reader)?;
-        let shared = Self::get_metadata(&mut reader, &footer, cde_start_pos)?;
+    /// This uses the central directory record of the ZIP file, and ignores local file headers. To
+    /// open an archive with other settings, see [`ZipArchiveBuilder`].
+    ///
+    /// An archive with no entries, which is just an end-of-central-directory record, is valid and
+    /// has a [`ZipArchive::len`] of 0. A file with no bytes at all isn't an archive, and gives
+    /// [`ZipError::InvalidArchive`].
+    pub fn new(reader: R) -> ZipResult<ZipArchive<R>> {
+        Self::new_with_eocd_search_limit(reader, spec::MAX_HEADER_AND_COMMENT_SIZE)
+    }
+
+    /// Read a ZIP archive, looking for its end-of-central-directory record only within the last
+    /// `max_bytes` bytes of `reader`
+    ///
+    /// [`ZipArchive::new`] searches the last 65557 bytes, which is the size of the record with the
+    /// longest possible archive comment. A larger limit finds archives that have data appended
+    /// after them, and a smaller one bounds the work done on files that aren't archives at all.
+    /// If no record is found, this returns `ZipError::InvalidArchive("EOCD not found")`.
+    pub fn new_with_eocd_search_limit(mut reader: R, max_bytes: u64) -> ZipResult<ZipArchive<R>> {
+        let (footer, shared) = Self::find_archive(&mut reader, max_bytes, Self::get_metadata)?;
+        Ok(ZipArchive {
+            reader,
+            shared: shared.into(),
+            comment: footer.zip_file_comment.into(),
+            limits: DecompressionLimits::default(),
+            verify_crc: true,
+            aes_iterations: None,
+            strictness: Strictness::Lenient,
+            decompressors: Default::default(),
+        })
+    }
+
+    /// Read a ZIP archive without keeping its central directory in memory up front
+    ///
+    /// Central directory records are kept as [`ZipArchive::by_index`], [`ZipArchive::by_name`]
+    /// and similar methods need them, scanning forward from the last record kept, and are cached
+    /// from then on. Memory use grows with the furthest entry reached rather than the size of the
+    /// archive, which helps with archives that have many entries when only the first few are
+    /// needed.
+    ///
+    /// Opening the archive only reads its end of central directory records. When several entries
+    /// have the same name, the first of them is kept and later ones are skipped as they're
+    /// reached, whereas [`ZipArchive::new`] keeps the last; finding the last would mean reading
+    /// every record. Until every record has been reached, [`ZipArchive::len`] counts those not
+    /// reached yet as if none of them were skipped, so it can fall as more are read. Methods that
+    /// take `&self` and list entries, such as [`ZipArchive::file_names`] and
+    /// [`ZipArchive::index_for_name`], only see the records kept so far. When both ZIP32 and
+    /// ZIP64 footers are present, the one pointing at the later central directory is trusted,
+    /// and opening fails if its records don't parse rather than trying the other.
+    pub fn new_lazy(mut reader: R) -> ZipResult<ZipArchive<R>> {
+        let (footer, (dir_info, trailing_data_len)) = Self::find_archive(
+            &mut reader,
+            spec::MAX_HEADER_AND_COMMENT_SIZE,
+            |reader, footer, cde_start_pos| {
+                Ok((
+                    Self::find_directory_info(reader, footer, cde_start_pos)?,
+                    Self::measure_trailing_data(reader, footer, cde_start_pos)?,
+                ))
+            },
+        )?;
+        let shared = Shared {
+            files: IndexMap::new(),
+            offset: dir_info.archive_offset,
+            dir_start: dir_info.directory_start,
+            pending: (dir_info.number_of_files > 0).then_some(PendingDirectory {
+                next_header: dir_info.directory_start,
+                remaining: dir_info.number_of_files,
+            }),
+            lowercase_names: OnceLock::new(),
+            zip64: dir_info.is_zip64,
+            total_entries: dir_info.number_of_files as u64,
+            trailing_data_len,
+        };
         Ok(ZipArchive {
             reader,
             shared: shared.into(),
             comment: footer.zip_file_comment.into(),
+            limits: DecompressionLimits::default(),
+            verify_crc: true,
+            aes_iterations: None,
+            strictness: Strictness::Lenient,
+            decompressors: Default::default(),
         })
     }
+
+    /// Parse pending central directory records until `done` returns true for one of them, or
+    /// there are none left.
+    pub(crate) fn parse_pending(&mut self, mut done: impl FnMut(&Shared) -> bool) -> ZipResult<()> {
+        while self.shared.pending.is_some() && !done(&self.shared) {
+            let shared = Arc::make_mut(&mut self.shared);
+            let pending = shared.pending.as_mut().unwrap();
+            self.reader.seek(io::SeekFrom::Start(pending.next_header))?;
+            let file = central_header_to_zip_file(&mut self.reader, shared.offset)?;
+            pending.next_header = self.reader.stream_position()?;
+            pending.remaining -= 1;
+            if pending.remaining == 0 {
+                shared.pending = None;
+            }
+            // The first record with a name is kept, so that one found already doesn't change
+            if !shared.files.contains_key(&file.file_name) {
+                shared.files.insert(file.file_name.clone(), file);
+            }
+        }
+        Ok(())
+    }
+
+    /// Make sure the record for `file_number` has been parsed, if it exists.
+    fn parse_pending_through(&mut self, file_number: usize) -> ZipResult<()> {
+        self.parse_pending(|shared| shared.files.len() > file_number)
+    }
+
+    /// Find the index of `name`, parsing pending records until it's found.
+    fn parse_pending_for_name(&mut self, name: &str) -> ZipResult<Option<usize>> {
+        self.parse_pending(|shared| shared.files.contains_key(name))?;
+        Ok(self.shared.files.get_index_of(name))
+    }
+
+    /// Recover what entries can be found in an archive whose central directory is missing or
+    /// damaged, such as a download that was cut short.
+    ///
+    /// This scans the whole of `reader` for local file headers and reconstructs each entry's
+    /// metadata from its local header, so fields that only the central directory records (such as
+    /// comments and external attributes) are missing. When an entry's sizes are in a trailing data
+    /// descriptor, its data is taken to end at the next ZIP signature. Entries whose data doesn't
+    /// fit in `reader` are skipped, as are signatures found inside another entry's data.
+    pub fn with_salvage(mut reader: R) -> ZipResult<ZipArchive<R>> {
+        let signatures = find_signatures(&mut reader)?;
+        let file_end = reader.seek(io::SeekFrom::End(0))?;
+        let mut files = IndexMap::new();
+        let mut entries_end = 0;
+        for (index, &(header_start, signature)) in signatures.iter().enumerate() {
+            if signature != spec::LOCAL_FILE_HEADER_SIGNATURE || header_start < entries_end {
+                continue;
+            }
+            reader.seek(io::SeekFrom::Start(header_start + 4))?;
+            let Ok(mut data) = local_header_to_zip_file_data(&mut reader) else {
+                continue;
+            };
+            let data_start = reader.stream_position()?;
+            let data_end = if data.using_data_descriptor {
+                let Some(data_end) = salvage_data_descriptor(
+                    &mut reader,
+                    &mut data,
+                    data_start,
+                    &signatures[index + 1..],
+                )?
+                else {
+                    continue;
+                };
+                data_end
+            } else {
+                data_start + data.compressed_size
+            };
+            if data_end > file_end {
+                continue;
+            }
+            data.header_start = header_start;
+            data.data_start = OnceLock::from(data_start);
+            entries_end = data_end;
+            files.entry(data.file_name.clone()).or_insert(data);
+        }
+        Self::from_finalized_writer(files, Box::new([]), reader, entries_end)
+    }
+
     /// Extract a Zip archive into a directory, overwriting files if they
     /// already exist. Paths are sanitized with [`ZipFile::enclosed_name`].
     ///
+    /// On Unix targets, entries for which [`ZipFile::is_symlink`] is true are extracted as
+    /// symbolic links. The extraction fails with [`ZipError::InvalidPath`] if a link's target is
+    /// absolute or would resolve to a path outside `directory`, or if an entry would be written
+    /// through a link that leads outside it.
+    ///
     /// Extraction is not atomic. If an error is encountered, some of the files
     /// may be left on disk. However, on Unix targets, no newly-created directories with part but
     /// not all of their contents extracted will be readable, writable or usable as process working
     /// directories by any non-root user except you.
     pub fn extract<P: AsRef<Path>>(&mut self, directory: P) -> ZipResult<()> {
-        use std::fs;
-        #[cfg(unix)]
-        let mut files_by_unix_mode = Vec::new();
-        for i in 0..self.len() {
-            let mut file = self.by_index(i)?;
-            let filepath = file
-                .enclosed_name()
-                .ok_or(ZipError::InvalidArchive("Invalid file path"))?;
+        self.extract_with_options(directory, ExtractOptions::default())
+    }
 
-            let outpath = directory.as_ref().join(filepath);
+    /// Extract a Zip archive into a directory like [`ZipArchive::extract`], with `options`
+    /// controlling where each entry is extracted to and which metadata is restored on the
+    /// extracted files and directories.
+    pub fn extract_with_options<P: AsRef<Path>>(
+        &mut self,
+        directory: P,
+        options: ExtractOptions,
+    ) -> ZipResult<()> {
+        self.extract_filtered(directory.as_ref(), options, |_| true)
+    }
 
-            if file.is_dir() {
-                Self::make_writable_dir_all(&outpath)?;
-            } else {
-                if let Some(p) = outpath.parent() {
-                    Self::make_writable_dir_all(p)?;
-                }
-                let mut outfile = fs::File::create(&outpath)?;
-                io::copy(&mut file, &mut outfile)?;
+    /// Extract only the entries whose names match at least one of `patterns`, like
+    /// [`ZipArchive::extract`] otherwise. Other entries are skipped without reading their local
+    /// headers, and the only directories created are those that matching entries are in.
+    ///
+    /// Patterns are matched against the whole entry name, one `/`-separated component at a time.
+    /// `*` matches any run of characters within a component and `?` matches any one character,
+    /// while a component that's exactly `**` matches any number of components, including none. So
+    /// `*.json` only matches files at the top level, and `**/*.json` matches them everywhere. A
+    /// directory's name is matched without its trailing `/`.
+    pub fn extract_matching<P: AsRef<Path>>(
+        &mut self,
+        directory: P,
+        patterns: &[&str],
+    ) -> ZipResult<()> {
+        self.extract_filtered(directory.as_ref(), ExtractOptions::default(), |name| {
+            patterns.iter().any(|pattern| glob::matches(pattern, name))
+        })
+    }
+
+    /// Returns an iterator over the names of the entries that match `pattern`, using the same
+    /// syntax as [`ZipArchive::extract_matching`].
+    pub fn names_matching(&self, pattern: &str) -> impl Iterator<Item = &str> {
+        let pattern: Box<str> = pattern.into();
+        self.file_names()
+            .filter(move |name| glob::matches(&pattern, name))
+    }
+
+    fn extract_filtered(
+        &mut self,
+        directory: &Path,
+        options: ExtractOptions,
+        filter: impl Fn(&str) -> bool,
+    ) -> ZipResult<()> {
+        let target = options.target_directory(directory)?;
+        let mut state = ExtractState::new(options.buffer_size);
+        // The filter looks at every name, and the progress reports the total size
+        self.parse_pending(|_| false)?;
+        let total_entries = self.len();
+        let total_bytes = self.decompressed_size();
+        let mut failures = Vec::new();
+        for i in 0..total_entries {
+            let entry_name = self.name_for_index(i).unwrap();
+            if !filter(entry_name) {
+                options.report_progress(ExtractProgress {
+                    current_index: i,
+                    total_entries,
+                    bytes_written: state.bytes_written,
+                    total_bytes,
+                    current_name: entry_name,
+                });
+                continue;
             }
-            #[cfg(unix)]
-            {
-                // Check for real permissions, which we'll set in a second pass
-                if let Some(mode) = file.unix_mode() {
-                    files_by_unix_mode.push((outpath.clone(), mode));
+            let name: Box<str> = if options.on_progress.is_some() || options.continue_on_error {
+                entry_name.into()
+            } else {
+                Box::default()
+            };
+            let result =
+                self.extract_entry(i, &target, &options, &mut state, false, &mut |bytes| {
+                    options.report_progress(ExtractProgress {
+                        current_index: i,
+                        total_entries,
+                        bytes_written: bytes,
+                        total_bytes,
+                        current_name: &name,
+                    })
+                });
+            if let Err(e) = result {
+                if !options.continue_on_error {
+                    return Err(e);
                 }
+                state.remove_partial_file();
+                failures.push((name.to_string(), e));
             }
+            options.report_progress(ExtractProgress {
+                current_index: i,
+                total_entries,
+                bytes_written: state.bytes_written,
+                total_bytes,
+                current_name: &name,
+            });
         }
-        #[cfg(unix)]
-        {
-            use std::cmp::Reverse;
-            use std::os::unix::fs::PermissionsExt;
-
-            if files_by_unix_mode.len() > 1 {
-                // Ensure we update children's permissions before making a parent unwritable
-                files_by_unix_mode.sort_by_key(|(path, _)| Reverse(path.clone()));
-            }
-            for (path, mode) in files_by_unix_mode.into_iter() {
-                fs::set_permissions(&path, fs::Permissions::from_mode(mode))?;
-            }
+        state.finish()?;
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(ZipError::ExtractionFailed(failures))
         }
-        Ok(())
     }
 
-    fn make_writable_dir_all<T: AsRef<Path>>(outpath: T) -> Result<(), ZipError> {
-        create_dir_all(outpath.as_ref())?;
+    /// Extract entry `i` to wherever `options` puts it in `target`, noting in `state` what's
+    /// left to do for it once every entry has been extracted. `report` is given the total number
+    /// of bytes written so far as a file's contents are copied.
+    ///
+    /// With `defer_links`, symbolic and hard links aren't extracted and this returns false, so
+    /// that they can be extracted once every entry they could refer to has been.
+    #[cfg_attr(not(unix), allow(unused_variables))]
+    fn extract_entry(
+        &mut self,
+        i: usize,
+        target: &ExtractTarget,
+        options: &ExtractOptions,
+        state: &mut ExtractState,
+        defer_links: bool,
+        report: &mut dyn FnMut(u64),
+    ) -> ZipResult<bool> {
+        use std::fs;
+
+        state.partial_file = None;
+        let mut file = self.by_index_for_reading(i)?;
+        let Some(filepath) = options.destination(&file)? else {
+            return Ok(true);
+        };
+
+        let outpath = target.directory.join(filepath);
+        let parent = if file.is_dir() {
+            outpath.as_path()
+        } else {
+            outpath
+                .parent()
+                .ok_or_else(|| ZipError::InvalidPath(file.name().into()))?
+        };
+        Self::make_writable_dir_all_within(&target.root, parent, file.name())?;
+
+        #[cfg(unix)]
+        if file.is_symlink() {
+            if defer_links {
+                return Ok(false);
+            }
+            Self::extract_symlink(&mut file, &target.root, &outpath)?;
+            // The link's target is its contents, so it counts towards the total
+            state.bytes_written += file.size();
+            return Ok(true);
+        }
+        #[cfg(unix)]
+        if let Some(target_name) = file.hardlink_target().filter(|_| options.hard_links) {
+            if defer_links {
+                return Ok(false);
+            }
+            let name: Box<str> = file.name().into();
+            let mode = file.unix_mode().filter(|_| options.preserve_permissions);
+            let mtime = file
+                .last_modified_precise()
+                .filter(|_| options.preserve_mtime);
+            let ownership = file.unix_ownership().filter(|_| options.preserve_ownership);
+            state.bytes_written += file.size();
+            drop(file);
+            if self.extract_hardlink(
+                &target_name,
+                &outpath,
+                options,
+                &state.extracted_files,
+                &mut state.buf,
+            )? {
+                // The target's contents were copied, so this is a file of its own
+                if let Some(mode) = mode {
+                    state.files_by_unix_mode.push((outpath.clone(), mode));
+                }
+                if let Some(mtime) = mtime {
+                    state.files_by_mtime.push((outpath.clone(), mtime));
+                }
+                if let Some(ownership) = ownership {
+                    state.files_by_ownership.push((outpath.clone(), ownership));
+                }
+            }
+            state.extracted_files.insert(name, outpath);
+            return Ok(true);
+        }
+        if !file.is_dir() {
+            let mut open_options = fs::OpenOptions::new();
+            open_options.write(true).create(true).truncate(true);
+            #[cfg(windows)]
+            if options.preserve_permissions {
+                use std::os::windows::fs::OpenOptionsExt;
+                // Windows file attributes use the same bits as MS-DOS ones. A read-only file
+                // is made read-only once it's been written.
+                let dos_attributes = DosAttributes {
+                    read_only: false,
+                    ..file.dos_attributes()
+                };
+                open_options.attributes(dos_attributes.to_external_attributes());
+            }
+            state.partial_file = Some(outpath.clone());
+            let mut outfile = open_options.open(&outpath)?;
+            let bytes_written = state.bytes_written;
+            let copied =
+                copy_with_progress(&mut file, &mut outfile, &mut state.buf, |entry_bytes| {
+                    report(bytes_written + entry_bytes)
+                })?;
+            state.bytes_written += copied;
+            #[cfg(windows)]
+            if options.preserve_permissions && file.dos_attributes().read_only {
+                let mut permissions = outfile.metadata()?.permissions();
+                permissions.set_readonly(true);
+                outfile.set_permissions(permissions)?;
+            }
+            #[cfg(unix)]
+            if options.hard_links {
+                state
+                    .extracted_files
+                    .insert(file.name().into(), outpath.clone());
+            }
+        }
+        #[cfg(unix)]
+        {
+            // Check for real permissions, times and owners, which we'll set in a second
+            // pass
+            if let Some(mode) = file.unix_mode().filter(|_| options.preserve_permissions) {
+                state.files_by_unix_mode.push((outpath.clone(), mode));
+            }
+            if let Some(mtime) = file
+                .last_modified_precise()
+                .filter(|_| options.preserve_mtime)
+            {
+                state.files_by_mtime.push((outpath.clone(), mtime));
+            }
+            if let Some(ownership) = file.unix_ownership().filter(|_| options.preserve_ownership) {
+                state.files_by_ownership.push((outpath.clone(), ownership));
+            }
+        }
+        Ok(true)
+    }
+
+    /// Extract a Zip archive into a directory like [`ZipArchive::extract`], but decompress up to
+    /// `num_threads` entries at once. Each thread reads from its own clone of the archive, so this
+    /// is only useful when cloning `R` yields an independent handle (for example a memory-mapped
+    /// buffer or a `Cursor` over shared bytes).
+    ///
+    /// Symbolic and hard links are only created once every other entry has been extracted. If
+    /// extracting any entry fails, the remaining entries are skipped, the partially-written file
+    /// for each failed entry is removed, and the error for the entry with the lowest index is
+    /// returned.
+    pub fn extract_parallel<P: AsRef<Path>>(
+        &self,
+        directory: P,
+        num_threads: usize,
+    ) -> ZipResult<()>
+    where
+        R: Clone + Send,
+    {
+        self.extract_parallel_with_options(directory, num_threads, ExtractOptions::default())
+    }
+
+    /// Extract a Zip archive into a directory like [`ZipArchive::extract_parallel`], with
+    /// `options` applied to each entry as [`ZipArchive::extract_with_options`] applies them.
+    ///
+    /// Progress is reported once each entry has been extracted, in the order that they finish,
+    /// but not while a file is being written.
+    pub fn extract_parallel_with_options<P: AsRef<Path>>(
+        &self,
+        directory: P,
+        num_threads: usize,
+        options: ExtractOptions,
+    ) -> ZipResult<()>
+    where
+        R: Clone + Send,
+    {
+        use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+        if self.shared.pending.is_some() {
+            let mut archive = self.clone();
+            archive.parse_pending(|_| false)?;
+            return archive.extract_parallel_with_options(directory, num_threads, options);
+        }
+        let target = options.target_directory(directory.as_ref())?;
+        let total_entries = self.len();
+        let total_bytes = self.decompressed_size();
+        let next_index = AtomicUsize::new(0);
+        let bytes_written = AtomicU64::new(0);
+        let failed = AtomicBool::new(false);
+        let results: Vec<_> = std::thread::scope(|scope| {
+            let workers: Vec<_> = (0..num_threads.max(1))
+                .map(|_| {
+                    let mut archive = self.clone();
+                    let (next_index, bytes_written, failed) =
+                        (&next_index, &bytes_written, &failed);
+                    let (target, options) = (&target, &options);
+                    scope.spawn(move || {
+                        let mut state = ExtractState::new(options.buffer_size);
+                        let mut links = Vec::new();
+                        let mut failures = Vec::new();
+                        while !failed.load(Ordering::Relaxed) {
+                            let index = next_index.fetch_add(1, Ordering::Relaxed);
+                            if index >= total_entries {
+                                break;
+                            }
+                            let entry_start = state.bytes_written;
+                            match archive.extract_entry(
+                                index,
+                                target,
+                                options,
+                                &mut state,
+                                true,
+                                &mut |_| {},
+                            ) {
+                                Ok(true) => {}
+                                Ok(false) => {
+                                    links.push(index);
+                                    continue;
+                                }
+                                Err(e) => {
+                                    state.remove_partial_file();
+                                    if !options.continue_on_error {
+                                        failed.store(true, Ordering::Relaxed);
+                                    }
+                                    failures.push((index, e));
+                                }
+                            }
+                            let entry_bytes = state.bytes_written - entry_start;
+                            options.report_progress(ExtractProgress {
+                                current_index: index,
+                                total_entries,
+                                bytes_written: bytes_written
+                                    .fetch_add(entry_bytes, Ordering::Relaxed)
+                                    + entry_bytes,
+                                total_bytes,
+                                current_name: archive.name_for_index(index).unwrap(),
+                            });
+                        }
+                        (state, links, failures)
+                    })
+                })
+                .collect();
+            workers
+                .into_iter()
+                .map(|worker| worker.join().unwrap())
+                .collect()
+        });
+
+        let mut state = ExtractState::new(options.buffer_size);
+        let mut links = Vec::new();
+        let mut failures = Vec::new();
+        for (worker_state, worker_links, worker_failures) in results {
+            state.merge(worker_state);
+            links.extend(worker_links);
+            failures.extend(worker_failures);
+        }
+        failures.sort_by_key(|(index, _)| *index);
+        if !options.continue_on_error && !failures.is_empty() {
+            return Err(failures.swap_remove(0).1);
+        }
+        links.sort_unstable();
+        let mut archive = self.clone();
+        for index in links {
+            if let Err(e) =
+                archive.extract_entry(index, &target, &options, &mut state, false, &mut |_| {})
+            {
+                state.remove_partial_file();
+                if !options.continue_on_error {
+                    return Err(e);
+                }
+                failures.push((index, e));
+            }
+            options.report_progress(ExtractProgress {
+                current_index: index,
+                total_entries,
+                bytes_written: state.bytes_written,
+                total_bytes,
+                current_name: archive.name_for_index(index).unwrap(),
+            });
+        }
+        state.finish()?;
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            let failures = failures
+                .into_iter()
+                .map(|(index, e)| (self.name_for_index(index).unwrap().to_string(), e))
+                .collect();
+            Err(ZipError::ExtractionFailed(failures))
+        }
+    }
+
+    /// Decompress the file named `name` into `writer`, returning the number of bytes written
+    ///
+    /// The data is copied through a fixed-size buffer, so memory use doesn't depend on the size of
+    /// the file. The limits set with [`ZipArchive::set_max_decompressed_size`] and
+    /// [`ZipArchive::set_max_compression_ratio`] apply as they do to [`ZipArchive::by_name`], and
+    /// the data is checked against its CRC-32 unless this has been disabled with
+    /// [`ZipArchive::set_verify_crc`]. If an error occurs partway through, `writer` will already
+    /// have received some of the data.
+    pub fn extract_file_to_writer<W: Write + ?Sized>(
+        &mut self,
+        name: &str,
+        writer: &mut W,
+    ) -> ZipResult<u64> {
+        let Some(index) = self.parse_pending_for_name(name)? else {
+            return Err(ZipError::FileNotFound);
+        };
+        let mut file = self.by_index_for_reading(index)?;
+        Ok(copy(&mut file, writer)?)
+    }
+
+    /// Extract a Zip archive into a directory like [`ZipArchive::extract`], but check each entry's
+    /// name rather than relying on [`ZipFile::enclosed_name`] to make it safe.
+    ///
+    /// The whole extraction fails with [`ZipError::InvalidPath`] naming the offending entry if any
+    /// name is absolute, has a Windows drive or UNC prefix, contains a `..` component (with either
+    /// `/` or `\\` as the separator) or a NUL byte, or if the canonicalized parent directory of the
+    /// output path doesn't lie within the canonicalized `directory`, as can happen when it
+    /// already contains symlinks. Nothing is written for an entry that fails these checks.
+    /// Otherwise, entries are extracted as [`ZipArchive::extract`] extracts them, including
+    /// symbolic links.
+    pub fn extract_safe<P: AsRef<Path>>(&mut self, directory: P) -> ZipResult<()> {
+        let options = ExtractOptions {
+            check_paths: true,
+            ..ExtractOptions::default()
+        };
+        self.extract_with_options(directory, options)
+    }
+
+    #[cfg(unix)]
+    fn extract_symlink(file: &mut ZipFile, root: &Path, outpath: &Path) -> ZipResult<()> {
+        use std::ffi::OsString;
+        use std::os::unix::ffi::OsStringExt;
+
+        let mut target = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut target)?;
+        let target = PathBuf::from(OsString::from_vec(target));
+        let parent = outpath
+            .parent()
+            .ok_or_else(|| ZipError::InvalidPath(file.name().into()))?;
+        match resolve_link_target(&parent.canonicalize()?, &target) {
+            Some(resolved) if resolved.starts_with(root) => {}
+            _ => return Err(ZipError::InvalidPath(file.name().into())),
+        }
+        if outpath.symlink_metadata().is_ok() {
+            std::fs::remove_file(outpath)?;
+        }
+        std::os::unix::fs::symlink(&target, outpath)?;
+        Ok(())
+    }
+
+    /// Extract a hard link to the entry named `target`. If `target` has already been extracted,
+    /// `outpath` becomes a hard link to it; otherwise, if `options` would extract the target, its
+    /// contents are copied from the archive and this returns true. The directory `outpath` is in
+    /// must already exist.
+    #[cfg(unix)]
+    fn extract_hardlink(
+        &mut self,
+        target: &str,
+        outpath: &Path,
+        options: &ExtractOptions,
+        extracted_files: &std::collections::HashMap<Box<str>, PathBuf>,
+        buf: &mut [u8],
+    ) -> ZipResult<bool> {
+        if outpath.symlink_metadata().is_ok() {
+            std::fs::remove_file(outpath)?;
+        }
+        if let Some(target_path) = extracted_files.get(target) {
+            std::fs::hard_link(target_path, outpath)?;
+            return Ok(false);
+        }
+        let Some(index) = self.parse_pending_for_name(target)? else {
+            return Err(ZipError::FileNotFound);
+        };
+        let mut target_file = self.by_index_for_reading(index)?;
+        if target_file.is_dir() || target_file.is_symlink() {
+            return Err(ZipError::InvalidArchive(
+                "Hard link target is not a regular file",
+            ));
+        }
+        // A link mustn't copy the contents of an entry that isn't extracted itself
+        if options.destination(&target_file)?.is_none() {
+            return Err(ZipError::FileNotFound);
+        }
+        let mut outfile = std::fs::File::create(outpath)?;
+        copy_with_progress(&mut target_file, &mut outfile, buf, |_| {})?;
+        Ok(true)
+    }
+
+    /// Create `dir` like [`Self::make_writable_dir_all`], but fail with [`ZipError::InvalidPath`]
+    /// naming the entry `name` if it doesn't lie within `root`, which must be a real path, as
+    /// happens when a link leads out of it.
+    ///
+    /// The deepest of `dir` and its ancestors that already exists is checked before anything is
+    /// created, so nothing is created outside `root` either.
+    fn make_writable_dir_all_within(root: &Path, dir: &Path, name: &str) -> ZipResult<()> {
+        let invalid_path = || ZipError::InvalidPath(name.into());
+        let mut existing = dir;
+        while existing.symlink_metadata().is_err() {
+            existing = existing.parent().ok_or_else(invalid_path)?;
+        }
+        if !existing.canonicalize()?.starts_with(root) {
+            return Err(invalid_path());
+        }
+        Self::make_writable_dir_all(dir)?;
+        if !dir.canonicalize()?.starts_with(root) {
+            return Err(invalid_path());
+        }
+        Ok(())
+    }
+
+    fn make_writable_dir_all<T: AsRef<Path>>(outpath: T) -> Result<(), ZipError> {
+        create_dir_all(outpath.as_ref())?;
         #[cfg(unix)]
         {
             // Dirs must be writable until all normal files are extracted
@@ -718,9 +1872,221 @@ impl<R: Read + Seek> ZipArchive<R> {
         Ok(())
     }
 
+    /// Decode the names of entries that don't have the UTF-8 flag set using `encoding`, rather than
+    /// IBM codepage 437 as the ZIP specification says. This changes what [`ZipFile::name`],
+    /// [`ZipArchive::file_names`] and [`ZipArchive::by_name`] see; [`ZipFile::name_raw`] is
+    /// unaffected.
+    ///
+    /// If two files' names would then be the same, this fails with [`ZipError::DuplicateName`]
+    /// and leaves the names as they were, rather than hiding one of the files. For an archive
+    /// opened with [`ZipArchive::new_lazy`], this parses every remaining central directory record
+    /// first, since any of them could collide.
+    pub fn set_name_encoding(&mut self, encoding: NameEncoding) -> ZipResult<()> {
+        self.parse_pending(|_| false)?;
+        let mut files = IndexMap::with_capacity(self.shared.files.len());
+        for file in self.shared.files.values() {
+            let mut file = file.clone();
+            if !file.is_utf8 {
+                file.file_name = decode_file_name(&file, encoding);
+            }
+            if let Some(file) = files.insert(file.file_name.clone(), file) {
+                return Err(ZipError::DuplicateName(file.file_name));
+            }
+        }
+        self.shared = Arc::new(Shared {
+            files,
+            offset: self.shared.offset,
+            dir_start: self.shared.dir_start,
+            pending: None,
+            lowercase_names: OnceLock::new(),
+            zip64: self.shared.zip64,
+            total_entries: self.shared.total_entries,
+            trailing_data_len: self.shared.trailing_data_len,
+        });
+        Ok(())
+    }
+
+    /// Limit how many bytes may be read from any one file in this archive. Once more than `limit`
+    /// bytes have been decompressed, reading from the [`ZipFile`] fails with an I/O error.
+    ///
+    /// This applies to every compression method, and guards against "zip bombs" that expand a
+    /// small entry into far more data than its header claims.
+    pub fn set_max_decompressed_size(&mut self, limit: u64) {
+        self.limits.max_decompressed_size = Some(limit);
+    }
+
+    /// Limit the ratio of decompressed to compressed size for any one file in this archive. Once
+    /// the bytes decompressed from a [`ZipFile`] exceed `ratio` times its compressed size,
+    /// reading from it fails with an I/O error.
+    pub fn set_max_compression_ratio(&mut self, ratio: f64) {
+        self.limits.max_compression_ratio = Some(ratio);
+    }
+
+    /// Choose whether the CRC-32 of each file read from this archive is checked against the value
+    /// stored in the archive. This is enabled by default.
+    ///
+    /// The check happens once a [`ZipFile`] has been read to the end: the read that reaches EOF
+    /// fails with an I/O error wrapping [`ZipError::InvalidChecksum`] if the data doesn't match.
+    /// Files that are only partly read are never checked. AE-2 encrypted files don't store a
+    /// CRC-32, so they're never checked either.
+    pub fn set_verify_crc(&mut self, verify_crc: bool) {
+        self.verify_crc = verify_crc;
+    }
+
+    /// Choose what happens when a file's local header disagrees with its central directory record.
+    /// The default is [`Strictness::Lenient`], which uses the central directory.
+    ///
+    /// With [`Strictness::Strict`], the local header is read in full and compared each time a file
+    /// is opened, including with [`ZipArchive::by_index_raw`], and a mismatch is reported as
+    /// [`ZipError::InconsistentMetadata`]. Tools that inspect untrusted archives can use this to
+    /// catch ones crafted to look different to readers that use the local headers. Files that share
+    /// another file's local header, as made by
+    /// [`ZipWriter::shallow_copy_file`](crate::ZipWriter::shallow_copy_file) and
+    /// [`ZipWriter::set_dedup`](crate::ZipWriter::set_dedup), don't match it either.
+    pub fn set_strictness(&mut self, strictness: Strictness) {
+        self.strictness = strictness;
+    }
+
+    /// Use `iterations` rounds of PBKDF2 to derive the key for every AES encrypted file in this
+    /// archive, rather than the count recorded by
+    /// [`FileOptions::with_aes_iterations`](crate::write::FileOptions::with_aes_iterations).
+    ///
+    /// By default, files that record a count use it, and other files use the standard 1000. A
+    /// recorded count comes from the archive itself, so one above 1,000,000 is refused with
+    /// [`ZipError::UnsupportedArchive`] rather than letting a hostile archive make decryption
+    /// arbitrarily slow; setting this is the only way to open such a file.
+    #[cfg(feature = "aes-crypto")]
+    pub fn set_aes_iterations(&mut self, iterations: u32) {
+        self.aes_iterations = Some(iterations);
+    }
+
+    /// Check the integrity of every file in this archive without writing anything to disk.
+    ///
+    /// Each file is fully decompressed, and its CRC-32 and uncompressed size are compared with
+    /// the values stored in the archive, regardless of [`ZipArchive::set_verify_crc`]. Encrypted
+    /// files are skipped. Rather than stopping at the first problem, this returns the name of
+    /// every file that failed along with its error.
+    pub fn verify(&mut self) -> Result<(), Vec<(String, ZipError)>> {
+        let verify_crc = mem::replace(&mut self.verify_crc, true);
+        let mut failures = Vec::new();
+        for file_number in 0..self.len() {
+            if let Err(e) = self.parse_pending_through(file_number) {
+                failures.push((format!("#{file_number}"), e));
+                break;
+            }
+            let Some((name, data)) = self.shared.files.get_index(file_number) else {
+                break;
+            };
+            if data.encrypted {
+                continue;
+            }
+            let name = name.to_string();
+            if let Err(e) = self.verify_file(file_number) {
+                failures.push((name, e));
+            }
+        }
+        self.verify_crc = verify_crc;
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures)
+        }
+    }
+
+    fn verify_file(&mut self, file_number: usize) -> ZipResult<()> {
+        let mut file = self.by_index(file_number)?;
+        let size = copy(&mut file, &mut sink()).map_err(unwrap_zip_error)?;
+        if size != file.size() {
+            return Err(ZipError::InvalidArchive(
+                "Uncompressed size doesn't match the central directory",
+            ));
+        }
+        Ok(())
+    }
+
+    /// Decompress every file in this archive into memory, keyed by name. Directories are skipped.
+    ///
+    /// This is meant for small archives and tests, since the whole archive's uncompressed contents
+    /// are held at once. Each file is read like [`ZipArchive::by_index`] reads it, so the limits
+    /// set with [`ZipArchive::set_max_decompressed_size`] and
+    /// [`ZipArchive::set_max_compression_ratio`] apply to each file, and the first error stops
+    /// reading and is returned.
+    pub fn into_map(mut self) -> ZipResult<std::collections::HashMap<String, Vec<u8>>> {
+        self.parse_pending(|_| false)?;
+        let mut map = std::collections::HashMap::with_capacity(self.len());
+        for file_number in 0..self.len() {
+            let mut file = self.by_index(file_number)?;
+            if file.is_dir() {
+                continue;
+            }
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents).map_err(unwrap_zip_error)?;
+            map.insert(file.name().to_owned(), contents);
+        }
+        Ok(map)
+    }
+
+    /// Compute a SHA-256 digest of every entry's name and decompressed contents, so that two
+    /// archives holding the same files compare equal however they were compressed.
+    ///
+    /// Entries are taken in central-directory order, so archives with the same files in a
+    /// different order have different digests; directories are included with empty contents.
+    /// For each entry, the length of its name as a little-endian `u64`, the name's bytes and the
+    /// SHA-256 of its contents are fed to the digest in turn. Every entry is fully decompressed
+    /// as [`ZipArchive::by_index`] reads it, so this takes as long as extracting the archive, and
+    /// the first error is returned.
+    #[cfg(feature = "sha2")]
+    pub fn content_digest(&mut self) -> ZipResult<[u8; 32]> {
+        use sha2::{Digest, Sha256};
+
+        self.parse_pending(|_| false)?;
+        let mut digest = Sha256::new();
+        for file_number in 0..self.len() {
+            let mut file = self.by_index(file_number)?;
+            let mut contents = Sha256::new();
+            copy(&mut file, &mut contents).map_err(unwrap_zip_error)?;
+            let name = file.name_raw();
+            digest.update((name.len() as u64).to_le_bytes());
+            digest.update(name);
+            digest.update(contents.finalize());
+        }
+        Ok(digest.finalize().into())
+    }
+
+    /// Read every extra field in the local header of a contained file. These often differ from the
+    /// central directory's, which [`ZipFile::raw_extra_fields`] returns.
+    pub fn local_extra_fields(&mut self, file_number: usize) -> ZipResult<Vec<RawExtraField>> {
+        self.parse_pending_through(file_number)?;
+        let (_, data) = self
+            .shared
+            .files
+            .get_index(file_number)
+            .ok_or(ZipError::FileNotFound)?;
+        self.reader.seek(io::SeekFrom::Start(data.header_start))?;
+        let signature = self.reader.read_u32_le()?;
+        if signature != spec::LOCAL_FILE_HEADER_SIGNATURE {
+            return Err(ZipError::InvalidArchive("Invalid local file header"));
+        }
+        self.reader.seek(io::SeekFrom::Current(22))?;
+        let file_name_length = self.reader.read_u16_le()? as i64;
+        let extra_field_length = self.reader.read_u16_le()? as usize;
+        self.reader.seek(io::SeekFrom::Current(file_name_length))?;
+        let mut extra_field = vec![0; extra_field_length];
+        self.reader.read_exact(&mut extra_field)?;
+        Ok(RawExtraField::parse_all(&extra_field).collect())
+    }
+
     /// Number of files contained in this zip.
+    ///
+    /// For an archive opened with [`ZipArchive::new_lazy`], this can be too high until every
+    /// central directory record has been read, if some of those left have the same name.
     pub fn len(&self) -> usize {
-        self.shared.files.len()
+        let pending = self
+            .shared
+            .pending
+            .as_ref()
+            .map_or(0, |pending| pending.remaining);
+        self.shared.files.len() + pending
     }
 
     /// Whether this zip archive contains no files
@@ -728,6 +2094,21 @@ impl<R: Read + Seek> ZipArchive<R> {
         self.len() == 0
     }
 
+    /// Whether the central directory was found through a ZIP64 end of central directory record,
+    /// which archives with more than 65535 entries or over 4 GiB need
+    pub fn is_zip64(&self) -> bool {
+        self.shared.zip64
+    }
+
+    /// The number of entries recorded in the end of central directory record, read from the ZIP64
+    /// record when [`ZipArchive::is_zip64`] is true.
+    ///
+    /// This is usually the same as [`ZipArchive::len`], but can be higher when several entries
+    /// have the same name, since only the last of them is kept.
+    pub fn total_entries(&self) -> u64 {
+        self.shared.total_entries
+    }
+
     /// Get the offset from the beginning of the underlying reader that this zip begins at, in bytes.
     ///
     /// Normally this value is zero, but if the zip has arbitrary data prepended to it, then this value will be the size
@@ -736,6 +2117,30 @@ impl<R: Read + Seek> ZipArchive<R> {
         self.shared.offset
     }
 
+    /// Get the length of any data before the archive's first entry, in bytes
+    ///
+    /// This is the size of the stub in a self-extracting archive, or of other data such as a
+    /// shebang line that was prepended to the archive. Unlike [`ZipArchive::offset`], it doesn't
+    /// depend on whether the archive's offsets were adjusted to account for that data. For an
+    /// archive opened with [`ZipArchive::new_lazy`], only the records parsed so far are considered.
+    pub fn prepended_data_len(&self) -> u64 {
+        self.shared
+            .files
+            .values()
+            .map(|file| file.header_start)
+            .min()
+            .unwrap_or(self.shared.dir_start)
+    }
+
+    /// Get the length of any data after the end of the archive, in bytes
+    ///
+    /// Some tools append data to an archive, such as a signature or padding to a block size. The
+    /// end of central directory record is still found as long as it's within the search limit
+    /// (see [`ZipArchive::new_with_eocd_search_limit`]), and this is the number of bytes after it.
+    pub fn trailing_data_len(&self) -> u64 {
+        self.shared.trailing_data_len
+    }
+
     /// Get the comment of the zip archive.
     pub fn comment(&self) -> &[u8] {
         &self.comment
@@ -746,6 +2151,28 @@ impl<R: Read + Seek> ZipArchive<R> {
         self.shared.files.keys().map(|s| s.as_ref())
     }
 
+    /// Returns an iterator over the names of the directories in this archive, which are the
+    /// entries whose name ends with a `/` or whose mode marks them as a directory, like
+    /// [`ZipFile::is_dir`].
+    pub fn dir_names(&self) -> impl Iterator<Item = &str> {
+        self.shared
+            .files
+            .iter()
+            .filter(|(_, file)| file.is_dir())
+            .map(|(name, _)| name.as_ref())
+    }
+
+    /// Returns an iterator over the names of the entries in this archive that aren't directories.
+    ///
+    /// Together with [`ZipArchive::dir_names`], this partitions [`ZipArchive::file_names`].
+    pub fn file_names_nondir(&self) -> impl Iterator<Item = &str> {
+        self.shared
+            .files
+            .iter()
+            .filter(|(_, file)| !file.is_dir())
+            .map(|(name, _)| name.as_ref())
+    }
+
     /// Search for a file entry by name, decrypt with given password
     ///
     /// # Warning
@@ -764,10 +2191,54 @@ impl<R: Read + Seek> ZipArchive<R> {
     }
 
     /// Search for a file entry by name
+    ///
+    /// As with [`ZipArchive::by_index`], an encrypted file can be opened, but not read.
     pub fn by_name(&mut self, name: &str) -> ZipResult<ZipFile> {
         self.by_name_with_optional_password(name, None)
     }
 
+    /// Search for a file entry by name, ignoring ASCII case
+    ///
+    /// An entry whose name matches `name` exactly is always preferred. Otherwise, this fails with
+    /// [`ZipError::InvalidArchive`] if more than one entry's name differs from `name` only by
+    /// case. Only the ASCII letters `A`-`Z` are folded to lowercase; other characters, including
+    /// non-ASCII letters, must match exactly, since Unicode case folding is out of scope.
+    ///
+    /// The index of lowercased names is built the first time this is called, so looking names up
+    /// with [`ZipArchive::by_name`] doesn't pay for it. For an archive opened with
+    /// [`ZipArchive::new_lazy`], this parses every remaining central directory record.
+    pub fn by_name_case_insensitive(&mut self, name: &str) -> ZipResult<ZipFile<'_>> {
+        self.parse_pending(|_| false)?;
+        let index = match self.shared.files.get_index_of(name) {
+            Some(index) => index,
+            None => {
+                let lowercase_names = self.shared.lowercase_names.get_or_init(|| {
+                    let mut lowercase_names = std::collections::HashMap::new();
+                    for (index, name) in self.shared.files.keys().enumerate() {
+                        lowercase_names
+                            .entry(name.to_ascii_lowercase().into_boxed_str())
+                            .or_insert_with(Vec::new)
+                            .push(index);
+                    }
+                    lowercase_names
+                });
+                match lowercase_names
+                    .get(name.to_ascii_lowercase().as_str())
+                    .map(Vec::as_slice)
+                {
+                    Some(&[index]) => index,
+                    Some(_) => {
+                        return Err(ZipError::InvalidArchive(
+                            "More than one file has this name when case is ignored",
+                        ))
+                    }
+                    None => return Err(ZipError::FileNotFound),
+                }
+            }
+        };
+        self.by_index(index)
+    }
+
     /// Get the index of a file entry by name, if it's present.
     #[inline(always)]
     pub fn index_for_name(&self, name: &str) -> Option<usize> {
@@ -777,7 +2248,7 @@ impl<R: Read + Seek> ZipArchive<R> {
     /// Get the index of a file entry by path, if it's present.
     #[inline(always)]
     pub fn index_for_path<T: AsRef<Path>>(&self, path: T) -> Option<usize> {
-        self.index_for_name(&path_to_string(path))
+        self.index_for_name(&path_to_string(path, false))
     }
 
     /// Get the name of a file entry, if it's present.
@@ -794,7 +2265,7 @@ impl<R: Read + Seek> ZipArchive<R> {
         name: &str,
         password: Option<&[u8]>,
     ) -> ZipResult<ZipFile<'a>> {
-        let Some(index) = self.shared.files.get_index_of(name) else {
+        let Some(index) = self.parse_pending_for_name(name)? else {
             return Err(ZipError::FileNotFound);
         };
         self.by_index_with_optional_password(index, password)
@@ -821,62 +2292,289 @@ impl<R: Read + Seek> ZipArchive<R> {
         self.by_index_with_optional_password(file_number, Some(password))
     }
 
+    /// Get a contained file by index, decrypting it with the first of `passwords` that's accepted
+    ///
+    /// Each password is only checked against the encryption header, as [`by_index_decrypt`]
+    /// does, so nothing is decompressed until the file is read. If none is accepted, this fails
+    /// with [`ZipError::InvalidPassword`]. Files that aren't encrypted are returned as they are.
+    ///
+    /// # Warning
+    ///
+    /// The same warnings apply as for [`by_index_decrypt`]. In particular, a wrong ZipCrypto
+    /// password passes the check about 1 time in 256, and a wrong AES password about 1 time in
+    /// 65536. If that happens, this returns the file decrypted with the wrong password, and reading
+    /// it fails, rather than trying the passwords after it.
+    ///
+    /// [`by_index_decrypt`]: ZipArchive::by_index_decrypt
+    pub fn by_index_try_passwords(
+        &mut self,
+        file_number: usize,
+        passwords: &[&[u8]],
+    ) -> ZipResult<ZipFile<'_>> {
+        self.parse_pending_through(file_number)?;
+        let (_, data) = self
+            .shared
+            .files
+            .get_index(file_number)
+            .ok_or(ZipError::FileNotFound)?;
+        if data.encrypted && passwords.is_empty() {
+            return Err(ZipError::InvalidPassword);
+        }
+        self.by_index_with_passwords(file_number, passwords)
+    }
+
     /// Get a contained file by index
+    ///
+    /// An encrypted file is still returned, so that its name, sizes and other metadata can be
+    /// looked at, but reading its data fails with [`ZipError::PasswordRequired`]. Use
+    /// [`ZipArchive::by_index_decrypt`] to read it.
     pub fn by_index(&mut self, file_number: usize) -> ZipResult<ZipFile<'_>> {
         self.by_index_with_optional_password(file_number, None)
     }
 
+    /// Like [`ZipArchive::by_index`], but fails with [`ZipError::PasswordRequired`] straight
+    /// away if the file is encrypted, for reading it in full
+    fn by_index_for_reading(&mut self, file_number: usize) -> ZipResult<ZipFile<'_>> {
+        let file = self.by_index(file_number)?;
+        if file.is_locked() {
+            return Err(ZipError::PasswordRequired);
+        }
+        Ok(file)
+    }
+
+    /// Read the first `n` bytes of a contained file, or all of it if it's shorter
+    ///
+    /// Only as much of the file is decompressed as that needs, so this is much cheaper than
+    /// reading the whole file when `n` is small, such as to sniff the content type of many files
+    /// from their first few bytes. Since the rest of the file isn't read, its CRC-32 is only
+    /// checked if `n` covers all of it. Returns [`ZipError::PasswordRequired`] for an encrypted
+    /// file; use [`ZipArchive::by_index_decrypt`] and [`Read::take`] for that.
+    pub fn peek_bytes(&mut self, file_number: usize, n: usize) -> ZipResult<Vec<u8>> {
+        let file = self.by_index_for_reading(file_number)?;
+        // The size in the header isn't trusted, so the buffer only grows as data is read
+        let mut bytes = Vec::new();
+        file.take(n as u64).read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+
+    /// Get a contained file by index, decompressing it with the given Zstandard dictionary
+    ///
+    /// Zstandard dictionaries aren't stored in the archive, so this must be the same dictionary
+    /// that was passed to [`FileOptions::with_zstd_dictionary`](crate::write::FileOptions::with_zstd_dictionary)
+    /// when the file was written. The dictionary is ignored for entries that weren't compressed
+    /// with [`CompressionMethod::Zstd`].
+    #[cfg(feature = "zstd")]
+    pub fn by_index_with_zstd_dictionary<'a>(
+        &'a mut self,
+        file_number: usize,
+        dictionary: &'a [u8],
+    ) -> ZipResult<ZipFile<'a>> {
+        let mut file = self.by_index_with_optional_password(file_number, None)?;
+        file.zstd_dictionary = Some(dictionary);
+        file.get_reader()?;
+        Ok(file)
+    }
+
+    /// Search for a file entry by name, decompressing it with the given Zstandard dictionary
+    ///
+    /// See [`ZipArchive::by_index_with_zstd_dictionary`].
+    #[cfg(feature = "zstd")]
+    pub fn by_name_with_zstd_dictionary<'a>(
+        &'a mut self,
+        name: &str,
+        dictionary: &'a [u8],
+    ) -> ZipResult<ZipFile<'a>> {
+        let Some(index) = self.parse_pending_for_name(name)? else {
+            return Err(ZipError::FileNotFound);
+        };
+        self.by_index_with_zstd_dictionary(index, dictionary)
+    }
+
+    /// Get a contained file by index, with support for seeking within its contents
+    ///
+    /// Only unencrypted Stored and Deflated files are supported. See [`SeekableZipFile`] for the
+    /// cost of seeking.
+    pub fn by_index_seekable(&mut self, file_number: usize) -> ZipResult<SeekableZipFile<&mut R>> {
+        self.parse_pending_through(file_number)?;
+        let (_, data) = self
+            .shared
+            .files
+            .get_index(file_number)
+            .ok_or(ZipError::FileNotFound)?;
+        if data.encrypted {
+            return Err(ZipError::UnsupportedArchive(
+                "Seeking isn't supported for encrypted files",
+            ));
+        }
+        if self.strictness == Strictness::Strict {
+            check_local_header(data, &mut self.reader)?;
+        }
+        // Reading the local header fills in `data_start`
+        find_content(data, &mut self.reader)?;
+        SeekableZipFile::new(
+            &mut self.reader,
+            data.compression_method,
+            *data.data_start.get().unwrap(),
+            data.compressed_size,
+            data.uncompressed_size,
+        )
+    }
+
+    /// Search for a file entry by name, with support for seeking within its contents
+    ///
+    /// See [`ZipArchive::by_index_seekable`].
+    pub fn by_name_seekable(&mut self, name: &str) -> ZipResult<SeekableZipFile<&mut R>> {
+        let Some(index) = self.parse_pending_for_name(name)? else {
+            return Err(ZipError::FileNotFound);
+        };
+        self.by_index_seekable(index)
+    }
+
+    /// Get the metadata of a contained file by index, including where its compressed data is,
+    /// without decompressing it
+    ///
+    /// The offsets are from the start of the reader, so `data_start..data_start + compressed_size`
+    /// is the range of bytes to fetch to decompress the file elsewhere. This reads the file's
+    /// local header, unless it's already been read, since that's the only place that gives the
+    /// length of the extra field preceding the data. To decode the file from just its part of the
+    /// archive, fetch from `local_header_offset` instead and pass that to [`decode_entry`].
+    pub fn entry_metadata(&mut self, file_number: usize) -> ZipResult<EntryMetadata> {
+        self.parse_pending_through(file_number)?;
+        let (_, data) = self
+            .shared
+            .files
+            .get_index(file_number)
+            .ok_or(ZipError::FileNotFound)?;
+        Ok(EntryMetadata {
+            name: data.file_name.clone(),
+            compression_method: data.compression_method,
+            compressed_size: data.compressed_size,
+            uncompressed_size: data.uncompressed_size,
+            crc32: data.crc32,
+            local_header_offset: data.header_start,
+            data_start: find_data_start(data, &mut self.reader)?,
+            encrypted: data.encrypted,
+        })
+    }
+
     /// Get a contained file by index without decompressing it
+    ///
+    /// Reading the returned file yields exactly the [`ZipFile::compressed_size`] bytes stored after
+    /// its local header. An encrypted file isn't decrypted either, so its data still starts with
+    /// the ZipCrypto header or the AES salt and password verifier, and an AES file's data ends with
+    /// its authentication code. [`ZipFile::compression`] and [`ZipFile::crc32`] say how to decode
+    /// the data, so it can be hashed as stored or copied into another archive with
+    /// [`ZipWriter::raw_copy_file`](crate::ZipWriter::raw_copy_file).
     pub fn by_index_raw(&mut self, file_number: usize) -> ZipResult<ZipFile<'_>> {
+        self.parse_pending_through(file_number)?;
         let reader = &mut self.reader;
         let (_, data) = self
             .shared
             .files
             .get_index(file_number)
             .ok_or(ZipError::FileNotFound)?;
+        if self.strictness == Strictness::Strict {
+            check_local_header(data, reader)?;
+        }
         Ok(ZipFile {
             crypto_reader: None,
             reader: ZipFileReader::Raw(find_content(data, reader)?),
             data: Cow::Borrowed(data),
+            limits: DecompressionLimits::default(),
+            bytes_decompressed: 0,
+            verify_crc: true,
+            decompressor: None,
+            #[cfg(feature = "zstd")]
+            zstd_dictionary: None,
+            rewindable: true,
         })
     }
 
     fn by_index_with_optional_password(
         &mut self,
         file_number: usize,
-        mut password: Option<&[u8]>,
+        password: Option<&[u8]>,
+    ) -> ZipResult<ZipFile<'_>> {
+        self.by_index_with_passwords(file_number, password.as_ref().map_or(&[], slice::from_ref))
+    }
+
+    /// Open a file with the first of `passwords` that's accepted, without decrypting it if there
+    /// are none
+    fn by_index_with_passwords(
+        &mut self,
+        file_number: usize,
+        passwords: &[&[u8]],
     ) -> ZipResult<ZipFile<'_>> {
+        self.parse_pending_through(file_number)?;
         let (_, data) = self
             .shared
             .files
             .get_index(file_number)
             .ok_or(ZipError::FileNotFound)?;
 
-        match (password, data.encrypted) {
-            (None, true) => return Err(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED)),
-            (Some(_), false) => password = None, //Password supplied, but none needed! Discard.
-            _ => {}
+        let passwords = match password_for(data, passwords.first().copied()) {
+            // The file can still be opened to look at its metadata, and reading it fails instead
+            Err(ZipError::PasswordRequired) | Ok(None) => &[],
+            Ok(Some(_)) => passwords,
+            Err(e) => return Err(e),
+        };
+        let locked = data.encrypted && passwords.is_empty();
+        if self.strictness == Strictness::Strict {
+            check_local_header(data, &mut self.reader)?;
         }
         let limit_reader = find_content(data, &mut self.reader)?;
 
+        let decompressor = self.decompressors.get(data);
+        if decompressor.is_none() {
+            check_compression_supported(data.compression_method)?;
+        }
+        #[cfg(feature = "aes-crypto")]
+        let aes_iterations = match self.aes_iterations {
+            Some(iterations) => iterations,
+            None if data.aes_mode.is_some() && !locked => recorded_aes_iterations(data)?,
+            None => crate::aes::DEFAULT_ITERATION_COUNT,
+        };
         let crypto_reader = make_crypto_reader(
-            data.compression_method,
             data.crc32,
             data.last_modified_time,
             data.using_data_descriptor,
             limit_reader,
-            password,
-            data.aes_mode,
+            passwords,
+            data.aes_mode.filter(|_| !locked),
             #[cfg(feature = "aes-crypto")]
             data.compressed_size,
+            #[cfg(feature = "aes-crypto")]
+            aes_iterations,
         )?;
         Ok(ZipFile {
             crypto_reader: Some(crypto_reader),
             reader: ZipFileReader::NoReader,
             data: Cow::Borrowed(data),
+            limits: self.limits,
+            bytes_decompressed: 0,
+            verify_crc: self.verify_crc,
+            decompressor,
+            #[cfg(feature = "zstd")]
+            zstd_dictionary: None,
+            rewindable: true,
         })
     }
 
+    /// Decompress files whose compression method has the ID `method` with `decompressor`,
+    /// such as those written with
+    /// [`FileOptions::with_custom_compressor`](crate::write::FileOptions::with_custom_compressor)
+    ///
+    /// This replaces any decompressor registered for the same ID before, and is used instead of
+    /// the built-in one if this crate implements that method.
+    pub fn register_decompressor<D: Decompressor + 'static>(
+        &mut self,
+        method: u16,
+        decompressor: D,
+    ) {
+        let map = Arc::make_mut(&mut self.decompressors.0);
+        map.insert(method, Arc::new(decompressor));
+    }
+
     /// Unwrap and return the inner reader object
     ///
     /// The position of the reader is undefined.
@@ -889,6 +2587,498 @@ const fn unsupported_zip_error<T>(detail: &'static str) -> ZipResult<T> {
     Err(ZipError::UnsupportedArchive(detail))
 }
 
+/// Resolves the target of a symbolic link in the directory `parent`, following any links it
+/// passes through that already exist. Returns `None` if the target is absolute, or if a `..` in
+/// it follows a component that doesn't exist yet or is a link, since a later entry could make
+/// that component lead anywhere.
+#[cfg(unix)]
+fn resolve_link_target(parent: &Path, target: &Path) -> Option<PathBuf> {
+    use std::path::Component;
+
+    let mut resolved = parent.to_path_buf();
+    let mut unsettled = false;
+    for component in target.components() {
+        match component {
+            Component::Normal(name) => {
+                resolved.push(name);
+                unsettled = resolved
+                    .symlink_metadata()
+                    .map_or(true, |metadata| metadata.file_type().is_symlink());
+                if let Ok(canonical) = resolved.canonicalize() {
+                    resolved = canonical;
+                }
+            }
+            Component::ParentDir => {
+                if unsettled {
+                    return None;
+                }
+                resolved.pop();
+            }
+            Component::CurDir => {}
+            Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    Some(resolved)
+}
+
+/// Where a file's data is in an archive and how to decode it, as returned by
+/// [`ZipArchive::entry_metadata`]
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct EntryMetadata {
+    /// The name of the file, as given by [`ZipFile::name`]
+    pub name: Box<str>,
+    /// How the data is compressed
+    pub compression_method: CompressionMethod,
+    /// The length of the data as stored in the archive, including any encryption header
+    pub compressed_size: u64,
+    /// The length of the data once decompressed
+    pub uncompressed_size: u64,
+    /// The CRC-32 of the decompressed data
+    pub crc32: u32,
+    /// The offset of the file's local header
+    pub local_header_offset: u64,
+    /// The offset of the file's compressed data, which follows its local header
+    pub data_start: u64,
+    /// Whether the data is encrypted, so that it needs a password to be read
+    pub encrypted: bool,
+}
+
+/// Rewrites the path an entry is extracted to; see [`ExtractOptions::map_path`]
+type PathMapper = Arc<dyn Fn(&Path) -> Option<PathBuf> + Send + Sync>;
+
+/// Receives progress reports during extraction; see [`ExtractOptions::on_progress`]
+type ProgressCallback = Arc<Mutex<dyn FnMut(ExtractProgress<'_>) + Send>>;
+
+/// How often [`ExtractOptions::on_progress`] is called while a file is being written
+const PROGRESS_INTERVAL: u64 = 1 << 20;
+
+/// How far [`ZipArchive::extract_with_options`] has got, as passed to
+/// [`ExtractOptions::on_progress`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ExtractProgress<'a> {
+    /// The index of the entry being extracted
+    pub current_index: usize,
+    /// The number of entries in the archive
+    pub total_entries: usize,
+    /// The number of bytes written to extracted files so far, including the current one
+    pub bytes_written: u64,
+    /// The total size of the files in the archive, as given by
+    /// [`ZipArchive::decompressed_size`]. Entries that are skipped count towards this but not
+    /// towards `bytes_written`, so it only reaches this when every entry is extracted.
+    pub total_bytes: Option<u128>,
+    /// The name of the entry being extracted, as given by [`ZipFile::name`]
+    pub current_name: &'a str,
+}
+
+/// Default size of the buffer that file contents are copied through, which is the same as
+/// [`io::copy`]'s
+pub(crate) const DEFAULT_COPY_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Copy `reader` to `writer` through `buf` like [`io::copy`], calling `progress` with the number
+/// of bytes copied so far each time another [`PROGRESS_INTERVAL`] bytes have been copied
+pub(crate) fn copy_with_progress<R: Read + ?Sized, W: Write + ?Sized>(
+    reader: &mut R,
+    writer: &mut W,
+    buf: &mut [u8],
+    mut progress: impl FnMut(u64),
+) -> io::Result<u64> {
+    let mut copied = 0;
+    let mut next_report = PROGRESS_INTERVAL;
+    loop {
+        let count = match reader.read(buf) {
+            Ok(0) => return Ok(copied),
+            Ok(count) => count,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        writer.write_all(&buf[..count])?;
+        copied += count as u64;
+        if copied >= next_report {
+            progress(copied);
+            next_report = copied + PROGRESS_INTERVAL;
+        }
+    }
+}
+
+/// Options for [`ZipArchive::extract_with_options`].
+#[derive(Clone)]
+pub struct ExtractOptions {
+    preserve_permissions: bool,
+    preserve_mtime: bool,
+    preserve_ownership: bool,
+    hard_links: bool,
+    buffer_size: usize,
+    strip_prefix: Option<PathBuf>,
+    map_path: Option<PathMapper>,
+    on_progress: Option<ProgressCallback>,
+    continue_on_error: bool,
+    /// Whether names are checked as [`ZipArchive::extract_safe`] checks them, rather than
+    /// sanitized with [`ZipFile::enclosed_name`]
+    check_paths: bool,
+}
+
+impl Default for ExtractOptions {
+    /// Preserves permissions but not modification times, and extracts every entry to its own
+    /// name, like [`ZipArchive::extract`].
+    fn default() -> Self {
+        Self {
+            preserve_permissions: true,
+            preserve_mtime: false,
+            preserve_ownership: false,
+            hard_links: false,
+            buffer_size: DEFAULT_COPY_BUFFER_SIZE,
+            strip_prefix: None,
+            map_path: None,
+            on_progress: None,
+            continue_on_error: false,
+            check_paths: false,
+        }
+    }
+}
+
+impl fmt::Debug for ExtractOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ExtractOptions")
+            .field("preserve_permissions", &self.preserve_permissions)
+            .field("preserve_mtime", &self.preserve_mtime)
+            .field("preserve_ownership", &self.preserve_ownership)
+            .field("hard_links", &self.hard_links)
+            .field("buffer_size", &self.buffer_size)
+            .field("strip_prefix", &self.strip_prefix)
+            .field("map_path", &self.map_path.as_ref().map(|_| "Fn"))
+            .field("on_progress", &self.on_progress.as_ref().map(|_| "FnMut"))
+            .field("continue_on_error", &self.continue_on_error)
+            .field("check_paths", &self.check_paths)
+            .finish()
+    }
+}
+
+impl ExtractOptions {
+    /// Set whether each extracted file and directory gets the mode from [`ZipFile::unix_mode`] on
+    /// Unix targets. On Windows, extracted files get the read-only, hidden, system and archive
+    /// flags from [`ZipFile::dos_attributes`] instead. Has no effect on other targets.
+    #[must_use]
+    pub const fn preserve_permissions(mut self, preserve_permissions: bool) -> Self {
+        self.preserve_permissions = preserve_permissions;
+        self
+    }
+
+    /// Set whether each extracted file and directory gets the modification time from
+    /// [`ZipFile::last_modified_precise`]. Has no effect except on Unix targets.
+    #[must_use]
+    pub const fn preserve_mtime(mut self, preserve_mtime: bool) -> Self {
+        self.preserve_mtime = preserve_mtime;
+        self
+    }
+
+    /// Set whether each extracted file and directory is given the owner and group from
+    /// [`ZipFile::unix_ownership`] on Unix targets. This is off by default, and has no effect on
+    /// other targets.
+    ///
+    /// Changing a file's owner usually needs root privileges. Where the process isn't allowed to,
+    /// the file is left with the ownership it was created with rather than failing the extraction.
+    #[must_use]
+    pub const fn preserve_ownership(mut self, preserve_ownership: bool) -> Self {
+        self.preserve_ownership = preserve_ownership;
+        self
+    }
+
+    /// Set whether entries that are hard links, according to [`ZipFile::is_hardlink`], are
+    /// extracted as hard links to the file extracted for their target on Unix targets. If the
+    /// target hasn't been extracted yet, its contents are copied from the archive instead, and if
+    /// [`ExtractOptions::strip_prefix`] or [`ExtractOptions::map_path`] skips the target,
+    /// extracting the link fails with [`ZipError::FileNotFound`]. When this is off, which is the
+    /// default, and on other targets, hard links are extracted with their own (usually empty)
+    /// contents.
+    #[must_use]
+    pub const fn hard_links(mut self, hard_links: bool) -> Self {
+        self.hard_links = hard_links;
+        self
+    }
+
+    /// Set the size of the buffer that each file's contents are copied through, which is
+    /// allocated once for the whole extraction. The default is 8 KiB.
+    ///
+    /// A larger buffer means fewer, larger writes, which can be much faster on storage with high
+    /// latency per operation, such as network file systems, at the cost of the memory it takes.
+    /// Extraction fails if this is 0.
+    #[must_use]
+    pub const fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.buffer_size = buffer_size;
+        self
+    }
+
+    /// Remove `prefix` from the start of each entry's path, as given by
+    /// [`ZipFile::enclosed_name`], such as the top-level directory that wraps the contents of many
+    /// source archives. Entries that aren't inside `prefix`, and the entry for `prefix` itself,
+    /// are skipped.
+    #[must_use]
+    pub fn strip_prefix<P: Into<PathBuf>>(mut self, prefix: P) -> Self {
+        self.strip_prefix = Some(prefix.into());
+        self
+    }
+
+    /// Rewrite each entry's path, after any [`ExtractOptions::strip_prefix`] has been removed.
+    /// Returning `None` skips the entry.
+    ///
+    /// The rewritten path is still relative to the directory being extracted into. Extraction
+    /// fails with [`ZipError::InvalidPath`] if it's absolute or contains a `..` component.
+    #[must_use]
+    pub fn map_path<F>(mut self, map_path: F) -> Self
+    where
+        F: Fn(&Path) -> Option<PathBuf> + Send + Sync + 'static,
+    {
+        self.map_path = Some(Arc::new(map_path));
+        self
+    }
+
+    /// Call `on_progress` after each entry has been extracted or skipped, and after every MiB
+    /// written to a file while it's being extracted.
+    ///
+    /// The callback must be [`Send`], so that the options can be moved to another thread along
+    /// with the archive. [`ZipArchive::extract_parallel_with_options`] calls it from every thread
+    /// that it extracts entries on, and only once each entry has been extracted.
+    #[must_use]
+    pub fn on_progress<F>(mut self, on_progress: F) -> Self
+    where
+        F: FnMut(ExtractProgress<'_>) + Send + 'static,
+    {
+        self.on_progress = Some(Arc::new(Mutex::new(on_progress)));
+        self
+    }
+
+    /// Set whether extraction carries on with the next entry when one can't be read or
+    /// written, to recover as much as possible from a damaged archive.
+    ///
+    /// The file that a failed entry was being written to is removed, and once every other entry
+    /// has been extracted, the name of each failed entry and its error are returned together in
+    /// [`ZipError::ExtractionFailed`]. Errors in the central directory itself still stop
+    /// extraction at once. This is false by default, so the first error stops extraction and is
+    /// returned.
+    #[must_use]
+    pub const fn continue_on_error(mut self, continue_on_error: bool) -> Self {
+        self.continue_on_error = continue_on_error;
+        self
+    }
+
+    fn report_progress(&self, progress: ExtractProgress<'_>) {
+        if let Some(on_progress) = &self.on_progress {
+            // The lock is only poisoned if the callback panicked, which has already unwound
+            // through extraction
+            if let Ok(mut on_progress) = on_progress.lock() {
+                (on_progress)(progress);
+            }
+        }
+    }
+
+    /// Where `file` should be extracted to, relative to the target directory, or `None` if it
+    /// should be skipped
+    fn destination(&self, file: &ZipFile) -> ZipResult<Option<PathBuf>> {
+        use std::path::Component;
+
+        let mut path = if self.check_paths {
+            safe_relative_path(file.name())
+                .ok_or_else(|| ZipError::InvalidPath(file.name().into()))?
+        } else {
+            file.enclosed_name()
+                .ok_or(ZipError::InvalidArchive("Invalid file path"))?
+        };
+        if self.strip_prefix.is_none() && self.map_path.is_none() {
+            return Ok(Some(path));
+        }
+        if let Some(prefix) = &self.strip_prefix {
+            match path.strip_prefix(prefix) {
+                Ok(rest) => path = rest.to_path_buf(),
+                Err(_) => return Ok(None),
+            }
+        }
+        if let Some(map_path) = &self.map_path {
+            match map_path(&path) {
+                Some(mapped) => path = mapped,
+                None => return Ok(None),
+            }
+            if !path
+                .components()
+                .all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+            {
+                return Err(ZipError::InvalidPath(file.name().into()));
+            }
+        }
+        if path.components().next().is_none() {
+            // This would be the target directory itself
+            return if file.is_dir() {
+                Ok(None)
+            } else {
+                Err(ZipError::InvalidPath(file.name().into()))
+            };
+        }
+        Ok(Some(path))
+    }
+
+    /// Find the directory that entries are to be extracted into
+    fn target_directory<'a>(&self, directory: &'a Path) -> ZipResult<ExtractTarget<'a>> {
+        if self.buffer_size == 0 {
+            return Err(ZipError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Extraction buffer size must be at least 1 byte",
+            )));
+        }
+        create_dir_all(directory)?;
+        let root = directory.canonicalize()?;
+        let directory = if self.check_paths {
+            Cow::Owned(root.clone())
+        } else {
+            Cow::Borrowed(directory)
+        };
+        Ok(ExtractTarget { directory, root })
+    }
+}
+
+/// The directory that entries are extracted into
+struct ExtractTarget<'a> {
+    /// What output paths are made from, which is only the real path of the directory when paths
+    /// are checked
+    directory: Cow<'a, Path>,
+    /// The real path of the directory, which everything extracted must lie within
+    root: PathBuf,
+}
+
+/// What an extraction has done so far that matters to the metadata that's restored once every
+/// entry has been extracted
+struct ExtractState {
+    /// What each file's contents are copied through
+    buf: Vec<u8>,
+    bytes_written: u64,
+    /// The file being written, which is removed if extracting the entry fails
+    partial_file: Option<PathBuf>,
+    #[cfg(unix)]
+    files_by_unix_mode: Vec<(PathBuf, u32)>,
+    #[cfg(unix)]
+    files_by_mtime: Vec<(PathBuf, SystemTime)>,
+    #[cfg(unix)]
+    files_by_ownership: Vec<(PathBuf, (u32, u32))>,
+    /// Where each file was extracted to, so that hard links to it can be created
+    #[cfg(unix)]
+    extracted_files: std::collections::HashMap<Box<str>, PathBuf>,
+}
+
+impl ExtractState {
+    fn new(buffer_size: usize) -> Self {
+        Self {
+            buf: vec![0; buffer_size],
+            bytes_written: 0,
+            partial_file: None,
+            #[cfg(unix)]
+            files_by_unix_mode: Vec::new(),
+            #[cfg(unix)]
+            files_by_mtime: Vec::new(),
+            #[cfg(unix)]
+            files_by_ownership: Vec::new(),
+            #[cfg(unix)]
+            extracted_files: std::collections::HashMap::new(),
+        }
+    }
+
+    /// Take on what another thread extracted
+    fn merge(&mut self, other: Self) {
+        self.bytes_written += other.bytes_written;
+        #[cfg(unix)]
+        {
+            self.files_by_unix_mode.extend(other.files_by_unix_mode);
+            self.files_by_mtime.extend(other.files_by_mtime);
+            self.files_by_ownership.extend(other.files_by_ownership);
+            self.extracted_files.extend(other.extracted_files);
+        }
+    }
+
+    /// Remove the file that a failed entry was being written to
+    fn remove_partial_file(&mut self) {
+        if let Some(partial_file) = self.partial_file.take() {
+            let _ = std::fs::remove_file(partial_file);
+        }
+    }
+
+    /// Restore the metadata of everything extracted, once nothing more will be written
+    fn finish(self) -> ZipResult<()> {
+        #[cfg(unix)]
+        {
+            Self::set_extracted_mtimes(self.files_by_mtime)?;
+            // Changing the owner can clear the setuid and setgid bits, so it's done first
+            Self::set_extracted_ownership(self.files_by_ownership)?;
+            Self::set_extracted_permissions(self.files_by_unix_mode)?;
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn set_extracted_permissions(mut files_by_unix_mode: Vec<(PathBuf, u32)>) -> ZipResult<()> {
+        use std::cmp::Reverse;
+        use std::os::unix::fs::PermissionsExt;
+
+        if files_by_unix_mode.len() > 1 {
+            // Ensure we update children's permissions before making a parent unwritable
+            files_by_unix_mode.sort_by_key(|(path, _)| Reverse(path.clone()));
+        }
+        for (path, mode) in files_by_unix_mode.into_iter() {
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode))?;
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn set_extracted_ownership(files_by_ownership: Vec<(PathBuf, (u32, u32))>) -> ZipResult<()> {
+        use std::ffi::CString;
+        use std::os::unix::ffi::OsStrExt;
+
+        for (path, (uid, gid)) in files_by_ownership {
+            let c_path = CString::new(path.as_os_str().as_bytes())
+                .map_err(|_| ZipError::InvalidPath(path.to_string_lossy().into()))?;
+            // SAFETY: `c_path` is a valid NUL-terminated string that outlives the call
+            if unsafe { libc::lchown(c_path.as_ptr(), uid, gid) } != 0 {
+                let error = io::Error::last_os_error();
+                // Without the privilege to change the owner, the file keeps the extracting user's
+                if error.raw_os_error() != Some(libc::EPERM) {
+                    return Err(error.into());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    fn set_extracted_mtimes(mut files_by_mtime: Vec<(PathBuf, SystemTime)>) -> ZipResult<()> {
+        use std::cmp::Reverse;
+
+        // Set each directory's time after its children's, since changing those doesn't affect it
+        files_by_mtime.sort_by_key(|(path, _)| Reverse(path.clone()));
+        for (path, mtime) in files_by_mtime {
+            filetime::set_file_mtime(&path, filetime::FileTime::from_system_time(mtime))?;
+        }
+        Ok(())
+    }
+}
+
+/// Converts an entry name to a relative path, or returns `None` if it could point anywhere other
+/// than inside the directory it's extracted to on any platform.
+fn safe_relative_path(name: &str) -> Option<PathBuf> {
+    if name.contains('\0') || name.starts_with(['/', '\\']) {
+        return None;
+    }
+    let mut path = PathBuf::new();
+    for (index, component) in name.split(['/', '\\']).enumerate() {
+        match component {
+            "" | "." => {}
+            ".." => return None,
+            // Windows drive letters and alternate data streams
+            _ if component.contains(':') && (index == 0 || cfg!(windows)) => return None,
+            _ => path.push(component),
+        }
+    }
+    Some(path)
+}
+
 /// Parse a central directory entry to collect the information for the file.
 pub(crate) fn central_header_to_zip_file<R: Read + Seek>(
     reader: &mut R,
@@ -898,13 +3088,36 @@ pub(crate) fn central_header_to_zip_file<R: Read + Seek>(
 
     // Parse central header
     let signature = reader.read_u32_le()?;
-    if signature != spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE {
-        Err(ZipError::InvalidArchive("Invalid Central Directory header"))
-    } else {
-        central_header_to_zip_file_inner(reader, archive_offset, central_header_start)
+    match signature {
+        spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE => {
+            central_header_to_zip_file_inner(reader, archive_offset, central_header_start)
+        }
+        spec::ARCHIVE_EXTRA_DATA_SIGNATURE => Err(encrypted_directory_error(reader)),
+        _ => Err(ZipError::InvalidArchive("Invalid Central Directory header")),
     }
 }
 
+/// The error for a central directory that starts with an archive extra data record, whose
+/// signature has already been read. That record only comes before a central directory encrypted
+/// with PKWARE's strong encryption, and its Strong Encryption Header says which algorithm that is.
+fn encrypted_directory_error<R: Read>(reader: &mut R) -> ZipError {
+    let mut read_algorithm = || -> ZipResult<Option<StrongEncryptionAlgorithm>> {
+        let len = reader.read_u32_le()?;
+        let mut extra_field = Vec::new();
+        reader
+            .take(len.min(u16::MAX as u32) as u64)
+            .read_to_end(&mut extra_field)?;
+        let header = RawExtraField::parse_all(&extra_field)
+            .find(|field| field.header_id() == StrongEncryption::HEADER_ID)
+            .and_then(|field| {
+                StrongEncryption::try_from_reader(&mut field.data(), field.data().len() as u16).ok()
+            });
+        Ok(header.map(|header| header.algorithm()))
+    };
+    let algorithm = read_algorithm().ok().flatten();
+    ZipError::UnsupportedEncryption(algorithm.unwrap_or(StrongEncryptionAlgorithm::Unknown(0xFFFF)))
+}
+
 /// Parse a central directory entry to collect the information for the file.
 fn central_header_to_zip_file_inner<R: Read>(
     reader: &mut R,
@@ -941,10 +3154,13 @@ fn central_header_to_zip_file_inner<R: Read>(
         true => String::from_utf8_lossy(&file_name_raw).into(),
         false => file_name_raw.from_cp437().into(),
     };
-    let file_comment: Box<str> = match is_utf8 {
-        true => String::from_utf8_lossy(&file_comment_raw).into(),
-        false => file_comment_raw.from_cp437().into(),
-    };
+    let file_comment: Box<str> = RawExtraField::parse_all(&extra_field)
+        .filter(|field| field.header_id() == UNICODE_COMMENT_EXTRA_FIELD_ID)
+        .find_map(|field| unicode_extra_field_text(field.data(), &file_comment_raw))
+        .unwrap_or_else(|| match is_utf8 {
+            true => String::from_utf8_lossy(&file_comment_raw).into(),
+            false => file_comment_raw.from_cp437().into(),
+        });
 
     // Construct the result
     let mut result = ZipFileData {
@@ -952,10 +3168,12 @@ fn central_header_to_zip_file_inner<R: Read>(
         version_made_by: version_made_by as u8,
         encrypted,
         using_data_descriptor,
+        is_utf8,
         compression_method: {
             #[allow(deprecated)]
             CompressionMethod::from_u16(compression_method)
         },
+        compression_flags: flags & COMPRESSION_FLAGS,
         compression_level: None,
         last_modified_time: DateTime::from_msdos(last_mod_date, last_mod_time),
         crc32,
@@ -966,6 +3184,7 @@ fn central_header_to_zip_file_inner<R: Read>(
         extra_field: Some(Arc::new(extra_field)),
         central_extra_field: None,
         file_comment,
+        file_comment_raw: file_comment_raw.into(),
         header_start: offset,
         extra_data_start: None,
         central_header_start,
@@ -974,6 +3193,9 @@ fn central_header_to_zip_file_inner<R: Read>(
         large_file: false,
         aes_mode: None,
         aes_extra_data_start: 0,
+        aes_iterations: None,
+        strong_encryption_flag: flags & (1 << 6) != 0,
+        min_version_needed: 0,
         extra_fields: Vec::new(),
     };
 
@@ -998,6 +3220,47 @@ fn central_header_to_zip_file_inner<R: Read>(
     Ok(result)
 }
 
+/// Bits 1 and 2 of the general purpose flags, which each compression method may use as it likes.
+/// Implode keeps its window size and whether literals are coded there, and Deflate a hint about how
+/// hard the compressor tried.
+const COMPRESSION_FLAGS: u16 = 0b110;
+
+/// Header ID of the Info-ZIP Unicode Path extra field
+const UNICODE_PATH_EXTRA_FIELD_ID: u16 = 0x7075;
+/// Header ID of the Info-ZIP Unicode Comment extra field
+const UNICODE_COMMENT_EXTRA_FIELD_ID: u16 = 0x6375;
+/// Header ID of the PKWARE Unix extra field, whose data after the fixed 12 bytes of times and IDs
+/// is the target of a symbolic or hard link
+pub(crate) const PKWARE_UNIX_EXTRA_FIELD_ID: u16 = 0x000d;
+
+/// The UTF-8 text in the data of an Info-ZIP Unicode Path or Unicode Comment extra field, unless
+/// the field is for a different version of the format, or has gone stale because the name or
+/// comment in the header (whose CRC-32 the field records as `raw`) was changed by a tool that
+/// didn't update it
+fn unicode_extra_field_text(data: &[u8], raw: &[u8]) -> Option<Box<str>> {
+    if data.len() < 5 || data[0] != 1 {
+        return None;
+    }
+    let (header, name) = data.split_at(5);
+    if u32::from_le_bytes([header[1], header[2], header[3], header[4]]) != crc32fast::hash(raw) {
+        return None;
+    }
+    std::str::from_utf8(name).ok().map(Into::into)
+}
+
+/// The name of an entry without the UTF-8 flag, which comes from its Unicode Path extra field if
+/// that's up to date, and is otherwise decoded from the header using `encoding`
+fn decode_file_name(file: &ZipFileData, encoding: NameEncoding) -> Box<str> {
+    file.extra_field
+        .as_deref()
+        .and_then(|extra_field| {
+            RawExtraField::parse_all(extra_field)
+                .filter(|field| field.header_id() == UNICODE_PATH_EXTRA_FIELD_ID)
+                .find_map(|field| unicode_extra_field_text(field.data(), &file.file_name_raw))
+        })
+        .unwrap_or_else(|| encoding.decode(&file.file_name_raw))
+}
+
 fn parse_extra_field(file: &mut ZipFileData) -> ZipResult<()> {
     let Some(extra_field) = &file.extra_field else {
         return Ok(());
@@ -1062,6 +3325,20 @@ fn parse_extra_field(file: &mut ZipFileData) -> ZipResult<()> {
                     _ => return Err(ZipError::InvalidArchive("Invalid AES encryption strength")),
                 };
                 file.compression_method = compression_method;
+                len_left -= 7;
+            }
+            UNICODE_PATH_EXTRA_FIELD_ID => {
+                let mut data = vec![0; len as usize];
+                reader.read_exact(&mut data)?;
+                if let Some(name) = unicode_extra_field_text(&data, &file.file_name_raw) {
+                    file.file_name = name;
+                }
+                len_left = 0;
+            }
+            AES_ITERATIONS_EXTRA_FIELD_ID if len == 4 => {
+                // PBKDF2 iteration count written by FileOptions::with_aes_iterations
+                file.aes_iterations = Some(reader.read_u32_le()?);
+                len_left -= 4;
             }
             0x5455 => {
                 // extended timestamp
@@ -1074,6 +3351,38 @@ fn parse_extra_field(file: &mut ZipFileData) -> ZipResult<()> {
                 // the reader for ExtendedTimestamp consumes `len` bytes
                 len_left = 0;
             }
+            0x000a => {
+                // NTFS timestamps, which are skipped if they're malformed rather than making the
+                // whole archive unreadable
+                match Ntfs::try_from_reader(&mut reader, len) {
+                    Ok(ntfs) => file.extra_fields.push(ExtraField::Ntfs(ntfs)),
+                    Err(ZipError::Io(e)) => return Err(e.into()),
+                    Err(_) => {}
+                }
+
+                // the reader for Ntfs consumes `len` bytes
+                len_left = 0;
+            }
+            StrongEncryption::HEADER_ID => {
+                // Without the header, a strongly encrypted entry is still reported as one by its
+                // flag, so a malformed header only needs to be skipped
+                match StrongEncryption::try_from_reader(&mut reader, len) {
+                    Ok(header) => file.extra_fields.push(ExtraField::StrongEncryption(header)),
+                    Err(ZipError::Io(e)) => return Err(e.into()),
+                    Err(_) => {}
+                }
+                len_left = 0;
+            }
+            UnixOwnership::HEADER_ID => {
+                // A field that's malformed, is a later version or has an ID too wide for a u32 is
+                // skipped
+                match UnixOwnership::try_from_reader(&mut reader, len) {
+                    Ok(ownership) => file.extra_fields.push(ExtraField::UnixOwnership(ownership)),
+                    Err(ZipError::Io(e)) => return Err(e.into()),
+                    Err(_) => {}
+                }
+                len_left = 0;
+            }
             _ => {
                 // Other fields are ignored
             }
@@ -1091,9 +3400,29 @@ fn parse_extra_field(file: &mut ZipFileData) -> ZipResult<()> {
 impl<'a> ZipFile<'a> {
     fn get_reader(&mut self) -> ZipResult<&mut ZipFileReader<'a>> {
         if let ZipFileReader::NoReader = self.reader {
+            if self.is_locked() {
+                return Err(ZipError::PasswordRequired);
+            }
             let data = &self.data;
             let crypto_reader = self.crypto_reader.take().expect("Invalid reader state");
-            self.reader = make_reader(data.compression_method, data.crc32, crypto_reader)?;
+            self.reader = match &self.decompressor {
+                Some(decompressor) => {
+                    let verify_crc = self.verify_crc && !crypto_reader.is_ae2_encrypted();
+                    let reader = decompressor.decompress(Box::new(crypto_reader))?;
+                    ZipFileReader::Custom(Crc32Reader::new(reader, data.crc32, verify_crc))
+                }
+                None => make_reader(
+                    data.compression_method,
+                    data.crc32,
+                    data.uncompressed_size,
+                    crypto_reader,
+                    self.verify_crc,
+                    #[cfg(feature = "zstd")]
+                    self.zstd_dictionary,
+                    #[cfg(feature = "legacy")]
+                    data.compression_flags,
+                )?,
+            };
         }
         Ok(&mut self.reader)
     }
@@ -1106,6 +3435,27 @@ impl<'a> ZipFile<'a> {
         &mut self.reader
     }
 
+    /// Get a reader for the data once it's decrypted, but before it's decompressed. Fails if the
+    /// file is encrypted and was opened without a password.
+    pub(crate) fn get_decrypted_raw_reader(&mut self) -> ZipResult<&mut dyn Read> {
+        match &self.reader {
+            ZipFileReader::NoReader if self.is_locked() => return Err(ZipError::PasswordRequired),
+            ZipFileReader::NoReader => {
+                let crypto_reader = self.crypto_reader.take().expect("Invalid reader state");
+                self.reader = ZipFileReader::Decrypted(crypto_reader);
+            }
+            ZipFileReader::Raw(_) if self.data.encrypted => return Err(ZipError::PasswordRequired),
+            _ => {}
+        }
+        Ok(&mut self.reader)
+    }
+
+    /// Whether this is an encrypted file that was opened without a password, so its data can't be
+    /// read
+    fn is_locked(&self) -> bool {
+        self.data.encrypted && matches!(self.crypto_reader, Some(CryptoReader::Plaintext(_)))
+    }
+
     /// Get the version of the file
     pub fn version_made_by(&self) -> (u8, u8) {
         (
@@ -1114,6 +3464,22 @@ impl<'a> ZipFile<'a> {
         )
     }
 
+    /// Get the host system the file was written on, from the high byte of "version made by"
+    ///
+    /// This says how [`ZipFile::external_attributes`] should be read, for instance as a Unix mode
+    /// or as MS-DOS attributes.
+    pub fn system(&self) -> System {
+        self.data.system
+    }
+
+    /// Get the low byte of "version made by": the version of the specification the writing
+    /// software supports, times 10
+    ///
+    /// [`ZipFile::version_made_by`] gives the same version split into its major and minor parts.
+    pub fn host_version(&self) -> u8 {
+        self.data.version_made_by
+    }
+
     /// Get the name of the file
     ///
     /// # Warnings
@@ -1179,6 +3545,9 @@ impl<'a> ZipFile<'a> {
     }
 
     /// Get the comment of the file
+    ///
+    /// If the file has an Info-ZIP Unicode Comment extra field that's up to date with the comment
+    /// in its header, the comment comes from there.
     pub fn comment(&self) -> &str {
         &self.data.file_comment
     }
@@ -1198,16 +3567,158 @@ impl<'a> ZipFile<'a> {
         self.data.uncompressed_size
     }
 
+    /// Get how many bytes have been read from this file so far
+    ///
+    /// Those are decompressed bytes, so together with [`ZipFile::size`] this shows how far
+    /// through the file a reader is, whichever compression method it uses. For a file opened with
+    /// [`ZipArchive::by_index_raw`], they're compressed bytes, out of
+    /// [`ZipFile::compressed_size`].
+    pub fn position(&self) -> u64 {
+        self.bytes_decompressed
+    }
+
+    /// Go back to the start of the file, so it can be read again from the beginning
+    ///
+    /// This is cheaper than opening the file again with [`ZipArchive::by_index`]: the underlying
+    /// reader seeks straight back to the file's data, without reading its local header again, and
+    /// a new decompressor is made on the next read. So a Stored file just seeks, and a Deflated
+    /// one also restarts decoding. It's useful for looking at the first few bytes of a file, such
+    /// as its magic number, before deciding how to read it.
+    ///
+    /// ```
+    /// # fn main() -> zip::result::ZipResult<()> {
+    /// use std::io::{Read, Write};
+    /// use zip::write::SimpleFileOptions;
+    ///
+    /// let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    /// zip.start_file("image.png", SimpleFileOptions::default())?;
+    /// zip.write_all(b"\x89PNG\r\n\x1a\n...")?;
+    /// let mut archive = zip::ZipArchive::new(zip.finish()?)?;
+    ///
+    /// let mut file = archive.by_index(0)?;
+    /// let mut magic = [0; 4];
+    /// file.read_exact(&mut magic)?;
+    /// assert_eq!(&magic, b"\x89PNG");
+    /// file.rewind()?;
+    /// let mut contents = Vec::new();
+    /// file.read_to_end(&mut contents)?;
+    /// assert_eq!(contents, b"\x89PNG\r\n\x1a\n...");
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Encrypted files can only be rewound when they were opened with
+    /// [`ZipArchive::by_index_raw`], since the password isn't kept. Neither can files read with
+    /// [`read_zipfile_from_stream`] that have no data descriptor, nor ones decompressed by a
+    /// [`Decompressor`] registered with [`ZipArchive::register_decompressor`]. Those give
+    /// [`ZipError::UnsupportedArchive`] and can still be read on from where they were.
+    pub fn rewind(&mut self) -> ZipResult<()> {
+        let (raw, decrypted) = match &self.reader {
+            // Nothing has been read yet
+            ZipFileReader::NoReader => return Ok(()),
+            ZipFileReader::Raw(_) => (true, false),
+            ZipFileReader::Custom(_) => {
+                return unsupported_zip_error(
+                    "Files read with a custom decompressor can't be rewound",
+                )
+            }
+            _ if self.data.encrypted => {
+                return unsupported_zip_error("Encrypted files can't be rewound")
+            }
+            ZipFileReader::Decrypted(_) => (false, true),
+            _ => (false, false),
+        };
+        if !self.rewindable {
+            return unsupported_zip_error("Files read from a stream can't be rewound");
+        }
+        let reader = std::mem::replace(&mut self.reader, ZipFileReader::NoReader)
+            .into_entry_reader()
+            .expect("Only custom decompressors keep the reader");
+        let mut reader = reader.into_inner();
+        reader.rewind()?;
+        let reader = reader.take(self.data.compressed_size);
+        self.bytes_decompressed = 0;
+        if raw {
+            self.reader = ZipFileReader::Raw(reader);
+        } else if decrypted {
+            self.reader = ZipFileReader::Decrypted(CryptoReader::Plaintext(reader));
+        } else {
+            // The decompressor is made again on the next read
+            self.crypto_reader = Some(CryptoReader::Plaintext(reader));
+        }
+        Ok(())
+    }
+
     /// Get the time the file was last modified
+    ///
+    /// This is the MS-DOS date and time exactly as the archive stores them, which may not be
+    /// [valid](DateTime::is_valid): some archivers write a date of 0, which has a month and day of
+    /// 0. [`ZipFile::last_modified_precise`] never falls back to such a value.
     pub fn last_modified(&self) -> DateTime {
         self.data.last_modified_time
     }
-    /// Returns whether the file is actually a directory
+    /// Get the time the file was last modified, as precisely as the archive records it
+    ///
+    /// This prefers the NTFS extra field (0x000a), then the Info-ZIP extended timestamp extra
+    /// field (0x5455) if it has a modification time, and otherwise interprets
+    /// [`ZipFile::last_modified`] as UTC. Returns `None` if none of them is usable.
+    pub fn last_modified_precise(&self) -> Option<SystemTime> {
+        self.ntfs()
+            .and_then(Ntfs::modified)
+            .or_else(|| self.extended_timestamp(ExtendedTimestamp::mod_time))
+            .or_else(|| self.data.last_modified_time.to_system_time())
+    }
+
+    /// Get the time the file was last accessed, from the NTFS extra field (0x000a) or else the
+    /// Info-ZIP extended timestamp extra field (0x5455)
+    pub fn last_accessed(&self) -> Option<SystemTime> {
+        self.ntfs()
+            .and_then(Ntfs::accessed)
+            .or_else(|| self.extended_timestamp(ExtendedTimestamp::ac_time))
+    }
+
+    /// Get the time the file was created, from the NTFS extra field (0x000a) or else the
+    /// Info-ZIP extended timestamp extra field (0x5455)
+    pub fn created(&self) -> Option<SystemTime> {
+        self.ntfs()
+            .and_then(Ntfs::created)
+            .or_else(|| self.extended_timestamp(ExtendedTimestamp::cr_time))
+    }
+
+    /// Get the user and group IDs of the file's owner on Unix, from the Info-ZIP new Unix extra
+    /// field (0x7875), as `(uid, gid)`
+    pub fn unix_ownership(&self) -> Option<(u32, u32)> {
+        self.data.extra_fields.iter().find_map(|field| match field {
+            ExtraField::UnixOwnership(ownership) => Some((ownership.uid(), ownership.gid())),
+            _ => None,
+        })
+    }
+
+    fn ntfs(&self) -> Option<&Ntfs> {
+        self.data.extra_fields.iter().find_map(|field| match field {
+            ExtraField::Ntfs(ntfs) => Some(ntfs),
+            _ => None,
+        })
+    }
+
+    fn extended_timestamp(
+        &self,
+        time: fn(&ExtendedTimestamp) -> Option<&u32>,
+    ) -> Option<SystemTime> {
+        self.data
+            .extra_fields
+            .iter()
+            .find_map(|field| match field {
+                ExtraField::ExtendedTimestamp(ts) => time(ts),
+                _ => None,
+            })
+            .map(|&secs| UNIX_EPOCH + Duration::from_secs(secs as u64))
+    }
+
+    /// Returns whether the file is actually a directory, because either its name ends with `/`
+    /// or its mode says so
     pub fn is_dir(&self) -> bool {
-        self.name()
-            .chars()
-            .next_back()
-            .map_or(false, |c| c == '/' || c == '\\')
+        self.data.is_dir()
     }
 
     /// Returns whether the file is a regular file
@@ -1215,16 +3726,74 @@ impl<'a> ZipFile<'a> {
         !self.is_dir()
     }
 
+    /// Returns whether the file is a symbolic link, according to its Unix mode. The link target
+    /// is the file's contents.
+    pub fn is_symlink(&self) -> bool {
+        self.data.is_symlink()
+    }
+
+    /// Returns whether the file is a hard link to another entry, which has a link target in its
+    /// PKWARE Unix extra field. The file's own contents are usually empty.
+    pub fn is_hardlink(&self) -> bool {
+        self.hardlink_target().is_some()
+    }
+
+    /// Get the name of the entry that this file is a hard link to, from its PKWARE Unix extra
+    /// field. Directories and symbolic links are never hard links.
+    pub fn hardlink_target(&self) -> Option<Box<str>> {
+        if self.is_dir() || self.is_symlink() {
+            return None;
+        }
+        let encoding = if self.data.is_utf8 {
+            NameEncoding::Utf8
+        } else {
+            NameEncoding::Cp437
+        };
+        self.raw_extra_fields()
+            .filter(|field| field.header_id() == PKWARE_UNIX_EXTRA_FIELD_ID)
+            .find_map(|field| {
+                let target = field.data().get(12..)?;
+                (!target.is_empty()).then(|| encoding.decode(target))
+            })
+    }
+
     /// Get unix mode for the file
     pub fn unix_mode(&self) -> Option<u32> {
         self.data.unix_mode()
     }
 
+    /// Get the external file attributes, whose meaning depends on the host system that made the
+    /// file
+    pub fn external_attributes(&self) -> u32 {
+        self.data.external_attributes
+    }
+
+    /// Get the MS-DOS attributes of the file from the low byte of its external file attributes
+    pub fn dos_attributes(&self) -> DosAttributes {
+        DosAttributes::from_external_attributes(self.data.external_attributes)
+    }
+
     /// Get the CRC32 hash of the original file
     pub fn crc32(&self) -> u32 {
         self.data.crc32
     }
 
+    /// Returns whether bit 3 of the general purpose flags is set, meaning that the file's CRC-32
+    /// and sizes follow its data in a data descriptor, which a raw copy of the data has to carry
+    /// along
+    pub fn has_data_descriptor(&self) -> bool {
+        self.data.using_data_descriptor
+    }
+
+    /// Returns whether the file has a ZIP64 extended information extra field, or had sizes too
+    /// large for its header
+    pub fn uses_zip64(&self) -> bool {
+        self.data.large_file
+            || self
+                .raw_extra_fields()
+                .any(|field| field.header_id() == 0x0001)
+    }
+
     /// Get the extra data of the zip header for this file
     pub fn extra_data(&self) -> Option<&[u8]> {
         self.data.extra_field.as_ref().map(|v| v.deref().deref())
@@ -1248,11 +3817,28 @@ impl<'a> ZipFile<'a> {
     pub fn extra_data_fields(&self) -> impl Iterator<Item = &ExtraField> {
         self.data.extra_fields.iter()
     }
+
+    /// Iterate through every field in [`ZipFile::extra_data`], including ones this crate doesn't
+    /// interpret.
+    ///
+    /// For a file opened from a [`ZipArchive`], this is the extra data in the central directory;
+    /// use [`ZipArchive::local_extra_fields`] for the local header's. For a file read with
+    /// [`read_zipfile_from_stream`], it's the local header's extra data.
+    pub fn raw_extra_fields(&self) -> impl Iterator<Item = RawExtraField> + '_ {
+        let central_only = self.data.central_extra_field.as_deref();
+        RawExtraField::parse_all(self.extra_data().unwrap_or_default()).chain(
+            RawExtraField::parse_all(central_only.map(Vec::as_slice).unwrap_or_default()),
+        )
+    }
 }
 
 impl<'a> Read for ZipFile<'a> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.get_reader()?.read(buf)
+        let count = self.get_reader()?.read(buf)?;
+        self.bytes_decompressed += count as u64;
+        self.limits
+            .check(self.bytes_decompressed, self.data.compressed_size)?;
+        Ok(count)
     }
 }
 
@@ -1279,31 +3865,8 @@ impl<'a> Drop for ZipFile<'a> {
     }
 }
 
-/// Read ZipFile structures from a non-seekable reader.
-///
-/// This is an alternative method to read a zip file. If possible, use the ZipArchive functions
-/// as some information will be missing when reading this manner.
-///
-/// Reads a file header from the start of the stream. Will return `Ok(Some(..))` if a file is
-/// present at the start of the stream. Returns `Ok(None)` if the start of the central directory
-/// is encountered. No more files should be read after this.
-///
-/// The Drop implementation of ZipFile ensures that the reader will be correctly positioned after
-/// the structure is done.
-///
-/// Missing fields are:
-/// * `comment`: set to an empty string
-/// * `data_start`: set to 0
-/// * `external_attributes`: `unix_mode()`: will return None
-pub fn read_zipfile_from_stream<'a, R: Read>(reader: &'a mut R) -> ZipResult<Option<ZipFile<'_>>> {
-    let signature = reader.read_u32_le()?;
-
-    match signature {
-        spec::LOCAL_FILE_HEADER_SIGNATURE => (),
-        spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE => return Ok(None),
-        _ => return Err(ZipError::InvalidArchive("Invalid local file header")),
-    }
-
+/// Parse a local file header, whose signature has already been read, up to the start of the data.
+fn local_header_to_zip_file_data<R: Read>(reader: &mut R) -> ZipResult<ZipFileData> {
     let version_made_by = reader.read_u16_le()?;
     let flags = reader.read_u16_le()?;
     let encrypted = flags & 1 == 1;
@@ -1334,7 +3897,9 @@ pub fn read_zipfile_from_stream<'a, R: Read>(reader: &'a mut R) -> ZipResult<Opt
         version_made_by: version_made_by as u8,
         encrypted,
         using_data_descriptor,
+        is_utf8,
         compression_method,
+        compression_flags: flags & COMPRESSION_FLAGS,
         compression_level: None,
         last_modified_time: DateTime::from_msdos(last_mod_date, last_mod_time),
         crc32,
@@ -1345,6 +3910,7 @@ pub fn read_zipfile_from_stream<'a, R: Read>(reader: &'a mut R) -> ZipResult<Opt
         extra_field: Some(Arc::new(extra_field)),
         central_extra_field: None,
         file_comment: String::with_capacity(0).into_boxed_str(), // file comment is only available in the central directory
+        file_comment_raw: Box::new([]),
         // header_start and data start are not available, but also don't matter, since seeking is
         // not available.
         header_start: 0,
@@ -1358,6 +3924,9 @@ pub fn read_zipfile_from_stream<'a, R: Read>(reader: &'a mut R) -> ZipResult<Opt
         large_file: false,
         aes_mode: None,
         aes_extra_data_start: 0,
+        aes_iterations: None,
+        strong_encryption_flag: flags & (1 << 6) != 0,
+        min_version_needed: 0,
         extra_fields: Vec::new(),
     };
 
@@ -1366,38 +3935,383 @@ pub fn read_zipfile_from_stream<'a, R: Read>(reader: &'a mut R) -> ZipResult<Opt
         Err(e) => return Err(e),
     }
 
-    if encrypted {
-        return unsupported_zip_error("Encrypted files are not supported");
+    Ok(result)
+}
+
+/// Finds the offsets of every ZIP signature in `reader`, in order.
+fn find_signatures<R: Read + Seek>(reader: &mut R) -> ZipResult<Vec<(u64, u32)>> {
+    const SIGNATURES: [u32; 6] = [
+        spec::LOCAL_FILE_HEADER_SIGNATURE,
+        spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE,
+        spec::CENTRAL_DIRECTORY_END_SIGNATURE,
+        spec::ZIP64_CENTRAL_DIRECTORY_END_SIGNATURE,
+        spec::ZIP64_CENTRAL_DIRECTORY_END_LOCATOR_SIGNATURE,
+        spec::DATA_DESCRIPTOR_SIGNATURE,
+    ];
+    reader.rewind()?;
+    let mut reader = io::BufReader::new(reader);
+    let mut signatures = Vec::new();
+    let mut window = 0u32;
+    let mut position = 0u64;
+    loop {
+        let buffer = reader.fill_buf()?;
+        if buffer.is_empty() {
+            break;
+        }
+        for &byte in buffer {
+            window = window >> 8 | (byte as u32) << 24;
+            position += 1;
+            if position >= 4 && SIGNATURES.contains(&window) {
+                signatures.push((position - 4, window));
+            }
+        }
+        let len = buffer.len();
+        reader.consume(len);
+    }
+    Ok(signatures)
+}
+
+/// Fills in the CRC and sizes of a salvaged entry from the data descriptor that ends its data,
+/// trying each following signature in turn as the one after the entry's data. Returns the end of
+/// the entry's data, or `None` if no signature fits.
+fn salvage_data_descriptor<R: Read + Seek>(
+    reader: &mut R,
+    data: &mut ZipFileData,
+    data_start: u64,
+    following_signatures: &[(u64, u32)],
+) -> ZipResult<Option<u64>> {
+    for &(position, signature) in following_signatures {
+        // A signed data descriptor starts at the signature, and an unsigned one ends at the next.
+        let candidates = if signature == spec::DATA_DESCRIPTOR_SIGNATURE {
+            [
+                (Some(position), position + 4, false),
+                (Some(position), position + 4, true),
+            ]
+        } else {
+            [
+                (position.checked_sub(12), position.saturating_sub(12), false),
+                (position.checked_sub(20), position.saturating_sub(20), true),
+            ]
+        };
+        for (data_end, descriptor_start, zip64) in candidates {
+            let Some(data_end) = data_end.filter(|&end| end >= data_start) else {
+                continue;
+            };
+            reader.seek(io::SeekFrom::Start(descriptor_start))?;
+            let mut read_descriptor = || -> io::Result<_> {
+                let crc32 = reader.read_u32_le()?;
+                Ok(if zip64 {
+                    (crc32, reader.read_u64_le()?, reader.read_u64_le()?)
+                } else {
+                    let compressed_size = reader.read_u32_le()? as u64;
+                    (crc32, compressed_size, reader.read_u32_le()? as u64)
+                })
+            };
+            let Ok((crc32, compressed_size, uncompressed_size)) = read_descriptor() else {
+                continue;
+            };
+            if compressed_size == data_end - data_start {
+                data.crc32 = crc32;
+                data.compressed_size = compressed_size;
+                data.uncompressed_size = uncompressed_size;
+                return Ok(Some(data_end));
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// Count the entries in a ZIP archive, reading only its end-of-central-directory record and any
+/// ZIP64 one
+///
+/// This is much cheaper than opening a large archive with [`ZipArchive::new`], and fails if no
+/// end-of-central-directory record can be found, so it also makes a quick check of whether
+/// `reader` looks like a ZIP archive. None of the central directory is parsed, so a count is no
+/// guarantee that the entries themselves can be read; it's the same count that
+/// [`ZipArchive::total_entries`] reports.
+pub fn count_entries<R: Read + Seek>(mut reader: R) -> ZipResult<usize> {
+    let (_, dir_info) = ZipArchive::find_archive(
+        &mut reader,
+        spec::MAX_HEADER_AND_COMMENT_SIZE,
+        ZipArchive::find_directory_info,
+    )?;
+    Ok(dir_info.number_of_files)
+}
+
+/// Read ZipFile structures from a non-seekable reader.
+///
+/// This is an alternative method to read a zip file. If possible, use the ZipArchive functions
+/// as some information will be missing when reading this manner.
+///
+/// Reads a file header from the start of the stream. Will return `Ok(Some(..))` if a file is
+/// present at the start of the stream. Returns `Ok(None)` if the start of the central directory
+/// is encountered. No more files should be read after this.
+///
+/// The Drop implementation of ZipFile ensures that the reader will be correctly positioned after
+/// the structure is done.
+///
+/// Missing fields are:
+/// * `comment`: set to an empty string
+/// * `data_start`: set to 0
+/// * `external_attributes`: `unix_mode()`: will return None
+///
+/// A file whose sizes are left for the data descriptor after its data is read into memory in
+/// full, since the end of its data can only be found by searching for that descriptor, and its
+/// sizes and CRC-32 have to be known once it's opened. Reading fails with
+/// [`ZipError::InvalidArchive`] if that data is longer than 1 GiB. The search reads the stream a
+/// few bytes at a time so as not to read past the descriptor, so wrap an unbuffered reader in a
+/// [`BufReader`](std::io::BufReader), or use [`ZipStreamReader`](crate::unstable::stream::ZipStreamReader),
+/// which does that itself.
+///
+/// Encrypted files can't be read this way; use [`read_zipfile_from_stream_with_password`] for
+/// those.
+pub fn read_zipfile_from_stream<'a, R: Read>(reader: &'a mut R) -> ZipResult<Option<ZipFile<'_>>> {
+    read_zipfile_from_stream_inner(reader, None, MAX_BUFFERED_ENTRY_SIZE)
+}
+
+/// Read the data of a file from a stream, up to and including the data descriptor that follows
+/// it, and fill in the file's CRC-32 and sizes from the descriptor
+///
+/// Since the local header doesn't say how long the data is, it's taken to end at the first signed
+/// data descriptor whose compressed size matches the length of the data before it. That
+/// descriptor has 8-byte sizes if the local header has a ZIP64 extra field. Each read stops at the
+/// end of the next place a matching descriptor could end, so nothing after it is consumed. Fails
+/// once more than `max_size` bytes have been read without finding it.
+fn read_to_data_descriptor<R: Read>(
+    reader: &mut R,
+    file: &mut ZipFileData,
+    max_size: u64,
+) -> ZipResult<Vec<u8>> {
+    const SIGNATURE: [u8; 4] = spec::DATA_DESCRIPTOR_SIGNATURE.to_le_bytes();
+    let descriptor_len = if file.large_file { 24 } else { 16 };
+    let mut data = Vec::new();
+    loop {
+        if let Some(start) = data.len().checked_sub(descriptor_len) {
+            let mut descriptor = &data[start..];
+            if descriptor.read_u32_le()? == spec::DATA_DESCRIPTOR_SIGNATURE {
+                let crc32 = descriptor.read_u32_le()?;
+                let (compressed_size, uncompressed_size) = if file.large_file {
+                    (descriptor.read_u64_le()?, descriptor.read_u64_le()?)
+                } else {
+                    (
+                        descriptor.read_u32_le()? as u64,
+                        descriptor.read_u32_le()? as u64,
+                    )
+                };
+                if compressed_size == start as u64 {
+                    file.crc32 = crc32;
+                    file.compressed_size = compressed_size;
+                    file.uncompressed_size = uncompressed_size;
+                    data.truncate(start);
+                    return Ok(data);
+                }
+            }
+        }
+        // A descriptor can start anywhere from the last few bytes read, as long as they match the
+        // start of the signature, up to right after them.
+        let read_so_far = data.len();
+        let next_end = (read_so_far.saturating_sub(descriptor_len - 1)..=read_so_far)
+            .filter(|&start| SIGNATURE.starts_with(&data[start..read_so_far.min(start + 4)]))
+            .map(|start| start + descriptor_len)
+            .find(|&end| end > read_so_far)
+            .unwrap_or(read_so_far + descriptor_len);
+        if (next_end - descriptor_len) as u64 > max_size {
+            return Err(ZipError::InvalidArchive(
+                "Data before the data descriptor is too long to buffer",
+            ));
+        }
+        data.resize(next_end, 0);
+        let count = reader.read(&mut data[read_so_far..])?;
+        if count == 0 {
+            return Err(ZipError::InvalidArchive(
+                "Data descriptor not found before the end of the stream",
+            ));
+        }
+        data.truncate(read_so_far + count);
+    }
+}
+
+/// Read ZipFile structures from a non-seekable reader, decrypting them with `password`
+///
+/// This works like [`read_zipfile_from_stream`], except that ZipCrypto and AES encrypted files
+/// are decrypted as they're read, after reading their encryption header from the stream. Files
+/// that aren't encrypted are read as usual. A wrong password is reported as
+/// [`ZipError::InvalidPassword`], after which the stream is left partway through the file and no
+/// more files can be read from it. AE-2 encrypted files don't store a CRC-32, so they're checked
+/// against their authentication code instead.
+pub fn read_zipfile_from_stream_with_password<'a, R: Read>(
+    reader: &'a mut R,
+    password: &[u8],
+) -> ZipResult<Option<ZipFile<'a>>> {
+    read_zipfile_from_stream_inner(reader, Some(password), MAX_BUFFERED_ENTRY_SIZE)
+}
+
+/// The longest that the data of a file read from a stream can be when it has to be buffered to
+/// find its data descriptor, unless [`ZipStreamReader::max_buffered_entry_size`] says otherwise
+///
+/// [`ZipStreamReader::max_buffered_entry_size`]: crate::unstable::stream::ZipStreamReader::max_buffered_entry_size
+pub(crate) const MAX_BUFFERED_ENTRY_SIZE: u64 = 1 << 30;
+
+fn read_zipfile_from_stream_inner<'a, R: Read>(
+    reader: &'a mut R,
+    password: Option<&[u8]>,
+    max_buffered_size: u64,
+) -> ZipResult<Option<ZipFile<'a>>> {
+    let signature = reader.read_u32_le()?;
+
+    match signature {
+        spec::LOCAL_FILE_HEADER_SIGNATURE => (),
+        spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE => return Ok(None),
+        _ => return Err(ZipError::InvalidArchive("Invalid local file header")),
+    }
+
+    let mut result = local_header_to_zip_file_data(reader)?;
+    let password = password_for(&result, password)?;
+    let reader = if result.using_data_descriptor {
+        let data = read_to_data_descriptor(reader, &mut result, max_buffered_size)?;
+        EntryReader::Buffered(io::Cursor::new(data))
+    } else {
+        EntryReader::Borrowed(reader)
+    };
+    open_local_entry(result, reader, password).map(Some)
+}
+
+/// Decode a file from a copy of just its part of an archive, such as one fetched with an HTTP
+/// range request
+///
+/// `bytes` must start at the file's local header, at [`EntryMetadata::local_header_offset`], and
+/// run at least to the end of its data, [`EntryMetadata::compressed_size`] bytes after
+/// [`EntryMetadata::data_start`]. The local header is parsed to find where the data starts past
+/// its extra field, which must agree with `meta`. The CRC-32 and sizes are taken from `meta`,
+/// since the local header may leave them for a data descriptor.
+///
+/// Encrypted files are decrypted with `password`, and can't be decoded without one.
+pub fn decode_entry<'a>(
+    bytes: &'a [u8],
+    meta: &EntryMetadata,
+    password: Option<&[u8]>,
+) -> ZipResult<ZipFile<'a>> {
+    let mut header = bytes;
+    if header.read_u32_le()? != spec::LOCAL_FILE_HEADER_SIGNATURE {
+        return Err(ZipError::InvalidArchive("Invalid local file header"));
+    }
+    let mut result = local_header_to_zip_file_data(&mut header)?;
+    let header_len = (bytes.len() - header.len()) as u64;
+    if meta.data_start.checked_sub(meta.local_header_offset) != Some(header_len) {
+        return Err(ZipError::InvalidArchive(
+            "Local header doesn't match the entry metadata",
+        ));
+    }
+    let data = usize::try_from(meta.compressed_size)
+        .ok()
+        .and_then(|compressed_size| header.get(..compressed_size))
+        .ok_or(ZipError::InvalidArchive(
+            "File data runs past the end of the given bytes",
+        ))?;
+    result.crc32 = meta.crc32;
+    result.compressed_size = meta.compressed_size;
+    result.uncompressed_size = meta.uncompressed_size;
+    result.header_start = meta.local_header_offset;
+    result.data_start = OnceLock::from(meta.data_start);
+    let password = password_for(&result, password)?;
+    open_local_entry(result, EntryReader::Slice(io::Cursor::new(data)), password)
+}
+
+/// The password to decrypt a file with, or an error if it's encrypted and there isn't one or it
+/// can't be decrypted at all
+fn password_for<'p>(data: &ZipFileData, password: Option<&'p [u8]>) -> ZipResult<Option<&'p [u8]>> {
+    if let Some(algorithm) = data.strong_encryption() {
+        return Err(ZipError::UnsupportedEncryption(algorithm));
+    }
+    match (password, data.encrypted) {
+        (None, true) => Err(ZipError::PasswordRequired),
+        (Some(_), false) => Ok(None),
+        (password, _) => Ok(password),
+    }
+}
+
+/// Recover the [`ZipError`] that reading a [`ZipFile`] wrapped in an [`io::Error`], such as a
+/// checksum mismatch, or wrap any other I/O error
+fn unwrap_zip_error(e: io::Error) -> ZipError {
+    if e.get_ref().is_some_and(|inner| inner.is::<ZipError>()) {
+        *e.into_inner().unwrap().downcast::<ZipError>().unwrap()
+    } else {
+        ZipError::Io(e)
     }
-    if using_data_descriptor {
-        return unsupported_zip_error("The file length is not available in the local header");
+}
+
+/// The PBKDF2 iteration count recorded for an AES encrypted file, or the standard count if it
+/// doesn't record one. Counts above [`crate::aes::MAX_RECORDED_ITERATION_COUNT`] are refused.
+#[cfg(feature = "aes-crypto")]
+fn recorded_aes_iterations(data: &ZipFileData) -> ZipResult<u32> {
+    match data.aes_iterations {
+        Some(iterations) if iterations > crate::aes::MAX_RECORDED_ITERATION_COUNT => Err(
+            ZipError::UnsupportedArchive("AES iteration count recorded in the archive is too high"),
+        ),
+        iterations => Ok(iterations.unwrap_or(crate::aes::DEFAULT_ITERATION_COUNT)),
     }
+}
 
-    let limit_reader = (reader as &'a mut dyn Read).take(result.compressed_size);
+/// Open a file whose metadata comes from its local header, reading its data from `reader`
+fn open_local_entry<'a>(
+    result: ZipFileData,
+    reader: EntryReader<'a>,
+    password: Option<&[u8]>,
+) -> ZipResult<ZipFile<'a>> {
+    let rewindable = !matches!(reader, EntryReader::Borrowed(_));
+    let limit_reader = reader.take(result.compressed_size);
 
     let result_crc32 = result.crc32;
+    let result_uncompressed_size = result.uncompressed_size;
     let result_compression_method = result.compression_method;
+    #[cfg(feature = "legacy")]
+    let result_compression_flags = result.compression_flags;
+    check_compression_supported(result_compression_method)?;
+    #[cfg(feature = "aes-crypto")]
+    let aes_iterations = match result.aes_mode {
+        Some(_) if password.is_some() => recorded_aes_iterations(&result)?,
+        _ => crate::aes::DEFAULT_ITERATION_COUNT,
+    };
     let crypto_reader = make_crypto_reader(
-        result_compression_method,
         result_crc32,
         result.last_modified_time,
         result.using_data_descriptor,
         limit_reader,
-        None,
-        None,
+        password.as_ref().map_or(&[], slice::from_ref),
+        result.aes_mode,
         #[cfg(feature = "aes-crypto")]
         result.compressed_size,
+        #[cfg(feature = "aes-crypto")]
+        aes_iterations,
     )?;
 
-    Ok(Some(ZipFile {
+    Ok(ZipFile {
         data: Cow::Owned(result),
         crypto_reader: None,
-        reader: make_reader(result_compression_method, result_crc32, crypto_reader)?,
-    }))
+        reader: make_reader(
+            result_compression_method,
+            result_crc32,
+            result_uncompressed_size,
+            crypto_reader,
+            true,
+            #[cfg(feature = "zstd")]
+            None,
+            #[cfg(feature = "legacy")]
+            result_compression_flags,
+        )?,
+        limits: DecompressionLimits::default(),
+        bytes_decompressed: 0,
+        verify_crc: true,
+        decompressor: None,
+        #[cfg(feature = "zstd")]
+        zstd_dictionary: None,
+        rewindable,
+    })
 }
 
 #[cfg(test)]
 mod test {
+    use crate::extra_fields::ExtraField;
     use crate::ZipArchive;
     use std::io::Cursor;
 
@@ -1594,4 +4508,2432 @@ mod test {
         let mut file = reader.by_index(0).unwrap();
         assert_eq!(file.read(&mut decompressed).unwrap(), 12);
     }
+
+    #[test]
+    fn extract_parallel() {
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::Write;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .add_directory("dir/", SimpleFileOptions::default())
+            .unwrap();
+        for i in 0..50 {
+            writer
+                .start_file(
+                    format!("dir/sub{}/file{i}.txt", i % 3),
+                    SimpleFileOptions::default(),
+                )
+                .unwrap();
+            writer
+                .write_all(format!("contents {i}").as_bytes())
+                .unwrap();
+        }
+        let archive = writer.finish_into_readable().unwrap();
+
+        let dir = tempdir::TempDir::new("extract_parallel").unwrap();
+        archive.extract_parallel(dir.path(), 4).unwrap();
+        assert!(dir.path().join("dir").is_dir());
+        for i in 0..50 {
+            let path = dir.path().join(format!("dir/sub{}/file{i}.txt", i % 3));
+            assert_eq!(
+                std::fs::read_to_string(path).unwrap(),
+                format!("contents {i}")
+            );
+        }
+    }
+
+    #[test]
+    fn extract_parallel_removes_failed_file() {
+        use crate::write::SimpleFileOptions;
+        use crate::{CompressionMethod, ZipWriter};
+        use std::io::Write;
+
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("good.txt", options).unwrap();
+        writer.write_all(b"good").unwrap();
+        writer.start_file("bad.txt", options).unwrap();
+        writer.write_all(b"corrupted").unwrap();
+        let mut v = writer.finish().unwrap().into_inner();
+        let pos = v.windows(9).position(|w| w == b"corrupted").unwrap();
+        v[pos] = b'C';
+        let archive = ZipArchive::new(Cursor::new(v)).unwrap();
+
+        let dir = tempdir::TempDir::new("extract_parallel").unwrap();
+        let err = archive.extract_parallel(dir.path(), 2).unwrap_err();
+        assert!(err.to_string().contains("Invalid checksum"));
+        assert!(!dir.path().join("bad.txt").exists());
+    }
+
+    #[test]
+    fn salvage_truncated_archive() {
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::{Read, Write};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in [("a.txt", "first"), ("b.txt", "second"), ("c.txt", "third")] {
+            writer
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents.repeat(100).as_bytes()).unwrap();
+        }
+        let mut v = writer.finish().unwrap().into_inner();
+        let c_start = v.windows(5).rposition(|w| w == b"c.txt").unwrap();
+        let c_start = v[..c_start]
+            .windows(5)
+            .rposition(|w| w == b"c.txt")
+            .unwrap();
+        v.truncate(c_start + 8);
+        assert!(ZipArchive::new(Cursor::new(v.clone())).is_err());
+
+        let mut archive = ZipArchive::with_salvage(Cursor::new(v)).unwrap();
+        assert_eq!(archive.file_names().collect::<Vec<_>>(), ["a.txt", "b.txt"]);
+        let mut contents = String::new();
+        archive
+            .by_name("b.txt")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "second".repeat(100));
+    }
+
+    #[test]
+    fn salvage_data_descriptor() {
+        use std::io::Read;
+
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!("../tests/data/data_descriptor.zip"));
+        let central_start = v.windows(4).position(|w| w == b"PK\x01\x02").unwrap();
+        v.truncate(central_start);
+        let signed = v.clone();
+        // The same archive, but without the optional data descriptor signature
+        let descriptor_start = v.windows(4).position(|w| w == b"PK\x07\x08").unwrap();
+        v.drain(descriptor_start..descriptor_start + 4);
+        let mut unsigned = v;
+        // Followed by another entry, which is truncated
+        unsigned.extend_from_slice(&signed[..40]);
+
+        for v in [signed, unsigned] {
+            let mut archive = ZipArchive::with_salvage(Cursor::new(v)).unwrap();
+            assert_eq!(archive.len(), 1);
+            let mut file = archive.by_index(0).unwrap();
+            assert_eq!(file.name(), "hello.txt");
+            assert_eq!(file.size(), 12);
+            let mut contents = String::new();
+            file.read_to_string(&mut contents).unwrap();
+            assert_eq!(contents, "Hello World\n");
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn extract_with_options() {
+        use super::ExtractOptions;
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::Write;
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .add_directory(
+                "dir/",
+                SimpleFileOptions::default()
+                    .unix_permissions(0o750)
+                    .with_extended_timestamp(1_000_000_000, None, None),
+            )
+            .unwrap();
+        writer
+            .start_file(
+                "dir/run.sh",
+                SimpleFileOptions::default()
+                    .unix_permissions(0o755)
+                    .with_extended_timestamp(1_500_000_000, None, None),
+            )
+            .unwrap();
+        writer.write_all(b"#!/bin/sh\n").unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        let metadata = |dir: &tempdir::TempDir, name| {
+            let metadata = std::fs::metadata(dir.path().join(name)).unwrap();
+            (
+                metadata.permissions().mode() & 0o777,
+                metadata.modified().unwrap(),
+            )
+        };
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+
+        let dir = tempdir::TempDir::new("extract_with_options").unwrap();
+        archive
+            .extract_with_options(dir.path(), ExtractOptions::default().preserve_mtime(true))
+            .unwrap();
+        assert_eq!(metadata(&dir, "dir"), (0o750, at(1_000_000_000)));
+        assert_eq!(metadata(&dir, "dir/run.sh"), (0o755, at(1_500_000_000)));
+
+        let dir = tempdir::TempDir::new("extract_with_options").unwrap();
+        archive
+            .extract_with_options(
+                dir.path(),
+                ExtractOptions::default().preserve_permissions(false),
+            )
+            .unwrap();
+        let (mode, mtime) = metadata(&dir, "dir/run.sh");
+        assert_eq!(mode & 0o111, 0);
+        assert!(mtime > at(1_500_000_000));
+    }
+
+    #[test]
+    fn extract_lazy_duplicates() -> crate::result::ZipResult<()> {
+        use crate::write::{DuplicatePolicy, SimpleFileOptions};
+        use crate::{CompressionMethod, ZipWriter};
+        use std::io::Write;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_duplicate_policy(DuplicatePolicy::Allow);
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        for (name, contents) in [("a.txt", "first"), ("a.txt", "second"), ("b.txt", "third")] {
+            writer.start_file(name, options)?;
+            writer.write_all(contents.as_bytes())?;
+        }
+        let bytes = writer.finish()?.into_inner();
+
+        let mut archive = ZipArchive::new_lazy(Cursor::new(bytes))?;
+        let dir = tempdir::TempDir::new("extract_lazy_duplicates")?;
+        archive.extract(dir.path())?;
+        // A lazily read archive keeps the first entry with a name
+        assert_eq!(std::fs::read_to_string(dir.path().join("a.txt"))?, "first");
+        assert_eq!(std::fs::read_to_string(dir.path().join("b.txt"))?, "third");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn unix_ownership() {
+        use super::ExtractOptions;
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::os::unix::fs::MetadataExt;
+
+        let dir = tempdir::TempDir::new("unix_ownership").unwrap();
+        let own = std::fs::metadata(dir.path()).unwrap();
+        let (uid, gid) = (own.uid(), own.gid());
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer
+            .start_file("mine.txt", options.with_unix_ownership(uid, gid))
+            .unwrap();
+        writer
+            .start_file("other.txt", options.with_unix_ownership(54_321, 54_321))
+            .unwrap();
+        writer.start_file("unowned.txt", options).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+        assert_eq!(
+            archive.by_index(0).unwrap().unix_ownership(),
+            Some((uid, gid))
+        );
+        assert_eq!(
+            archive.by_index(1).unwrap().unix_ownership(),
+            Some((54_321, 54_321))
+        );
+        assert_eq!(archive.by_index(2).unwrap().unix_ownership(), None);
+
+        // Files can't be given away without privileges, which doesn't stop the extraction
+        archive
+            .extract_with_options(
+                dir.path(),
+                ExtractOptions::default().preserve_ownership(true),
+            )
+            .unwrap();
+        let owner = |name: &str| {
+            let metadata = std::fs::metadata(dir.path().join(name)).unwrap();
+            (metadata.uid(), metadata.gid())
+        };
+        assert_eq!(owner("mine.txt"), (uid, gid));
+        let other = owner("other.txt");
+        assert!(other == (54_321, 54_321) || other.0 == uid, "{other:?}");
+        assert_eq!(owner("unowned.txt"), (uid, gid));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn extract_symlinks() {
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::Write;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("dir/file.txt", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"contents").unwrap();
+        writer
+            .add_symlink("dir/link", "file.txt", SimpleFileOptions::default())
+            .unwrap();
+        writer
+            .add_symlink("up", "dir/../dir/file.txt", SimpleFileOptions::default())
+            .unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+        assert!(archive.by_name("up").unwrap().is_symlink());
+        assert!(!archive.by_name("dir/file.txt").unwrap().is_symlink());
+
+        let dir = tempdir::TempDir::new("extract_symlinks").unwrap();
+        archive.extract(dir.path()).unwrap();
+        assert_eq!(
+            std::fs::read_link(dir.path().join("dir/link")).unwrap(),
+            std::path::Path::new("file.txt")
+        );
+        assert_eq!(std::fs::read(dir.path().join("up")).unwrap(), b"contents");
+
+        let dir = tempdir::TempDir::new("extract_symlinks").unwrap();
+        archive.extract_parallel(dir.path(), 2).unwrap();
+        assert_eq!(
+            std::fs::read(dir.path().join("dir/link")).unwrap(),
+            b"contents"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn extract_rejects_symlink_chains() {
+        use crate::result::ZipError;
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::Write;
+
+        // Once `sub/y` exists, `sub/x` leads to the parent of the directory
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .add_symlink("sub/x", "y/../..", SimpleFileOptions::default())
+            .unwrap();
+        writer
+            .add_symlink("sub/y", ".", SimpleFileOptions::default())
+            .unwrap();
+        writer
+            .start_file("sub/x/escaped.txt", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"escaped").unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        let parent = tempdir::TempDir::new("extract_symlinks").unwrap();
+        let dir = parent.path().join("out");
+        std::fs::create_dir(&dir).unwrap();
+        match archive.extract(&dir) {
+            Err(ZipError::InvalidPath(name)) => assert_eq!(&*name, "sub/x"),
+            other => panic!("unexpected result {other:?}"),
+        }
+        assert!(parent
+            .path()
+            .join("escaped.txt")
+            .symlink_metadata()
+            .is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn extract_rejects_escaping_symlinks() {
+        use crate::result::ZipError;
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+
+        for links in [
+            &[("evil", "../outside")][..],
+            &[("evil", "/etc/passwd")],
+            &[("dir/evil", "../../outside")],
+            // Each link is fine on its own, but the second goes through the first
+            &[("here", "."), ("here/evil", "..")],
+        ] {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            for (name, target) in links {
+                writer
+                    .add_symlink(*name, *target, SimpleFileOptions::default())
+                    .unwrap();
+            }
+            let mut archive = writer.finish_into_readable().unwrap();
+            let parent = tempdir::TempDir::new("extract_symlinks").unwrap();
+            let dir = parent.path().join("out");
+            std::fs::create_dir(&dir).unwrap();
+            match archive.extract(&dir) {
+                Err(ZipError::InvalidPath(name)) => {
+                    assert_eq!(&*name, links.last().unwrap().0)
+                }
+                other => panic!("{links:?}: unexpected result {other:?}"),
+            }
+            assert!(dir
+                .join(links.last().unwrap().0)
+                .symlink_metadata()
+                .is_err());
+        }
+    }
+
+    #[test]
+    fn extract_safe_rejects_escaping_names() {
+        use crate::result::ZipError;
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::Write;
+
+        for name in [
+            "../../etc/passwd",
+            "a/../../b",
+            "/etc/passwd",
+            "C:\\foo",
+            "C:foo",
+            "..\\..\\foo",
+            "\\\\server\\share\\foo",
+        ] {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            writer
+                .start_file("ok.txt", SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(b"ok").unwrap();
+            writer
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(b"evil").unwrap();
+            let mut archive = writer.finish_into_readable().unwrap();
+
+            let parent = tempdir::TempDir::new("extract_safe").unwrap();
+            let dir = parent.path().join("out");
+            match archive.extract_safe(&dir) {
+                Err(ZipError::InvalidPath(entry)) => assert_eq!(&*entry, name),
+                other => panic!("{name}: unexpected result {other:?}"),
+            }
+            assert!(dir.join("ok.txt").exists());
+            assert_eq!(std::fs::read_dir(parent.path()).unwrap().count(), 1);
+        }
+    }
+
+    #[test]
+    fn extract_safe_accepts_normal_names() {
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::Write;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .add_directory("dir/", SimpleFileOptions::default())
+            .unwrap();
+        writer
+            .start_file("dir/./sub/file.txt", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"contents").unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        let dir = tempdir::TempDir::new("extract_safe").unwrap();
+        archive.extract_safe(dir.path()).unwrap();
+        assert_eq!(
+            std::fs::read(dir.path().join("dir/sub/file.txt")).unwrap(),
+            b"contents"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn extract_safe_symlinks() {
+        use crate::result::ZipError;
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.start_file("file.txt", options).unwrap();
+        writer.add_symlink("link", "file.txt", options).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+        let dir = tempdir::TempDir::new("extract_safe_symlinks").unwrap();
+        archive.extract_safe(dir.path()).unwrap();
+        assert_eq!(
+            std::fs::read_link(dir.path().join("link")).unwrap(),
+            std::path::Path::new("file.txt")
+        );
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_symlink("escape", "../outside", options).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+        let dir = tempdir::TempDir::new("extract_safe_symlinks").unwrap();
+        assert!(matches!(
+            archive.extract_safe(dir.path()),
+            Err(ZipError::InvalidPath(_))
+        ));
+        assert!(dir.path().join("escape").symlink_metadata().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn extract_safe_rejects_symlinked_parent() {
+        use crate::result::ZipError;
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::Write;
+
+        let outside = tempdir::TempDir::new("extract_safe_outside").unwrap();
+        let dir = tempdir::TempDir::new("extract_safe").unwrap();
+        std::os::unix::fs::symlink(outside.path(), dir.path().join("link")).unwrap();
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("link/file.txt", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"evil").unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        assert!(matches!(
+            archive.extract_safe(dir.path()),
+            Err(ZipError::InvalidPath(_))
+        ));
+        assert!(!outside.path().join("file.txt").exists());
+
+        // Nor are directories created out there before the error is noticed
+        for name in ["link/new/file.txt", "link/new/deeper/"] {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            if name.ends_with('/') {
+                writer
+                    .add_directory(name, SimpleFileOptions::default())
+                    .unwrap();
+            } else {
+                writer
+                    .start_file(name, SimpleFileOptions::default())
+                    .unwrap();
+                writer.write_all(b"evil").unwrap();
+            }
+            let mut archive = writer.finish_into_readable().unwrap();
+            match archive.extract_safe(dir.path()) {
+                Err(ZipError::InvalidPath(invalid)) => assert_eq!(&*invalid, name),
+                other => panic!("{name}: unexpected result {other:?}"),
+            }
+            assert!(!outside.path().join("new").exists());
+        }
+    }
+
+    #[test]
+    fn decompression_limits() {
+        use crate::write::SimpleFileOptions;
+        use crate::{CompressionMethod, ZipWriter};
+        use std::io::{Read, Write};
+
+        #[allow(unused_mut)]
+        let mut methods = vec![CompressionMethod::Stored];
+        #[cfg(feature = "_deflate-any")]
+        methods.push(CompressionMethod::Deflated);
+        #[cfg(feature = "bzip2")]
+        methods.push(CompressionMethod::Bzip2);
+        #[cfg(feature = "zstd")]
+        methods.push(CompressionMethod::Zstd);
+        #[cfg(feature = "brotli")]
+        methods.push(CompressionMethod::Brotli);
+
+        let contents = vec![0u8; 1 << 20];
+        for method in methods {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            writer
+                .start_file(
+                    "zeros",
+                    SimpleFileOptions::default().compression_method(method),
+                )
+                .unwrap();
+            writer.write_all(&contents).unwrap();
+            let mut archive = writer.finish_into_readable().unwrap();
+
+            let mut out = Vec::new();
+            archive.by_index(0).unwrap().read_to_end(&mut out).unwrap();
+            assert_eq!(out, contents);
+
+            let mut limited = archive.clone();
+            limited.set_max_decompressed_size(1 << 19);
+            let err = limited
+                .by_index(0)
+                .unwrap()
+                .read_to_end(&mut Vec::new())
+                .unwrap_err();
+            assert!(err.to_string().contains("size limit"), "{method:?}: {err}");
+
+            let mut limited = archive.clone();
+            limited.set_max_compression_ratio(10.0);
+            let result = limited.by_index(0).unwrap().read_to_end(&mut Vec::new());
+            if method == CompressionMethod::Stored {
+                result.unwrap();
+            } else {
+                let err = result.unwrap_err();
+                assert!(err.to_string().contains("ratio limit"), "{method:?}: {err}");
+            }
+        }
+    }
+
+    #[test]
+    fn verify_crc() {
+        use crate::result::ZipError;
+        use crate::spec;
+        use crate::write::SimpleFileOptions;
+        use crate::{CompressionMethod, ZipWriter};
+        use std::io::{Read, Write};
+
+        #[allow(unused_mut)]
+        let mut methods = vec![CompressionMethod::Stored];
+        #[cfg(feature = "_deflate-any")]
+        methods.push(CompressionMethod::Deflated);
+        #[cfg(feature = "bzip2")]
+        methods.push(CompressionMethod::Bzip2);
+        #[cfg(feature = "zstd")]
+        methods.push(CompressionMethod::Zstd);
+        #[cfg(feature = "brotli")]
+        methods.push(CompressionMethod::Brotli);
+
+        for method in methods {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            writer
+                .start_file(
+                    "file",
+                    SimpleFileOptions::default().compression_method(method),
+                )
+                .unwrap();
+            writer.write_all(b"Hello, World!").unwrap();
+            let mut v = writer.finish().unwrap().into_inner();
+            let central = v
+                .windows(4)
+                .rposition(|w| w == spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE.to_le_bytes())
+                .unwrap();
+            let expected = u32::from_le_bytes(v[central + 16..central + 20].try_into().unwrap());
+            v[central + 16..central + 20].copy_from_slice(&(!expected).to_le_bytes());
+            let mut archive = ZipArchive::new(Cursor::new(v)).unwrap();
+
+            let err = archive
+                .by_index(0)
+                .unwrap()
+                .read_to_end(&mut Vec::new())
+                .unwrap_err();
+            match err.into_inner().unwrap().downcast::<ZipError>().map(|e| *e) {
+                Ok(ZipError::InvalidChecksum {
+                    expected: stored,
+                    actual,
+                }) => {
+                    assert_eq!(stored, !expected);
+                    assert_eq!(actual, expected);
+                }
+                other => panic!("{method:?}: {other:?}"),
+            }
+
+            let mut partial = [0u8; 5];
+            archive
+                .by_index(0)
+                .unwrap()
+                .read_exact(&mut partial)
+                .unwrap();
+            assert_eq!(&partial, b"Hello");
+
+            archive.set_verify_crc(false);
+            let mut contents = String::new();
+            archive
+                .by_index(0)
+                .unwrap()
+                .read_to_string(&mut contents)
+                .unwrap();
+            assert_eq!(contents, "Hello, World!");
+        }
+    }
+
+    #[test]
+    fn verify() {
+        use crate::result::ZipError;
+        use crate::spec;
+        use crate::write::SimpleFileOptions;
+        use crate::{CompressionMethod, ZipWriter};
+        use std::io::Write;
+
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("good.txt", options).unwrap();
+        writer.write_all(b"good").unwrap();
+        writer.start_file("bad.txt", options).unwrap();
+        writer.write_all(b"corrupted").unwrap();
+        writer.start_file("resized.txt", options).unwrap();
+        writer.write_all(b"resized").unwrap();
+        writer
+            .start_file(
+                "encrypted.txt",
+                options.with_deprecated_encryption(b"password"),
+            )
+            .unwrap();
+        writer.write_all(b"encrypted").unwrap();
+        let mut v = writer.finish().unwrap().into_inner();
+
+        let archive = ZipArchive::new(Cursor::new(v.clone())).unwrap();
+        let mut archive_ok = archive.clone();
+        archive_ok.set_verify_crc(false);
+        archive_ok.verify().unwrap();
+
+        let pos = v.windows(9).position(|w| w == b"corrupted").unwrap();
+        v[pos] = b'C';
+        let central = archive.shared.files["resized.txt"].central_header_start as usize;
+        assert_eq!(
+            v[central..central + 4],
+            spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE.to_le_bytes()
+        );
+        v[central + 24..central + 28].copy_from_slice(&8u32.to_le_bytes());
+
+        let mut archive = ZipArchive::new(Cursor::new(v)).unwrap();
+        archive.set_verify_crc(false);
+        let failures = archive.verify().unwrap_err();
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].0, "bad.txt");
+        assert!(matches!(failures[0].1, ZipError::InvalidChecksum { .. }));
+        assert_eq!(failures[1].0, "resized.txt");
+        assert!(matches!(failures[1].1, ZipError::InvalidArchive(_)));
+    }
+
+    #[test]
+    fn raw_extra_fields() {
+        use crate::result::ZipError;
+        use crate::write::FullFileOptions;
+        use crate::ZipWriter;
+        use std::io::Write;
+
+        let mut options = FullFileOptions::default();
+        options.add_extra_data(0x7a7a, b"both", false).unwrap();
+        options.add_extra_data(0x7b7b, b"central", true).unwrap();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("file", options).unwrap();
+        writer.write_all(b"contents").unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        let central: Vec<_> = archive
+            .by_index(0)
+            .unwrap()
+            .raw_extra_fields()
+            .map(|field| (field.header_id(), field.data().to_vec()))
+            .collect();
+        assert_eq!(
+            central,
+            [(0x7a7a, b"both".to_vec()), (0x7b7b, b"central".to_vec())]
+        );
+        let local: Vec<_> = archive
+            .local_extra_fields(0)
+            .unwrap()
+            .into_iter()
+            .map(|field| (field.header_id(), field.data().to_vec()))
+            .collect();
+        assert_eq!(local, [(0x7a7a, b"both".to_vec())]);
+        assert!(matches!(
+            archive.local_extra_fields(1),
+            Err(ZipError::FileNotFound)
+        ));
+    }
+
+    /// The extra fields parsed from `extra_field`, as if it were an entry's
+    fn parse_extra_fields(extra_field: &[u8]) -> crate::result::ZipResult<Vec<ExtraField>> {
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("file", SimpleFileOptions::default())?;
+        let archive = writer.finish_into_readable()?;
+        let mut data = archive.shared.files[0].clone();
+        data.extra_field = Some(std::sync::Arc::new(extra_field.to_vec()));
+        super::parse_extra_field(&mut data)?;
+        Ok(data.extra_fields)
+    }
+
+    #[test]
+    fn malformed_optional_extra_fields() -> crate::result::ZipResult<()> {
+        let extended_timestamp = [0x55, 0x54, 5, 0, 1, 0x11, 0x22, 0x33, 0x44];
+        for malformed in [
+            // NTFS timestamps without the timestamp attribute, or too short for the reserved field
+            &[0x0a, 0x00, 4, 0, 0, 0, 0, 0][..],
+            &[0x0a, 0x00, 2, 0, 0, 0],
+            // a Strong Encryption Header shorter than 8 bytes
+            &[0x17, 0x00, 4, 0, 2, 0, 0x10, 0x66],
+            // empty and truncated Unix ownership fields
+            &[0x75, 0x78, 0, 0],
+            &[0x75, 0x78, 4, 0, 1, 4, 0xe8, 0x03],
+        ] {
+            let fields = parse_extra_fields(&[malformed, &extended_timestamp].concat())?;
+            assert!(
+                matches!(fields[..], [ExtraField::ExtendedTimestamp(_)]),
+                "{fields:?}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn new_lazy() {
+        use crate::result::ZipError;
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::{Read, Write};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for i in 0..100 {
+            writer
+                .start_file(format!("file{i}"), SimpleFileOptions::default())
+                .unwrap();
+            write!(writer, "contents of {i}").unwrap();
+        }
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let mut archive = ZipArchive::new_lazy(Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(archive.len(), 100);
+        assert_eq!(archive.file_names().count(), 0);
+        assert_eq!(archive.decompressed_size(), None);
+        assert_eq!(archive.compressed_size(), None);
+
+        let mut contents = String::new();
+        archive
+            .by_name("file49")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "contents of 49");
+        assert_eq!(archive.file_names().count(), 50);
+        contents.clear();
+        archive
+            .by_index(10)
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "contents of 10");
+        assert_eq!(archive.file_names().count(), 50);
+        assert_eq!(archive.len(), 100);
+
+        assert!(matches!(
+            archive.by_name("missing"),
+            Err(ZipError::FileNotFound)
+        ));
+        assert_eq!(archive.file_names().count(), 100);
+        assert_eq!(
+            archive.decompressed_size(),
+            ZipArchive::new(Cursor::new(bytes))
+                .unwrap()
+                .decompressed_size()
+        );
+        assert!(archive.by_index(100).is_err());
+    }
+
+    #[test]
+    fn new_lazy_zip64() {
+        use crate::spec::ZIP64_ENTRY_THR;
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options =
+            SimpleFileOptions::default().compression_method(crate::CompressionMethod::Stored);
+        for i in 0..=ZIP64_ENTRY_THR {
+            writer.start_file(format!("{i}"), options).unwrap();
+        }
+        let bytes = writer.finish().unwrap().into_inner();
+
+        // The ZIP32 footer's count is saturated, and the directory start it implies is after the
+        // ZIP64 records rather than at a central directory header, so it mustn't be trusted
+        let mut archive = ZipArchive::new_lazy(Cursor::new(bytes)).unwrap();
+        assert!(archive.is_zip64());
+        assert_eq!(archive.len(), ZIP64_ENTRY_THR + 1);
+        let last = archive.by_index(ZIP64_ENTRY_THR).unwrap();
+        assert_eq!(last.name(), ZIP64_ENTRY_THR.to_string());
+    }
+
+    #[test]
+    fn new_lazy_duplicates() -> crate::result::ZipResult<()> {
+        use crate::write::{DuplicatePolicy, SimpleFileOptions};
+        use crate::ZipWriter;
+        use std::io::{Read, Write};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_duplicate_policy(DuplicatePolicy::Allow);
+        for (name, contents) in [("a", "first"), ("b", "b"), ("a", "second"), ("c", "c")] {
+            writer.start_file(name, SimpleFileOptions::default())?;
+            writer.write_all(contents.as_bytes())?;
+        }
+        let bytes = writer.finish()?.into_inner();
+
+        let mut archive = ZipArchive::new_lazy(Cursor::new(bytes))?;
+        assert_eq!(archive.len(), 4);
+        assert_eq!(archive.total_entries(), 4);
+        let mut contents = String::new();
+        archive.by_name("a")?.read_to_string(&mut contents)?;
+        assert_eq!(contents, "first");
+        assert_eq!(archive.file_names().count(), 1);
+
+        // The second "a" is skipped once it's reached, and doesn't replace the first
+        assert_eq!(archive.by_name("c")?.name(), "c");
+        assert_eq!(archive.len(), 3);
+        assert!(archive.file_names().eq(["a", "b", "c"]));
+        contents.clear();
+        archive.by_name("a")?.read_to_string(&mut contents)?;
+        assert_eq!(contents, "first");
+        assert!(archive.by_index(3).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn prepended_data() {
+        use super::ZipArchive;
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::{Read, Write};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file("file.txt", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"contents").unwrap();
+        let archive = writer.finish().unwrap().into_inner();
+        assert_eq!(
+            ZipArchive::new(Cursor::new(&archive))
+                .unwrap()
+                .prepended_data_len(),
+            0
+        );
+
+        let mut bytes = vec![0xa5; 1000];
+        bytes.extend_from_slice(&archive);
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.prepended_data_len(), 1000);
+        assert_eq!(archive.offset(), 1000);
+        let mut contents = String::new();
+        archive
+            .by_name("file.txt")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "contents");
+    }
+
+    #[test]
+    fn extract_file_to_writer() {
+        use crate::result::ZipError;
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::{self, Write};
+
+        let contents: Vec<u8> = (0..1u32 << 20).map(|i| (i % 251) as u8).collect();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file(
+                "big.bin",
+                SimpleFileOptions::default().compression_level(Some(1)),
+            )
+            .unwrap();
+        writer.write_all(&contents).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        let mut output = Vec::new();
+        assert_eq!(
+            archive
+                .extract_file_to_writer("big.bin", &mut output)
+                .unwrap(),
+            contents.len() as u64
+        );
+        assert_eq!(output, contents);
+        assert!(matches!(
+            archive.extract_file_to_writer("missing", &mut output),
+            Err(ZipError::FileNotFound)
+        ));
+
+        archive.set_max_decompressed_size(1000);
+        assert!(archive
+            .extract_file_to_writer("big.bin", &mut io::sink())
+            .is_err());
+    }
+
+    #[test]
+    fn by_name_case_insensitive() {
+        use crate::result::ZipError;
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::Write;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in ["readme.txt", "Docs/A.md", "docs/a.md", "ÉTÉ.txt"] {
+            writer
+                .start_file(name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(name.as_bytes()).unwrap();
+        }
+        let bytes = writer.finish().unwrap().into_inner();
+
+        for lazy in [false, true] {
+            let mut archive = if lazy {
+                ZipArchive::new_lazy(Cursor::new(&bytes)).unwrap()
+            } else {
+                ZipArchive::new(Cursor::new(&bytes)).unwrap()
+            };
+            assert_eq!(
+                archive
+                    .by_name_case_insensitive("Readme.TXT")
+                    .unwrap()
+                    .name(),
+                "readme.txt"
+            );
+            // An exact match isn't ambiguous
+            assert_eq!(
+                archive
+                    .by_name_case_insensitive("docs/a.md")
+                    .unwrap()
+                    .name(),
+                "docs/a.md"
+            );
+            assert!(matches!(
+                archive.by_name_case_insensitive("DOCS/A.MD"),
+                Err(ZipError::InvalidArchive(_))
+            ));
+            assert!(matches!(
+                archive.by_name_case_insensitive("été.txt"),
+                Err(ZipError::FileNotFound)
+            ));
+            assert_eq!(
+                archive.by_name_case_insensitive("ÉtÉ.TXT").unwrap().name(),
+                "ÉTÉ.txt"
+            );
+        }
+    }
+
+    #[test]
+    fn dir_names() {
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.add_directory("docs/", options).unwrap();
+        writer.start_file("docs/readme.txt", options).unwrap();
+        writer.start_file("unmarked", options).unwrap();
+        writer.start_file("top.txt", options).unwrap();
+        let mut bytes = writer.finish().unwrap().into_inner();
+
+        // Give "unmarked" a directory mode without a trailing slash
+        let name_at = bytes
+            .windows(8)
+            .rposition(|window| window == b"unmarked")
+            .unwrap();
+        let header_start = name_at - 46;
+        assert_eq!(bytes[header_start..header_start + 4], [b'P', b'K', 1, 2]);
+        bytes[header_start + 38..header_start + 42]
+            .copy_from_slice(&((crate::types::ffi::S_IFDIR | 0o755) << 16).to_le_bytes());
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        assert_eq!(
+            archive.dir_names().collect::<Vec<_>>(),
+            ["docs/", "unmarked"]
+        );
+        assert_eq!(
+            archive.file_names_nondir().collect::<Vec<_>>(),
+            ["docs/readme.txt", "top.txt"]
+        );
+        assert!(archive.by_name("unmarked").unwrap().is_dir());
+        assert!(!archive.by_name("top.txt").unwrap().is_dir());
+    }
+
+    #[test]
+    fn extract_with_rewritten_paths() {
+        use super::ExtractOptions;
+        use crate::result::ZipError;
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::Write;
+        use std::path::{Path, PathBuf};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.add_directory("project-1.0/", options).unwrap();
+        writer
+            .start_file("project-1.0/src/main.rs", options)
+            .unwrap();
+        writer.write_all(b"fn main() {}\n").unwrap();
+        writer
+            .start_file("project-1.0/.gitignore", options)
+            .unwrap();
+        writer.start_file("elsewhere.txt", options).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        let dir = tempdir::TempDir::new("extract_with_rewritten_paths").unwrap();
+        archive
+            .extract_with_options(
+                dir.path(),
+                ExtractOptions::default()
+                    .strip_prefix("project-1.0")
+                    .map_path(|path| (!path.starts_with(".gitignore")).then(|| path.to_path_buf())),
+            )
+            .unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("src/main.rs")).unwrap(),
+            "fn main() {}\n"
+        );
+        assert!(!dir.path().join("project-1.0").exists());
+        assert!(!dir.path().join(".gitignore").exists());
+        assert!(!dir.path().join("elsewhere.txt").exists());
+
+        let dir = tempdir::TempDir::new("extract_with_rewritten_paths").unwrap();
+        let result = archive.extract_with_options(
+            dir.path(),
+            ExtractOptions::default().map_path(|path| Some(Path::new("..").join(path))),
+        );
+        assert!(matches!(result, Err(ZipError::InvalidPath(_))));
+        let result = archive.extract_with_options(
+            dir.path(),
+            ExtractOptions::default().map_path(|_| Some(PathBuf::from("/tmp/escaped"))),
+        );
+        assert!(matches!(result, Err(ZipError::InvalidPath(_))));
+    }
+
+    #[test]
+    fn extract_continue_on_error() {
+        use super::ExtractOptions;
+        use crate::result::ZipError;
+        use crate::write::SimpleFileOptions;
+        use crate::{CompressionMethod, ZipWriter};
+        use std::io::Write;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        for (name, contents) in [
+            ("a.txt", "first"),
+            ("b.txt", "corrupted"),
+            ("../escape.txt", "escaping"),
+            ("c.txt", "last"),
+        ] {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(contents.as_bytes()).unwrap();
+        }
+        let mut bytes = writer.finish().unwrap().into_inner();
+        let data_start = bytes
+            .windows(9)
+            .position(|window| window == b"corrupted")
+            .unwrap();
+        bytes[data_start] = b'C';
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+
+        let dir = tempdir::TempDir::new("extract_continue_on_error").unwrap();
+        assert!(archive.extract(dir.path()).is_err());
+        assert!(dir.path().join("a.txt").exists());
+        assert!(!dir.path().join("c.txt").exists());
+
+        let dir = tempdir::TempDir::new("extract_continue_on_error").unwrap();
+        let result = archive.extract_with_options(
+            dir.path(),
+            ExtractOptions::default().continue_on_error(true),
+        );
+        let Err(ZipError::ExtractionFailed(failures)) = result else {
+            panic!("expected ExtractionFailed, got {result:?}");
+        };
+        let names: Vec<_> = failures.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["b.txt", "../escape.txt"]);
+        assert!(matches!(failures[1].1, ZipError::InvalidArchive(_)));
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("a.txt")).unwrap(),
+            "first"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("c.txt")).unwrap(),
+            "last"
+        );
+        assert!(!dir.path().join("b.txt").exists());
+    }
+
+    #[test]
+    fn entry_metadata() {
+        use crate::write::SimpleFileOptions;
+        use crate::{CompressionMethod, ZipWriter};
+        use std::io::{Read, Seek, SeekFrom, Write};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("first.txt", options).unwrap();
+        writer.write_all(b"first").unwrap();
+        writer.start_file("second.txt", options).unwrap();
+        writer.write_all(b"second file").unwrap();
+        let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        let metadata = archive.entry_metadata(1).unwrap();
+        assert_eq!(&*metadata.name, "second.txt");
+        assert_eq!(metadata.compression_method, CompressionMethod::Stored);
+        assert_eq!(metadata.compressed_size, 11);
+        assert_eq!(metadata.uncompressed_size, 11);
+        assert_eq!(metadata.crc32, crc32fast::hash(b"second file"));
+        assert_eq!(
+            metadata.local_header_offset,
+            archive.entry_metadata(0).unwrap().data_start + 5
+        );
+        assert_eq!(
+            metadata.data_start,
+            metadata.local_header_offset + 30 + "second.txt".len() as u64
+        );
+        assert!(archive.entry_metadata(2).is_err());
+
+        let mut reader = archive.into_inner();
+        reader.seek(SeekFrom::Start(metadata.data_start)).unwrap();
+        let mut data = vec![0; metadata.compressed_size as usize];
+        reader.read_exact(&mut data).unwrap();
+        assert_eq!(data, b"second file");
+    }
+
+    #[test]
+    fn strictness() -> crate::result::ZipResult<()> {
+        use super::Strictness;
+        use crate::result::ZipError;
+        use crate::write::SimpleFileOptions;
+        use crate::{CompressionMethod, ZipWriter};
+        use std::io::{Read, Write};
+
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("a.txt", options)?;
+        writer.write_all(b"the whole contents")?;
+        writer.start_file("b.txt", options)?;
+        writer.write_all(b"more")?;
+        let bytes = writer.finish()?.into_inner();
+
+        // The local header's compressed size is smaller than the central directory's
+        let mut short = bytes.clone();
+        short[18] = 4;
+        // And here, the name in it is different
+        let mut renamed = bytes.clone();
+        renamed[30] = b'c';
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        archive.set_strictness(Strictness::Strict);
+        let mut contents = String::new();
+        archive.by_index(0)?.read_to_string(&mut contents)?;
+        assert_eq!(contents, "the whole contents");
+
+        for (bytes, field) in [(short, "compressed size"), (renamed, "file name")] {
+            let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+            // Lenient, by default, so the central directory is used
+            let mut contents = String::new();
+            archive.by_index(0)?.read_to_string(&mut contents)?;
+            assert_eq!(contents, "the whole contents");
+
+            archive.set_strictness(Strictness::Strict);
+            for result in [
+                archive.by_index(0).map(drop),
+                archive.by_index_raw(0).map(drop),
+            ] {
+                match result {
+                    Err(ZipError::InconsistentMetadata(found)) => assert_eq!(found, field),
+                    other => panic!("expected an inconsistency in the {field}, got {other:?}"),
+                }
+            }
+            assert!(archive.by_index(1).is_ok());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn decode_entry() -> crate::result::ZipResult<()> {
+        use super::{decode_entry, EntryMetadata};
+        use crate::result::ZipError;
+        use crate::write::{FullFileOptions, SimpleFileOptions};
+        use crate::{CompressionMethod, ZipWriter};
+        use std::io::{Read, Write};
+
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("first.txt", options)?;
+        writer.write_all(b"first")?;
+        // Extra data only in the local header, so the central directory doesn't give its length
+        let mut with_extra =
+            FullFileOptions::default().compression_method(CompressionMethod::Stored);
+        with_extra.add_extra_data(0xbeef, &[1; 37], false)?;
+        writer.start_file("extra.txt", with_extra)?;
+        writer.write_all(b"after a local extra field")?;
+        writer.start_file(
+            "secret.txt",
+            options.with_deprecated_encryption(b"password"),
+        )?;
+        writer.write_all(b"encrypted")?;
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+        let bytes = archive.clone().into_inner().into_inner();
+
+        let range = |meta: &EntryMetadata| {
+            &bytes[meta.local_header_offset as usize
+                ..(meta.data_start + meta.compressed_size) as usize]
+        };
+        for (index, (contents, password)) in [
+            (&b"first"[..], None),
+            (b"after a local extra field", None),
+            (b"encrypted", Some(&b"password"[..])),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let meta = archive.entry_metadata(index)?;
+            let mut file = decode_entry(range(&meta), &meta, password)?;
+            assert_eq!(file.name(), &*meta.name);
+            let mut read = Vec::new();
+            file.read_to_end(&mut read)?;
+            assert_eq!(read, contents);
+        }
+
+        let meta = archive.entry_metadata(1)?;
+        let bytes = range(&meta);
+        assert!(matches!(
+            decode_entry(&bytes[..bytes.len() - 1], &meta, None),
+            Err(ZipError::InvalidArchive(_))
+        ));
+        let other = archive.entry_metadata(0)?;
+        assert!(matches!(
+            decode_entry(bytes, &other, None),
+            Err(ZipError::InvalidArchive(_))
+        ));
+        let secret = archive.entry_metadata(2)?;
+        assert!(matches!(
+            decode_entry(range(&secret), &secret, None),
+            Err(ZipError::PasswordRequired)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn encrypted_without_password() -> crate::result::ZipResult<()> {
+        use crate::result::ZipError;
+        use crate::write::SimpleFileOptions;
+        use crate::{CompressionMethod, ZipWriter};
+        use std::io::{self, Read, Write};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file(
+            "zipcrypto.txt",
+            options.with_deprecated_encryption(b"password"),
+        )?;
+        writer.write_all(b"secret")?;
+        #[cfg(feature = "aes-crypto")]
+        {
+            let aes = options.with_aes_encryption(crate::AesMode::Aes256, "password");
+            writer.start_file("aes.txt", aes)?;
+            writer.write_all(b"secret")?;
+        }
+        writer.start_file("plain.txt", options)?;
+        writer.write_all(b"plain")?;
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+
+        let names: Vec<_> = archive.file_names().map(str::to_owned).collect();
+        for name in names.iter().filter(|name| *name != "plain.txt") {
+            let index = archive.index_for_name(name).unwrap();
+            assert!(archive.entry_metadata(index)?.encrypted);
+            let mut file = archive.by_index(index)?;
+            assert_eq!(file.name(), name);
+            assert_eq!(file.size(), 6);
+            let error = file.read_to_end(&mut Vec::new()).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::PermissionDenied);
+            assert!(matches!(
+                *error.into_inner().unwrap().downcast::<ZipError>().unwrap(),
+                ZipError::PasswordRequired
+            ));
+            drop(file);
+            let mut file = archive.by_name(name)?;
+            assert!(file.read_to_end(&mut Vec::new()).is_err());
+            drop(file);
+            let mut contents = String::new();
+            archive
+                .by_name_decrypt(name, b"password")?
+                .read_to_string(&mut contents)?;
+            assert_eq!(contents, "secret");
+        }
+        assert!(!archive.entry_metadata(names.len() - 1)?.encrypted);
+        let mut contents = String::new();
+        archive
+            .by_name("plain.txt")?
+            .read_to_string(&mut contents)?;
+        assert_eq!(contents, "plain");
+
+        assert!(matches!(
+            archive.extract_file_to_writer("zipcrypto.txt", &mut Vec::new()),
+            Err(ZipError::PasswordRequired)
+        ));
+        let dir = tempdir::TempDir::new("encrypted_without_password")?;
+        assert!(matches!(
+            archive.extract(dir.path()),
+            Err(ZipError::PasswordRequired)
+        ));
+        assert!(!dir.path().join("zipcrypto.txt").exists());
+        Ok(())
+    }
+
+    #[test]
+    fn by_index_try_passwords() {
+        use crate::result::ZipError;
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::{Read, Write};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer
+            .start_file("secret.txt", options.with_deprecated_encryption(b"right"))
+            .unwrap();
+        writer.write_all(b"secret").unwrap();
+        #[cfg(feature = "aes-crypto")]
+        {
+            writer
+                .start_file(
+                    "aes.txt",
+                    options.with_aes_encryption(crate::AesMode::Aes256, "right"),
+                )
+                .unwrap();
+            writer.write_all(b"secret").unwrap();
+        }
+        writer.start_file("plain.txt", options).unwrap();
+        writer.write_all(b"plain").unwrap();
+        let mut archive = ZipArchive::new(writer.finish().unwrap()).unwrap();
+
+        for index in 0..archive.len() - 1 {
+            let mut contents = String::new();
+            archive
+                .by_index_try_passwords(index, &[b"wrong", b"also wrong", b"right"])
+                .unwrap()
+                .read_to_string(&mut contents)
+                .unwrap();
+            assert_eq!(contents, "secret");
+            assert!(matches!(
+                archive.by_index_try_passwords(index, &[b"wrong", b"also wrong"]),
+                Err(ZipError::InvalidPassword)
+            ));
+            assert!(matches!(
+                archive.by_index_try_passwords(index, &[]),
+                Err(ZipError::InvalidPassword)
+            ));
+        }
+        let mut contents = String::new();
+        archive
+            .by_index_try_passwords(archive.len() - 1, &[])
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "plain");
+        assert!(matches!(
+            archive.by_index_try_passwords(archive.len(), &[b"right"]),
+            Err(ZipError::FileNotFound)
+        ));
+    }
+
+    #[test]
+    fn dos_attributes() -> crate::result::ZipResult<()> {
+        use crate::write::SimpleFileOptions;
+        use crate::{DosAttributes, ZipWriter};
+
+        let hidden = DosAttributes {
+            hidden: true,
+            archive: true,
+            ..DosAttributes::default()
+        };
+        let read_only = DosAttributes {
+            read_only: true,
+            system: true,
+            ..DosAttributes::default()
+        };
+        let options = SimpleFileOptions::default().unix_permissions(0o600);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("plain", options)?;
+        writer.start_file("hidden", options.with_dos_attributes(hidden))?;
+        writer.start_file("read_only", options.with_dos_attributes(read_only))?;
+        let mut archive = writer.finish_into_readable()?;
+
+        for (name, expected) in [
+            ("plain", DosAttributes::default()),
+            ("hidden", hidden),
+            ("read_only", read_only),
+        ] {
+            let file = archive.by_name(name)?;
+            assert_eq!(file.dos_attributes(), expected, "{name}");
+            assert_eq!(file.unix_mode(), Some(0o100600), "{name}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn external_attributes_round_trip() -> crate::result::ZipResult<()> {
+        use crate::write::SimpleFileOptions;
+        use crate::{System, ZipWriter};
+
+        let values = [0, 1, 0x20, 0x8000_0010, 0xDEAD_BEEF, u32::MAX];
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (index, value) in values.into_iter().enumerate() {
+            let options = SimpleFileOptions::default()
+                .with_external_attributes(value)
+                .with_version_made_by(System::Ntfs, 63);
+            writer.start_file(format!("ntfs{index}"), options)?;
+        }
+        let unix = SimpleFileOptions::default().with_external_attributes(0o100640 << 16);
+        writer.start_file("unix", unix)?;
+        let mut archive = writer.finish_into_readable()?;
+
+        for (index, value) in values.into_iter().enumerate() {
+            let file = archive.by_index(index)?;
+            assert_eq!(file.external_attributes(), value, "{}", file.name());
+            assert_eq!(file.version_made_by(), (6, 3));
+            // Attributes from an NTFS host don't hold Unix permissions
+            assert_eq!(file.unix_mode(), None);
+        }
+        let file = archive.by_name("unix")?;
+        assert_eq!(file.external_attributes(), 0o100640 << 16);
+        assert_eq!(file.unix_mode(), Some(0o100640));
+        Ok(())
+    }
+
+    #[test]
+    fn data_descriptor_and_zip64_flags() -> crate::result::ZipResult<()> {
+        use crate::read::read_zipfile_from_stream;
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::Write;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("plain", SimpleFileOptions::default())?;
+        writer.start_file(
+            "descriptor",
+            SimpleFileOptions::default().with_data_descriptor(true),
+        )?;
+        writer.write_all(b"data")?;
+        writer.start_file("zip64", SimpleFileOptions::default().large_file(true))?;
+        writer.write_all(b"data")?;
+        let mut archive = writer.finish_into_readable()?;
+
+        for (name, descriptor, zip64) in [
+            ("plain", false, false),
+            ("descriptor", true, false),
+            ("zip64", false, true),
+        ] {
+            let file = archive.by_name(name)?;
+            assert_eq!(file.has_data_descriptor(), descriptor, "{name}");
+            assert_eq!(file.uses_zip64(), zip64, "{name}");
+        }
+
+        let mut stream = Cursor::new(archive.into_inner().into_inner());
+        let file = read_zipfile_from_stream(&mut stream)?.unwrap();
+        assert!(!file.has_data_descriptor());
+        drop(file);
+        let file = read_zipfile_from_stream(&mut stream)?.unwrap();
+        assert!(file.has_data_descriptor());
+        Ok(())
+    }
+
+    #[test]
+    fn zip64_entry_count() -> crate::result::ZipResult<()> {
+        use crate::write::SimpleFileOptions;
+        use crate::{CompressionMethod, ZipWriter};
+
+        const ENTRIES: u64 = 70_000;
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        for index in 0..ENTRIES {
+            writer.start_file(format!("{index}"), options)?;
+        }
+        let bytes = writer.finish()?.into_inner();
+        // The 16-bit counts in the end of central directory record are saturated
+        let eocd = &bytes[bytes.len() - 22..];
+        assert_eq!(eocd[8..12], [0xff; 4]);
+
+        let archive = ZipArchive::new(Cursor::new(bytes.clone()))?;
+        assert!(archive.is_zip64());
+        assert_eq!(archive.total_entries(), ENTRIES);
+        assert_eq!(archive.len() as u64, ENTRIES);
+        let mut archive = ZipArchive::new_lazy(Cursor::new(bytes))?;
+        assert!(archive.is_zip64());
+        assert_eq!(archive.total_entries(), ENTRIES);
+        assert_eq!(archive.by_index(ENTRIES as usize - 1)?.name(), "69999");
+
+        let archive = ZipArchive::new(Cursor::new(include_bytes!("../tests/data/zip64_demo.zip")))?;
+        assert!(archive.is_zip64());
+        assert_eq!(archive.total_entries(), 1);
+        let archive = ZipArchive::new(Cursor::new(include_bytes!("../tests/data/mimetype.zip")))?;
+        assert!(!archive.is_zip64());
+        assert_eq!(archive.total_entries(), archive.len() as u64);
+        Ok(())
+    }
+
+    #[test]
+    fn empty_archives() -> crate::result::ZipResult<()> {
+        use crate::result::ZipError;
+        use crate::ZipWriter;
+
+        let empty = ZipWriter::new(Cursor::new(Vec::new()))
+            .finish()?
+            .into_inner();
+        let mut eocd_only = vec![0x50, 0x4b, 0x05, 0x06];
+        eocd_only.resize(22, 0);
+        assert_eq!(empty, eocd_only);
+        let mut archive = ZipArchive::new(Cursor::new(empty.clone()))?;
+        assert_eq!(archive.len(), 0);
+        assert!(archive.is_empty());
+        assert_eq!(archive.file_names().count(), 0);
+        assert!(matches!(archive.by_index(0), Err(ZipError::FileNotFound)));
+        assert_eq!(ZipArchive::new_lazy(Cursor::new(empty))?.len(), 0);
+
+        for (bytes, message) in [
+            (&[][..], "File is empty, so it isn't a ZIP archive"),
+            (&eocd_only[..21], "File is too short to be a ZIP archive"),
+        ] {
+            assert!(
+                matches!(
+                    ZipArchive::new(Cursor::new(bytes)),
+                    Err(ZipError::InvalidArchive(m)) if m == message
+                ),
+                "{message}"
+            );
+            assert!(matches!(
+                ZipArchive::new_lazy(Cursor::new(bytes)),
+                Err(ZipError::InvalidArchive(m)) if m == message
+            ));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn position() -> crate::result::ZipResult<()> {
+        use crate::write::SimpleFileOptions;
+        use crate::{CompressionMethod, ZipWriter};
+        use std::io::{self, Read, Write};
+
+        let methods = [
+            CompressionMethod::Stored,
+            #[cfg(feature = "_deflate-any")]
+            CompressionMethod::Deflated,
+            #[cfg(feature = "bzip2")]
+            CompressionMethod::Bzip2,
+            #[cfg(feature = "zstd")]
+            CompressionMethod::Zstd,
+        ];
+        let data: Vec<u8> = (0..50_000u32)
+            .flat_map(|i| (i % 251).to_le_bytes())
+            .collect();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for method in methods {
+            let options = SimpleFileOptions::default().compression_method(method);
+            writer.start_file(format!("{method}"), options)?;
+            writer.write_all(&data)?;
+        }
+        let mut archive = writer.finish_into_readable()?;
+
+        for method in methods {
+            let mut file = archive.by_name(&format!("{method}"))?;
+            assert_eq!(file.position(), 0);
+            let mut buf = [0; 1000];
+            let mut total = 0;
+            loop {
+                let count = file.read(&mut buf)?;
+                if count == 0 {
+                    break;
+                }
+                total += count as u64;
+                assert_eq!(file.position(), total, "{method}");
+            }
+            assert_eq!(file.position(), file.size(), "{method}");
+        }
+        let mut file = archive.by_index_raw(0)?;
+        io::copy(&mut file, &mut io::sink())?;
+        assert_eq!(file.position(), file.compressed_size());
+        Ok(())
+    }
+
+    #[test]
+    fn host_system() -> crate::result::ZipResult<()> {
+        use crate::System;
+
+        let mut unix = ZipArchive::new(Cursor::new(include_bytes!("../tests/data/mimetype.zip")))?;
+        let file = unix.by_index_raw(0)?;
+        assert_eq!(file.system(), System::Unix);
+        assert_eq!(file.host_version(), 46);
+        assert_eq!(file.version_made_by(), (4, 6));
+        assert!(file.unix_mode().is_some());
+        drop(file);
+
+        let mut dos = ZipArchive::new(Cursor::new(include_bytes!("../tests/data/implode.zip")))?;
+        let file = dos.by_index_raw(0)?;
+        assert_eq!(file.system(), System::Dos);
+        assert_eq!(file.host_version(), 10);
+        assert_eq!(file.version_made_by(), (1, 0));
+        Ok(())
+    }
+
+    #[test]
+    fn unicode_comment_extra_field() -> crate::result::ZipResult<()> {
+        use crate::write::SimpleFileOptions;
+        use crate::{CompressionMethod, ZipWriter};
+
+        // Give the only entry of an archive a comment, along with the given central extra data
+        fn with_comment(comment: &[u8], extra_field: &[u8]) -> crate::result::ZipResult<Vec<u8>> {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            writer.start_file(
+                "a",
+                SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+            )?;
+            let bytes = writer.finish()?.into_inner();
+            let footer_start = bytes.len() - 22;
+            let central_start = u32::from_le_bytes(
+                bytes[footer_start + 16..footer_start + 20]
+                    .try_into()
+                    .unwrap(),
+            ) as usize;
+            let mut central = bytes[central_start..footer_start].to_vec();
+            assert_eq!(central[30..34], [0, 0, 0, 0]);
+            central[30..32].copy_from_slice(&(extra_field.len() as u16).to_le_bytes());
+            central[32..34].copy_from_slice(&(comment.len() as u16).to_le_bytes());
+            central.extend_from_slice(extra_field);
+            central.extend_from_slice(comment);
+            let mut footer = bytes[footer_start..].to_vec();
+            footer[12..16].copy_from_slice(&(central.len() as u32).to_le_bytes());
+            let mut result = bytes[..central_start].to_vec();
+            result.extend(central);
+            result.extend(footer);
+            Ok(result)
+        }
+        fn unicode_comment(crc32: u32, comment: &str) -> Vec<u8> {
+            let mut field = vec![0x75, 0x63];
+            field.extend_from_slice(&(5 + comment.len() as u16).to_le_bytes());
+            field.push(1);
+            field.extend_from_slice(&crc32.to_le_bytes());
+            field.extend_from_slice(comment.as_bytes());
+            field
+        }
+
+        // "café" in codepage 437
+        let legacy = b"caf\x82";
+        let bytes = with_comment(legacy, &[])?;
+        assert_eq!(
+            ZipArchive::new(Cursor::new(bytes))?.by_index(0)?.comment(),
+            "café"
+        );
+
+        let field = unicode_comment(crc32fast::hash(legacy), "café ☕");
+        let bytes = with_comment(legacy, &field)?;
+        assert_eq!(
+            ZipArchive::new(Cursor::new(bytes))?.by_index(0)?.comment(),
+            "café ☕"
+        );
+
+        // A field that doesn't match the comment in the header is ignored
+        let field = unicode_comment(crc32fast::hash(b"cafe"), "café ☕");
+        let bytes = with_comment(legacy, &field)?;
+        assert_eq!(
+            ZipArchive::new(Cursor::new(bytes))?.by_index(0)?.comment(),
+            "café"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn eocd_search_limit() {
+        use crate::result::ZipError;
+        use std::io::{Read, Write};
+
+        let mut writer = crate::ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file(
+                "a.txt",
+                crate::write::SimpleFileOptions::default()
+                    .compression_method(crate::CompressionMethod::Stored),
+            )
+            .unwrap();
+        writer.write_all(b"hello").unwrap();
+        let mut archive = writer.finish().unwrap().into_inner();
+        assert!(ZipArchive::new_with_eocd_search_limit(Cursor::new(&archive), 21).is_err());
+        assert!(ZipArchive::new_with_eocd_search_limit(Cursor::new(&archive), 22).is_ok());
+
+        archive.extend(std::iter::repeat(0).take(70000));
+        assert!(matches!(
+            ZipArchive::new(Cursor::new(&archive)),
+            Err(ZipError::InvalidArchive("EOCD not found"))
+        ));
+        let mut archive =
+            ZipArchive::new_with_eocd_search_limit(Cursor::new(&archive), 70022).unwrap();
+        assert_eq!(archive.len(), 1);
+        let mut contents = String::new();
+        archive
+            .by_name("a.txt")
+            .unwrap()
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "hello");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn extract_hard_links() {
+        use super::ExtractOptions;
+        use crate::result::ZipError;
+        use crate::write::SimpleFileOptions;
+        use std::io::Write;
+        use std::os::unix::fs::MetadataExt;
+
+        let mut writer = crate::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.start_file("a.txt", options).unwrap();
+        writer.write_all(b"first").unwrap();
+        writer
+            .add_hardlink("link_to_a.txt", "a.txt", options)
+            .unwrap();
+        writer
+            .add_hardlink("link_to_b.txt", "b.txt", options)
+            .unwrap();
+        writer.start_file("b.txt", options).unwrap();
+        writer.write_all(b"second").unwrap();
+        writer
+            .add_hardlink("dir/link_to_a.txt", "a.txt", options)
+            .unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+        assert!(!archive.by_name("a.txt").unwrap().is_hardlink());
+        let link = archive.by_name("link_to_a.txt").unwrap();
+        assert!(link.is_hardlink());
+        assert_eq!(link.hardlink_target().as_deref(), Some("a.txt"));
+        drop(link);
+
+        let dir = tempdir::TempDir::new("extract_hard_links").unwrap();
+        archive.extract(dir.path()).unwrap();
+        assert_eq!(
+            std::fs::read(dir.path().join("link_to_a.txt")).unwrap(),
+            b""
+        );
+
+        let dir = tempdir::TempDir::new("extract_hard_links").unwrap();
+        archive
+            .extract_with_options(dir.path(), ExtractOptions::default().hard_links(true))
+            .unwrap();
+        let inode = |name| std::fs::metadata(dir.path().join(name)).unwrap().ino();
+        assert_eq!(inode("link_to_a.txt"), inode("a.txt"));
+        assert_eq!(
+            std::fs::read(dir.path().join("link_to_a.txt")).unwrap(),
+            b"first"
+        );
+        // b.txt comes after its link, so its contents were copied
+        assert_ne!(inode("link_to_b.txt"), inode("b.txt"));
+        assert_eq!(
+            std::fs::read(dir.path().join("link_to_b.txt")).unwrap(),
+            b"second"
+        );
+
+        // a.txt is outside the prefix, so its contents can't be copied through a link
+        let dir = tempdir::TempDir::new("extract_hard_links").unwrap();
+        let options = ExtractOptions::default()
+            .hard_links(true)
+            .strip_prefix("dir");
+        assert!(matches!(
+            archive.extract_with_options(dir.path(), options),
+            Err(ZipError::FileNotFound)
+        ));
+        assert!(!dir.path().join("link_to_a.txt").exists());
+    }
+
+    #[test]
+    fn total_sizes() {
+        use crate::write::SimpleFileOptions;
+        use crate::{CompressionMethod, ZipWriter};
+        use std::io::Write;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("stored.txt", stored).unwrap();
+        writer.write_all(&[b'a'; 1000]).unwrap();
+        writer.add_directory("dir/", stored).unwrap();
+        writer.start_file("dir/empty.txt", stored).unwrap();
+        writer.start_file("other.txt", stored).unwrap();
+        writer.write_all(b"hello").unwrap();
+        let archive = writer.finish_into_readable().unwrap();
+        assert_eq!(archive.decompressed_size(), Some(1005));
+        assert_eq!(archive.compressed_size(), Some(1005));
+    }
+
+    #[test]
+    fn extract_progress() {
+        use super::{ExtractOptions, ExtractProgress};
+        use crate::write::SimpleFileOptions;
+        use crate::{CompressionMethod, ZipWriter};
+        use std::io::Write;
+        use std::sync::{Arc, Mutex};
+
+        fn assert_send<T: Send>(_: &T) {}
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer.start_file("small.txt", options).unwrap();
+        writer.write_all(b"small").unwrap();
+        writer.add_directory("dir/", options).unwrap();
+        writer.start_file("dir/large.bin", options).unwrap();
+        writer.write_all(&vec![0; 5 << 19]).unwrap();
+        writer.add_symlink("link", "small.txt", options).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let reports_in_callback = Arc::clone(&reports);
+        let dir = tempdir::TempDir::new("extract_progress").unwrap();
+        let total = 5 + (5 << 19) + 9;
+        let options = ExtractOptions::default().on_progress(move |progress: ExtractProgress| {
+            assert_eq!(progress.total_entries, 4);
+            assert_eq!(progress.total_bytes, Some(total));
+            reports_in_callback.lock().unwrap().push((
+                progress.current_index,
+                progress.current_name.to_owned(),
+                progress.bytes_written,
+            ));
+        });
+        assert_send(&options);
+        archive.extract_with_options(dir.path(), options).unwrap();
+        let large = "dir/large.bin".to_owned();
+        assert_eq!(
+            *reports.lock().unwrap(),
+            [
+                (0, "small.txt".to_owned(), 5),
+                (1, "dir/".to_owned(), 5),
+                (2, large.clone(), 5 + (1 << 20)),
+                (2, large.clone(), 5 + (2 << 20)),
+                (2, large, 5 + (5 << 19)),
+                (3, "link".to_owned(), total as u64),
+            ]
+        );
+    }
+
+    #[test]
+    fn extract_parallel_with_options() -> crate::result::ZipResult<()> {
+        use super::ExtractOptions;
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::Write;
+        use std::path::Path;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let options = SimpleFileOptions::default();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for i in 0..20 {
+            writer.start_file(format!("top/file{i}.txt"), options)?;
+            writer.write_all(format!("contents {i}").as_bytes())?;
+        }
+        writer.add_symlink("top/link", "file0.txt", options)?;
+        writer.start_file("outside.txt", options)?;
+        writer.write_all(b"outside")?;
+        let archive = writer.finish_into_readable()?;
+
+        let dir = tempdir::TempDir::new("extract_parallel_with_options")?;
+        let reported = Arc::new(AtomicUsize::new(0));
+        let counter = reported.clone();
+        let options = ExtractOptions::default()
+            .strip_prefix("top")
+            .on_progress(move |_| {
+                counter.fetch_add(1, Ordering::Relaxed);
+            });
+        archive.extract_parallel_with_options(dir.path(), 4, options)?;
+        for i in 0..20 {
+            assert_eq!(
+                std::fs::read_to_string(dir.path().join(format!("file{i}.txt")))?,
+                format!("contents {i}")
+            );
+        }
+        assert!(!dir.path().join("outside.txt").exists());
+        assert!(!dir.path().join("top").exists());
+        assert_eq!(reported.load(Ordering::Relaxed), archive.len());
+        #[cfg(unix)]
+        assert_eq!(
+            std::fs::read_link(dir.path().join("link"))?,
+            Path::new("file0.txt")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn raw_entry_data() {
+        use crate::write::SimpleFileOptions;
+        use crate::{CompressionMethod, ZipWriter};
+        use std::io::{Read, Write};
+
+        let contents = b"raw data, raw data, raw data, raw data".repeat(10);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.start_file("plain", options).unwrap();
+        writer.write_all(&contents).unwrap();
+        writer
+            .start_file("encrypted", options.with_deprecated_encryption(b"password"))
+            .unwrap();
+        writer.write_all(&contents).unwrap();
+        #[cfg(feature = "aes-crypto")]
+        {
+            writer
+                .start_file(
+                    "aes",
+                    options.with_aes_encryption(crate::AesMode::Aes256, "password"),
+                )
+                .unwrap();
+            writer.write_all(&contents).unwrap();
+        }
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+        for i in 0..archive.len() {
+            let mut file = archive.by_index_raw(i).unwrap();
+            assert_eq!(file.compression(), CompressionMethod::default());
+            let start = file.data_start() as usize;
+            let stored = &bytes[start..start + file.compressed_size() as usize];
+            let mut raw = Vec::new();
+            file.read_to_end(&mut raw).unwrap();
+            assert_eq!(raw, stored, "{}", file.name());
+        }
+        let mut raw = Vec::new();
+        let mut file = archive.by_index_raw(1).unwrap();
+        assert!(file.data.encrypted);
+        let crc32 = file.crc32();
+        file.read_to_end(&mut raw).unwrap();
+        drop(file);
+        assert_eq!(crc32, archive.by_index(0).unwrap().crc32());
+        // The ZipCrypto header is still there, and nothing was decrypted
+        assert!(raw.len() >= 12);
+        assert!(!raw.windows(8).any(|window| window == b"raw data"));
+    }
+
+    #[test]
+    fn count_entries() {
+        use super::count_entries;
+        use crate::result::ZipError;
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options =
+            SimpleFileOptions::default().compression_method(crate::CompressionMethod::Stored);
+        for i in 0..70000 {
+            writer.start_file(format!("{i}"), options).unwrap();
+        }
+        let bytes = writer.finish().unwrap().into_inner();
+        // More entries than a ZIP32 footer can count, so this comes from the ZIP64 one
+        assert_eq!(count_entries(Cursor::new(&bytes)).unwrap(), 70000);
+        assert_eq!(
+            ZipArchive::new_lazy(Cursor::new(&bytes)).unwrap().len(),
+            70000
+        );
+
+        let writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let empty = writer.finish().unwrap().into_inner();
+        assert_eq!(count_entries(Cursor::new(empty)).unwrap(), 0);
+
+        assert!(matches!(
+            count_entries(Cursor::new(b"not a zip file".repeat(10))),
+            Err(ZipError::InvalidArchive("EOCD not found"))
+        ));
+    }
+
+    #[test]
+    fn extract_buffer_size() {
+        use super::ExtractOptions;
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::Write;
+
+        let files = [
+            ("empty.txt", Vec::new()),
+            ("one.txt", b"1".to_vec()),
+            ("dir/text.txt", b"Some text that compresses. ".repeat(300)),
+        ];
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in &files {
+            writer
+                .start_file(*name, SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        let mut archive = writer.finish_into_readable().unwrap();
+        for buffer_size in [1, 7, 1 << 20] {
+            let dir = tempdir::TempDir::new("extract_buffer_size").unwrap();
+            archive
+                .extract_with_options(
+                    dir.path(),
+                    ExtractOptions::default().buffer_size(buffer_size),
+                )
+                .unwrap();
+            for (name, contents) in &files {
+                assert_eq!(
+                    std::fs::read(dir.path().join(name)).unwrap(),
+                    *contents,
+                    "{name} with a {buffer_size}-byte buffer"
+                );
+            }
+        }
+        let dir = tempdir::TempDir::new("extract_buffer_size").unwrap();
+        assert!(archive
+            .extract_with_options(dir.path(), ExtractOptions::default().buffer_size(0))
+            .is_err());
+    }
+
+    #[test]
+    fn extract_matching() {
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::Write;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        for name in ["a.json", "data/b.json", "data/c.txt", "data/deep/d.json"] {
+            writer.start_file(name, options).unwrap();
+            writer.write_all(name.as_bytes()).unwrap();
+        }
+        writer.add_directory("docs/", options).unwrap();
+        writer.start_file("docs/readme.md", options).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        assert_eq!(
+            archive.names_matching("data/*").collect::<Vec<_>>(),
+            ["data/b.json", "data/c.txt"]
+        );
+        assert_eq!(
+            archive.names_matching("*.json").collect::<Vec<_>>(),
+            ["a.json"]
+        );
+
+        let dir = tempdir::TempDir::new("extract_matching").unwrap();
+        archive
+            .extract_matching(dir.path(), &["**/*.json", "nothing/**"])
+            .unwrap();
+        for name in ["a.json", "data/b.json", "data/deep/d.json"] {
+            assert_eq!(
+                std::fs::read_to_string(dir.path().join(name)).unwrap(),
+                name
+            );
+        }
+        assert!(!dir.path().join("data/c.txt").exists());
+        assert!(!dir.path().join("docs").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn directory_mode_round_trip() {
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::Write;
+        use std::os::unix::fs::PermissionsExt;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .add_directory_with_options(
+                "private",
+                SimpleFileOptions::default().unix_permissions(0o700),
+            )
+            .unwrap();
+        writer
+            .start_file("private/key", SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(b"secret").unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+
+        let dir_entry = archive.by_name("private/").unwrap();
+        assert!(dir_entry.is_dir());
+        assert_eq!(dir_entry.unix_mode(), Some(0o40700));
+        assert_eq!(dir_entry.data.external_attributes & 0x10, 0x10);
+        assert_eq!(dir_entry.size(), 0);
+        drop(dir_entry);
+
+        let dir = tempdir::TempDir::new("directory_mode_round_trip").unwrap();
+        archive.extract(dir.path()).unwrap();
+        let mode = std::fs::metadata(dir.path().join("private"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o700);
+        assert_eq!(
+            std::fs::read(dir.path().join("private/key")).unwrap(),
+            b"secret"
+        );
+    }
+
+    #[test]
+    fn into_map() {
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::collections::HashMap;
+        use std::io::Write;
+
+        let map: HashMap<String, Vec<u8>> = [
+            ("empty", Vec::new()),
+            ("hello.txt", b"Hello, World!".to_vec()),
+            ("dir/repeated.txt", b"repeat ".repeat(1000)),
+        ]
+        .into_iter()
+        .map(|(name, contents)| (name.to_owned(), contents))
+        .collect();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .add_directory("dir/", SimpleFileOptions::default())
+            .unwrap();
+        for (name, contents) in &map {
+            writer
+                .start_file(name.as_str(), SimpleFileOptions::default())
+                .unwrap();
+            writer.write_all(contents).unwrap();
+        }
+        let bytes = writer.finish().unwrap().into_inner();
+
+        let archive = ZipArchive::new(Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(archive.into_map().unwrap(), map);
+        let archive = ZipArchive::new_lazy(Cursor::new(bytes.clone())).unwrap();
+        assert_eq!(archive.into_map().unwrap(), map);
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes)).unwrap();
+        archive.set_max_decompressed_size(100);
+        assert!(archive.into_map().is_err());
+    }
+
+    #[test]
+    #[cfg(all(feature = "sha2", feature = "deflate"))]
+    fn content_digest() {
+        use crate::write::SimpleFileOptions;
+        use crate::{CompressionMethod, ZipWriter};
+        use std::io::Write;
+
+        let files = [
+            ("hello.txt", b"Hello, World!".to_vec()),
+            ("repeated.txt", b"repeat ".repeat(1000)),
+        ];
+        let write = |options: SimpleFileOptions, reverse: bool| {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            writer.add_directory("dir/", options).unwrap();
+            let mut files = files.clone();
+            if reverse {
+                files.reverse();
+            }
+            for (name, contents) in files {
+                writer.start_file(name, options).unwrap();
+                writer.write_all(&contents).unwrap();
+            }
+            let bytes = writer.finish().unwrap().into_inner();
+            let digest = ZipArchive::new(Cursor::new(bytes.clone()))
+                .unwrap()
+                .content_digest()
+                .unwrap();
+            let lazy = ZipArchive::new_lazy(Cursor::new(bytes))
+                .unwrap()
+                .content_digest()
+                .unwrap();
+            assert_eq!(lazy, digest);
+            digest
+        };
+
+        let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+        let digest = write(deflated.compression_level(Some(1)), false);
+        assert_eq!(write(deflated.compression_level(Some(9)), false), digest);
+        assert_eq!(
+            write(
+                SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+                false
+            ),
+            digest
+        );
+        assert_ne!(write(deflated, true), digest);
+    }
+
+    #[test]
+    fn prepended_and_trailing_data() -> crate::result::ZipResult<()> {
+        use super::count_entries;
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+        use std::io::{Read, Write};
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_comment("comment");
+        for name in ["a.txt", "b.txt"] {
+            writer.start_file(name, SimpleFileOptions::default())?;
+            writer.write_all(name.as_bytes())?;
+        }
+        let archive = writer.finish()?.into_inner();
+        let stub = b"#!/bin/sh\nexec unzip \"$0\"\n".repeat(10);
+        // Looks like an end of central directory record, but only makes sense if every byte
+        // before it were prepended data
+        let mut trailer = b"signature".to_vec();
+        trailer.extend_from_slice(b"PK\x05\x06\0\0\0\0\x01\0\x01\0\x30\0\0\0\x07\0\0\0\0\0");
+        trailer.extend_from_slice(&[0; 100]);
+
+        for (prefix, suffix) in [
+            (&[][..], &[][..]),
+            (&stub[..], &[][..]),
+            (&[][..], &trailer[..]),
+            (&stub[..], &trailer[..]),
+        ] {
+            let bytes = [prefix, &archive, suffix].concat();
+            for mut archive in [
+                ZipArchive::new(Cursor::new(&bytes))?,
+                ZipArchive::new_lazy(Cursor::new(&bytes))?,
+            ] {
+                assert_eq!(archive.offset(), prefix.len() as u64);
+                assert_eq!(archive.trailing_data_len(), suffix.len() as u64);
+                assert_eq!(archive.comment(), b"comment");
+                let mut contents = String::new();
+                archive.by_name("b.txt")?.read_to_string(&mut contents)?;
+                assert_eq!(contents, "b.txt");
+                assert_eq!(archive.prepended_data_len(), prefix.len() as u64);
+            }
+            assert_eq!(count_entries(Cursor::new(&bytes))?, 2);
+        }
+
+        // The ZIP64 locator is found next to the record, wherever the file ends
+        let bytes = [
+            &stub[..],
+            include_bytes!("../tests/data/zip64_demo.zip"),
+            &trailer,
+        ]
+        .concat();
+        let archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert!(archive.is_zip64());
+        assert_eq!(archive.len(), 1);
+        assert_eq!(archive.trailing_data_len(), trailer.len() as u64);
+        Ok(())
+    }
+
+    #[test]
+    fn many_false_footers() -> crate::result::ZipResult<()> {
+        use crate::write::SimpleFileOptions;
+        use crate::ZipWriter;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("a.txt", SimpleFileOptions::default())?;
+        let archive = writer.finish()?.into_inner();
+
+        // A central directory too small for the file it claims to hold is skipped without being
+        // read, however many there are
+        let implausible = b"PK\x05\x06\0\0\0\0\x01\0\x01\0\0\0\0\0\x07\0\0\0\0\0".repeat(500);
+        let bytes = [&archive[..], &implausible].concat();
+        assert_eq!(ZipArchive::new(Cursor::new(bytes))?.len(), 1);
+
+        // Ones that have to be read to be ruled out are only read so many times
+        let plausible = b"PK\x05\x06\0\0\0\0\x01\0\x01\0\x30\0\0\0\x07\0\0\0\0\0"
+            .repeat(super::MAX_FOOTER_CANDIDATES - 1);
+        let bytes = [&archive[..], &plausible].concat();
+        assert_eq!(ZipArchive::new(Cursor::new(bytes))?.len(), 1);
+        let bytes = [&archive[..], &plausible, &plausible[..22]].concat();
+        assert!(ZipArchive::new(Cursor::new(bytes)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn rewind() -> crate::result::ZipResult<()> {
+        use super::read_zipfile_from_stream;
+        use crate::result::ZipError;
+        use crate::write::SimpleFileOptions;
+        use crate::{CompressionMethod, ZipWriter};
+        use std::io::{Read, Write};
+
+        let contents = b"magic, then the rest of the file ".repeat(100);
+        #[allow(unused_mut)]
+        let mut methods = vec![CompressionMethod::Stored];
+        #[cfg(feature = "_deflate-any")]
+        methods.push(CompressionMethod::Deflated);
+        #[cfg(feature = "bzip2")]
+        methods.push(CompressionMethod::Bzip2);
+        #[cfg(feature = "zstd")]
+        methods.push(CompressionMethod::Zstd);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for method in &methods {
+            let options = SimpleFileOptions::default().compression_method(*method);
+            writer.start_file(format!("{method}"), options)?;
+            writer.write_all(&contents)?;
+        }
+        writer.start_file(
+            "encrypted",
+            SimpleFileOptions::default().with_deprecated_encryption(b"password"),
+        )?;
+        writer.write_all(&contents)?;
+        let bytes = writer.finish()?.into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes.clone()))?;
+        for index in 0..methods.len() {
+            let mut file = archive.by_index(index)?;
+            // Rewinding a file that hasn't been read yet changes nothing
+            file.rewind()?;
+            let mut magic = [0; 5];
+            file.read_exact(&mut magic)?;
+            assert_eq!(&magic, b"magic");
+            file.rewind()?;
+            assert_eq!(file.position(), 0);
+            let mut read = Vec::new();
+            file.read_to_end(&mut read)?;
+            assert_eq!(read, contents, "{}", file.name());
+            file.rewind()?;
+            read.clear();
+            file.read_to_end(&mut read)?;
+            assert_eq!(read, contents, "{}", file.name());
+            drop(file);
+
+            let mut file = archive.by_index_raw(index)?;
+            let mut raw = Vec::new();
+            file.read_to_end(&mut raw)?;
+            file.rewind()?;
+            let mut again = Vec::new();
+            file.read_to_end(&mut again)?;
+            assert_eq!(again, raw);
+        }
+
+        let mut file = archive.by_name_decrypt("encrypted", b"password")?;
+        file.read_exact(&mut [0; 5])?;
+        assert!(matches!(
+            file.rewind(),
+            Err(ZipError::UnsupportedArchive(_))
+        ));
+        // Reading goes on from where it was
+        let mut rest = Vec::new();
+        file.read_to_end(&mut rest)?;
+        assert_eq!(rest, contents[5..]);
+
+        let mut stream = Cursor::new(bytes);
+        let mut file = read_zipfile_from_stream(&mut stream)?.unwrap();
+        file.read_exact(&mut [0; 5])?;
+        assert!(matches!(
+            file.rewind(),
+            Err(ZipError::UnsupportedArchive(_))
+        ));
+        let mut rest = Vec::new();
+        file.read_to_end(&mut rest)?;
+        assert_eq!(rest, contents[5..]);
+        Ok(())
+    }
+
+    #[test]
+    fn peek_bytes() -> crate::result::ZipResult<()> {
+        use crate::result::ZipError;
+        use crate::write::SimpleFileOptions;
+        use crate::{CompressionMethod, ZipWriter};
+        use std::io::Write;
+
+        let contents: Vec<u8> = (0..50_000u32).flat_map(u32::to_le_bytes).collect();
+        #[allow(unused_mut)]
+        let mut methods = vec![CompressionMethod::Stored];
+        #[cfg(feature = "_deflate-any")]
+        methods.push(CompressionMethod::Deflated);
+        #[cfg(feature = "bzip2")]
+        methods.push(CompressionMethod::Bzip2);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for method in &methods {
+            let options = SimpleFileOptions::default().compression_method(*method);
+            writer.start_file(format!("{method}"), options)?;
+            writer.write_all(&contents)?;
+            writer.start_file(format!("{method} empty"), options)?;
+        }
+        writer.start_file(
+            "encrypted",
+            SimpleFileOptions::default().with_deprecated_encryption(b"password"),
+        )?;
+        writer.write_all(&contents)?;
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+
+        for index in 0..methods.len() {
+            for n in [
+                0,
+                1,
+                8,
+                1000,
+                contents.len(),
+                contents.len() + 1,
+                usize::MAX,
+            ] {
+                let peeked = archive.peek_bytes(index * 2, n)?;
+                assert_eq!(peeked, contents[..n.min(contents.len())]);
+                assert_eq!(archive.peek_bytes(index * 2 + 1, n)?, []);
+            }
+        }
+        let encrypted = archive.index_for_name("encrypted").unwrap();
+        assert!(matches!(
+            archive.peek_bytes(encrypted, 4),
+            Err(ZipError::PasswordRequired)
+        ));
+        assert!(matches!(
+            archive.peek_bytes(archive.len(), 4),
+            Err(ZipError::FileNotFound)
+        ));
+        Ok(())
+    }
 }
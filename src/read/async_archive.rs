@@ -0,0 +1,590 @@
+//! Types for reading ZIP archives asynchronously with tokio
+
+use crate::compression::CompressionMethod;
+use crate::crc32::Crc32Reader;
+use crate::read::zip_archive::Shared;
+use crate::read::ZipArchive;
+use crate::result::{ZipError, ZipResult};
+use crate::spec;
+use crate::types::{DateTime, ZipFileData};
+use crate::unstable::AsyncLittleEndianReadExt;
+use std::io::{self, Read, Seek};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncSeek, AsyncSeekExt, ReadBuf, Take};
+
+/// Asynchronous ZIP archive reader
+///
+/// This mirrors [`ZipArchive`], but performs all I/O through tokio's [`AsyncRead`] and
+/// [`AsyncSeek`]. Only unencrypted entries using [`CompressionMethod::Stored`] or
+/// [`CompressionMethod::Deflated`] can be read.
+///
+/// ```no_run
+/// # async fn list_zip_contents(
+/// #     reader: impl tokio::io::AsyncRead + tokio::io::AsyncSeek + Unpin,
+/// # ) -> zip::result::ZipResult<()> {
+/// let mut zip = zip::read::AsyncZipArchive::new(reader).await?;
+///
+/// for i in 0..zip.len() {
+///     let mut file = zip.by_index(i).await?;
+///     println!("Filename: {}", file.name());
+///     let mut contents = Vec::new();
+///     tokio::io::AsyncReadExt::read_to_end(&mut file, &mut contents).await?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct AsyncZipArchive<R> {
+    reader: R,
+    shared: Arc<Shared>,
+    comment: Arc<[u8]>,
+}
+
+/// A struct for reading a zip file from an [`AsyncZipArchive`]
+pub struct AsyncZipFile<'a, R> {
+    data: &'a ZipFileData,
+    reader: AsyncZipFileReader<'a, R>,
+}
+
+enum AsyncZipFileReader<'a, R> {
+    Stored(Crc32Reader<Take<&'a mut R>>),
+    #[cfg(feature = "_deflate-any")]
+    Deflated(Crc32Reader<AsyncDeflateDecoder<Take<&'a mut R>>>),
+}
+
+impl<R: AsyncRead + AsyncSeek + Unpin> AsyncZipArchive<R> {
+    /// Read a ZIP archive, collecting the files it contains
+    ///
+    /// This uses the central directory record of the ZIP file, and ignores local file headers
+    pub async fn new(mut reader: R) -> ZipResult<AsyncZipArchive<R>> {
+        let file_length = reader.seek(io::SeekFrom::End(0)).await?;
+        let mut tail = ArchiveTail::new(file_length);
+        tail.extend_to(
+            &mut reader,
+            file_length.saturating_sub(spec::MAX_HEADER_AND_COMMENT_SIZE),
+        )
+        .await?;
+        let (footer, cde_start_pos) = spec::CentralDirectoryEnd::find_and_parse(&mut tail)?;
+
+        // The ZIP64 end-of-central-directory record may be anywhere between the offset its
+        // locator gives and the standard footer, so fetch that range before looking for it.
+        if let Some(locator_start) = cde_start_pos.checked_sub(20) {
+            tail.extend_to(&mut reader, locator_start).await?;
+            tail.pos = locator_start;
+            if let (Ok(locator64), Some(search_upper_bound)) = (
+                spec::Zip64CentralDirectoryEndLocator::parse(&mut tail),
+                cde_start_pos.checked_sub(60),
+            ) {
+                if locator64.end_of_central_directory_offset <= search_upper_bound {
+                    tail.extend_to(&mut reader, locator64.end_of_central_directory_offset)
+                        .await?;
+                }
+            }
+        }
+
+        // Then fetch the earliest of the candidate central directories, so that the sync parser
+        // can pick between them exactly as `ZipArchive::new` does.
+        let mut directory_start = cde_start_pos;
+        if let Ok(results) =
+            ZipArchive::<ArchiveTail>::get_directory_info_zip64(&mut tail, cde_start_pos)
+        {
+            for info in results.into_iter().flatten() {
+                directory_start = directory_start.min(info.directory_start);
+            }
+        }
+        if let Ok(info) =
+            ZipArchive::<ArchiveTail>::get_directory_info_zip32(&footer, cde_start_pos)
+        {
+            directory_start = directory_start.min(info.directory_start);
+        }
+        tail.extend_to(&mut reader, directory_start).await?;
+
+        let shared = ZipArchive::<ArchiveTail>::get_metadata(&mut tail, &footer, cde_start_pos)?;
+        Ok(AsyncZipArchive {
+            reader,
+            shared: shared.into(),
+            comment: footer.zip_file_comment.into(),
+        })
+    }
+
+    /// Number of files contained in this zip.
+    pub fn len(&self) -> usize {
+        self.shared.files.len()
+    }
+
+    /// Whether this zip archive contains no files
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Get the offset from the beginning of the underlying reader that this zip begins at, in bytes.
+    ///
+    /// Normally this value is zero, but if the zip has arbitrary data prepended to it, then this value will be the size
+    /// of that prepended data.
+    pub fn offset(&self) -> u64 {
+        self.shared.offset
+    }
+
+    /// Get the comment of the zip archive.
+    pub fn comment(&self) -> &[u8] {
+        &self.comment
+    }
+
+    /// Returns an iterator over all the file and directory names in this archive.
+    pub fn file_names(&self) -> impl Iterator<Item = &str> {
+        self.shared.files.keys().map(|s| s.as_ref())
+    }
+
+    /// Get the index of a file entry by name, if it's present.
+    #[inline(always)]
+    pub fn index_for_name(&self, name: &str) -> Option<usize> {
+        self.shared.files.get_index_of(name)
+    }
+
+    /// Get the name of a file entry, if it's present.
+    #[inline(always)]
+    pub fn name_for_index(&self, index: usize) -> Option<&str> {
+        self.shared
+            .files
+            .get_index(index)
+            .map(|(name, _)| name.as_ref())
+    }
+
+    /// Search for a file entry by name
+    pub async fn by_name(&mut self, name: &str) -> ZipResult<AsyncZipFile<'_, R>> {
+        let Some(index) = self.shared.files.get_index_of(name) else {
+            return Err(ZipError::FileNotFound);
+        };
+        self.by_index(index).await
+    }
+
+    /// Get a contained file by index
+    pub async fn by_index(&mut self, file_number: usize) -> ZipResult<AsyncZipFile<'_, R>> {
+        let (_, data) = self
+            .shared
+            .files
+            .get_index(file_number)
+            .ok_or(ZipError::FileNotFound)?;
+        if data.encrypted {
+            return Err(ZipError::PasswordRequired);
+        }
+        let limit_reader = find_content(data, &mut self.reader).await?;
+        let reader = match data.compression_method {
+            CompressionMethod::Stored => {
+                AsyncZipFileReader::Stored(Crc32Reader::new(limit_reader, data.crc32, true))
+            }
+            #[cfg(feature = "_deflate-any")]
+            CompressionMethod::Deflated => AsyncZipFileReader::Deflated(Crc32Reader::new(
+                AsyncDeflateDecoder::new(limit_reader),
+                data.crc32,
+                true,
+            )),
+            _ => {
+                return Err(ZipError::UnsupportedArchive(
+                    "Compression method not supported",
+                ))
+            }
+        };
+        Ok(AsyncZipFile { data, reader })
+    }
+
+    /// Unwrap and return the inner reader object
+    ///
+    /// The position of the reader is undefined.
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+}
+
+async fn find_content<'a, R: AsyncRead + AsyncSeek + Unpin>(
+    data: &ZipFileData,
+    reader: &'a mut R,
+) -> ZipResult<Take<&'a mut R>> {
+    // Parse local header
+    reader.seek(io::SeekFrom::Start(data.header_start)).await?;
+    let signature = reader.read_u32_le().await?;
+    if signature != spec::LOCAL_FILE_HEADER_SIGNATURE {
+        return Err(ZipError::InvalidArchive("Invalid local file header"));
+    }
+    let data_start = match data.data_start.get() {
+        None => {
+            reader.seek(io::SeekFrom::Current(22)).await?;
+            let file_name_length = reader.read_u16_le().await? as u64;
+            let extra_field_length = reader.read_u16_le().await? as u64;
+            let magic_and_header = 4 + 22 + 2 + 2;
+            let data_start =
+                data.header_start + magic_and_header + file_name_length + extra_field_length;
+            data.data_start.get_or_init(|| data_start);
+            data_start
+        }
+        Some(start) => *start,
+    };
+
+    reader.seek(io::SeekFrom::Start(data_start)).await?;
+    Ok(reader.take(data.compressed_size))
+}
+
+impl<'a, R> AsyncZipFile<'a, R> {
+    /// Get the name of the file
+    ///
+    /// # Warnings
+    ///
+    /// It is dangerous to use this name directly when extracting an archive.
+    /// It may contain an absolute path (`/etc/shadow`), or break out of the
+    /// current directory (`../runtime`). Carelessly writing to these paths
+    /// allows an attacker to craft a ZIP archive that will overwrite critical
+    /// files.
+    ///
+    /// You can use the [`AsyncZipFile::enclosed_name`] method to validate the name
+    /// as a safe path.
+    pub fn name(&self) -> &str {
+        &self.data.file_name
+    }
+
+    /// Get the name of the file, in the raw (internal) byte representation.
+    ///
+    /// The encoding of this data is currently undefined.
+    pub fn name_raw(&self) -> &[u8] {
+        &self.data.file_name_raw
+    }
+
+    /// Ensure the file path is safe to use as a [`Path`](std::path::Path).
+    ///
+    /// See [`ZipFile::enclosed_name`](crate::read::ZipFile::enclosed_name).
+    pub fn enclosed_name(&self) -> Option<std::path::PathBuf> {
+        self.data.enclosed_name()
+    }
+
+    /// Get the comment of the file
+    pub fn comment(&self) -> &str {
+        &self.data.file_comment
+    }
+
+    /// Get the compression method used to store the file
+    pub fn compression(&self) -> CompressionMethod {
+        self.data.compression_method
+    }
+
+    /// Get the size of the file, in bytes, in the archive
+    pub fn compressed_size(&self) -> u64 {
+        self.data.compressed_size
+    }
+
+    /// Get the size of the file, in bytes, when uncompressed
+    pub fn size(&self) -> u64 {
+        self.data.uncompressed_size
+    }
+
+    /// Get the time the file was last modified
+    pub fn last_modified(&self) -> DateTime {
+        self.data.last_modified_time
+    }
+
+    /// Returns whether the file is actually a directory
+    pub fn is_dir(&self) -> bool {
+        self.data.is_dir()
+    }
+
+    /// Returns whether the file is a regular file
+    pub fn is_file(&self) -> bool {
+        !self.is_dir()
+    }
+
+    /// Get unix mode for the file
+    pub fn unix_mode(&self) -> Option<u32> {
+        self.data.unix_mode()
+    }
+
+    /// Get the CRC32 hash of the original file
+    pub fn crc32(&self) -> u32 {
+        self.data.crc32
+    }
+
+    /// Get the starting offset of the data of the compressed file
+    pub fn data_start(&self) -> u64 {
+        *self.data.data_start.get().unwrap_or(&0)
+    }
+
+    /// Get the starting offset of the zip header for this file
+    pub fn header_start(&self) -> u64 {
+        self.data.header_start
+    }
+}
+
+impl<'a, R: AsyncRead + Unpin> AsyncRead for AsyncZipFile<'a, R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match &mut self.get_mut().reader {
+            AsyncZipFileReader::Stored(r) => Pin::new(r).poll_read(cx, buf),
+            #[cfg(feature = "_deflate-any")]
+            AsyncZipFileReader::Deflated(r) => Pin::new(r).poll_read(cx, buf),
+        }
+    }
+}
+
+/// Raw DEFLATE decoder on top of an [`AsyncRead`], feeding [`flate2::Decompress`] as compressed
+/// data arrives.
+#[cfg(feature = "_deflate-any")]
+struct AsyncDeflateDecoder<R> {
+    inner: R,
+    decompress: flate2::Decompress,
+    input: Box<[u8]>,
+    input_pos: usize,
+    input_len: usize,
+    eof: bool,
+}
+
+#[cfg(feature = "_deflate-any")]
+impl<R> AsyncDeflateDecoder<R> {
+    fn new(inner: R) -> Self {
+        AsyncDeflateDecoder {
+            inner,
+            decompress: flate2::Decompress::new(false),
+            input: vec![0; 32 * 1024].into_boxed_slice(),
+            input_pos: 0,
+            input_len: 0,
+            eof: false,
+        }
+    }
+}
+
+#[cfg(feature = "_deflate-any")]
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncDeflateDecoder<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        if buf.remaining() == 0 {
+            return Poll::Ready(Ok(()));
+        }
+        loop {
+            if this.input_pos == this.input_len && !this.eof {
+                let mut input = ReadBuf::new(&mut this.input);
+                std::task::ready!(Pin::new(&mut this.inner).poll_read(cx, &mut input))?;
+                this.input_len = input.filled().len();
+                this.input_pos = 0;
+                this.eof = this.input_len == 0;
+            }
+
+            let flush = if this.eof {
+                flate2::FlushDecompress::Finish
+            } else {
+                flate2::FlushDecompress::None
+            };
+            let total_in = this.decompress.total_in();
+            let total_out = this.decompress.total_out();
+            let status = this
+                .decompress
+                .decompress(
+                    &this.input[this.input_pos..this.input_len],
+                    buf.initialize_unfilled(),
+                    flush,
+                )
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            let consumed = (this.decompress.total_in() - total_in) as usize;
+            let produced = (this.decompress.total_out() - total_out) as usize;
+            this.input_pos += consumed;
+            buf.advance(produced);
+
+            if produced > 0 || status == flate2::Status::StreamEnd {
+                return Poll::Ready(Ok(()));
+            }
+            if consumed == 0 && (this.eof || this.input_pos < this.input_len) {
+                return Poll::Ready(Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "corrupt deflate stream",
+                )));
+            }
+        }
+    }
+}
+
+/// The bytes from some offset to the end of the archive, addressed by their absolute offsets, so
+/// that the sync central directory parsers can run on them unchanged.
+struct ArchiveTail {
+    start: u64,
+    data: Vec<u8>,
+    pos: u64,
+}
+
+impl ArchiveTail {
+    fn new(file_length: u64) -> Self {
+        ArchiveTail {
+            start: file_length,
+            data: Vec::new(),
+            pos: file_length,
+        }
+    }
+
+    /// Read the bytes between `start` and the current start of the buffer, if any
+    async fn extend_to<R: AsyncRead + AsyncSeek + Unpin>(
+        &mut self,
+        reader: &mut R,
+        start: u64,
+    ) -> io::Result<()> {
+        if start >= self.start {
+            return Ok(());
+        }
+        // The offset comes from the archive itself, so the buffer only grows as data arrives
+        // rather than being allocated up front
+        let len = self.start - start;
+        let mut data = Vec::new();
+        reader.seek(io::SeekFrom::Start(start)).await?;
+        (&mut *reader).take(len).read_to_end(&mut data).await?;
+        if (data.len() as u64) < len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        data.append(&mut self.data);
+        self.data = data;
+        self.start = start;
+        Ok(())
+    }
+
+    fn end(&self) -> u64 {
+        self.start + self.data.len() as u64
+    }
+}
+
+impl Read for ArchiveTail {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos < self.start {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "Central directory lies outside the prefetched range",
+            ));
+        }
+        let offset = (self.pos - self.start).min(self.data.len() as u64) as usize;
+        let count = Read::read(&mut &self.data[offset..], buf)?;
+        self.pos += count as u64;
+        Ok(count)
+    }
+}
+
+impl Seek for ArchiveTail {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            io::SeekFrom::Start(pos) => Some(pos),
+            io::SeekFrom::End(delta) => self.end().checked_add_signed(delta),
+            io::SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+        };
+        self.pos = new_pos.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::AsyncZipArchive;
+    use crate::write::SimpleFileOptions;
+    use crate::{CompressionMethod, ZipArchive, ZipWriter};
+    use std::io::{Cursor, Write};
+    use tokio::io::AsyncReadExt;
+
+    fn make_archive() -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file(
+                "stored.txt",
+                SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+            )
+            .unwrap();
+        writer.write_all(b"stored contents").unwrap();
+        #[cfg(feature = "_deflate-any")]
+        {
+            writer
+                .start_file(
+                    "deflated.txt",
+                    SimpleFileOptions::default().compression_method(CompressionMethod::Deflated),
+                )
+                .unwrap();
+            writer
+                .write_all(&b"deflated contents ".repeat(1000))
+                .unwrap();
+        }
+        writer
+            .add_directory("dir/", SimpleFileOptions::default())
+            .unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[tokio::test]
+    async fn read_matches_sync() {
+        let v = make_archive();
+        let mut sync = ZipArchive::new(Cursor::new(v.clone())).unwrap();
+        let mut archive = AsyncZipArchive::new(Cursor::new(v)).await.unwrap();
+        assert_eq!(sync.len(), archive.len());
+        assert!(sync.file_names().eq(archive.file_names()));
+        for i in 0..archive.len() {
+            let mut expected = Vec::new();
+            std::io::Read::read_to_end(&mut sync.by_index(i).unwrap(), &mut expected).unwrap();
+            let mut file = archive.by_index(i).await.unwrap();
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents).await.unwrap();
+            assert_eq!(expected, contents);
+        }
+        let mut file = archive.by_name("stored.txt").await.unwrap();
+        let mut contents = String::new();
+        file.read_to_string(&mut contents).await.unwrap();
+        assert_eq!("stored contents", contents);
+    }
+
+    #[tokio::test]
+    async fn zip64_with_leading_junk() {
+        let v = include_bytes!("../../tests/data/zip64_demo.zip").to_vec();
+        let archive = AsyncZipArchive::new(Cursor::new(v.clone())).await.unwrap();
+        let sync = ZipArchive::new(Cursor::new(v)).unwrap();
+        assert_eq!(sync.len(), archive.len());
+        assert_eq!(sync.offset(), archive.offset());
+    }
+
+    #[tokio::test]
+    async fn zip64_magic_in_filenames() {
+        let files = vec![
+            include_bytes!("../../tests/data/zip64_magic_in_filename_1.zip").to_vec(),
+            include_bytes!("../../tests/data/zip64_magic_in_filename_2.zip").to_vec(),
+            include_bytes!("../../tests/data/zip64_magic_in_filename_3.zip").to_vec(),
+            include_bytes!("../../tests/data/zip64_magic_in_filename_4.zip").to_vec(),
+            include_bytes!("../../tests/data/zip64_magic_in_filename_5.zip").to_vec(),
+        ];
+        for file in files {
+            let sync = ZipArchive::new(Cursor::new(file.clone())).unwrap();
+            let archive = AsyncZipArchive::new(Cursor::new(file)).await.unwrap();
+            assert!(sync.file_names().eq(archive.file_names()));
+        }
+    }
+
+    #[tokio::test]
+    async fn invalid_offset() {
+        let v = include_bytes!("../../tests/data/invalid_offset.zip").to_vec();
+        let sync = ZipArchive::new(Cursor::new(v.clone())).unwrap_err();
+        let err = AsyncZipArchive::new(Cursor::new(v)).await.unwrap_err();
+        assert_eq!(sync.to_string(), err.to_string());
+    }
+
+    #[tokio::test]
+    async fn invalid_checksum() {
+        let mut v = make_archive();
+        let pos = v
+            .windows(b"stored contents".len())
+            .position(|w| w == b"stored contents")
+            .unwrap();
+        v[pos] = b'S';
+        let mut archive = AsyncZipArchive::new(Cursor::new(v)).await.unwrap();
+        let mut file = archive.by_name("stored.txt").await.unwrap();
+        let mut contents = Vec::new();
+        let err = file.read_to_end(&mut contents).await.unwrap_err();
+        assert!(err.to_string().contains("Invalid checksum"));
+    }
+}
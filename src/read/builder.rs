@@ -0,0 +1,222 @@
+//! Opening archives with non-default settings
+
+use crate::read::{Strictness, ZipArchive};
+use crate::result::ZipResult;
+use crate::spec;
+use crate::types::NameEncoding;
+use std::io::{Read, Seek};
+
+/// Settings for opening a [`ZipArchive`], gathered in one place
+///
+/// Each method sets the same thing as the `ZipArchive` setter of the same name, so for example
+///
+/// ```
+/// # fn main() -> zip::result::ZipResult<()> {
+/// use zip::read::ZipArchiveBuilder;
+/// use zip::NameEncoding;
+/// # let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+/// # writer.start_file("a.txt", zip::write::SimpleFileOptions::default())?;
+/// # let reader = writer.finish()?;
+///
+/// let archive = ZipArchiveBuilder::new()
+///     .verify_crc(true)
+///     .max_decompressed_size(1 << 30)
+///     .name_encoding(NameEncoding::Cp437)
+///     .open(reader)?;
+/// # assert_eq!(archive.len(), 1);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// opens an archive the way [`ZipArchive::new`] does, then limits each file to 1 GiB. A builder
+/// can open any number of archives. Its defaults are those of `ZipArchive::new`.
+#[derive(Clone, Debug)]
+pub struct ZipArchiveBuilder {
+    name_encoding: Option<NameEncoding>,
+    verify_crc: bool,
+    max_decompressed_size: Option<u64>,
+    max_compression_ratio: Option<f64>,
+    eocd_search_limit: u64,
+    #[cfg(feature = "aes-crypto")]
+    aes_iterations: Option<u32>,
+    strictness: Strictness,
+}
+
+impl Default for ZipArchiveBuilder {
+    fn default() -> Self {
+        ZipArchiveBuilder {
+            name_encoding: None,
+            verify_crc: true,
+            max_decompressed_size: None,
+            max_compression_ratio: None,
+            eocd_search_limit: spec::MAX_HEADER_AND_COMMENT_SIZE,
+            #[cfg(feature = "aes-crypto")]
+            aes_iterations: None,
+            strictness: Strictness::Lenient,
+        }
+    }
+}
+
+impl ZipArchiveBuilder {
+    /// Start with the settings that [`ZipArchive::new`] uses
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// See [`ZipArchive::set_name_encoding`]
+    pub fn name_encoding(mut self, encoding: NameEncoding) -> Self {
+        self.name_encoding = Some(encoding);
+        self
+    }
+
+    /// See [`ZipArchive::set_verify_crc`]
+    pub fn verify_crc(mut self, verify_crc: bool) -> Self {
+        self.verify_crc = verify_crc;
+        self
+    }
+
+    /// See [`ZipArchive::set_max_decompressed_size`]
+    pub fn max_decompressed_size(mut self, limit: u64) -> Self {
+        self.max_decompressed_size = Some(limit);
+        self
+    }
+
+    /// See [`ZipArchive::set_max_compression_ratio`]
+    pub fn max_compression_ratio(mut self, ratio: f64) -> Self {
+        self.max_compression_ratio = Some(ratio);
+        self
+    }
+
+    /// Look for the end-of-central-directory record only within the last `max_bytes` bytes of
+    /// the reader; see [`ZipArchive::new_with_eocd_search_limit`]
+    pub fn eocd_search_limit(mut self, max_bytes: u64) -> Self {
+        self.eocd_search_limit = max_bytes;
+        self
+    }
+
+    /// See [`ZipArchive::set_aes_iterations`]
+    #[cfg(feature = "aes-crypto")]
+    pub fn aes_iterations(mut self, iterations: u32) -> Self {
+        self.aes_iterations = Some(iterations);
+        self
+    }
+
+    /// See [`ZipArchive::set_strictness`]
+    pub fn strictness(mut self, strictness: Strictness) -> Self {
+        self.strictness = strictness;
+        self
+    }
+
+    /// Read the archive in `reader` with these settings
+    pub fn open<R: Read + Seek>(&self, reader: R) -> ZipResult<ZipArchive<R>> {
+        let mut archive = ZipArchive::new_with_eocd_search_limit(reader, self.eocd_search_limit)?;
+        if let Some(encoding) = self.name_encoding {
+            archive.set_name_encoding(encoding)?;
+        }
+        archive.set_verify_crc(self.verify_crc);
+        if let Some(limit) = self.max_decompressed_size {
+            archive.set_max_decompressed_size(limit);
+        }
+        if let Some(ratio) = self.max_compression_ratio {
+            archive.set_max_compression_ratio(ratio);
+        }
+        #[cfg(feature = "aes-crypto")]
+        if let Some(iterations) = self.aes_iterations {
+            archive.set_aes_iterations(iterations);
+        }
+        archive.set_strictness(self.strictness);
+        Ok(archive)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::ZipArchiveBuilder;
+    use crate::result::ZipError;
+    use crate::write::SimpleFileOptions;
+    use crate::{NameEncoding, ZipWriter};
+    use std::io::{Cursor, Read, Write};
+
+    fn archive_bytes() -> Vec<u8> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().with_name_encoding(NameEncoding::Cp437);
+        writer.start_file("caf\u{e9}.txt", options).unwrap();
+        writer.write_all(&[b'a'; 1000]).unwrap();
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn settings_apply() {
+        let bytes = archive_bytes();
+        let builder = ZipArchiveBuilder::new()
+            .name_encoding(NameEncoding::Utf8)
+            .max_decompressed_size(100);
+        let mut archive = builder.open(Cursor::new(bytes.clone())).unwrap();
+        // The name was written in codepage 437, so it isn't valid UTF-8
+        assert_eq!(archive.name_for_index(0), Some("caf\u{fffd}.txt"));
+        let mut content = Vec::new();
+        assert!(archive
+            .by_index(0)
+            .unwrap()
+            .read_to_end(&mut content)
+            .is_err());
+
+        // The same builder can be reused, and the defaults change nothing
+        let mut archive = builder
+            .clone()
+            .max_decompressed_size(1000)
+            .open(Cursor::new(bytes.clone()))
+            .unwrap();
+        content.clear();
+        archive
+            .by_index(0)
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content.len(), 1000);
+        let archive = ZipArchiveBuilder::new().open(Cursor::new(bytes)).unwrap();
+        assert_eq!(archive.name_for_index(0), Some("caf\u{e9}.txt"));
+    }
+
+    #[test]
+    fn verify_crc_and_eocd_search_limit() {
+        let mut bytes = archive_bytes();
+        // Corrupt the stored CRC-32 in the central directory, which is what's checked
+        let central = bytes
+            .windows(4)
+            .position(|w| w == [0x50, 0x4b, 0x01, 0x02])
+            .unwrap();
+        bytes[central + 16] ^= 0xFF;
+        let mut content = Vec::new();
+        let mut archive = ZipArchiveBuilder::new()
+            .open(Cursor::new(bytes.clone()))
+            .unwrap();
+        assert!(archive
+            .by_index(0)
+            .unwrap()
+            .read_to_end(&mut content)
+            .is_err());
+        let mut archive = ZipArchiveBuilder::new()
+            .verify_crc(false)
+            .open(Cursor::new(bytes.clone()))
+            .unwrap();
+        content.clear();
+        archive
+            .by_index(0)
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+
+        bytes.extend_from_slice(&[0; 100]);
+        assert!(matches!(
+            ZipArchiveBuilder::new()
+                .eocd_search_limit(64)
+                .open(Cursor::new(bytes.clone())),
+            Err(ZipError::InvalidArchive(_))
+        ));
+        assert!(ZipArchiveBuilder::new()
+            .eocd_search_limit(200)
+            .open(Cursor::new(bytes))
+            .is_ok());
+    }
+}
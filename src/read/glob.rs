@@ -0,0 +1,90 @@
+//! Matching entry names against glob patterns
+
+/// Whether the entry `name` matches `pattern`
+///
+/// Both are split into components at each `/`, and a trailing `/` on a directory's name is
+/// ignored. Within a component, `*` matches any run of characters and `?` matches any one
+/// character. A component that's exactly `**` matches any number of whole components, including
+/// none, so `**/*.json` matches `a.json` and `a/b/c.json`.
+pub(crate) fn matches(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let name = name.strip_suffix('/').unwrap_or(name);
+    let name: Vec<&str> = name.split('/').collect();
+    matches_components(&pattern, &name)
+}
+
+fn matches_components(pattern: &[&str], name: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((&"**", rest)) => (0..=name.len()).any(|skip| matches_components(rest, &name[skip..])),
+        Some((first, rest)) => match name.split_first() {
+            Some((name_first, name_rest)) => {
+                matches_component(first, name_first) && matches_components(rest, name_rest)
+            }
+            None => false,
+        },
+    }
+}
+
+/// Match one component, backtracking to the last `*` whenever the rest doesn't match
+fn matches_component(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Where the last `*` was in the pattern, and how much of the name it's matched so far
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+#[cfg(test)]
+mod test {
+    use super::matches;
+
+    #[test]
+    fn wildcards() {
+        assert!(matches("*.json", "config.json"));
+        assert!(!matches("*.json", "dir/config.json"));
+        assert!(matches("*/*.json", "dir/config.json"));
+        assert!(matches("data?.csv", "data1.csv"));
+        assert!(!matches("data?.csv", "data10.csv"));
+        assert!(matches("a*b*c", "aXbYbZc"));
+        assert!(!matches("a*b*c", "aXbYbZ"));
+        assert!(matches("*", "file"));
+        assert!(!matches("*", "dir/file"));
+        assert!(matches("dir", "dir/"));
+    }
+
+    #[test]
+    fn double_star() {
+        assert!(matches("**/*.json", "a.json"));
+        assert!(matches("**/*.json", "a/b/c.json"));
+        assert!(!matches("**/*.json", "a/b/c.txt"));
+        assert!(matches("src/**", "src/a/b.rs"));
+        assert!(matches("src/**", "src/"));
+        assert!(!matches("src/**", "other/a.rs"));
+        assert!(matches("a/**/z", "a/z"));
+        assert!(matches("a/**/z", "a/b/c/z"));
+        assert!(!matches("a/**/z", "a/b/c/y"));
+        assert!(matches("**", "anything/at/all"));
+    }
+}
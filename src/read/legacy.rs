@@ -0,0 +1,393 @@
+//! Decoders for Shrink (method 1) and Implode (method 6), which PKZIP 1.x wrote before Deflate
+//! replaced them
+
+use std::io::{Error, ErrorKind, Read, Result};
+
+const COMPRESSED_BYTES_TO_BUFFER: usize = 4096;
+
+/// Reads codes of up to 16 bits, starting from the least significant bit of each byte
+#[derive(Debug)]
+struct BitReader<R> {
+    inner: R,
+    buffer: Box<[u8; COMPRESSED_BYTES_TO_BUFFER]>,
+    buffer_pos: usize,
+    buffer_len: usize,
+    bits: u32,
+    bit_count: u8,
+}
+
+impl<R: Read> BitReader<R> {
+    fn new(inner: R) -> Self {
+        BitReader {
+            inner,
+            buffer: Box::new([0; COMPRESSED_BYTES_TO_BUFFER]),
+            buffer_pos: 0,
+            buffer_len: 0,
+            bits: 0,
+            bit_count: 0,
+        }
+    }
+
+    fn read_bits(&mut self, count: u8) -> Result<u16> {
+        debug_assert!(count <= 16);
+        while self.bit_count < count {
+            if self.buffer_pos == self.buffer_len {
+                self.buffer_len = self.inner.read(&mut self.buffer[..])?;
+                self.buffer_pos = 0;
+                if self.buffer_len == 0 {
+                    return Err(Error::new(
+                        ErrorKind::UnexpectedEof,
+                        "compressed data ended before the whole file was decoded",
+                    ));
+                }
+            }
+            self.bits |= (self.buffer[self.buffer_pos] as u32) << self.bit_count;
+            self.buffer_pos += 1;
+            self.bit_count += 8;
+        }
+        let value = self.bits & ((1 << count) - 1);
+        self.bits >>= count;
+        self.bit_count -= count;
+        Ok(value as u16)
+    }
+
+    fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+/// The size of the Shrink string table, which is also how many codes 13 bits can hold
+const SHRINK_TABLE_SIZE: usize = 1 << 13;
+
+/// Code 256 isn't a string: it's followed by a control code
+const SHRINK_CONTROL: u16 = 256;
+/// Control code that makes every following code one bit longer
+const SHRINK_GROW: u16 = 1;
+/// Control code that frees every string that isn't a prefix of another one
+const SHRINK_PARTIAL_CLEAR: u16 = 2;
+
+/// Parent of a table entry that doesn't hold a string
+const FREE: u16 = u16::MAX;
+
+/// Decodes Shrink, a variant of LZW with codes of 9 to 13 bits
+///
+/// Unlike most LZW variants, Shrink doesn't reset its whole table when it fills up. Instead, the
+/// encoder sends a partial clear, which frees the strings that aren't a prefix of another one, and
+/// new strings go into the lowest free codes from then on.
+#[derive(Debug)]
+pub struct ShrinkDecoder<R> {
+    compressed_reader: BitReader<R>,
+    /// For each code, the code of the string it extends by one byte, or `FREE`
+    parent: Box<[u16]>,
+    /// For each code, the last byte of its string
+    last_byte: Box<[u8]>,
+    code_size: u8,
+    /// The code most recently added to the table; new codes are searched for after it
+    last_code: u16,
+    /// The previous code read, and the first byte of its string
+    previous: Option<(u16, u8)>,
+    /// The decoded string that's being returned
+    output: Vec<u8>,
+    output_pos: usize,
+    remaining: u64,
+}
+
+impl<R: Read> ShrinkDecoder<R> {
+    /// Start decoding a Shrink entry. The stream has no end marker, so decoding stops after
+    /// `uncompressed_size` bytes.
+    pub fn new(inner: R, uncompressed_size: u64) -> Self {
+        let mut parent = vec![FREE; SHRINK_TABLE_SIZE].into_boxed_slice();
+        let mut last_byte = vec![0; SHRINK_TABLE_SIZE].into_boxed_slice();
+        for byte in 0..=u8::MAX {
+            // Single bytes have no parent, which is marked with the control code
+            parent[byte as usize] = SHRINK_CONTROL;
+            last_byte[byte as usize] = byte;
+        }
+        ShrinkDecoder {
+            compressed_reader: BitReader::new(inner),
+            parent,
+            last_byte,
+            code_size: 9,
+            last_code: SHRINK_CONTROL,
+            previous: None,
+            output: Vec::new(),
+            output_pos: 0,
+            remaining: uncompressed_size,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.compressed_reader.into_inner()
+    }
+
+    /// Read the next code other than a control code, and put its string in `output`
+    fn decode_string(&mut self) -> Result<()> {
+        let code = loop {
+            let code = self.compressed_reader.read_bits(self.code_size)?;
+            if code != SHRINK_CONTROL {
+                break code;
+            }
+            match self.compressed_reader.read_bits(self.code_size)? {
+                SHRINK_GROW if self.code_size < 13 => self.code_size += 1,
+                SHRINK_PARTIAL_CLEAR => self.partial_clear(),
+                _ => return Err(invalid_data("invalid Shrink control code")),
+            }
+        };
+        self.output.clear();
+        self.output_pos = 0;
+        let Some((previous, previous_first_byte)) = self.previous else {
+            if code > u8::MAX as u16 {
+                return Err(invalid_data("Shrink data starts with an undefined code"));
+            }
+            self.output.push(code as u8);
+            self.previous = Some((code, code as u8));
+            return Ok(());
+        };
+        // A code that isn't in the table yet is the one about to be added: the previous string
+        // followed by its own first byte
+        let mut walk = code;
+        if self.parent[code as usize] == FREE {
+            self.output.push(previous_first_byte);
+            walk = previous;
+        }
+        while walk != SHRINK_CONTROL {
+            let parent = self.parent[walk as usize];
+            if parent == FREE || self.output.len() >= SHRINK_TABLE_SIZE {
+                return Err(invalid_data("Shrink data refers to an undefined code"));
+            }
+            self.output.push(self.last_byte[walk as usize]);
+            walk = parent;
+        }
+        self.output.reverse();
+        let first_byte = self.output[0];
+
+        let new_code = (self.last_code as usize + 1..SHRINK_TABLE_SIZE)
+            .find(|&code| self.parent[code] == FREE)
+            .ok_or_else(|| invalid_data("Shrink string table overflowed"))?;
+        self.parent[new_code] = previous;
+        self.last_byte[new_code] = first_byte;
+        self.last_code = new_code as u16;
+        self.previous = Some((code, first_byte));
+        Ok(())
+    }
+
+    fn partial_clear(&mut self) {
+        let strings = SHRINK_CONTROL as usize + 1..=self.last_code as usize;
+        let mut is_prefix = vec![false; SHRINK_TABLE_SIZE];
+        for code in strings.clone() {
+            let parent = self.parent[code];
+            if parent != FREE && parent > SHRINK_CONTROL {
+                is_prefix[parent as usize] = true;
+            }
+        }
+        for code in strings {
+            if !is_prefix[code] {
+                self.parent[code] = FREE;
+            }
+        }
+        self.last_code = SHRINK_CONTROL;
+    }
+}
+
+impl<R: Read> Read for ShrinkDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if buf.is_empty() || self.remaining == 0 {
+            return Ok(0);
+        }
+        if self.output_pos == self.output.len() {
+            self.decode_string()?;
+        }
+        let count = (self.output.len() - self.output_pos)
+            .min(buf.len())
+            .min(self.remaining.try_into().unwrap_or(usize::MAX));
+        buf[..count].copy_from_slice(&self.output[self.output_pos..][..count]);
+        self.output_pos += count;
+        self.remaining -= count as u64;
+        Ok(count)
+    }
+}
+
+/// A Shannon-Fano code, as stored at the start of an imploded entry
+#[derive(Debug)]
+struct ShannonFanoTree {
+    /// For each code length from 1 to 16, the first code of that length and how many there are
+    ranges: [(u16, u16); 16],
+    /// The symbols in the order of their codes
+    symbols: Vec<u8>,
+}
+
+impl ShannonFanoTree {
+    /// Read a tree of `symbol_count` code lengths, which are run-length encoded in whole bytes
+    fn read<R: Read>(reader: &mut BitReader<R>, symbol_count: usize) -> Result<Self> {
+        let mut lengths = Vec::with_capacity(symbol_count);
+        let runs = reader.read_bits(8)? + 1;
+        for _ in 0..runs {
+            let run = reader.read_bits(8)?;
+            let length = (run & 0xF) as u8 + 1;
+            let count = (run >> 4) as usize + 1;
+            if lengths.len() + count > symbol_count {
+                return Err(invalid_data("Implode tree has too many code lengths"));
+            }
+            lengths.extend(std::iter::repeat(length).take(count));
+        }
+        if lengths.len() != symbol_count {
+            return Err(invalid_data("Implode tree has too few code lengths"));
+        }
+
+        // APPNOTE 5.3.7: counting down from the symbols with the longest codes, each code is one
+        // more than the last, with 16-bit codes aligned to the left
+        let mut order: Vec<u8> = (0..symbol_count).map(|symbol| symbol as u8).collect();
+        order.sort_by_key(|&symbol| lengths[symbol as usize]);
+        order.reverse();
+        let mut ranges = [(0, 0); 16];
+        let mut code = 0u32;
+        let mut increment = 0;
+        let mut last_length = 0;
+        for &symbol in &order {
+            let length = lengths[symbol as usize];
+            code += increment;
+            if length != last_length {
+                last_length = length;
+                increment = 1 << (16 - length);
+            }
+            if code > u16::MAX as u32 {
+                return Err(invalid_data("Implode tree has too many short codes"));
+            }
+            let range = &mut ranges[length as usize - 1];
+            if range.1 == 0 {
+                range.0 = (code >> (16 - length)) as u16;
+            }
+            range.1 += 1;
+        }
+        Ok(ShannonFanoTree {
+            ranges,
+            symbols: order,
+        })
+    }
+
+    /// Read one code, most significant bit first
+    fn decode<R: Read>(&self, reader: &mut BitReader<R>) -> Result<u8> {
+        let mut code = 0u16;
+        let mut index = self.symbols.len();
+        for &(first, count) in &self.ranges {
+            code = code << 1 | reader.read_bits(1)?;
+            // The symbols with the shortest codes are at the end of `symbols`
+            index -= count as usize;
+            if count > 0 && code >= first && code - first < count {
+                return Ok(self.symbols[index + (code - first) as usize]);
+            }
+        }
+        Err(invalid_data("Implode data has an invalid code"))
+    }
+}
+
+/// General purpose flag that selects an 8 KiB window for Implode, rather than 4 KiB
+const IMPLODE_LARGE_WINDOW: u16 = 1 << 1;
+/// General purpose flag that means literal bytes are Shannon-Fano coded
+const IMPLODE_LITERAL_TREE: u16 = 1 << 2;
+
+const IMPLODE_WINDOW_SIZE: usize = 8192;
+
+/// Decodes Implode, which combines a sliding window with Shannon-Fano coding
+///
+/// The size of the window and whether literals are coded are set by bits 1 and 2 of the entry's
+/// general purpose flags.
+#[derive(Debug)]
+pub struct ImplodeDecoder<R> {
+    compressed_reader: BitReader<R>,
+    literal_tree: Option<ShannonFanoTree>,
+    length_tree: ShannonFanoTree,
+    distance_tree: ShannonFanoTree,
+    distance_low_bits: u8,
+    min_match_length: u16,
+    window: Box<[u8; IMPLODE_WINDOW_SIZE]>,
+    window_pos: usize,
+    /// The distance and remaining length of the match being copied
+    copy: (usize, u16),
+    remaining: u64,
+}
+
+impl<R: Read> ImplodeDecoder<R> {
+    /// Start decoding an Implode entry with the given general purpose flags. The stream has no
+    /// end marker, so decoding stops after `uncompressed_size` bytes.
+    pub fn new(inner: R, uncompressed_size: u64, flags: u16) -> Result<Self> {
+        let mut compressed_reader = BitReader::new(inner);
+        let literal_tree = if flags & IMPLODE_LITERAL_TREE != 0 {
+            Some(ShannonFanoTree::read(&mut compressed_reader, 256)?)
+        } else {
+            None
+        };
+        let length_tree = ShannonFanoTree::read(&mut compressed_reader, 64)?;
+        let distance_tree = ShannonFanoTree::read(&mut compressed_reader, 64)?;
+        Ok(ImplodeDecoder {
+            compressed_reader,
+            min_match_length: if literal_tree.is_some() { 3 } else { 2 },
+            literal_tree,
+            length_tree,
+            distance_tree,
+            distance_low_bits: if flags & IMPLODE_LARGE_WINDOW != 0 {
+                7
+            } else {
+                6
+            },
+            // Matches may reach back past the start of the file, where they copy zeros
+            window: Box::new([0; IMPLODE_WINDOW_SIZE]),
+            window_pos: 0,
+            copy: (0, 0),
+            remaining: uncompressed_size,
+        })
+    }
+
+    pub fn into_inner(self) -> R {
+        self.compressed_reader.into_inner()
+    }
+
+    /// Read the next literal or match, and return the literal or the start of the match
+    fn decode_next(&mut self) -> Result<u8> {
+        let reader = &mut self.compressed_reader;
+        if reader.read_bits(1)? == 1 {
+            return match &self.literal_tree {
+                Some(tree) => tree.decode(reader),
+                None => Ok(reader.read_bits(8)? as u8),
+            };
+        }
+        let low = reader.read_bits(self.distance_low_bits)? as usize;
+        let high = self.distance_tree.decode(reader)? as usize;
+        let distance = (high << self.distance_low_bits | low) + 1;
+        let mut length = self.length_tree.decode(reader)? as u16;
+        if length == 63 {
+            length += reader.read_bits(8)?;
+        }
+        self.copy = (distance, length + self.min_match_length);
+        Ok(self.next_copied_byte())
+    }
+
+    fn next_copied_byte(&mut self) -> u8 {
+        let (distance, length) = &mut self.copy;
+        *length -= 1;
+        self.window[(self.window_pos + IMPLODE_WINDOW_SIZE - *distance) % IMPLODE_WINDOW_SIZE]
+    }
+}
+
+impl<R: Read> Read for ImplodeDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let count = buf
+            .len()
+            .min(self.remaining.try_into().unwrap_or(usize::MAX));
+        for byte in &mut buf[..count] {
+            *byte = if self.copy.1 > 0 {
+                self.next_copied_byte()
+            } else {
+                self.decode_next()?
+            };
+            self.window[self.window_pos] = *byte;
+            self.window_pos = (self.window_pos + 1) % IMPLODE_WINDOW_SIZE;
+        }
+        self.remaining -= count as u64;
+        Ok(count)
+    }
+}
+
+fn invalid_data(message: &'static str) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
@@ -1,52 +1,93 @@
 use lzma_rs::decompress::{Options, Stream, UnpackedSize};
 use std::collections::VecDeque;
-use std::io::{copy, Error, Read, Result, Write};
+use std::io::{Error, ErrorKind, Read, Result, Write};
 
 const COMPRESSED_BYTES_TO_BUFFER: usize = 4096;
 
-const OPTIONS: Options = Options {
-    unpacked_size: UnpackedSize::ReadFromHeader,
-    memlimit: None,
-    allow_incomplete: true,
-};
+/// Length of the LZMA properties (lc/lp/pb byte and dictionary size) in the ZIP header
+const PROPERTIES_LEN: u16 = 5;
 
 #[derive(Debug)]
 pub struct LzmaDecoder<R> {
     compressed_reader: R,
-    stream: Stream<VecDeque<u8>>,
+    /// Taken once the compressed data runs out, leaving the rest of the output in `remaining`
+    stream: Option<Stream<VecDeque<u8>>>,
+    remaining: VecDeque<u8>,
 }
 
 impl<R: Read> LzmaDecoder<R> {
-    pub fn new(inner: R) -> Self {
-        LzmaDecoder {
+    /// Start decoding an LZMA entry. ZIP wraps the raw LZMA stream in its own header: a two-byte
+    /// encoder version, then the length of the properties that follow. Unlike an `.lzma` file,
+    /// there's no uncompressed size after the properties, so it's taken from the ZIP metadata
+    /// instead; decoding stops there whether or not the stream has an end marker.
+    pub fn new(mut inner: R, uncompressed_size: u64) -> Result<Self> {
+        let mut header = [0u8; 4];
+        inner.read_exact(&mut header)?;
+        if u16::from_le_bytes([header[2], header[3]]) != PROPERTIES_LEN {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "LZMA properties header has an unexpected length",
+            ));
+        }
+        let options = Options {
+            unpacked_size: UnpackedSize::UseProvided(Some(uncompressed_size)),
+            memlimit: None,
+            allow_incomplete: false,
+        };
+        Ok(LzmaDecoder {
             compressed_reader: inner,
-            stream: Stream::new_with_options(&OPTIONS, VecDeque::new()),
+            stream: Some(Stream::new_with_options(&options, VecDeque::new())),
+            remaining: VecDeque::new(),
+        })
+    }
+
+    /// Consumes this decoder, returning the reader of the compressed data
+    pub fn into_inner(self) -> R {
+        self.compressed_reader
+    }
+
+    /// Pass compressed data to the decoder. Once the decoder has produced the whole entry, it
+    /// stops accepting input, so any end marker and padding after that point are ignored.
+    fn feed(&mut self, mut compressed: &[u8]) -> Result<()> {
+        while let Some(stream) = &mut self.stream {
+            if compressed.is_empty() {
+                break;
+            }
+            match stream.write(compressed)? {
+                0 => self.finish_stream()?,
+                count => compressed = &compressed[count..],
+            }
         }
+        Ok(())
     }
 
-    pub fn finish(mut self) -> Result<VecDeque<u8>> {
-        copy(&mut self.compressed_reader, &mut self.stream)?;
-        self.stream.finish().map_err(Error::from)
+    /// Collect the rest of the output once there's no more input for the decoder. It holds back
+    /// the last few input bytes until then, so this can produce more output.
+    fn finish_stream(&mut self) -> Result<()> {
+        if let Some(stream) = self.stream.take() {
+            self.remaining.extend(stream.finish().map_err(Error::from)?);
+        }
+        Ok(())
     }
 }
 
 impl<R: Read> Read for LzmaDecoder<R> {
     fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        let mut bytes_read = self.stream.get_output_mut().unwrap().read(buf)?;
-        while bytes_read < buf.len() {
+        loop {
+            let Some(stream) = &mut self.stream else {
+                return self.remaining.read(buf);
+            };
+            let bytes_read = stream.get_output_mut().unwrap().read(buf)?;
+            if bytes_read > 0 || buf.is_empty() {
+                return Ok(bytes_read);
+            }
             let mut next_compressed = [0u8; COMPRESSED_BYTES_TO_BUFFER];
             let compressed_bytes_read = self.compressed_reader.read(&mut next_compressed)?;
             if compressed_bytes_read == 0 {
-                break;
+                self.finish_stream()?;
+            } else {
+                self.feed(&next_compressed[..compressed_bytes_read])?;
             }
-            self.stream
-                .write_all(&next_compressed[..compressed_bytes_read])?;
-            bytes_read += self
-                .stream
-                .get_output_mut()
-                .unwrap()
-                .read(&mut buf[bytes_read..])?;
         }
-        Ok(bytes_read)
     }
 }
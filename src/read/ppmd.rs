@@ -0,0 +1,61 @@
+//! Reading PPMd (method 98) entries
+
+use ppmd_rust::{Ppmd8Decoder, RestoreMethod, PPMD8_MAX_ORDER, PPMD8_MIN_ORDER};
+use std::io::{self, Error, ErrorKind, Read, Result};
+
+/// Decodes PPMd entries, as written by WinZip, WinRAR and 7-Zip
+///
+/// ZIP uses PPMd version I, revision 1, unlike the `.7z` format, which uses version H. The stream
+/// starts with two bytes of model parameters: the model order less one in the low 4 bits, the
+/// memory size in MiB less one in the next 8, and the method for restoring the model once that
+/// memory runs out in the top 4.
+pub struct PpmdDecoder<R: Read> {
+    inner: io::Take<Ppmd8Decoder<R>>,
+}
+
+impl<R: Read> PpmdDecoder<R> {
+    /// Start decoding a PPMd entry. Encoders don't always write an end marker, so decoding stops
+    /// after `uncompressed_size` bytes either way.
+    pub fn new(mut inner: R, uncompressed_size: u64) -> Result<Self> {
+        let mut properties = [0u8; 2];
+        inner.read_exact(&mut properties)?;
+        let properties = u16::from_le_bytes(properties);
+        let order = (properties & 0xF) as u32 + 1;
+        let memory_size = ((properties >> 4) & 0xFF) as u32 + 1;
+        let restore_method = match properties >> 12 {
+            0 => RestoreMethod::Restart,
+            1 => RestoreMethod::CutOff,
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    "PPMd restore method isn't supported",
+                ))
+            }
+        };
+        if !(PPMD8_MIN_ORDER..=PPMD8_MAX_ORDER).contains(&order) {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "PPMd model order is out of range",
+            ));
+        }
+        let decoder = Ppmd8Decoder::new(inner, order, memory_size << 20, restore_method).map_err(
+            |e| match e {
+                ppmd_rust::Error::IoError(e) => e,
+                e => Error::new(ErrorKind::InvalidData, e.to_string()),
+            },
+        )?;
+        Ok(PpmdDecoder {
+            inner: decoder.take(uncompressed_size),
+        })
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner().into_inner()
+    }
+}
+
+impl<R: Read> Read for PpmdDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.inner.read(buf)
+    }
+}
@@ -0,0 +1,260 @@
+//! Random access within a single entry of an archive
+
+use crate::compression::CompressionMethod;
+use crate::result::{ZipError, ZipResult};
+use std::io::{self, Read, Seek, SeekFrom};
+
+#[cfg(any(
+    feature = "deflate",
+    feature = "deflate-zlib",
+    feature = "deflate-zlib-ng"
+))]
+use flate2::read::DeflateDecoder;
+
+/// A file within an archive that supports [`Seek`] as well as [`Read`]
+///
+/// Returned by [`ZipArchive::by_index_seekable`](crate::ZipArchive::by_index_seekable) and
+/// [`ZipArchive::by_name_seekable`](crate::ZipArchive::by_name_seekable). For Stored entries,
+/// seeking just moves the position in the underlying reader. Deflate streams can't be entered at
+/// an arbitrary point, so for Deflated entries, seeking forward decompresses and discards the
+/// bytes in between, and seeking backward restarts decompression from the start of the entry.
+/// Each backward seek therefore costs O(offset) time, and reading a Deflated entry in reverse
+/// order costs O(n²) overall.
+///
+/// Unlike [`ZipFile`](crate::read::ZipFile), the data isn't checked against the entry's CRC-32.
+pub struct SeekableZipFile<R> {
+    reader: Option<SeekableReader<R>>,
+    data_start: u64,
+    compressed_size: u64,
+    size: u64,
+    pos: u64,
+}
+
+enum SeekableReader<R> {
+    Stored(io::Take<R>),
+    #[cfg(any(
+        feature = "deflate",
+        feature = "deflate-zlib",
+        feature = "deflate-zlib-ng"
+    ))]
+    Deflated(DeflateDecoder<io::Take<R>>),
+}
+
+impl<R> SeekableReader<R> {
+    fn into_inner(self) -> R {
+        match self {
+            SeekableReader::Stored(reader) => reader.into_inner(),
+            #[cfg(any(
+                feature = "deflate",
+                feature = "deflate-zlib",
+                feature = "deflate-zlib-ng"
+            ))]
+            SeekableReader::Deflated(reader) => reader.into_inner().into_inner(),
+        }
+    }
+}
+
+impl<R: Read + Seek> SeekableZipFile<R> {
+    pub(crate) fn new(
+        mut reader: R,
+        compression_method: CompressionMethod,
+        data_start: u64,
+        compressed_size: u64,
+        size: u64,
+    ) -> ZipResult<Self> {
+        reader.seek(SeekFrom::Start(data_start))?;
+        let reader = reader.take(compressed_size);
+        let reader = match compression_method {
+            CompressionMethod::Stored => SeekableReader::Stored(reader),
+            #[cfg(any(
+                feature = "deflate",
+                feature = "deflate-zlib",
+                feature = "deflate-zlib-ng"
+            ))]
+            CompressionMethod::Deflated => SeekableReader::Deflated(DeflateDecoder::new(reader)),
+            _ => {
+                return Err(ZipError::UnsupportedArchive(
+                    "Seeking is only supported for Stored and Deflated entries",
+                ))
+            }
+        };
+        Ok(SeekableZipFile {
+            reader: Some(reader),
+            data_start,
+            compressed_size,
+            size,
+            pos: 0,
+        })
+    }
+
+    /// Get the size of the file, in bytes, when uncompressed
+    pub const fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Unwrap and return the inner reader object
+    ///
+    /// The position of the reader is undefined.
+    pub fn into_inner(mut self) -> R {
+        self.reader.take().unwrap().into_inner()
+    }
+
+    fn reader(&mut self) -> io::Result<&mut SeekableReader<R>> {
+        self.reader.as_mut().ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::Other,
+                "A previous seek failed, so the reader is unusable",
+            )
+        })
+    }
+
+    /// Move to `target` by repositioning the underlying reader. Only valid for Stored entries.
+    fn seek_stored(&mut self, target: u64) -> io::Result<()> {
+        let Some(SeekableReader::Stored(reader)) = self.reader.take() else {
+            unreachable!()
+        };
+        let mut inner = reader.into_inner();
+        let offset = target.min(self.compressed_size);
+        inner.seek(SeekFrom::Start(self.data_start + offset))?;
+        self.reader = Some(SeekableReader::Stored(
+            inner.take(self.compressed_size - offset),
+        ));
+        self.pos = target;
+        Ok(())
+    }
+
+    /// Restart decompression from the start of the entry.
+    #[cfg(any(
+        feature = "deflate",
+        feature = "deflate-zlib",
+        feature = "deflate-zlib-ng"
+    ))]
+    fn rewind_deflated(&mut self) -> io::Result<()> {
+        let mut inner = self.reader.take().unwrap().into_inner();
+        inner.seek(SeekFrom::Start(self.data_start))?;
+        self.reader = Some(SeekableReader::Deflated(DeflateDecoder::new(
+            inner.take(self.compressed_size),
+        )));
+        self.pos = 0;
+        Ok(())
+    }
+}
+
+impl<R: Read + Seek> Read for SeekableZipFile<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = match self.reader()? {
+            SeekableReader::Stored(reader) => reader.read(buf)?,
+            #[cfg(any(
+                feature = "deflate",
+                feature = "deflate-zlib",
+                feature = "deflate-zlib-ng"
+            ))]
+            SeekableReader::Deflated(reader) => reader.read(buf)?,
+        };
+        self.pos += count as u64;
+        Ok(count)
+    }
+}
+
+impl<R: Read + Seek> Seek for SeekableZipFile<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        }
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        match self.reader()? {
+            SeekableReader::Stored(_) => self.seek_stored(target)?,
+            #[cfg(any(
+                feature = "deflate",
+                feature = "deflate-zlib",
+                feature = "deflate-zlib-ng"
+            ))]
+            SeekableReader::Deflated(_) => {
+                if target < self.pos {
+                    self.rewind_deflated()?;
+                }
+                let skip = target.min(self.size).saturating_sub(self.pos);
+                let skipped = io::copy(&mut (&mut *self).take(skip), &mut io::sink())?;
+                if skipped < skip {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+                self.pos = target;
+            }
+        }
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::write::SimpleFileOptions;
+    use crate::{CompressionMethod, ZipWriter};
+    use std::io::{Cursor, Read, Seek, SeekFrom, Write};
+
+    fn check_seeks(method: CompressionMethod) {
+        let contents: Vec<u8> = (0..4u32 << 20)
+            .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+            .collect();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer
+            .start_file(
+                "file",
+                SimpleFileOptions::default()
+                    .compression_method(method)
+                    // Keep the test fast when zopfli would otherwise be used
+                    .compression_level((method != CompressionMethod::Stored).then_some(1)),
+            )
+            .unwrap();
+        writer.write_all(&contents).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+        let mut file = archive.by_name_seekable("file").unwrap();
+        assert_eq!(file.size(), contents.len() as u64);
+
+        let mut buf = [0u8; 1000];
+        for (pos, expected) in [
+            (SeekFrom::Start(3 << 20), 3 << 20),
+            (SeekFrom::Start(1 << 20), 1 << 20),
+            (SeekFrom::Current(12345), (1 << 20) + 1000 + 12345),
+            (
+                SeekFrom::Current(-5000),
+                (1 << 20) + 1000 + 12345 + 1000 - 5000,
+            ),
+            (SeekFrom::End(-1000), contents.len() - 1000),
+            (SeekFrom::Start(0), 0),
+        ] {
+            assert_eq!(file.seek(pos).unwrap(), expected as u64, "{method:?}");
+            file.read_exact(&mut buf).unwrap();
+            assert_eq!(buf, contents[expected..expected + 1000], "{method:?}");
+        }
+        assert!(file.seek(SeekFrom::Current(-2000)).is_err());
+
+        file.seek(SeekFrom::End(10)).unwrap();
+        assert_eq!(file.read(&mut buf).unwrap(), 0);
+        file.seek(SeekFrom::End(-10)).unwrap();
+        let mut tail = Vec::new();
+        file.read_to_end(&mut tail).unwrap();
+        assert_eq!(tail, contents[contents.len() - 10..]);
+    }
+
+    #[test]
+    fn seek_stored() {
+        check_seeks(CompressionMethod::Stored);
+    }
+
+    #[cfg(any(
+        feature = "deflate",
+        feature = "deflate-zlib",
+        feature = "deflate-zlib-ng"
+    ))]
+    #[test]
+    fn seek_deflated() {
+        check_seeks(CompressionMethod::Deflated);
+    }
+}
@@ -0,0 +1,281 @@
+//! Reading archives that are split across several files
+
+use crate::read::zip_archive::Shared;
+use crate::read::{central_header_to_zip_file_inner, DecompressionLimits, Strictness, ZipArchive};
+use crate::result::{ZipError, ZipResult};
+use crate::spec;
+use indexmap::IndexMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::OnceLock;
+
+/// The fixed-size part of a central directory record, including its signature
+const CENTRAL_HEADER_LEN: usize = 46;
+
+/// The segments of a split (multi-volume) archive, presented as one continuous stream
+///
+/// Tools such as Info-ZIP's `zip -s` write a split archive as `name.z01`, `name.z02`, … followed
+/// by `name.zip`, which holds the last segment and the central directory. Offsets in the archive
+/// are relative to the start of the segment they point into, so open the result with
+/// [`ZipArchive::new_spanned`] rather than [`ZipArchive::new`].
+///
+/// The first segment usually starts with the spanning marker `0x08074b50`. That's part of the
+/// archive's first disk as far as its offsets are concerned, so it's read through like any other
+/// data.
+#[derive(Debug)]
+pub struct SpannedReader<R> {
+    segments: Vec<R>,
+    /// Where each segment starts within the combined stream, followed by its total length
+    starts: Vec<u64>,
+    pos: u64,
+    current: usize,
+}
+
+impl<R: Read + Seek> SpannedReader<R> {
+    /// Combine the segments of a split archive, which must be given in order with the segment
+    /// holding the central directory last
+    pub fn new(mut segments: Vec<R>) -> io::Result<Self> {
+        if segments.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "a spanned archive needs at least one segment",
+            ));
+        }
+        let mut starts = Vec::with_capacity(segments.len() + 1);
+        let mut total = 0u64;
+        for segment in segments.iter_mut() {
+            starts.push(total);
+            total += segment.seek(SeekFrom::End(0))?;
+            segment.rewind()?;
+        }
+        starts.push(total);
+        Ok(SpannedReader {
+            segments,
+            starts,
+            pos: 0,
+            current: 0,
+        })
+    }
+
+    /// Unwrap and return the segment readers
+    pub fn into_inner(self) -> Vec<R> {
+        self.segments
+    }
+}
+
+impl SpannedReader<File> {
+    /// Open the segments of a split archive, given the path of its last segment
+    ///
+    /// For `dir/name.zip`, this opens `dir/name.z01`, `dir/name.z02` and so on for as long as they
+    /// exist, followed by `dir/name.zip` itself.
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let path = path.as_ref();
+        let mut segments = Vec::new();
+        for number in 1.. {
+            let segment_path = path.with_extension(format!("z{number:02}"));
+            if !segment_path.is_file() {
+                break;
+            }
+            segments.push(File::open(segment_path)?);
+        }
+        segments.push(File::open(path)?);
+        Self::new(segments)
+    }
+}
+
+impl<R> SpannedReader<R> {
+    /// Where segment (disk) number `disk` starts within the combined stream
+    pub(crate) fn disk_start(&self, disk: usize) -> Option<u64> {
+        self.starts[..self.segments.len()].get(disk).copied()
+    }
+
+    fn disk_count(&self) -> usize {
+        self.segments.len()
+    }
+
+    fn len(&self) -> u64 {
+        *self.starts.last().unwrap()
+    }
+}
+
+impl<R: Read + Seek> Read for SpannedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.current < self.segments.len() {
+            let count = self.segments[self.current].read(buf)?;
+            if count > 0 || buf.is_empty() {
+                self.pos += count as u64;
+                return Ok(count);
+            }
+            // This segment is used up, so carry on from the start of the next one
+            self.current += 1;
+            if let Some(segment) = self.segments.get_mut(self.current) {
+                segment.rewind()?;
+            }
+        }
+        Ok(0)
+    }
+}
+
+impl<R: Read + Seek> Seek for SpannedReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.len().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        }
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        // The last segment that starts at or before the target; a position past the end is
+        // treated as a position past the end of the last segment.
+        let current = self.starts[1..self.segments.len()].partition_point(|&start| start <= target);
+        self.segments[current].seek(SeekFrom::Start(target - self.starts[current]))?;
+        self.current = current;
+        self.pos = target;
+        Ok(target)
+    }
+}
+
+impl<S: Read + Seek> ZipArchive<SpannedReader<S>> {
+    /// Read a ZIP archive that's split across several segments
+    ///
+    /// Local header offsets are resolved against the segment that each central directory record
+    /// names, and the central directory may itself start in an earlier segment than the one
+    /// holding the end-of-central-directory record. Split archives that need ZIP64 records aren't
+    /// supported yet.
+    pub fn new_spanned(mut reader: SpannedReader<S>) -> ZipResult<Self> {
+        let (footer, cde_start_pos) = spec::CentralDirectoryEnd::find_and_parse(&mut reader)?;
+        if footer.disk_number == u16::MAX
+            || footer.disk_with_central_directory == u16::MAX
+            || footer.number_of_files == u16::MAX
+            || footer.central_directory_offset == u32::MAX
+            || footer.central_directory_size == u32::MAX
+        {
+            return Err(ZipError::UnsupportedArchive(
+                "ZIP64 is not supported for spanned archives",
+            ));
+        }
+        if footer.disk_number as usize + 1 != reader.disk_count() {
+            return Err(ZipError::InvalidArchive(
+                "Number of segments doesn't match the number of disks in the archive",
+            ));
+        }
+        let dir_start = reader
+            .disk_start(footer.disk_with_central_directory as usize)
+            .ok_or(ZipError::InvalidArchive(
+                "Central directory starts on a disk that doesn't exist",
+            ))?
+            .checked_add(footer.central_directory_offset as u64)
+            .filter(|&dir_start| {
+                dir_start
+                    .checked_add(footer.central_directory_size as u64)
+                    .is_some_and(|dir_end| dir_end <= cde_start_pos)
+            })
+            .ok_or(ZipError::InvalidArchive(
+                "Invalid central directory size or offset",
+            ))?;
+
+        let mut files = IndexMap::with_capacity(footer.number_of_files as usize);
+        reader.seek(SeekFrom::Start(dir_start))?;
+        for _ in 0..footer.number_of_files {
+            let central_header_start = reader.stream_position()?;
+            let mut record = vec![0; CENTRAL_HEADER_LEN];
+            reader.read_exact(&mut record)?;
+            if record[..4] != spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE.to_le_bytes() {
+                return Err(ZipError::InvalidArchive("Invalid Central Directory header"));
+            }
+            let variable_len: usize = [28, 30, 32]
+                .iter()
+                .map(|&at| u16::from_le_bytes([record[at], record[at + 1]]) as usize)
+                .sum();
+            let disk = u16::from_le_bytes([record[34], record[35]]);
+            if disk == u16::MAX {
+                return Err(ZipError::UnsupportedArchive(
+                    "ZIP64 is not supported for spanned archives",
+                ));
+            }
+            let disk_start = reader
+                .disk_start(disk as usize)
+                .ok_or(ZipError::InvalidArchive(
+                    "File starts on a disk that doesn't exist",
+                ))?;
+            record.resize(CENTRAL_HEADER_LEN + variable_len, 0);
+            reader.read_exact(&mut record[CENTRAL_HEADER_LEN..])?;
+            let file = central_header_to_zip_file_inner(
+                &mut &record[4..],
+                disk_start,
+                central_header_start,
+            )?;
+            files.insert(file.file_name.clone(), file);
+        }
+
+        let trailing_data_len =
+            ZipArchive::measure_trailing_data(&mut reader, &footer, cde_start_pos)?;
+        let shared = Shared {
+            files,
+            offset: 0,
+            dir_start,
+            pending: None,
+            lowercase_names: OnceLock::new(),
+            zip64: false,
+            total_entries: footer.number_of_files as u64,
+            trailing_data_len,
+        };
+        Ok(ZipArchive {
+            reader,
+            shared: shared.into(),
+            comment: footer.zip_file_comment.into(),
+            limits: DecompressionLimits::default(),
+            verify_crc: true,
+            aes_iterations: None,
+            strictness: Strictness::Lenient,
+            decompressors: Default::default(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SpannedReader;
+    use std::io::{Cursor, Read, Seek, SeekFrom};
+
+    #[test]
+    fn read_and_seek_across_segments() {
+        let segments = vec![
+            Cursor::new(b"abc".to_vec()),
+            Cursor::new(Vec::new()),
+            Cursor::new(b"defg".to_vec()),
+            Cursor::new(b"h".to_vec()),
+        ];
+        let mut reader = SpannedReader::new(segments).unwrap();
+        let mut all = String::new();
+        reader.read_to_string(&mut all).unwrap();
+        assert_eq!(all, "abcdefgh");
+
+        let mut buf = [0u8; 4];
+        assert_eq!(reader.seek(SeekFrom::Start(2)).unwrap(), 2);
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"cdef");
+        assert_eq!(reader.seek(SeekFrom::End(-5)).unwrap(), 3);
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"defg");
+        assert_eq!(reader.seek(SeekFrom::Current(-1)).unwrap(), 6);
+        reader.read_exact(&mut buf[..2]).unwrap();
+        assert_eq!(&buf[..2], b"gh");
+        assert_eq!(reader.seek(SeekFrom::End(3)).unwrap(), 11);
+        assert_eq!(reader.read(&mut buf).unwrap(), 0);
+        assert!(reader.seek(SeekFrom::Current(-12)).is_err());
+
+        assert_eq!(reader.disk_start(2), Some(3));
+        assert_eq!(reader.disk_start(4), None);
+    }
+
+    #[test]
+    fn no_segments() {
+        assert!(SpannedReader::<Cursor<Vec<u8>>>::new(Vec::new()).is_err());
+    }
+}
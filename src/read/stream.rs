@@ -4,34 +4,67 @@ use std::io::{self, Read};
 use std::path::{Path, PathBuf};
 
 use super::{
-    central_header_to_zip_file_inner, read_zipfile_from_stream, spec, ZipError, ZipFile,
-    ZipFileData, ZipResult,
+    central_header_to_zip_file_inner, read_zipfile_from_stream_inner, spec, ZipError, ZipFile,
+    ZipFileData, ZipResult, MAX_BUFFERED_ENTRY_SIZE,
 };
 
 /// Stream decoder for zip.
+///
+/// The reader is wrapped in a [`BufReader`](io::BufReader) while it's read, since finding the end
+/// of a file whose sizes are in a data descriptor takes many small reads.
 #[derive(Debug)]
-pub struct ZipStreamReader<R>(R);
+pub struct ZipStreamReader<R> {
+    reader: R,
+    password: Option<Box<[u8]>>,
+    max_buffered_entry_size: u64,
+}
 
 impl<R> ZipStreamReader<R> {
     /// Create a new ZipStreamReader
     pub const fn new(reader: R) -> Self {
-        Self(reader)
+        Self {
+            reader,
+            password: None,
+            max_buffered_entry_size: MAX_BUFFERED_ENTRY_SIZE,
+        }
+    }
+
+    /// Set the most data that a file whose sizes are in a data descriptor can have
+    ///
+    /// Such a file is read into memory before it's visited, since the end of its data can only
+    /// be found by searching for the descriptor; see
+    /// [`read_zipfile_from_stream`](crate::read::read_zipfile_from_stream). Reaching one with
+    /// more data fails with [`ZipError::InvalidArchive`]. The default is 1 GiB.
+    pub fn max_buffered_entry_size(mut self, max_size: u64) -> Self {
+        self.max_buffered_entry_size = max_size;
+        self
+    }
+
+    /// Decrypt ZipCrypto and AES encrypted files with `password` as they're read
+    ///
+    /// Without a password, reaching an encrypted file fails with
+    /// [`ZipError::UnsupportedArchive`]. See
+    /// [`read_zipfile_from_stream_with_password`](crate::read::read_zipfile_from_stream_with_password)
+    /// for how decryption works on a stream.
+    pub fn with_password(mut self, password: &[u8]) -> Self {
+        self.password = Some(password.into());
+        self
     }
 }
 
 impl<R: Read> ZipStreamReader<R> {
-    fn parse_central_directory(&mut self) -> ZipResult<Option<ZipStreamFileMetadata>> {
+    fn parse_central_directory(reader: &mut impl Read) -> ZipResult<Option<ZipStreamFileMetadata>> {
         // Give archive_offset and central_header_start dummy value 0, since
         // they are not used in the output.
         let archive_offset = 0;
         let central_header_start = 0;
 
         // Parse central header
-        let signature = self.0.read_u32_le()?;
+        let signature = reader.read_u32_le()?;
         if signature != spec::CENTRAL_DIRECTORY_HEADER_SIGNATURE {
             Ok(None)
         } else {
-            central_header_to_zip_file_inner(&mut self.0, archive_offset, central_header_start)
+            central_header_to_zip_file_inner(reader, archive_offset, central_header_start)
                 .map(ZipStreamFileMetadata)
                 .map(Some)
         }
@@ -39,12 +72,17 @@ impl<R: Read> ZipStreamReader<R> {
 
     /// Iteraate over the stream and extract all file and their
     /// metadata.
-    pub fn visit<V: ZipStreamVisitor>(mut self, visitor: &mut V) -> ZipResult<()> {
-        while let Some(mut file) = read_zipfile_from_stream(&mut self.0)? {
+    pub fn visit<V: ZipStreamVisitor>(self, visitor: &mut V) -> ZipResult<()> {
+        let mut reader = io::BufReader::new(self.reader);
+        while let Some(mut file) = read_zipfile_from_stream_inner(
+            &mut reader,
+            self.password.as_deref(),
+            self.max_buffered_entry_size,
+        )? {
             visitor.visit_file(&mut file)?;
         }
 
-        while let Some(metadata) = self.parse_central_directory()? {
+        while let Some(metadata) = Self::parse_central_directory(&mut reader)? {
             visitor.visit_additional_metadata(&metadata)?;
         }
 
@@ -364,4 +402,135 @@ mod test {
         .visit(&mut DummyVisitor)
         .unwrap_err();
     }
+
+    #[cfg(feature = "aes-crypto")]
+    #[test]
+    fn stream_encrypted() -> ZipResult<()> {
+        use crate::write::SimpleFileOptions;
+        use crate::{AesMode, CompressionMethod, ZipWriter};
+        use std::io::Write;
+
+        let long = "Long enough to be encrypted with AE-1 ".repeat(10);
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.start_file("plain", options)?;
+        writer.write_all(b"plain")?;
+        writer.start_file("zipcrypto", options.with_deprecated_encryption(b"password"))?;
+        writer.write_all(long.as_bytes())?;
+        writer.start_file(
+            "ae1",
+            options.with_aes_encryption(AesMode::Aes256, "password"),
+        )?;
+        writer.write_all(long.as_bytes())?;
+        writer.start_file(
+            "ae2",
+            options.with_aes_encryption(AesMode::Aes128, "password"),
+        )?;
+        writer.write_all(b"short")?;
+        let bytes = writer.finish()?.into_inner();
+
+        #[derive(Default)]
+        struct V(Vec<(String, Vec<u8>)>);
+        impl ZipStreamVisitor for V {
+            fn visit_file(&mut self, file: &mut ZipFile<'_>) -> ZipResult<()> {
+                let mut contents = Vec::new();
+                file.read_to_end(&mut contents)?;
+                self.0.push((file.name().into(), contents));
+                Ok(())
+            }
+            fn visit_additional_metadata(&mut self, _: &ZipStreamFileMetadata) -> ZipResult<()> {
+                Ok(())
+            }
+        }
+
+        // A byte slice can only be read forwards
+        let mut visitor = V::default();
+        ZipStreamReader::new(&bytes[..])
+            .with_password(b"password")
+            .visit(&mut visitor)?;
+        let expected: Vec<(String, Vec<u8>)> = [
+            ("plain", &b"plain"[..]),
+            ("zipcrypto", long.as_bytes()),
+            ("ae1", long.as_bytes()),
+            ("ae2", b"short"),
+        ]
+        .into_iter()
+        .map(|(name, contents)| (name.into(), contents.into()))
+        .collect();
+        assert_eq!(visitor.0, expected);
+
+        assert!(matches!(
+            ZipStreamReader::new(&bytes[..]).visit(&mut V::default()),
+            Err(ZipError::PasswordRequired)
+        ));
+        assert!(matches!(
+            ZipStreamReader::new(&bytes[..])
+                .with_password(b"wrong")
+                .visit(&mut V::default()),
+            Err(ZipError::InvalidPassword)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn large_entry_with_data_descriptor() -> ZipResult<()> {
+        use crate::write::SimpleFileOptions;
+        use crate::{CompressionMethod, ZipWriter};
+        use std::io::Write;
+
+        /// Counts how many times the stream itself is read
+        struct CountingReader<'a>(&'a [u8], usize);
+        impl Read for CountingReader<'_> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                self.1 += 1;
+                self.0.read(buf)
+            }
+        }
+
+        let contents: Vec<u8> = (0..3 << 20).map(|i: u32| (i % 251) as u8).collect();
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Stored)
+            .with_data_descriptor(true);
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.start_file("large", options)?;
+        writer.write_all(&contents)?;
+        writer.start_file("after", options)?;
+        writer.write_all(b"after")?;
+        let bytes = writer.finish()?.into_inner();
+
+        #[derive(Default)]
+        struct V(Vec<(String, Vec<u8>)>);
+        impl ZipStreamVisitor for V {
+            fn visit_file(&mut self, file: &mut ZipFile<'_>) -> ZipResult<()> {
+                let mut contents = Vec::new();
+                file.read_to_end(&mut contents)?;
+                self.0.push((file.name().into(), contents));
+                Ok(())
+            }
+            fn visit_additional_metadata(&mut self, _: &ZipStreamFileMetadata) -> ZipResult<()> {
+                Ok(())
+            }
+        }
+
+        let mut visitor = V::default();
+        let mut stream = CountingReader(&bytes, 0);
+        ZipStreamReader::new(&mut stream).visit(&mut visitor)?;
+        assert_eq!(
+            visitor.0,
+            [
+                ("large".to_owned(), contents),
+                ("after".to_owned(), b"after".to_vec())
+            ]
+        );
+        // The descriptor is searched for a few bytes at a time, but the stream is read in blocks
+        assert!(stream.1 < bytes.len() / 1000);
+
+        assert!(matches!(
+            ZipStreamReader::new(&bytes[..])
+                .max_buffered_entry_size(1 << 20)
+                .visit(&mut V::default()),
+            Err(ZipError::InvalidArchive(_))
+        ));
+        Ok(())
+    }
 }
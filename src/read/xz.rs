@@ -0,0 +1,45 @@
+//! Reading XZ (method 95) entries
+
+use std::io::{Error, ErrorKind, Read, Result};
+
+/// Decodes an entry that holds a whole `.xz` stream, and checks that it's as long as the ZIP
+/// metadata says
+///
+/// The stream's own integrity check is verified as well as the entry's CRC-32, if it has one.
+pub struct XzDecoder<R: Read> {
+    inner: xz2::read::XzDecoder<R>,
+    remaining: u64,
+}
+
+impl<R: Read> XzDecoder<R> {
+    pub fn new(inner: R, uncompressed_size: u64) -> Self {
+        XzDecoder {
+            inner: xz2::read::XzDecoder::new(inner),
+            remaining: uncompressed_size,
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner.into_inner()
+    }
+}
+
+impl<R: Read> Read for XzDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let count = self.inner.read(buf)?;
+        if count as u64 > self.remaining {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "XZ data is longer than the file's uncompressed size",
+            ));
+        }
+        if count == 0 && self.remaining > 0 && !buf.is_empty() {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "XZ data is shorter than the file's uncompressed size",
+            ));
+        }
+        self.remaining -= count as u64;
+        Ok(count)
+    }
+}
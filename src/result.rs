@@ -2,6 +2,7 @@
 #![allow(non_local_definitions)]
 //! Error types that can be emitted from this library
 
+use crate::extra_fields::StrongEncryptionAlgorithm;
 use displaydoc::Display;
 use thiserror::Error;
 
@@ -26,25 +27,45 @@ pub enum ZipError {
     /// unsupported Zip archive: {0}
     UnsupportedArchive(&'static str),
 
+    /// unsupported encryption: PKWARE strong encryption with {0}
+    UnsupportedEncryption(StrongEncryptionAlgorithm),
+
     /// specified file not found in archive
     FileNotFound,
 
     /// The password provided is incorrect
     InvalidPassword,
+
+    /// a password is required to decrypt this file
+    PasswordRequired,
+
+    /// entry would be extracted outside the target directory: {0}
+    InvalidPath(Box<str>),
+
+    /// an entry with this name already exists: {0}
+    DuplicateName(Box<str>),
+
+    /// local header doesn't match the central directory: {0} differs
+    InconsistentMetadata(&'static str),
+
+    /// some entries couldn't be extracted
+    ExtractionFailed(Vec<(String, ZipError)>),
+
+    /// Invalid checksum: expected {expected:#010x}, found {actual:#010x}
+    InvalidChecksum {
+        /// The CRC-32 stored in the archive
+        expected: u32,
+        /// The CRC-32 of the data that was actually read
+        actual: u32,
+    },
 }
 
 impl ZipError {
-    /// The text used as an error when a password is required and not supplied
+    /// The text that was used as an error when a password is required and not supplied, in a
+    /// [`ZipError::UnsupportedArchive`]
     ///
-    /// ```rust,no_run
-    /// # use zip::result::ZipError;
-    /// # let mut archive = zip::ZipArchive::new(std::io::Cursor::new(&[])).unwrap();
-    /// match archive.by_index(1) {
-    ///     Err(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED)) => eprintln!("a password is needed to unzip this file"),
-    ///     _ => (),
-    /// }
-    /// # ()
-    /// ```
+    /// A missing password is now reported as [`ZipError::PasswordRequired`] instead.
+    #[deprecated(note = "a missing password is reported as `ZipError::PasswordRequired`")]
     pub const PASSWORD_REQUIRED: &'static str = "Password required to decrypt file";
 }
 
@@ -54,8 +75,15 @@ impl From<ZipError> for io::Error {
             ZipError::Io(err) => err.kind(),
             ZipError::InvalidArchive(_) => io::ErrorKind::InvalidData,
             ZipError::UnsupportedArchive(_) => io::ErrorKind::Unsupported,
+            ZipError::UnsupportedEncryption(_) => io::ErrorKind::Unsupported,
             ZipError::FileNotFound => io::ErrorKind::NotFound,
             ZipError::InvalidPassword => io::ErrorKind::InvalidInput,
+            ZipError::PasswordRequired => io::ErrorKind::PermissionDenied,
+            ZipError::InvalidPath(_) => io::ErrorKind::InvalidData,
+            ZipError::DuplicateName(_) => io::ErrorKind::AlreadyExists,
+            ZipError::InconsistentMetadata(_) => io::ErrorKind::InvalidData,
+            ZipError::InvalidChecksum { .. } => io::ErrorKind::InvalidData,
+            ZipError::ExtractionFailed(_) => io::ErrorKind::Other,
         };
 
         io::Error::new(kind, err)
@@ -11,10 +11,16 @@ pub const CENTRAL_DIRECTORY_HEADER_SIGNATURE: u32 = 0x02014b50;
 pub(crate) const CENTRAL_DIRECTORY_END_SIGNATURE: u32 = 0x06054b50;
 pub const ZIP64_CENTRAL_DIRECTORY_END_SIGNATURE: u32 = 0x06064b50;
 pub(crate) const ZIP64_CENTRAL_DIRECTORY_END_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+pub(crate) const DATA_DESCRIPTOR_SIGNATURE: u32 = 0x08074b50;
+pub(crate) const ARCHIVE_EXTRA_DATA_SIGNATURE: u32 = 0x08064b50;
 
 pub const ZIP64_BYTES_THR: u64 = u32::MAX as u64;
 pub const ZIP64_ENTRY_THR: usize = u16::MAX as usize;
 
+/// How far back from the end of the file [`CentralDirectoryEnd::find_and_parse`] will search:
+/// the size of an end-of-central-directory record with the longest possible comment
+pub(crate) const MAX_HEADER_AND_COMMENT_SIZE: u64 = 22 + u16::MAX as u64;
+
 pub struct CentralDirectoryEnd {
     pub disk_number: u16,
     pub disk_with_central_directory: u16,
@@ -26,6 +32,9 @@ pub struct CentralDirectoryEnd {
 }
 
 impl CentralDirectoryEnd {
+    /// The size of the record without its comment
+    const HEADER_SIZE: u64 = 22;
+
     pub fn parse<T: Read>(reader: &mut T) -> ZipResult<CentralDirectoryEnd> {
         let magic = reader.read_u32_le()?;
         if magic != CENTRAL_DIRECTORY_END_SIGNATURE {
@@ -53,18 +62,49 @@ impl CentralDirectoryEnd {
     }
 
     pub fn find_and_parse<T: Read + Seek>(reader: &mut T) -> ZipResult<(CentralDirectoryEnd, u64)> {
-        const HEADER_SIZE: u64 = 22;
-        const MAX_HEADER_AND_COMMENT_SIZE: u64 = 66000;
-        const BYTES_BETWEEN_MAGIC_AND_COMMENT_SIZE: u64 = HEADER_SIZE - 6;
+        Self::find_and_parse_within(reader, MAX_HEADER_AND_COMMENT_SIZE)
+    }
+
+    /// Like [`CentralDirectoryEnd::find_and_parse`], but only look for a record that starts
+    /// within the last `search_limit` bytes of the file
+    pub fn find_and_parse_within<T: Read + Seek>(
+        reader: &mut T,
+        search_limit: u64,
+    ) -> ZipResult<(CentralDirectoryEnd, u64)> {
         let file_length = reader.seek(io::SeekFrom::End(0))?;
 
-        let search_upper_bound = file_length.saturating_sub(MAX_HEADER_AND_COMMENT_SIZE);
+        let search_upper_bound = file_length.saturating_sub(search_limit);
 
-        if file_length < HEADER_SIZE {
-            return Err(ZipError::InvalidArchive("Invalid zip header"));
+        if file_length == 0 {
+            return Err(ZipError::InvalidArchive(
+                "File is empty, so it isn't a ZIP archive",
+            ));
+        }
+        if file_length < Self::HEADER_SIZE {
+            return Err(ZipError::InvalidArchive(
+                "File is too short to be a ZIP archive",
+            ));
         }
 
-        let mut pos = file_length - HEADER_SIZE;
+        Self::find_and_parse_before(
+            reader,
+            file_length - Self::HEADER_SIZE + 1,
+            search_upper_bound,
+        )?
+        .ok_or(ZipError::InvalidArchive("EOCD not found"))
+    }
+
+    /// Look backwards for the next record that starts before `end` and no earlier than
+    /// `search_upper_bound`, for when a later one turned out not to belong to the archive
+    pub fn find_and_parse_before<T: Read + Seek>(
+        reader: &mut T,
+        end: u64,
+        search_upper_bound: u64,
+    ) -> ZipResult<Option<(CentralDirectoryEnd, u64)>> {
+        const BYTES_BETWEEN_MAGIC_AND_COMMENT_SIZE: u64 = CentralDirectoryEnd::HEADER_SIZE - 6;
+        let Some(mut pos) = end.checked_sub(1) else {
+            return Ok(None);
+        };
         while pos >= search_upper_bound {
             let mut have_signature = false;
             reader.seek(io::SeekFrom::Start(pos))?;
@@ -75,7 +115,7 @@ impl CentralDirectoryEnd {
                 ))?;
                 let cde_start_pos = reader.seek(io::SeekFrom::Start(pos))?;
                 if let Ok(end_header) = CentralDirectoryEnd::parse(reader) {
-                    return Ok((end_header, cde_start_pos));
+                    return Ok(Some((end_header, cde_start_pos)));
                 }
             }
             pos = match pos.checked_sub(if have_signature {
@@ -87,9 +127,12 @@ impl CentralDirectoryEnd {
                 None => break,
             };
         }
-        Err(ZipError::InvalidArchive(
-            "Could not find central directory end",
-        ))
+        Ok(None)
+    }
+
+    /// How many bytes the record takes up, including the comment
+    pub fn record_len(&self) -> u64 {
+        Self::HEADER_SIZE + self.zip_file_comment.len() as u64
     }
 
     pub fn write<T: Write>(&self, writer: &mut T) -> ZipResult<()> {
@@ -227,8 +270,38 @@ impl Zip64CentralDirectoryEnd {
     }
 }
 
+/// Converts `\\` separators to `/`, and removes a Windows drive prefix such as `C:` and any
+/// separators at the start, since ZIP entry names are relative and always use `/`.
+pub(crate) fn normalize_separators(name: &str) -> Cow<'_, str> {
+    let bytes = name.as_bytes();
+    let without_drive = if bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':' {
+        &name[2..]
+    } else {
+        name
+    };
+    let relative = without_drive.trim_start_matches(['/', '\\']);
+    if relative.contains('\\') {
+        Cow::Owned(relative.replace('\\', "/"))
+    } else if relative.len() < name.len() {
+        Cow::Owned(relative.to_owned())
+    } else {
+        Cow::Borrowed(name)
+    }
+}
+
 /// Converts a path to the ZIP format (forward-slash-delimited and normalized).
-pub(crate) fn path_to_string<T: AsRef<Path>>(path: T) -> Box<str> {
+///
+/// With `normalize_separators`, a Windows-style path is normalized the same way on every
+/// platform, so `\\` is treated as a separator and any drive prefix is removed even where
+/// they're valid in file names.
+pub(crate) fn path_to_string<T: AsRef<Path>>(path: T, normalize_separators: bool) -> Box<str> {
+    if normalize_separators && MAIN_SEPARATOR != '\\' {
+        if let Some(original) = path.as_ref().to_str() {
+            if let Cow::Owned(normalized) = self::normalize_separators(original) {
+                return path_to_string(normalized, false);
+            }
+        }
+    }
     let mut maybe_original = None;
     if let Some(original) = path.as_ref().to_str() {
         if (MAIN_SEPARATOR == '/' || !original[1..].contains(MAIN_SEPARATOR))
@@ -9,30 +9,91 @@ use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
 use {crate::read::ZipFile, crate::write::FileOptions};
 
 pub(crate) mod ffi {
+    pub const S_IFMT: u32 = 0o0170000;
     pub const S_IFDIR: u32 = 0o0040000;
     pub const S_IFREG: u32 = 0o0100000;
+    pub const S_IFLNK: u32 = 0o0120000;
 }
 
-use crate::extra_fields::ExtraField;
+use crate::cp437::{to_cp437, FromCp437};
+use crate::extra_fields::{ExtraField, StrongEncryptionAlgorithm};
 use crate::result::DateTimeRangeError;
 use crate::types::ffi::S_IFDIR;
 use crate::CompressionMethod;
 #[cfg(feature = "time")]
 use time::{error::ComponentRange, Date, Month, OffsetDateTime, PrimitiveDateTime, Time};
 
+/// The host system that made a file, from the upper byte of its "version made by" field
+///
+/// This decides how the file's external attributes are interpreted (APPNOTE 4.4.2.2).
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u8)]
+#[non_exhaustive]
 pub enum System {
+    /// MS-DOS and OS/2 (FAT, VFAT and FAT32 file systems)
     Dos = 0,
+    /// Amiga
+    Amiga = 1,
+    /// OpenVMS
+    OpenVms = 2,
+    /// Unix
     Unix = 3,
-    Unknown,
+    /// Atari ST
+    AtariSt = 5,
+    /// OS/2 HPFS
+    Os2 = 6,
+    /// Classic Macintosh
+    Macintosh = 7,
+    /// Z-System
+    ZSystem = 8,
+    /// CP/M
+    Cpm = 9,
+    /// Windows NTFS
+    Ntfs = 10,
+    /// MVS (OS/390 and z/OS)
+    Mvs = 11,
+    /// VSE
+    Vse = 12,
+    /// Acorn RISC OS
+    AcornRisc = 13,
+    /// VFAT
+    Vfat = 14,
+    /// Alternate MVS
+    AlternateMvs = 15,
+    /// BeOS
+    BeOs = 16,
+    /// Tandem
+    Tandem = 17,
+    /// OS/400
+    Os400 = 18,
+    /// macOS
+    Osx = 19,
+    /// Any other host, including VM/CMS, whose number has always stood for an unknown host here
+    Unknown = 4,
 }
 
 impl From<u8> for System {
     fn from(system: u8) -> Self {
         match system {
             0 => Self::Dos,
+            1 => Self::Amiga,
+            2 => Self::OpenVms,
             3 => Self::Unix,
+            5 => Self::AtariSt,
+            6 => Self::Os2,
+            7 => Self::Macintosh,
+            8 => Self::ZSystem,
+            9 => Self::Cpm,
+            10 => Self::Ntfs,
+            11 => Self::Mvs,
+            12 => Self::Vse,
+            13 => Self::AcornRisc,
+            14 => Self::Vfat,
+            15 => Self::AlternateMvs,
+            16 => Self::BeOs,
+            17 => Self::Tandem,
+            18 => Self::Os400,
+            19 => Self::Osx,
             _ => Self::Unknown,
         }
     }
@@ -40,11 +101,7 @@ impl From<u8> for System {
 
 impl From<System> for u8 {
     fn from(system: System) -> Self {
-        match system {
-            System::Dos => 0,
-            System::Unix => 3,
-            System::Unknown => 4,
-        }
+        system as u8
     }
 }
 
@@ -135,6 +192,10 @@ impl Default for DateTime {
 
 impl DateTime {
     /// Converts an msdos (u16, u16) pair to a DateTime object
+    ///
+    /// The fields are taken as they are, so the result may not be [valid](DateTime::is_valid):
+    /// real archives often have a month or day of 0. Use [`DateTime::try_from_msdos`] to reject
+    /// those.
     pub const fn from_msdos(datepart: u16, timepart: u16) -> DateTime {
         let seconds = (timepart & 0b0000000000011111) << 1;
         let minutes = (timepart & 0b0000011111100000) >> 5;
@@ -153,6 +214,18 @@ impl DateTime {
         }
     }
 
+    /// Converts an msdos (u16, u16) pair to a DateTime object like [`DateTime::from_msdos`], but
+    /// returns `Err` if the result isn't [valid](DateTime::is_valid), such as for a month or day
+    /// of 0 or an hour of 24 or more.
+    pub fn try_from_msdos(datepart: u16, timepart: u16) -> Result<DateTime, DateTimeRangeError> {
+        let datetime = DateTime::from_msdos(datepart, timepart);
+        if datetime.is_valid() {
+            Ok(datetime)
+        } else {
+            Err(DateTimeRangeError)
+        }
+    }
+
     /// Constructs a DateTime from a specific date and time
     ///
     /// The bounds are:
@@ -224,6 +297,9 @@ impl DateTime {
 
     #[cfg(feature = "time")]
     /// Converts the DateTime to a OffsetDateTime structure
+    ///
+    /// The result is at UTC, with whole-second precision. Returns `Err` if the fields don't form
+    /// a valid date and time, as can happen for a [`DateTime`] read from a zip file.
     pub fn to_time(&self) -> Result<OffsetDateTime, ComponentRange> {
         let date =
             Date::from_calendar_date(self.year as i32, Month::try_from(self.month)?, self.day)?;
@@ -231,6 +307,30 @@ impl DateTime {
         Ok(PrimitiveDateTime::new(date, time).assume_utc())
     }
 
+    /// Interprets this date and time as UTC and converts it to a [`SystemTime`](std::time::SystemTime).
+    ///
+    /// Returns `None` if this isn't a valid date and time, as can happen for a [`DateTime`] read
+    /// from a zip file.
+    pub fn to_system_time(self) -> Option<std::time::SystemTime> {
+        if !self.is_valid() || self.day > days_in_month(self.year, self.month) {
+            return None;
+        }
+        // Days since 1970-01-01 (after Howard Hinnant's `days_from_civil`)
+        let (year, month) = if self.month <= 2 {
+            (self.year as u64 - 1, self.month as u64 + 9)
+        } else {
+            (self.year as u64, self.month as u64 - 3)
+        };
+        let era = year / 400;
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * month + 2) / 5 + self.day as u64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        let days = era * 146097 + day_of_era - 719468;
+        let seconds =
+            days * 86400 + self.hour as u64 * 3600 + self.minute as u64 * 60 + self.second as u64;
+        Some(std::time::UNIX_EPOCH + std::time::Duration::from_secs(seconds))
+    }
+
     /// Get the year. There is no epoch, i.e. 2018 will be returned as 2018.
     pub const fn year(&self) -> u16 {
         self.year
@@ -282,6 +382,20 @@ impl DateTime {
     }
 }
 
+const fn days_in_month(year: u16, month: u8) -> u8 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Takes the date and time as they appear at `dt`'s own offset, dropping any fraction of a second
+///
+/// Returns [`DateTimeRangeError`] for years before 1980 or after 2107, which the MS-DOS format can't
+/// represent. Note that the MS-DOS format only stores even seconds, so an odd second is rounded down
+/// when the [`DateTime`] is written to a zip file.
 #[cfg(feature = "time")]
 impl TryFrom<OffsetDateTime> for DateTime {
     type Error = DateTimeRangeError;
@@ -302,8 +416,105 @@ impl TryFrom<OffsetDateTime> for DateTime {
     }
 }
 
+/// Converts via the Unix epoch, taking the time as UTC and dropping any fraction of a second
+///
+/// Returns [`DateTimeRangeError`] for times before 1980 or after 2107, which the MS-DOS format
+/// can't represent.
+impl TryFrom<std::time::SystemTime> for DateTime {
+    type Error = DateTimeRangeError;
+
+    fn try_from(time: std::time::SystemTime) -> Result<Self, Self::Error> {
+        let seconds = time
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_err(|_| DateTimeRangeError)?
+            .as_secs();
+        // Date from days since 1970-01-01 (after Howard Hinnant's `civil_from_days`)
+        let days = seconds / 86400 + 719468;
+        let era = days / 146097;
+        let day_of_era = days - era * 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+        let month = if shifted_month < 10 {
+            shifted_month + 3
+        } else {
+            shifted_month - 9
+        };
+        let year = year_of_era + era * 400 + u64::from(month <= 2);
+        let second_of_day = seconds % 86400;
+        DateTime::from_date_and_time(
+            year.try_into()?,
+            month as u8,
+            day as u8,
+            (second_of_day / 3600) as u8,
+            (second_of_day / 60 % 60) as u8,
+            (second_of_day % 60) as u8,
+        )
+    }
+}
+
 pub const DEFAULT_VERSION: u8 = 46;
 
+/// The character encoding of an entry's name when the UTF-8 flag (bit 11 of the general-purpose
+/// flags) isn't set.
+///
+/// Used by [`ZipArchive::set_name_encoding`](crate::ZipArchive::set_name_encoding) to decode names
+/// and by [`FileOptions::with_name_encoding`] to encode them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NameEncoding {
+    /// UTF-8, with invalid sequences replaced by U+FFFD when decoding. Non-ASCII names written
+    /// this way have the UTF-8 flag set.
+    Utf8,
+    /// IBM codepage 437, which the ZIP specification prescribes and [`ZipArchive`](crate::ZipArchive)
+    /// uses by default.
+    Cp437,
+    /// Shift-JIS, which Japanese versions of Windows use.
+    #[cfg(feature = "encoding_rs")]
+    ShiftJis,
+    /// UTF-8 when the name is valid UTF-8, and codepage 437 otherwise. Writes the same as
+    /// [`NameEncoding::Utf8`].
+    Auto,
+}
+
+impl NameEncoding {
+    pub(crate) fn decode(self, raw: &[u8]) -> Box<str> {
+        match self {
+            NameEncoding::Utf8 => String::from_utf8_lossy(raw).into(),
+            NameEncoding::Cp437 => raw.from_cp437().into(),
+            #[cfg(feature = "encoding_rs")]
+            NameEncoding::ShiftJis => encoding_rs::SHIFT_JIS
+                .decode_without_bom_handling(raw)
+                .0
+                .into(),
+            NameEncoding::Auto => match std::str::from_utf8(raw) {
+                Ok(name) => name.into(),
+                Err(_) => raw.from_cp437().into(),
+            },
+        }
+    }
+
+    /// Returns `None` if the name can't be represented in this encoding.
+    pub(crate) fn encode(self, name: &str) -> Option<Box<[u8]>> {
+        match self {
+            NameEncoding::Utf8 | NameEncoding::Auto => Some(name.as_bytes().into()),
+            NameEncoding::Cp437 => to_cp437(name),
+            #[cfg(feature = "encoding_rs")]
+            NameEncoding::ShiftJis => {
+                let (encoded, _, had_errors) = encoding_rs::SHIFT_JIS.encode(name);
+                (!had_errors).then(|| encoded.into())
+            }
+        }
+    }
+
+    /// Whether names written this way should have the UTF-8 flag set, if they're not ASCII.
+    pub(crate) const fn is_utf8(self) -> bool {
+        matches!(self, NameEncoding::Utf8 | NameEncoding::Auto)
+    }
+}
+
 /// Structure representing a ZIP file.
 #[derive(Debug, Clone)]
 pub struct ZipFileData {
@@ -315,8 +526,12 @@ pub struct ZipFileData {
     pub encrypted: bool,
     /// True if the file uses a data-descriptor section
     pub using_data_descriptor: bool,
+    /// True if the file name is UTF-8 rather than in a legacy encoding
+    pub is_utf8: bool,
     /// Compression method used to store the file
     pub compression_method: crate::compression::CompressionMethod,
+    /// Bits 1 and 2 of the general purpose flags, whose meaning depends on the compression method
+    pub compression_flags: u16,
     /// Compression level to store the file
     pub compression_level: Option<i64>,
     /// Last modified time. This will only have a 2 second precision.
@@ -337,6 +552,8 @@ pub struct ZipFileData {
     pub central_extra_field: Option<Arc<Vec<u8>>>,
     /// File comment
     pub file_comment: Box<str>,
+    /// Raw file comment, as written in the central directory
+    pub file_comment_raw: Box<[u8]>,
     /// Specifies where the local header of the file starts
     pub header_start: u64,
     /// Specifies where the extra data of the file starts
@@ -355,6 +572,14 @@ pub struct ZipFileData {
     pub aes_mode: Option<(AesMode, AesVendorVersion, CompressionMethod)>,
     /// Specifies where in the extra data the AES metadata starts
     pub aes_extra_data_start: u64,
+    /// PBKDF2 iteration count for AES, if this crate's extra field records a nonstandard one
+    pub aes_iterations: Option<u32>,
+
+    /// Whether the strong encryption bit of the general purpose flags is set
+    pub strong_encryption_flag: bool,
+
+    /// Lowest "version needed to extract" to write for this file, whatever it uses
+    pub min_version_needed: u16,
 
     /// extra fields, see <https://libzip.org/specifications/extrafld.txt>
     pub extra_fields: Vec<ExtraField>,
@@ -405,6 +630,38 @@ impl ZipFileData {
         Some(path)
     }
 
+    /// Returns whether the file is actually a directory, because either its name ends with a
+    /// separator or its mode says so
+    pub(crate) fn is_dir(&self) -> bool {
+        self.file_name
+            .chars()
+            .next_back()
+            .is_some_and(|c| c == '/' || c == '\\')
+            || self
+                .unix_mode()
+                .is_some_and(|mode| mode & ffi::S_IFMT == ffi::S_IFDIR)
+    }
+
+    pub(crate) fn is_symlink(&self) -> bool {
+        self.unix_mode()
+            .is_some_and(|mode| mode & ffi::S_IFMT == ffi::S_IFLNK)
+    }
+
+    /// The algorithm this file is encrypted with, if it uses PKWARE's strong encryption
+    ///
+    /// That's signalled by either the strong encryption flag or a Strong Encryption Header, since
+    /// some archives only have one of them.
+    pub(crate) fn strong_encryption(&self) -> Option<StrongEncryptionAlgorithm> {
+        let header = self.extra_fields.iter().find_map(|field| match field {
+            ExtraField::StrongEncryption(header) => Some(header.algorithm()),
+            _ => None,
+        });
+        if !self.encrypted || !(self.strong_encryption_flag || header.is_some()) {
+            return None;
+        }
+        Some(header.unwrap_or(StrongEncryptionAlgorithm::Unknown(0xFFFF)))
+    }
+
     /// Get unix mode for the file
     pub(crate) const fn unix_mode(&self) -> Option<u32> {
         if self.external_attributes == 0 {
@@ -442,6 +699,14 @@ impl ZipFileData {
             CompressionMethod::Deflate64 => 21,
             #[cfg(feature = "lzma")]
             CompressionMethod::Lzma => 63,
+            #[cfg(feature = "legacy")]
+            CompressionMethod::Shrink => 10,
+            #[cfg(feature = "legacy")]
+            CompressionMethod::Implode => 10,
+            #[cfg(feature = "ppmd")]
+            CompressionMethod::Ppmd => 63,
+            #[cfg(feature = "xz")]
+            CompressionMethod::Xz => 63,
             // APPNOTE doesn't specify a version for Zstandard
             _ => DEFAULT_VERSION as u16,
         };
@@ -466,6 +731,7 @@ impl ZipFileData {
         compression_version
             .max(crypto_version)
             .max(misc_feature_version)
+            .max(self.min_version_needed)
     }
     #[inline(always)]
     pub(crate) fn extra_field_len(&self) -> usize {
@@ -487,13 +753,64 @@ impl ZipFileData {
 ///
 /// According to the [specification](https://www.winzip.com/win/en/aes_info.html#winzip11) AE-2
 /// does not make use of the CRC check.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(fuzzing, derive(arbitrary::Arbitrary))]
 #[repr(u16)]
 pub enum AesVendorVersion {
+    /// AE-1, which stores the CRC-32 of the file as well as the authentication code
     Ae1 = 0x0001,
+    /// AE-2, which stores zero in place of the CRC-32
     Ae2 = 0x0002,
 }
 
+/// The MS-DOS (FAT) attributes in the low byte of an entry's external file attributes
+///
+/// Windows tools set these whatever the rest of the attributes mean, so they're meaningful for
+/// archives made on any system. The directory bit is left out, since it's implied by the entry's
+/// name.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(fuzzing, derive(arbitrary::Arbitrary))]
+pub struct DosAttributes {
+    /// The file shouldn't be written to
+    pub read_only: bool,
+    /// The file isn't shown in ordinary directory listings
+    pub hidden: bool,
+    /// The file belongs to the operating system
+    pub system: bool,
+    /// The file has changed since it was last backed up
+    pub archive: bool,
+}
+
+impl DosAttributes {
+    const READ_ONLY: u32 = 0x01;
+    const HIDDEN: u32 = 0x02;
+    const SYSTEM: u32 = 0x04;
+    pub(crate) const DIRECTORY: u32 = 0x10;
+    const ARCHIVE: u32 = 0x20;
+
+    pub(crate) const fn from_external_attributes(external_attributes: u32) -> Self {
+        Self {
+            read_only: external_attributes & Self::READ_ONLY != 0,
+            hidden: external_attributes & Self::HIDDEN != 0,
+            system: external_attributes & Self::SYSTEM != 0,
+            archive: external_attributes & Self::ARCHIVE != 0,
+        }
+    }
+
+    /// The bits of the external file attributes that these attributes occupy
+    pub(crate) const fn to_external_attributes(self) -> u32 {
+        (if self.read_only { Self::READ_ONLY } else { 0 })
+            | (if self.hidden { Self::HIDDEN } else { 0 })
+            | (if self.system { Self::SYSTEM } else { 0 })
+            | (if self.archive { Self::ARCHIVE } else { 0 })
+    }
+}
+
+/// Header ID of the extra field in which this crate records a nonstandard PBKDF2 iteration count
+/// for an AES encrypted file, as a 4-byte little-endian integer. The ID spells "zi", alongside the
+/// "za" field used for alignment padding.
+pub(crate) const AES_ITERATIONS_EXTRA_FIELD_ID: u16 = 0x697a;
+
 /// AES variant used.
 #[derive(Copy, Clone, Debug)]
 #[cfg_attr(fuzzing, derive(arbitrary::Arbitrary))]
@@ -535,6 +852,10 @@ mod test {
         assert_eq!(u8::from(System::Unix), 3u8);
         assert_eq!(System::from(0), System::Dos);
         assert_eq!(System::from(3), System::Unix);
+        assert_eq!(System::from(10), System::Ntfs);
+        assert_eq!(u8::from(System::Osx), 19u8);
+        assert_eq!(System::from(4), System::Unknown);
+        assert_eq!(System::from(20), System::Unknown);
         assert_eq!(u8::from(System::Unknown), 4u8);
         assert_eq!(System::Unknown as u8, 4u8);
     }
@@ -548,7 +869,9 @@ mod test {
             version_made_by: 0,
             encrypted: false,
             using_data_descriptor: false,
+            is_utf8: false,
             compression_method: crate::compression::CompressionMethod::Stored,
+            compression_flags: 0,
             compression_level: None,
             last_modified_time: DateTime::default(),
             crc32: 0,
@@ -559,6 +882,7 @@ mod test {
             extra_field: None,
             central_extra_field: None,
             file_comment: String::with_capacity(0).into_boxed_str(),
+            file_comment_raw: Box::new([]),
             header_start: 0,
             extra_data_start: None,
             data_start: OnceLock::new(),
@@ -567,6 +891,9 @@ mod test {
             large_file: false,
             aes_mode: None,
             aes_extra_data_start: 0,
+            aes_iterations: None,
+            strong_encryption_flag: false,
+            min_version_needed: 0,
             extra_fields: Vec::new(),
         };
         assert_eq!(data.file_name_sanitized(), PathBuf::from("path/etc/passwd"));
@@ -590,6 +917,25 @@ mod test {
         assert_eq!(dt.datepart(), 0b1111111_1100_11111);
     }
 
+    #[test]
+    fn datetime_to_system_time() {
+        use super::DateTime;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let dt = DateTime::from_date_and_time(1980, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(
+            dt.to_system_time(),
+            Some(UNIX_EPOCH + Duration::from_secs(315532800))
+        );
+        let dt = DateTime::from_date_and_time(2024, 2, 29, 12, 30, 58).unwrap();
+        assert_eq!(
+            dt.to_system_time(),
+            Some(UNIX_EPOCH + Duration::from_secs(1709209858))
+        );
+        let dt = DateTime::from_date_and_time(2023, 2, 29, 0, 0, 0).unwrap();
+        assert_eq!(dt.to_system_time(), None);
+    }
+
     #[test]
     fn datetime_bounds() {
         use super::DateTime;
@@ -609,6 +955,26 @@ mod test {
         assert!(DateTime::from_date_and_time(2107, 12, 32, 0, 0, 0).is_err());
     }
 
+    #[test]
+    #[allow(clippy::unusual_byte_groupings)]
+    fn datetime_try_from_msdos() {
+        use super::DateTime;
+
+        let dt = DateTime::try_from_msdos(0b0101100_0011_01111, 0b10111_111011_11101).unwrap();
+        assert_eq!((dt.year(), dt.month(), dt.day()), (2024, 3, 15));
+        assert_eq!((dt.hour(), dt.minute(), dt.second()), (23, 59, 58));
+
+        // A zero date field, as some archivers write
+        let dt = DateTime::from_msdos(0, 0);
+        assert_eq!((dt.year(), dt.month(), dt.day()), (1980, 0, 0));
+        assert!(!dt.is_valid());
+        assert!(DateTime::try_from_msdos(0, 0).is_err());
+        // Hour 24, minute 60 and second 62
+        assert!(DateTime::try_from_msdos(0b0000000_0001_00001, 0b11000_000000_00000).is_err());
+        assert!(DateTime::try_from_msdos(0b0000000_0001_00001, 0b00000_111100_00000).is_err());
+        assert!(DateTime::try_from_msdos(0b0000000_0001_00001, 0b00000_000000_11111).is_err());
+    }
+
     #[cfg(feature = "time")]
     use time::{format_description::well_known::Rfc3339, OffsetDateTime};
 
@@ -688,4 +1054,57 @@ mod test {
 
         assert!(DateTime::try_from(clock).is_ok());
     }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn offset_date_time_round_trip() {
+        use super::DateTime;
+        use time::macros::datetime;
+
+        let dt = DateTime::try_from(datetime!(2024-05-01 12:34:57.789 UTC)).unwrap();
+        assert_eq!(dt.second(), 57);
+        assert_eq!(dt.to_time().unwrap(), datetime!(2024-05-01 12:34:57 UTC));
+        // Only even seconds survive the MS-DOS encoding
+        let dt = DateTime::from_msdos(dt.datepart(), dt.timepart());
+        assert_eq!(dt.to_time().unwrap(), datetime!(2024-05-01 12:34:56 UTC));
+
+        // The local fields are kept; the offset isn't applied
+        let dt = DateTime::try_from(datetime!(2024-05-01 23:30:00 -02:00)).unwrap();
+        assert_eq!(dt.to_time().unwrap(), datetime!(2024-05-01 23:30:00 UTC));
+    }
+
+    #[test]
+    fn system_time_round_trip() {
+        use super::DateTime;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        for (seconds, fields) in [
+            (315_532_800, (1980, 1, 1, 0, 0, 0)),
+            (951_782_400, (2000, 2, 29, 0, 0, 0)),
+            (1_714_566_897, (2024, 5, 1, 12, 34, 57)),
+            (4_354_819_199, (2107, 12, 31, 23, 59, 59)),
+        ] {
+            let time = UNIX_EPOCH + Duration::new(seconds, 999_999_999);
+            let dt = DateTime::try_from(time).unwrap();
+            assert_eq!(
+                (
+                    dt.year(),
+                    dt.month(),
+                    dt.day(),
+                    dt.hour(),
+                    dt.minute(),
+                    dt.second()
+                ),
+                fields
+            );
+            assert_eq!(
+                dt.to_system_time(),
+                Some(UNIX_EPOCH + Duration::from_secs(seconds))
+            );
+        }
+        assert!(DateTime::try_from(UNIX_EPOCH + Duration::from_secs(315_532_799)).is_err());
+        assert!(DateTime::try_from(UNIX_EPOCH + Duration::from_secs(4_354_819_200)).is_err());
+        assert!(DateTime::try_from(UNIX_EPOCH - Duration::from_secs(1)).is_err());
+        assert_eq!(DateTime::from_msdos(0, 0).to_system_time(), None);
+    }
 }
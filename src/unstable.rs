@@ -18,7 +18,7 @@ pub mod write {
         fn with_deprecated_encryption(self, password: &[u8]) -> Self;
     }
     impl<'k, T: FileOptionExtension> FileOptionsExt for FileOptions<'k, T> {
-        fn with_deprecated_encryption(self, password: &[u8]) -> FileOptions<'static, T> {
+        fn with_deprecated_encryption(self, password: &[u8]) -> Self {
             self.with_deprecated_encryption(password)
         }
     }
@@ -67,3 +67,11 @@ pub trait LittleEndianReadExt: Read {
 }
 
 impl<R: Read> LittleEndianReadExt for R {}
+
+/// Helper methods for reading unsigned integers in little-endian form from an async reader.
+///
+/// Tokio's [`AsyncReadExt`](tokio::io::AsyncReadExt) already provides `read_u16_le`,
+/// `read_u32_le` and `read_u64_le`, so this is simply a re-export under a name that mirrors
+/// [`LittleEndianReadExt`].
+#[cfg(feature = "tokio")]
+pub use tokio::io::AsyncReadExt as AsyncLittleEndianReadExt;
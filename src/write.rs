@@ -1,20 +1,30 @@
 //! Types for creating ZIP archives
 
 #[cfg(feature = "aes-crypto")]
-use crate::aes::AesWriter;
-use crate::compression::CompressionMethod;
-use crate::read::{find_content, ZipArchive, ZipFile, ZipFileReader};
-use crate::result::{ZipError, ZipResult};
+use crate::aes::{AesWriter, DEFAULT_ITERATION_COUNT};
+use crate::compression::{CompressionMethod, CompressionStream, Compressor};
+use crate::extra_fields::{ExtendedTimestamp, ExtraField, Ntfs, UnixOwnership};
+use crate::read::{
+    copy_with_progress, find_content, ZipArchive, ZipFile, ZipFileReader, DEFAULT_COPY_BUFFER_SIZE,
+    PKWARE_UNIX_EXTRA_FIELD_ID,
+};
+use crate::result::{DateTimeRangeError, ZipError, ZipResult};
 use crate::spec;
+use crate::types::{
+    ffi, AesVendorVersion, DateTime, DosAttributes, NameEncoding, System, ZipFileData,
+    DEFAULT_VERSION,
+};
 #[cfg(feature = "aes-crypto")]
-use crate::types::AesMode;
-use crate::types::{ffi, AesVendorVersion, DateTime, System, ZipFileData, DEFAULT_VERSION};
+use crate::types::{AesMode, AES_ITERATIONS_EXTRA_FIELD_ID};
 #[cfg(any(feature = "_deflate-any", feature = "bzip2", feature = "zstd",))]
 use core::num::NonZeroU64;
 use crc32fast::Hasher;
 use indexmap::IndexMap;
-use std::borrow::ToOwned;
+use std::borrow::{Cow, ToOwned};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::default::Default;
+use std::fmt;
+use std::fs::{self, File};
 use std::io;
 use std::io::prelude::*;
 use std::io::{BufReader, SeekFrom};
@@ -22,6 +32,7 @@ use std::marker::PhantomData;
 use std::mem;
 use std::str::{from_utf8, Utf8Error};
 use std::sync::{Arc, OnceLock};
+use std::time::SystemTime;
 
 #[cfg(any(
     feature = "deflate",
@@ -41,7 +52,7 @@ use zopfli::Options;
 
 #[cfg(feature = "deflate-zopfli")]
 use std::io::BufWriter;
-use std::path::Path;
+use std::path::{Path, MAIN_SEPARATOR};
 
 #[cfg(feature = "zstd")]
 use zstd::stream::write::Encoder as ZstdEncoder;
@@ -51,6 +62,21 @@ enum MaybeEncrypted<W> {
     #[cfg(feature = "aes-crypto")]
     Aes(crate::aes::AesWriter<W>),
     ZipCrypto(crate::zipcrypto::ZipCryptoWriter<W>),
+    /// Collects what a compressor writes while it's being finished, after the real writer has been
+    /// taken out of it so that it isn't lost if finishing fails
+    Detached(Vec<u8>),
+}
+impl<W: Write> MaybeEncrypted<W> {
+    /// Returns the writer, abandoning whatever the encryption still holds
+    fn into_inner(self) -> W {
+        match self {
+            MaybeEncrypted::Unencrypted(w) => w,
+            #[cfg(feature = "aes-crypto")]
+            MaybeEncrypted::Aes(w) => w.into_inner(),
+            MaybeEncrypted::ZipCrypto(w) => w.writer,
+            MaybeEncrypted::Detached(_) => unreachable!("A detached writer is never kept"),
+        }
+    }
 }
 impl<W: Write> Write for MaybeEncrypted<W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
@@ -59,6 +85,7 @@ impl<W: Write> Write for MaybeEncrypted<W> {
             #[cfg(feature = "aes-crypto")]
             MaybeEncrypted::Aes(w) => w.write(buf),
             MaybeEncrypted::ZipCrypto(w) => w.write(buf),
+            MaybeEncrypted::Detached(bytes) => bytes.write(buf),
         }
     }
     fn flush(&mut self) -> io::Result<()> {
@@ -67,11 +94,15 @@ impl<W: Write> Write for MaybeEncrypted<W> {
             #[cfg(feature = "aes-crypto")]
             MaybeEncrypted::Aes(w) => w.flush(),
             MaybeEncrypted::ZipCrypto(w) => w.flush(),
+            MaybeEncrypted::Detached(_) => Ok(()),
         }
     }
 }
 enum GenericZipWriter<W: Write + Seek> {
     Closed,
+    /// What's left once finishing a compressor or encryption has failed, so that
+    /// [`ZipWriter::abort`] can still return the writer
+    Broken(MaybeEncrypted<W>),
     Storer(MaybeEncrypted<W>),
     #[cfg(any(
         feature = "deflate",
@@ -87,6 +118,33 @@ enum GenericZipWriter<W: Write + Seek> {
     Bzip2(BzEncoder<MaybeEncrypted<W>>),
     #[cfg(feature = "zstd")]
     Zstd(ZstdEncoder<'static, MaybeEncrypted<W>>),
+    #[cfg(feature = "brotli")]
+    Brotli(Box<brotli::CompressorWriter<MaybeEncrypted<W>>>),
+    Custom(CustomEncoder<W>),
+}
+
+/// Writes a file's data through the [`CompressionStream`] of a custom compressor
+struct CustomEncoder<W> {
+    inner: MaybeEncrypted<W>,
+    stream: Box<dyn CompressionStream>,
+}
+
+impl<W: Write> CustomEncoder<W> {
+    fn finish(mut self) -> io::Result<MaybeEncrypted<W>> {
+        self.stream.finish(&mut self.inner)?;
+        Ok(self.inner)
+    }
+}
+
+impl<W: Write> Write for CustomEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.compress(buf, &mut self.inner)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
 }
 
 // Put the struct declaration in a private module to convince rustdoc to display ZipWriter nicely
@@ -128,6 +186,20 @@ pub(crate) mod zip_writer {
         pub(super) writing_raw: bool,
         pub(super) comment: Box<[u8]>,
         pub(super) flush_on_finish_file: bool,
+        pub(super) raw_copy_buffer_size: usize,
+        pub(super) on_finish_file: Option<fn(&mut W) -> io::Result<()>>,
+        pub(super) deterministic: bool,
+        pub(super) compression_heuristic: CompressionHeuristic,
+        pub(super) pending_auto_file: Option<PendingAutoFile<W>>,
+        pub(super) method_by_size: Vec<(u64, CompressionMethod)>,
+        pub(super) pending_sized_file: Option<PendingSizedFile>,
+        pub(super) duplicate_policy: DuplicatePolicy,
+        pub(super) fold_case_for_duplicates: bool,
+        pub(super) shadowed_files: Vec<ShadowedFile>,
+        pub(super) dedup_index: Option<HashMap<DedupKey, Box<str>>>,
+        pub(super) dedup_stats: DedupStats,
+        pub(super) appended_directory: Option<AppendedDirectory>,
+        pub(super) default_file_options: FullFileOptions<'static>,
     }
 }
 #[doc(inline)]
@@ -135,24 +207,107 @@ pub use self::sealed::FileOptionExtension;
 use crate::result::ZipError::InvalidArchive;
 #[cfg(feature = "lzma")]
 use crate::result::ZipError::UnsupportedArchive;
-use crate::spec::path_to_string;
+use crate::spec::{normalize_separators, path_to_string};
 use crate::unstable::LittleEndianWriteExt;
-use crate::write::GenericZipWriter::{Closed, Storer};
+use crate::write::GenericZipWriter::{Broken, Closed, Storer};
 use crate::zipcrypto::ZipCryptoKeys;
 use crate::CompressionMethod::Stored;
 pub use zip_writer::ZipWriter;
 
+#[cfg(feature = "tokio")]
+pub(crate) mod async_writer;
+#[cfg(feature = "tokio")]
+pub use async_writer::AsyncZipWriter;
+
+pub(crate) mod heuristic;
+pub use heuristic::CompressionHeuristic;
+
+mod parallel;
+pub use parallel::ParallelZipWriter;
+
+mod spill;
+pub use spill::SpillWriter;
+
+/// An earlier entry whose name was reused under [`DuplicatePolicy::Allow`] or
+/// [`DuplicatePolicy::Overwrite`]
+#[derive(Debug)]
+pub(crate) struct ShadowedFile {
+    data: ZipFileData,
+    /// Whether it's still written to the central directory, as it is under
+    /// [`DuplicatePolicy::Allow`]
+    keep: bool,
+    /// The name of the entry that replaced it, so that aborting that entry can restore it
+    replaced_by: Box<str>,
+}
+
+/// The central directory records of the entries that were already in an archive opened with
+/// [`ZipWriter::new_append_lazy`], which are written back as they were
+pub(crate) struct AppendedDirectory {
+    records: Box<[u8]>,
+    count: usize,
+}
+
+/// How much [`ZipWriter::set_dedup`] has saved, as returned by [`ZipWriter::dedup_stats`]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct DedupStats {
+    /// How many entries share their data with an earlier entry instead of having their own
+    pub files_deduplicated: u64,
+    /// How many bytes of local headers and data weren't written because of that
+    pub bytes_saved: u64,
+}
+
+/// The uncompressed size, CRC-32 and SHA-256 of an entry's contents, which identify entries that
+/// [`ZipWriter::set_dedup`] treats as identical
+pub(crate) type DedupKey = (u64, u32, [u8; 32]);
+
+/// An entry started by [`ZipWriter::start_file_auto`] that's still being sampled
+pub(crate) struct PendingAutoFile<W: Write + Seek> {
+    sample: Vec<u8>,
+    /// Starts the entry, without compression if the argument is true
+    start: StartAutoFileFunction<W>,
+}
+
+type StartAutoFileFunction<W> =
+    Box<dyn FnOnce(&mut ZipWriter<W>, bool) -> ZipResult<()> + Send + Sync>;
+
+/// A file whose compression method [`ZipWriter::set_method_by_size`] chooses once enough of it
+/// has been buffered
+pub(crate) struct PendingSizedFile {
+    buffer: Vec<u8>,
+    /// The method from the file's options, for sizes below every threshold
+    default_method: CompressionMethod,
+    compression_level: Option<i64>,
+    #[cfg(feature = "deflate-zopfli")]
+    zopfli_buffer_size: Option<usize>,
+    #[cfg(feature = "zstd")]
+    zstd_dictionary: Option<Box<[u8]>>,
+    #[cfg(feature = "brotli")]
+    brotli_window: u32,
+}
+
 #[derive(Default)]
 struct ZipWriterStats {
     hasher: Hasher,
     start: u64,
     bytes_written: u64,
+    /// The AES format version that the file must use, rather than one chosen by its size
+    aes_vendor_version: Option<AesVendorVersion>,
+    /// Hashes the contents for [`ZipWriter::set_dedup`], if it's enabled
+    #[cfg(feature = "sha2")]
+    content_hasher: Option<sha2::Sha256>,
+    /// Whether the local header was written with a placeholder compression method
+    compression_method_chosen_late: bool,
 }
 
+#[derive(Clone, Copy)]
 struct ZipRawValues {
     crc32: u32,
     compressed_size: u64,
     uncompressed_size: u64,
+    /// The general purpose flags that belong to the compression method, which are copied along
+    /// with already-compressed data
+    compression_flags: u16,
 }
 mod sealed {
     use std::sync::Arc;
@@ -194,6 +349,8 @@ enum EncryptWith<'k> {
     #[cfg(feature = "aes-crypto")]
     Aes {
         mode: AesMode,
+        /// `None` to choose AE-1 or AE-2 by the file's size
+        vendor_version: Option<AesVendorVersion>,
         password: &'k str,
     },
     ZipCrypto(ZipCryptoKeys, PhantomData<&'k ()>),
@@ -206,6 +363,7 @@ impl<'a> arbitrary::Arbitrary<'a> for EncryptWith<'a> {
         if bool::arbitrary(u)? {
             return Ok(EncryptWith::Aes {
                 mode: AesMode::arbitrary(u)?,
+                vendor_version: u.arbitrary()?,
                 password: u.arbitrary::<&str>()?,
             });
         }
@@ -217,24 +375,118 @@ impl<'a> arbitrary::Arbitrary<'a> for EncryptWith<'a> {
     }
 }
 
+/// What [`ZipWriter::merge_archive_with_policy`] does with an entry from the source archive whose
+/// name is already in use.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum MergeDuplicatePolicy {
+    /// Keep the existing entry and discard the one from the source archive.
+    Skip,
+    /// Replace the existing entry with the one from the source archive, which takes its place in
+    /// the central directory.
+    #[default]
+    Overwrite,
+    /// Fail before copying anything.
+    Error,
+}
+
+/// What [`ZipWriter`] does when an entry is added with the same name as an existing one.
+///
+/// Set with [`ZipWriter::set_duplicate_policy`]. Names are compared exactly unless
+/// [`ZipWriter::set_duplicate_case_folding`] is enabled.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Write both entries. [`ZipArchive`] and most other readers then only see the one written
+    /// last, but some readers extract both or fail, so this is only useful to reproduce such
+    /// archives.
+    Allow,
+    /// Fail with [`ZipError::DuplicateName`].
+    #[default]
+    Error,
+    /// Replace the existing entry, leaving it out of the central directory that
+    /// [`ZipWriter::finish`] writes. Its data isn't erased, so it remains as unused bytes. This is
+    /// most useful after [`ZipWriter::new_append`], to update an entry from the original archive.
+    Overwrite,
+}
+
 /// Metadata for a file to be written
 #[derive(Clone, Debug, Copy)]
 pub struct FileOptions<'k, T: FileOptionExtension> {
     pub(crate) compression_method: CompressionMethod,
     pub(crate) compression_level: Option<i64>,
+    /// Whether `compression_method` was chosen, rather than left for
+    /// [`ZipWriter::set_method_by_size`] to choose
+    compression_method_set: bool,
     pub(crate) last_modified_time: DateTime,
     pub(crate) permissions: Option<u32>,
-    pub(crate) large_file: bool,
+    pub(crate) zip64: Zip64Mode,
     encrypt_with: Option<EncryptWith<'k>>,
     extended_options: T,
     alignment: u16,
     #[cfg(feature = "deflate-zopfli")]
     pub(super) zopfli_buffer_size: Option<usize>,
+    #[cfg(feature = "zstd")]
+    zstd_dictionary: Option<&'k [u8]>,
+    #[cfg(feature = "brotli")]
+    brotli_window: u32,
+    #[cfg(feature = "aes-crypto")]
+    aes_iterations: u32,
+    extended_timestamp: Option<ExtendedTimestamp>,
+    ntfs_times: Option<Ntfs>,
+    unix_ownership: Option<UnixOwnership>,
+    hardlink_target: Option<&'k str>,
+    name_encoding: NameEncoding,
+    dos_attributes: DosAttributes,
+    unicode_path_extra_field: bool,
+    unicode_comment_extra_field: bool,
+    external_attributes: Option<u32>,
+    system: System,
+    version_made_by: u8,
+    data_descriptor: bool,
+    normalize_separators: bool,
+    comment: &'k str,
+    min_version: u16,
+    custom_compressor: Option<CustomCompressor<'k>>,
+}
+
+/// A compressor set with [`FileOptions::with_custom_compressor`]
+#[derive(Clone, Copy)]
+struct CustomCompressor<'k>(&'k dyn Compressor);
+
+impl fmt::Debug for CustomCompressor<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CustomCompressor")
+    }
 }
+
 /// Simple File Options. Can be copied and good for simple writing zip files
 pub type SimpleFileOptions = FileOptions<'static, ()>;
 /// Adds Extra Data and Central Extra Data. It does not implement copy.
 pub type FullFileOptions<'k> = FileOptions<'k, ExtendedFileOptions>;
+/// Whether an entry's headers use ZIP64 extended information, which stores sizes and offsets of
+/// 4 GiB or more
+///
+/// A file's local header is written before its data, and this crate doesn't use data
+/// descriptors, so the header of a file written with [`Write`] can't switch to ZIP64 once the
+/// file turns out to be large. Use [`Zip64Mode::Always`] for files that may reach 4 GiB.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+#[cfg_attr(fuzzing, derive(arbitrary::Arbitrary))]
+pub enum Zip64Mode {
+    /// Use ZIP64 where it's known to be needed when the local header is written, such as for a
+    /// raw copy of a large entry, and in the central directory record of an entry that starts
+    /// 4 GiB or more into the archive. A file written with [`Write`] that reaches 4 GiB is aborted
+    /// with an error.
+    #[default]
+    Auto,
+    /// Always store the sizes in ZIP64 extended information, even for small files. Readers
+    /// need ZIP64 support, and 20 bytes are used in each header that may not have been needed.
+    Always,
+    /// Never use ZIP64 for this entry, so readers without ZIP64 support can open it. Starting the
+    /// entry fails if it's known to need ZIP64, and a file that reaches 4 GiB is aborted with an
+    /// error. The archive as a whole still gets ZIP64 end-of-central-directory records if it has
+    /// too many entries or its central directory starts too far in.
+    Never,
+}
+
 /// The Extension for Extra Data and Central Extra Data
 #[derive(Clone, Debug, Default)]
 pub struct ExtendedFileOptions {
@@ -254,7 +506,7 @@ impl<'a> arbitrary::Arbitrary<'a> for FileOptions<'a, ExtendedFileOptions> {
             },
             last_modified_time: DateTime::arbitrary(u)?,
             permissions: Option::<u32>::arbitrary(u)?,
-            large_file: bool::arbitrary(u)?,
+            zip64: Zip64Mode::arbitrary(u)?,
             encrypt_with: Option::<EncryptWith>::arbitrary(u)?,
             alignment: u16::arbitrary(u)?,
             #[cfg(feature = "deflate-zopfli")]
@@ -286,9 +538,13 @@ impl<'k, T: FileOptionExtension> FileOptions<'k, T> {
     /// `CompressionMethod::Bzip2` is the default if it is enabled. If neither `bzip2` nor `deflate`
     /// is enabled, `CompressionMethod::Zlib` is the default. If all else fails,
     /// `CompressionMethod::Stored` becomes the default and files are written uncompressed.
+    ///
+    /// Setting this, even to the default, overrides [`ZipWriter::set_method_by_size`].
     #[must_use]
     pub const fn compression_method(mut self, method: CompressionMethod) -> Self {
         self.compression_method = method;
+        self.compression_method_set = true;
+        self.custom_compressor = None;
         self
     }
 
@@ -297,11 +553,15 @@ impl<'k, T: FileOptionExtension> FileOptions<'k, T> {
     /// `None` value specifies default compression level.
     ///
     /// Range of values depends on compression method:
-    /// * `Deflated`: 10 - 264 for Zopfli, 0 - 9 for other encoders. Default is 24 if Zopfli is the
-    ///   only encoder, or 6 otherwise.
-    /// * `Bzip2`: 0 - 9. Default is 6
+    /// * `Deflated`: 10 - 264 for Zopfli, 0 - 9 for other encoders. Default is 24 if the
+    ///   `deflate-zopfli` feature is enabled, or 6 otherwise.
+    /// * `Bzip2`: 1 - 9. Default is 6
     /// * `Zstd`: -7 - 22, with zero being mapped to default level. Default is 3
+    /// * `Brotli`: 0 - 11. Default is 11
     /// * others: only `None` is allowed
+    ///
+    /// Higher levels give smaller output and take longer. A level outside the method's range
+    /// isn't clamped; starting the file fails with [`ZipError::UnsupportedArchive`] instead.
     #[must_use]
     pub const fn compression_level(mut self, level: Option<i64>) -> Self {
         self.compression_level = level;
@@ -318,6 +578,32 @@ impl<'k, T: FileOptionExtension> FileOptions<'k, T> {
         self
     }
 
+    /// Set the last modified time from a [`SystemTime`], taken as UTC
+    ///
+    /// Returns [`DateTimeRangeError`] if the time is before 1980 or after 2107, which the MS-DOS
+    /// format can't represent. Use [`FileOptions::with_extended_timestamp`] or
+    /// [`FileOptions::with_ntfs_times`] as well to record the time more precisely.
+    pub fn last_modified_time_from_system(
+        self,
+        mod_time: SystemTime,
+    ) -> Result<Self, DateTimeRangeError> {
+        Ok(self.last_modified_time(mod_time.try_into()?))
+    }
+
+    /// Set the last modified time from any type that can be converted to a [`DateTime`], such as
+    /// `time::OffsetDateTime` with the `time` feature or `chrono::NaiveDateTime` with the `chrono`
+    /// feature
+    ///
+    /// Returns [`DateTimeRangeError`] if the time can't be represented, for example because it's
+    /// before 1980.
+    pub fn try_last_modified_time<D: TryInto<DateTime>>(
+        self,
+        mod_time: D,
+    ) -> Result<Self, DateTimeRangeError> {
+        let mod_time = mod_time.try_into().map_err(|_| DateTimeRangeError)?;
+        Ok(self.last_modified_time(mod_time))
+    }
+
     /// Set the permissions for the new file.
     ///
     /// The format is represented with unix-style permissions.
@@ -338,13 +624,29 @@ impl<'k, T: FileOptionExtension> FileOptions<'k, T> {
     /// If set to `false` and the file exceeds the limit, an I/O error is thrown and the file is
     /// aborted. If set to `true`, readers will require ZIP64 support and if the file does not
     /// exceed the limit, 20 B are wasted. The default is `false`.
+    ///
+    /// This is the same as `with_zip64(Zip64Mode::Always)` for `true`, and
+    /// `with_zip64(Zip64Mode::Auto)` for `false`.
     #[must_use]
     pub const fn large_file(mut self, large: bool) -> Self {
-        self.large_file = large;
+        self.zip64 = if large {
+            Zip64Mode::Always
+        } else {
+            Zip64Mode::Auto
+        };
+        self
+    }
+
+    /// Set when the new file's headers use ZIP64 extended information
+    ///
+    /// The default is [`Zip64Mode::Auto`].
+    #[must_use]
+    pub const fn with_zip64(mut self, zip64: Zip64Mode) -> Self {
+        self.zip64 = zip64;
         self
     }
 
-    pub(crate) fn with_deprecated_encryption(self, password: &[u8]) -> FileOptions<'static, T> {
+    pub(crate) fn with_deprecated_encryption(self, password: &[u8]) -> Self {
         FileOptions {
             encrypt_with: Some(EncryptWith::ZipCrypto(
                 ZipCryptoKeys::derive(password),
@@ -355,14 +657,66 @@ impl<'k, T: FileOptionExtension> FileOptions<'k, T> {
     }
 
     /// Set the AES encryption parameters.
+    ///
+    /// The file uses AE-1, which also stores its CRC-32, unless it's shorter than 20 bytes. Then
+    /// it uses AE-2, which stores zero instead, since the CRC-32 of such a short file could reveal
+    /// its contents. Use [`FileOptions::with_aes_encryption_version`] to choose for yourself.
+    #[cfg(feature = "aes-crypto")]
+    pub fn with_aes_encryption<'a>(self, mode: AesMode, password: &'a str) -> FileOptions<'a, T>
+    where
+        'k: 'a,
+    {
+        FileOptions {
+            encrypt_with: Some(EncryptWith::Aes {
+                mode,
+                vendor_version: None,
+                password,
+            }),
+            ..self
+        }
+    }
+
+    /// Set the AES encryption parameters, including which version of the format to use
+    ///
+    /// Some readers check the CRC-32 that [`AesVendorVersion::Ae1`] stores, and some reject a
+    /// nonzero CRC-32 with [`AesVendorVersion::Ae2`], so this can be used to suit a particular
+    /// reader. The version is written to the AES extra field, and the CRC-32 fields of the
+    /// headers hold zero for AE-2 even if the file is long.
     #[cfg(feature = "aes-crypto")]
-    pub fn with_aes_encryption(self, mode: AesMode, password: &str) -> FileOptions<'_, T> {
+    pub fn with_aes_encryption_version<'a>(
+        self,
+        mode: AesMode,
+        vendor_version: AesVendorVersion,
+        password: &'a str,
+    ) -> FileOptions<'a, T>
+    where
+        'k: 'a,
+    {
         FileOptions {
-            encrypt_with: Some(EncryptWith::Aes { mode, password }),
+            encrypt_with: Some(EncryptWith::Aes {
+                mode,
+                vendor_version: Some(vendor_version),
+                password,
+            }),
             ..self
         }
     }
 
+    /// Set how many PBKDF2 iterations derive the AES key from the password
+    ///
+    /// The WinZip AES specification fixes this at 1000, which is the default. Any other count is
+    /// recorded in an extra field specific to this crate, which [`ZipArchive`](crate::ZipArchive)
+    /// reads back automatically as long as it's at most 1,000,000;
+    /// [`ZipArchive::set_aes_iterations`](crate::ZipArchive::set_aes_iterations) can supply it
+    /// instead, and must for any higher count. Archives that use a nonstandard count won't open
+    /// in stock WinZip or most other readers. This has no effect unless the file is AES encrypted.
+    #[must_use]
+    #[cfg(feature = "aes-crypto")]
+    pub const fn with_aes_iterations(mut self, iterations: u32) -> Self {
+        self.aes_iterations = iterations;
+        self
+    }
+
     /// Sets the size of the buffer used to hold the next block that Zopfli will compress. The
     /// larger the buffer, the more effective the compression, but the more memory is required.
     /// A value of `None` indicates no buffer, which is recommended only when all non-empty writes
@@ -374,6 +728,216 @@ impl<'k, T: FileOptionExtension> FileOptions<'k, T> {
         self
     }
 
+    /// Sets the base-2 logarithm of the window size, from 10 to 24, for the Brotli encoder to use
+    /// when the compression method is [`CompressionMethod::Brotli`]. It has no effect with any
+    /// other compression method. The default is 22, for a 4 MiB window.
+    ///
+    /// A larger window can find matches further back in the file, but the reader needs that much
+    /// memory to decompress it.
+    #[cfg(feature = "brotli")]
+    #[must_use]
+    pub const fn with_brotli_window(mut self, lg_window: u32) -> Self {
+        self.brotli_window = lg_window;
+        self
+    }
+
+    /// Sets a dictionary for the Zstandard encoder to use when the compression method is
+    /// [`CompressionMethod::Zstd`]. It has no effect with any other compression method.
+    ///
+    /// The dictionary isn't stored in the archive, so readers will need the same one to
+    /// decompress the file; see [`ZipArchive::by_index_with_zstd_dictionary`].
+    #[cfg(feature = "zstd")]
+    pub fn with_zstd_dictionary<'a>(self, dictionary: &'a [u8]) -> FileOptions<'a, T>
+    where
+        'k: 'a,
+    {
+        FileOptions {
+            zstd_dictionary: Some(dictionary),
+            ..self
+        }
+    }
+
+    /// Store Unix timestamps in an Info-ZIP extended timestamp extra field (0x5455), which unlike
+    /// the MS-DOS time of [`FileOptions::last_modified_time`] has one-second granularity and is
+    /// always UTC.
+    ///
+    /// The local header holds every timestamp given; the central directory only holds `mod_time`.
+    #[must_use]
+    pub const fn with_extended_timestamp(
+        mut self,
+        mod_time: u32,
+        ac_time: Option<u32>,
+        cr_time: Option<u32>,
+    ) -> Self {
+        self.extended_timestamp = Some(ExtendedTimestamp::new(mod_time, ac_time, cr_time));
+        self
+    }
+
+    /// Store Windows timestamps in an NTFS extra field (0x000a), which has 100-nanosecond
+    /// granularity and is always UTC. Both the local header and the central directory hold all
+    /// three times. Times outside the range a FILETIME can represent (1601-01-01 to about 60056)
+    /// are clamped to it.
+    #[must_use]
+    pub fn with_ntfs_times(
+        mut self,
+        mtime: SystemTime,
+        atime: SystemTime,
+        ctime: SystemTime,
+    ) -> Self {
+        self.ntfs_times = Some(Ntfs::new(mtime, atime, ctime));
+        self
+    }
+
+    /// Store the file's Unix owner and group IDs in an Info-ZIP new Unix extra field (0x7875),
+    /// which both the local header and the central directory hold. They can be read back with
+    /// [`ZipFile::unix_ownership`](crate::read::ZipFile::unix_ownership) and restored by
+    /// [`ExtractOptions::preserve_ownership`](crate::read::ExtractOptions::preserve_ownership).
+    #[must_use]
+    pub const fn with_unix_ownership(mut self, uid: u32, gid: u32) -> Self {
+        self.unix_ownership = Some(UnixOwnership::new(uid, gid));
+        self
+    }
+
+    /// Set the encoding of the file name. Names in a legacy encoding are written without the
+    /// UTF-8 flag, which is mainly useful for testing how other software handles them; starting
+    /// the file fails if its name can't be represented in that encoding.
+    ///
+    /// The default is [`NameEncoding::Utf8`].
+    #[must_use]
+    pub const fn with_name_encoding(mut self, name_encoding: NameEncoding) -> Self {
+        self.name_encoding = name_encoding;
+        self
+    }
+
+    /// Set the MS-DOS attributes of the new file, which Windows tools use for its read-only,
+    /// hidden, system and archive flags. They're written alongside the Unix permissions, which
+    /// they don't affect.
+    ///
+    /// By default, none of them are set.
+    #[must_use]
+    pub const fn with_dos_attributes(mut self, dos_attributes: DosAttributes) -> Self {
+        self.dos_attributes = dos_attributes;
+        self
+    }
+
+    /// Set whether a non-ASCII name is also written to an Info-ZIP Unicode Path extra field
+    ///
+    /// Some older tools ignore the UTF-8 flag, but read the name from this field. It's most
+    /// useful along with a legacy [`FileOptions::with_name_encoding`], which still puts a name
+    /// those tools can show without this field in the header. The default is `false`.
+    #[must_use]
+    pub const fn with_unicode_path_extra(mut self, unicode_path_extra: bool) -> Self {
+        self.unicode_path_extra_field = unicode_path_extra;
+        self
+    }
+
+    /// Set whether a non-ASCII comment is also written to an Info-ZIP Unicode Comment extra field
+    ///
+    /// This is the counterpart of [`FileOptions::with_unicode_path_extra`] for the file's comment,
+    /// and is only written to the central directory, since that's where the comment is. The
+    /// default is `false`.
+    #[must_use]
+    pub const fn with_unicode_comment_extra(mut self, unicode_comment_extra: bool) -> Self {
+        self.unicode_comment_extra_field = unicode_comment_extra;
+        self
+    }
+
+    /// Set the new file's external attributes, which are written exactly as given instead of
+    /// being made from its Unix permissions and MS-DOS attributes
+    ///
+    /// How they're interpreted depends on the host system, which can be set with
+    /// [`FileOptions::with_version_made_by`]. By default, they're made from the permissions and
+    /// attributes set on these options.
+    #[must_use]
+    pub const fn with_external_attributes(mut self, external_attributes: u32) -> Self {
+        self.external_attributes = Some(external_attributes);
+        self
+    }
+
+    /// Set the "version made by" field of the new file: the host system whose conventions its
+    /// external attributes follow, and the version of the ZIP specification it was written
+    /// against, times 10 (so `63` for version 6.3).
+    ///
+    /// The default is [`System::Unix`] and version 4.6.
+    #[must_use]
+    pub const fn with_version_made_by(mut self, system: System, version: u8) -> Self {
+        self.system = system;
+        self.version_made_by = version;
+        self
+    }
+
+    /// Set whether the new file's CRC-32 and sizes are written in a data descriptor after its
+    /// data, instead of going back to fill them into its local header once the data is written
+    ///
+    /// Readers that work through an archive as a stream, such as [`read_zipfile_from_stream`],
+    /// then find them after the data; the local header has zeroes in their place. The descriptor's
+    /// sizes take 8 bytes each for a [large file](FileOptions::large_file). Entries copied from
+    /// another archive keep their existing headers. The default is `false`.
+    ///
+    /// [`read_zipfile_from_stream`]: crate::read::read_zipfile_from_stream
+    #[must_use]
+    pub const fn with_data_descriptor(mut self, data_descriptor: bool) -> Self {
+        self.data_descriptor = data_descriptor;
+        self
+    }
+
+    /// Set whether the entry's name is converted from a Windows path, by replacing each `\\` with
+    /// `/` and removing any drive prefix such as `C:` along with separators at the start.
+    ///
+    /// The ZIP format only allows `/` between the components of a name, and many readers treat `\\`
+    /// as part of a file name. Paths passed to [`ZipWriter::start_file_from_path`] and the
+    /// other `_from_path` methods that take options are normalized before their `..` and `.`
+    /// components are resolved. The default is `false`.
+    #[must_use]
+    pub const fn normalize_separators(mut self, normalize: bool) -> Self {
+        self.normalize_separators = normalize;
+        self
+    }
+
+    /// Set a comment for the new file, which is stored in its central directory record
+    ///
+    /// The comment is encoded like the name, as set by [`FileOptions::with_name_encoding`], and
+    /// starting the file fails if that takes more than 65535 bytes or the comment can't be
+    /// represented. The default is no comment.
+    #[must_use]
+    pub const fn with_comment(mut self, comment: &'k str) -> Self {
+        self.comment = comment;
+        self
+    }
+
+    /// Set the lowest "version needed to extract" written for the new file, as the version of
+    /// the ZIP specification times 10 (so `45` for version 4.5).
+    ///
+    /// The version written is the higher of this and the version the file needs for the features
+    /// it uses, such as 4.5 for ZIP64 or 5.1 for AES encryption, so readers that check it never
+    /// see one that's too low. Starting the file fails if this is above 63, since 6.3 is the latest
+    /// version of the specification. By default, only the features used are taken into account.
+    #[must_use]
+    pub const fn min_version(mut self, version: u16) -> Self {
+        self.min_version = version;
+        self
+    }
+
+    /// Compress the new file with `compressor`, and record `method` as its compression method
+    ///
+    /// This is for methods this crate doesn't implement, or other implementations of ones it does.
+    /// Reading the file back needs a matching [`Decompressor`](crate::Decompressor), registered
+    /// with [`ZipArchive::register_decompressor`]. Setting
+    /// [`FileOptions::compression_method`] afterwards goes back to a built-in method.
+    #[must_use]
+    pub const fn with_custom_compressor(
+        mut self,
+        method: u16,
+        compressor: &'k dyn Compressor,
+    ) -> Self {
+        #[allow(deprecated)]
+        {
+            self.compression_method = CompressionMethod::from_u16(method);
+        }
+        self.custom_compressor = Some(CustomCompressor(compressor));
+        self
+    }
+
     /// Returns the compression level currently set.
     pub const fn get_compression_level(&self) -> Option<i64> {
         self.compression_level
@@ -384,9 +948,64 @@ impl<'k, T: FileOptionExtension> FileOptions<'k, T> {
         self.alignment = alignment;
         self
     }
+
+    /// The same options, with the extra data of `T` if it has any
+    fn into_full(self) -> FullFileOptions<'k> {
+        FileOptions {
+            compression_method: self.compression_method,
+            compression_level: self.compression_level,
+            last_modified_time: self.last_modified_time,
+            permissions: self.permissions,
+            zip64: self.zip64,
+            encrypt_with: self.encrypt_with,
+            extended_options: ExtendedFileOptions {
+                extra_data: self
+                    .extended_options
+                    .extra_data()
+                    .cloned()
+                    .unwrap_or_default(),
+                central_extra_data: self
+                    .extended_options
+                    .central_extra_data()
+                    .cloned()
+                    .unwrap_or_default(),
+            },
+            alignment: self.alignment,
+            #[cfg(feature = "deflate-zopfli")]
+            zopfli_buffer_size: self.zopfli_buffer_size,
+            #[cfg(feature = "zstd")]
+            zstd_dictionary: self.zstd_dictionary,
+            #[cfg(feature = "brotli")]
+            brotli_window: self.brotli_window,
+            #[cfg(feature = "aes-crypto")]
+            aes_iterations: self.aes_iterations,
+            extended_timestamp: self.extended_timestamp,
+            ntfs_times: self.ntfs_times,
+            unix_ownership: self.unix_ownership,
+            hardlink_target: self.hardlink_target,
+            compression_method_set: self.compression_method_set,
+            name_encoding: self.name_encoding,
+            dos_attributes: self.dos_attributes,
+            unicode_path_extra_field: self.unicode_path_extra_field,
+            unicode_comment_extra_field: self.unicode_comment_extra_field,
+            external_attributes: self.external_attributes,
+            system: self.system,
+            version_made_by: self.version_made_by,
+            data_descriptor: self.data_descriptor,
+            normalize_separators: self.normalize_separators,
+            comment: self.comment,
+            min_version: self.min_version,
+            custom_compressor: self.custom_compressor,
+        }
+    }
 }
 impl<'k> FileOptions<'k, ExtendedFileOptions> {
     /// Adds an extra data field.
+    ///
+    /// The field is written to both the local header and the central directory, unless
+    /// `central_only` is true. Fails if the extra data for this file would exceed 65535 bytes, or
+    /// if `header_id` is reserved for a field this crate writes itself and the `unreserved`
+    /// feature isn't enabled.
     pub fn add_extra_data(
         &mut self,
         header_id: u16,
@@ -448,24 +1067,68 @@ impl<'k, T: FileOptionExtension> Default for FileOptions<'k, T> {
             #[cfg(not(feature = "time"))]
             last_modified_time: DateTime::default(),
             permissions: None,
-            large_file: false,
+            zip64: Zip64Mode::Auto,
             encrypt_with: None,
             extended_options: T::default(),
             alignment: 1,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: Some(1 << 15),
+            #[cfg(feature = "zstd")]
+            zstd_dictionary: None,
+            #[cfg(feature = "brotli")]
+            brotli_window: BROTLI_DEFAULT_WINDOW,
+            #[cfg(feature = "aes-crypto")]
+            aes_iterations: DEFAULT_ITERATION_COUNT,
+            extended_timestamp: None,
+            ntfs_times: None,
+            unix_ownership: None,
+            hardlink_target: None,
+            compression_method_set: false,
+            name_encoding: NameEncoding::Utf8,
+            dos_attributes: DosAttributes::default(),
+            unicode_path_extra_field: false,
+            unicode_comment_extra_field: false,
+            external_attributes: None,
+            system: System::Unix,
+            version_made_by: DEFAULT_VERSION,
+            data_descriptor: false,
+            normalize_separators: false,
+            comment: "",
+            min_version: 0,
+            custom_compressor: None,
         }
     }
 }
 
 impl<W: Write + Seek> Write for ZipWriter<W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(pending) = &mut self.pending_auto_file {
+            let sample_size = self.compression_heuristic.get_sample_size();
+            let count = buf
+                .len()
+                .min(sample_size.saturating_sub(pending.sample.len()));
+            pending.sample.extend_from_slice(&buf[..count]);
+            if pending.sample.len() >= sample_size {
+                self.start_pending_auto_file()?;
+            }
+            if count > 0 || buf.is_empty() {
+                return Ok(count);
+            }
+        }
         if !self.writing_to_file {
             return Err(io::Error::new(
                 io::ErrorKind::Other,
                 "No file has been started",
             ));
         }
+        if let Some(pending) = &mut self.pending_sized_file {
+            pending.buffer.extend_from_slice(buf);
+            let largest_threshold = self.method_by_size.last().map_or(0, |&(size, _)| size);
+            if pending.buffer.len() as u64 >= largest_threshold {
+                self.start_pending_sized_file()?;
+            }
+            return Ok(buf.len());
+        }
         if buf.is_empty() {
             return Ok(0);
         }
@@ -474,7 +1137,7 @@ impl<W: Write + Seek> Write for ZipWriter<W> {
                 let write_result = w.write(buf);
                 if let Ok(count) = write_result {
                     self.stats.update(&buf[0..count]);
-                    if self.stats.bytes_written > spec::ZIP64_BYTES_THR
+                    if self.stats.bytes_written >= spec::ZIP64_BYTES_THR
                         && !self.files.last_mut().unwrap().1.large_file
                     {
                         self.abort_file().unwrap();
@@ -507,12 +1170,18 @@ impl<W: Write + Seek> Write for ZipWriter<W> {
 impl ZipWriterStats {
     fn update(&mut self, buf: &[u8]) {
         self.hasher.update(buf);
+        #[cfg(feature = "sha2")]
+        if let Some(content_hasher) = &mut self.content_hasher {
+            sha2::Digest::update(content_hasher, buf);
+        }
         self.bytes_written += buf.len() as u64;
     }
 }
 
 impl<A: Read + Write + Seek> ZipWriter<A> {
     /// Initializes the archive from an existing ZIP archive, making it ready for append.
+    ///
+    /// The archive comment is carried over, and can be replaced with [`ZipWriter::set_comment`].
     pub fn new_append(mut readwriter: A) -> ZipResult<ZipWriter<A>> {
         let (footer, cde_start_pos) = spec::CentralDirectoryEnd::find_and_parse(&mut readwriter)?;
         let metadata = ZipArchive::get_metadata(&mut readwriter, &footer, cde_start_pos)?;
@@ -525,24 +1194,67 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
             comment: footer.zip_file_comment,
             writing_raw: true, // avoid recomputing the last file's header
             flush_on_finish_file: false,
+            raw_copy_buffer_size: DEFAULT_COPY_BUFFER_SIZE,
+            on_finish_file: None,
+            deterministic: false,
+            compression_heuristic: CompressionHeuristic::default(),
+            pending_auto_file: None,
+            method_by_size: Vec::new(),
+            pending_sized_file: None,
+            duplicate_policy: DuplicatePolicy::default(),
+            fold_case_for_duplicates: false,
+            shadowed_files: Vec::new(),
+            dedup_index: None,
+            dedup_stats: DedupStats::default(),
+            appended_directory: None,
+            default_file_options: FullFileOptions::default(),
         })
     }
 
-    /// `flush_on_finish_file` is designed to support a streaming `inner` that may unload flushed
-    /// bytes. It flushes a file's header and body once it starts writing another file. A ZipWriter
-    /// will not try to seek back into where a previous file was written unless
-    /// either [`ZipWriter::abort_file`] is called while [`ZipWriter::is_writing_file`] returns
-    /// false, or [`ZipWriter::deep_copy_file`] is called. In the latter case, it will only need to
-    /// read previously-written files and not overwrite them.
-    ///
-    /// Note: when using an `inner` that cannot overwrite flushed bytes, do not wrap it in a
-    /// [std::io::BufWriter], because that has a [Seek::seek] method that implicitly calls
-    /// [BufWriter::flush], and ZipWriter needs to seek backward to update each file's header with
-    /// the size and checksum after writing the body.
+    /// Like [`ZipWriter::new_append`], but without parsing the existing central directory, which
+    /// makes opening a huge archive much faster.
     ///
-    /// This setting is false by default.
-    pub fn set_flush_on_finish_file(&mut self, flush_on_finish_file: bool) {
-        self.flush_on_finish_file = flush_on_finish_file;
+    /// Only the footer is parsed. The central directory's records are read into memory as they
+    /// are, since the new entries are written over them, and written back ahead of the new
+    /// entries' records by [`ZipWriter::finish`]. So the whole central directory is still held in
+    /// memory from the start, taking as many bytes as it does in the archive, though that's much
+    /// less than [`ZipWriter::new_append`] keeps for each entry. Since this writer doesn't know
+    /// the existing entries, they can't be copied, renamed or removed, and adding an entry with
+    /// the same name as one of them isn't detected regardless of
+    /// [`ZipWriter::set_duplicate_policy`]. If the archive has data prepended to it, the existing
+    /// records' offsets need rewriting, so this falls back to [`ZipWriter::new_append`].
+    pub fn new_append_lazy(mut readwriter: A) -> ZipResult<ZipWriter<A>> {
+        let (footer, cde_start_pos) = spec::CentralDirectoryEnd::find_and_parse(&mut readwriter)?;
+        let dir_info = ZipArchive::find_directory_info(&mut readwriter, &footer, cde_start_pos)?;
+        if dir_info.archive_offset != 0 {
+            return Self::new_append(readwriter);
+        }
+        let directory_end = dir_info
+            .directory_start
+            .checked_add(dir_info.directory_size)
+            .filter(|&end| end <= cde_start_pos)
+            .ok_or(InvalidArchive("Invalid central directory size or offset"))?;
+        readwriter.seek(SeekFrom::Start(dir_info.directory_start))?;
+        let directory_size = directory_end - dir_info.directory_start;
+        let mut records = Vec::new();
+        (&mut readwriter)
+            .take(directory_size)
+            .read_to_end(&mut records)?;
+        if records.len() as u64 != directory_size {
+            return Err(InvalidArchive(
+                "Central directory runs past the end of the archive",
+            ));
+        }
+        readwriter.seek(SeekFrom::Start(dir_info.directory_start))?;
+
+        let mut writer = ZipWriter::new(readwriter);
+        writer.comment = footer.zip_file_comment;
+        writer.writing_raw = true; // avoid recomputing the last file's header
+        writer.appended_directory = Some(AppendedDirectory {
+            records: records.into(),
+            count: dir_info.number_of_files,
+        });
+        Ok(writer)
     }
 }
 
@@ -554,6 +1266,7 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
         let write_position = self.inner.get_plain().stream_position()?;
         let src_index = self.index_by_name(src_name)?;
         let src_data = &self.files[src_index];
+        let comment = src_data.file_comment.clone();
         let data_start = *src_data.data_start.get().unwrap_or(&0);
         let compressed_size = src_data.compressed_size;
         debug_assert!(compressed_size <= write_position - data_start);
@@ -563,6 +1276,7 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
             crc32: src_data.crc32,
             compressed_size,
             uncompressed_size,
+            compression_flags: src_data.compression_flags,
         };
         let mut reader = BufReader::new(ZipFileReader::Raw(find_content(
             src_data,
@@ -580,7 +1294,11 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
                 compression_level: src_data.compression_level,
                 last_modified_time: src_data.last_modified_time,
                 permissions: src_data.unix_mode(),
-                large_file: src_data.large_file,
+                zip64: if src_data.large_file {
+                    Zip64Mode::Always
+                } else {
+                    Zip64Mode::Auto
+                },
                 encrypt_with: None,
                 extended_options: ExtendedFileOptions {
                     extra_data: src_data.extra_field.clone().unwrap_or_default(),
@@ -589,11 +1307,36 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
                 alignment: 1,
                 #[cfg(feature = "deflate-zopfli")]
                 zopfli_buffer_size: None,
+                #[cfg(feature = "zstd")]
+                zstd_dictionary: None,
+                #[cfg(feature = "brotli")]
+                brotli_window: BROTLI_DEFAULT_WINDOW,
+                #[cfg(feature = "aes-crypto")]
+                aes_iterations: DEFAULT_ITERATION_COUNT,
+                extended_timestamp: None,
+                ntfs_times: None,
+                unix_ownership: None,
+                hardlink_target: None,
+                compression_method_set: false,
+                name_encoding: NameEncoding::Utf8,
+                dos_attributes: DosAttributes::from_external_attributes(
+                    src_data.external_attributes,
+                ),
+                unicode_path_extra_field: false,
+                unicode_comment_extra_field: false,
+                external_attributes: None,
+                system: System::Unix,
+                version_made_by: DEFAULT_VERSION,
+                data_descriptor: false,
+                normalize_separators: false,
+                comment: &comment,
+                min_version: src_data.min_version_needed,
+                custom_compressor: None,
             };
             if let Some(perms) = src_data.unix_mode() {
                 options = options.unix_permissions(perms);
             }
-            Self::normalize_options(&mut options);
+            normalize_options(&mut options);
             self.start_entry(dest_name, options, Some(raw_values))?;
         } else {
             let mut options = FileOptions::<()> {
@@ -601,17 +1344,46 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
                 compression_level: src_data.compression_level,
                 last_modified_time: src_data.last_modified_time,
                 permissions: src_data.unix_mode(),
-                large_file: src_data.large_file,
+                zip64: if src_data.large_file {
+                    Zip64Mode::Always
+                } else {
+                    Zip64Mode::Auto
+                },
                 encrypt_with: None,
                 extended_options: (),
                 alignment: 1,
                 #[cfg(feature = "deflate-zopfli")]
                 zopfli_buffer_size: None,
+                #[cfg(feature = "zstd")]
+                zstd_dictionary: None,
+                #[cfg(feature = "brotli")]
+                brotli_window: BROTLI_DEFAULT_WINDOW,
+                #[cfg(feature = "aes-crypto")]
+                aes_iterations: DEFAULT_ITERATION_COUNT,
+                extended_timestamp: None,
+                ntfs_times: None,
+                unix_ownership: None,
+                hardlink_target: None,
+                compression_method_set: false,
+                name_encoding: NameEncoding::Utf8,
+                dos_attributes: DosAttributes::from_external_attributes(
+                    src_data.external_attributes,
+                ),
+                unicode_path_extra_field: false,
+                unicode_comment_extra_field: false,
+                external_attributes: None,
+                system: System::Unix,
+                version_made_by: DEFAULT_VERSION,
+                data_descriptor: false,
+                normalize_separators: false,
+                comment: &comment,
+                min_version: src_data.min_version_needed,
+                custom_compressor: None,
             };
             if let Some(perms) = src_data.unix_mode() {
                 options = options.unix_permissions(perms);
             }
-            Self::normalize_options(&mut options);
+            normalize_options(&mut options);
             self.start_entry(dest_name, options, Some(raw_values))?;
         }
 
@@ -634,37 +1406,290 @@ impl<A: Read + Write + Seek> ZipWriter<A> {
         src_path: T,
         dest_path: U,
     ) -> ZipResult<()> {
-        self.deep_copy_file(&path_to_string(src_path), &path_to_string(dest_path))
+        self.deep_copy_file(
+            &path_to_string(src_path, false),
+            &path_to_string(dest_path, false),
+        )
     }
 
-    /// Write the zip file into the backing stream, then produce a readable archive of that data.
+    /// Removes an entry from the archive, which is most useful after [`ZipWriter::new_append`].
     ///
-    /// This method avoids parsing the central directory records at the end of the stream for
-    /// a slight performance improvement over running [`ZipArchive::new()`] on the output of
-    /// [`Self::finish()`].
+    /// The surviving entries aren't recompressed: the raw header-and-data blocks that follow the
+    /// removed entry (including any data descriptors) are moved back to fill its space, and their
+    /// offsets are updated for the central directory that [`ZipWriter::finish`] writes. If a
+    /// shallow copy of the entry remains, its data is left in place. Since [`Write`] and [`Seek`]
+    /// offer no way to truncate `inner`, any space that isn't reused by entries written afterward
+    /// remains as unused bytes before the central directory.
     ///
-    ///```
-    /// # fn main() -> Result<(), zip::result::ZipError> {
-    /// use std::io::{Cursor, prelude::*};
-    /// use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+    /// Returns [`ZipError::FileNotFound`] if there's no entry named `name`.
+    pub fn remove_file(&mut self, name: &str) -> ZipResult<()> {
+        self.finish_file()?;
+        let index = self.index_by_name(name)?;
+        let (_, removed) = self.files.shift_remove_index(index).unwrap();
+        // Entries replaced under `DuplicatePolicy::Overwrite` can no longer be restored by
+        // `abort_file`, so their space can be reused like the removed entry's
+        self.shadowed_files.retain(|shadowed| shadowed.keep);
+        let removed_start = removed.header_start;
+        if self
+            .files
+            .values()
+            .chain(self.shadowed_files.iter().map(|shadowed| &shadowed.data))
+            .any(|file| file.header_start == removed_start)
+        {
+            return Ok(());
+        }
+        let writer = self.inner.get_plain();
+        let end = writer.stream_position()?;
+        let next_start = self
+            .files
+            .values()
+            .chain(self.shadowed_files.iter().map(|shadowed| &shadowed.data))
+            .map(|file| file.header_start)
+            .filter(|&start| start > removed_start && start < end)
+            .min()
+            .unwrap_or(end);
+        let shift = next_start - removed_start;
+
+        let mut buffer = vec![0u8; 1 << 16];
+        let mut read_position = next_start;
+        while read_position < end {
+            let len = buffer.len().min((end - read_position) as usize);
+            writer.seek(SeekFrom::Start(read_position))?;
+            writer.read_exact(&mut buffer[..len])?;
+            writer.seek(SeekFrom::Start(read_position - shift))?;
+            writer.write_all(&buffer[..len])?;
+            read_position += len as u64;
+        }
+        writer.seek(SeekFrom::Start(end - shift))?;
+
+        for file in self.files.values_mut().chain(
+            self.shadowed_files
+                .iter_mut()
+                .map(|shadowed| &mut shadowed.data),
+        ) {
+            if file.header_start < next_start {
+                continue;
+            }
+            file.header_start -= shift;
+            if let Some(extra_data_start) = file.extra_data_start.as_mut() {
+                *extra_data_start -= shift;
+            }
+            if let Some(&data_start) = file.data_start.get() {
+                file.data_start = OnceLock::from(data_start - shift);
+            }
+        }
+        Ok(())
+    }
+
+    /// Renames many entries at once, which is most useful after [`ZipWriter::new_append`].
     ///
-    /// let buf = Cursor::new(Vec::new());
-    /// let mut zip = ZipWriter::new(buf);
-    /// let options = SimpleFileOptions::default();
-    /// zip.start_file("a.txt", options)?;
-    /// zip.write_all(b"hello\n")?;
+    /// `rename` is called with the name of each entry, in order, and returns its new name, or
+    /// `None` to keep the one it has. Names are stored in both the local header and the central
+    /// directory, so the local headers are rewritten too: the data after a renamed entry's name is
+    /// moved by the difference in length, as [`ZipWriter::remove_file`] moves the entries after a
+    /// removed one, and nothing is recompressed. New names are stored as UTF-8. Since a shallow
+    /// copy (see [`ZipWriter::shallow_copy_file`]) shares the local header of the entry it was
+    /// copied from, renaming either of them only changes its name in the central directory.
     ///
-    /// let mut zip = zip.finish_into_readable()?;
-    /// let mut s: String = String::new();
-    /// zip.by_name("a.txt")?.read_to_string(&mut s)?;
-    /// assert_eq!(s, "hello\n");
+    /// Returns [`ZipError::DuplicateName`] before changing anything if two entries would end up
+    /// with the same name.
+    ///
+    /// ```
+    /// # fn main() -> zip::result::ZipResult<()> {
+    /// use std::io::{Cursor, Write};
+    /// use zip::write::SimpleFileOptions;
+    /// use zip::{ZipArchive, ZipWriter};
+    ///
+    /// let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    /// zip.start_file("src/a.txt", SimpleFileOptions::default())?;
+    /// zip.write_all(b"a")?;
+    /// zip.start_file("README", SimpleFileOptions::default())?;
+    /// zip.write_all(b"readme")?;
+    /// let mut zip = ZipWriter::new_append(zip.finish()?)?;
+    /// zip.rename_entries(|name| Some(format!("package/{}", name.strip_prefix("src/")?)))?;
+    /// let archive = ZipArchive::new(zip.finish()?)?;
+    /// assert_eq!(archive.file_names().collect::<Vec<_>>(), ["package/a.txt", "README"]);
     /// # Ok(())
     /// # }
-    ///```
-    pub fn finish_into_readable(mut self) -> ZipResult<ZipArchive<A>> {
+    /// ```
+    pub fn rename_entries<F>(&mut self, mut rename: F) -> ZipResult<()>
+    where
+        F: FnMut(&str) -> Option<String>,
+    {
+        self.finish_file()?;
+        let new_names: Vec<Option<Box<str>>> = self
+            .files
+            .keys()
+            .map(|name| rename(name).filter(|new| **new != **name).map(Into::into))
+            .collect();
+        if new_names.iter().all(Option::is_none) {
+            return Ok(());
+        }
+        let mut names = HashSet::new();
+        for (name, new_name) in self.files.keys().zip(&new_names) {
+            let name = new_name.as_ref().unwrap_or(name);
+            if name.len() > u16::MAX as usize {
+                return Err(InvalidArchive("File name is longer than 65535 bytes"));
+            }
+            if !names.insert(name) {
+                return Err(ZipError::DuplicateName(name.clone()));
+            }
+        }
+
+        // Entries that share a local header with another one are only renamed in the central
+        // directory
+        let mut header_users = HashMap::<u64, usize>::new();
+        for file in self
+            .files
+            .values()
+            .chain(self.shadowed_files.iter().map(|shadowed| &shadowed.data))
+        {
+            *header_users.entry(file.header_start).or_default() += 1;
+        }
+        // The new name of the entry whose local header starts at each offset, and whether the
+        // header needs the UTF-8 flag, which is set as `start_entry` sets it
+        let renamed_headers: HashMap<u64, (&str, bool)> = self
+            .files
+            .values()
+            .zip(&new_names)
+            .filter(|(file, _)| header_users[&file.header_start] == 1)
+            .filter_map(|(file, new_name)| {
+                let new_name = new_name.as_deref()?;
+                let comment = &file.file_comment_raw;
+                let utf8_flag = sets_utf8_flag(true, new_name.as_bytes(), comment);
+                Some((file.header_start, (new_name, utf8_flag)))
+            })
+            .collect();
+        let writer = self.inner.get_plain();
+        let end = writer.stream_position()?;
+        let mut header_starts: Vec<u64> = header_users.into_keys().collect();
+        header_starts.sort_unstable();
+        let Some(first) = header_starts
+            .iter()
+            .position(|start| renamed_headers.contains_key(start))
+        else {
+            self.set_new_names(new_names);
+            return Ok(());
+        };
+
+        // Lay out the local headers and everything after them again. For each header, note where
+        // it moves to, and an offset after which everything moves the same way, with where that
+        // offset moves to.
+        let mut pieces = Vec::new();
+        let mut moves = HashMap::new();
+        let mut new_position = header_starts[first];
+        for (index, &header_start) in header_starts.iter().enumerate().skip(first) {
+            let next_start = header_starts.get(index + 1).copied().unwrap_or(end);
+            let Some(&(new_name, utf8_flag)) = renamed_headers.get(&header_start) else {
+                moves.insert(header_start, (new_position, header_start, new_position));
+                pieces.push(Splice::Copy(header_start..next_start));
+                new_position += next_start - header_start;
+                continue;
+            };
+            let mut header = [0; 30];
+            writer.seek(SeekFrom::Start(header_start))?;
+            writer.read_exact(&mut header)?;
+            if header[..4] != spec::LOCAL_FILE_HEADER_SIGNATURE.to_le_bytes() {
+                return Err(InvalidArchive("Invalid local file header"));
+            }
+            let old_name_end =
+                header_start + 30 + u16::from_le_bytes([header[26], header[27]]) as u64;
+            let mut flags = u16::from_le_bytes([header[6], header[7]]) & !(1 << 11);
+            if utf8_flag {
+                flags |= 1 << 11;
+            }
+            header[6..8].copy_from_slice(&flags.to_le_bytes());
+            header[26..28].copy_from_slice(&(new_name.len() as u16).to_le_bytes());
+            let mut literal = header.to_vec();
+            literal.extend_from_slice(new_name.as_bytes());
+            let new_name_end = new_position + literal.len() as u64;
+            moves.insert(header_start, (new_position, old_name_end, new_name_end));
+            pieces.push(Splice::Bytes(literal));
+            pieces.push(Splice::Copy(old_name_end..next_start));
+            new_position = new_name_end + (next_start - old_name_end);
+        }
+        let new_end = splice(writer, header_starts[first], end, pieces)?;
+        writer.seek(SeekFrom::Start(new_end))?;
+
+        for file in self.files.values_mut().chain(
+            self.shadowed_files
+                .iter_mut()
+                .map(|shadowed| &mut shadowed.data),
+        ) {
+            let Some(&(new_header_start, old_base, new_base)) = moves.get(&file.header_start)
+            else {
+                continue;
+            };
+            let shifted = |position: u64| position - old_base + new_base;
+            file.header_start = new_header_start;
+            if let Some(extra_data_start) = file.extra_data_start.as_mut() {
+                *extra_data_start = shifted(*extra_data_start);
+            }
+            if let Some(&data_start) = file.data_start.get() {
+                file.data_start = OnceLock::from(shifted(data_start));
+            }
+        }
+        self.set_new_names(new_names);
+        Ok(())
+    }
+
+    /// Give the entries the names chosen by [`ZipWriter::rename_entries`], in the same order
+    fn set_new_names(&mut self, new_names: Vec<Option<Box<str>>>) {
+        let mut renamed = HashMap::new();
+        self.files = mem::take(&mut self.files)
+            .into_iter()
+            .zip(new_names)
+            .map(|((name, mut file), new_name)| {
+                let Some(new_name) = new_name else {
+                    return (name, file);
+                };
+                file.file_name = new_name.clone();
+                file.file_name_raw = new_name.as_bytes().into();
+                file.is_utf8 = true;
+                renamed.insert(name, new_name.clone());
+                (new_name, file)
+            })
+            .collect();
+        if let Some(dedup_index) = &mut self.dedup_index {
+            for name in dedup_index.values_mut() {
+                if let Some(new_name) = renamed.get(name) {
+                    *name = new_name.clone();
+                }
+            }
+        }
+    }
+
+    /// Write the zip file into the backing stream, then produce a readable archive of that data.
+    ///
+    /// This method avoids parsing the central directory records at the end of the stream for
+    /// a slight performance improvement over running [`ZipArchive::new()`] on the output of
+    /// [`Self::finish()`].
+    ///
+    ///```
+    /// # fn main() -> Result<(), zip::result::ZipError> {
+    /// use std::io::{Cursor, prelude::*};
+    /// use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+    ///
+    /// let buf = Cursor::new(Vec::new());
+    /// let mut zip = ZipWriter::new(buf);
+    /// let options = SimpleFileOptions::default();
+    /// zip.start_file("a.txt", options)?;
+    /// zip.write_all(b"hello\n")?;
+    ///
+    /// let mut zip = zip.finish_into_readable()?;
+    /// let mut s: String = String::new();
+    /// zip.by_name("a.txt")?.read_to_string(&mut s)?;
+    /// assert_eq!(s, "hello\n");
+    /// # Ok(())
+    /// # }
+    ///```
+    pub fn finish_into_readable(mut self) -> ZipResult<ZipArchive<A>> {
         let central_start = self.finalize()?;
         let inner = mem::replace(&mut self.inner, Closed).unwrap();
         let comment = mem::take(&mut self.comment);
+        if self.appended_directory.is_some() {
+            return ZipArchive::new_lazy(inner);
+        }
         let files = mem::take(&mut self.files);
         let archive = ZipArchive::from_finalized_writer(files, comment, inner, central_start)?;
         Ok(archive)
@@ -686,7 +1711,116 @@ impl<W: Write + Seek> ZipWriter<W> {
             writing_raw: false,
             comment: Box::new([]),
             flush_on_finish_file: false,
+            raw_copy_buffer_size: DEFAULT_COPY_BUFFER_SIZE,
+            on_finish_file: None,
+            deterministic: false,
+            compression_heuristic: CompressionHeuristic::default(),
+            pending_auto_file: None,
+            method_by_size: Vec::new(),
+            pending_sized_file: None,
+            duplicate_policy: DuplicatePolicy::default(),
+            fold_case_for_duplicates: false,
+            shadowed_files: Vec::new(),
+            dedup_index: None,
+            dedup_stats: DedupStats::default(),
+            appended_directory: None,
+            default_file_options: FullFileOptions::default(),
+        }
+    }
+
+    /// `flush_on_finish_file` is designed to support a streaming `inner` that may unload flushed
+    /// bytes. It flushes a file's header and body once it starts writing another file. A ZipWriter
+    /// will not try to seek back into where a previous file was written unless
+    /// either [`ZipWriter::abort_file`] is called while [`ZipWriter::is_writing_file`] returns
+    /// false, or [`ZipWriter::deep_copy_file`] is called. In the latter case, it will only need to
+    /// read previously-written files and not overwrite them.
+    ///
+    /// Note: when using an `inner` that cannot overwrite flushed bytes, do not wrap it in a
+    /// [std::io::BufWriter], because that has a [Seek::seek] method that implicitly calls
+    /// [BufWriter::flush], and ZipWriter needs to seek backward to update each file's header with
+    /// the size and checksum after writing the body.
+    ///
+    /// The compressor for each file is finished before it's flushed, so this doesn't affect
+    /// compression. See [`ZipWriter::set_sync_data_on_finish_file`] to make each file durable
+    /// when `inner` is a [`File`](std::fs::File).
+    ///
+    /// This setting is false by default.
+    pub fn set_flush_on_finish_file(&mut self, flush_on_finish_file: bool) {
+        self.flush_on_finish_file = flush_on_finish_file;
+    }
+
+    /// Set the size of the buffer that [`ZipWriter::raw_copy_file`] and the other raw copying
+    /// methods, including [`ZipWriter::merge_archive`], copy compressed data through. The default
+    /// is 8 KiB.
+    ///
+    /// A larger buffer means fewer, larger reads and writes, which can be much faster on storage
+    /// with high latency per operation, at the cost of the memory it takes while copying. Fails if
+    /// `buffer_size` is 0.
+    pub fn set_raw_copy_buffer_size(&mut self, buffer_size: usize) -> ZipResult<()> {
+        if buffer_size == 0 {
+            return Err(ZipError::Io(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "Raw copy buffer size must be at least 1 byte",
+            )));
+        }
+        self.raw_copy_buffer_size = buffer_size;
+        Ok(())
+    }
+
+    /// Set the options that [`ZipWriter::start_file_with_defaults`] starts files with
+    ///
+    /// They're [`FileOptions::default`] until this is called. To change some of them for one
+    /// file, pass [`ZipWriter::default_file_options`] with those changes to
+    /// [`ZipWriter::start_file`].
+    pub fn set_default_file_options<T: FileOptionExtension>(
+        &mut self,
+        options: FileOptions<'static, T>,
+    ) {
+        self.default_file_options = options.into_full();
+    }
+
+    /// The options set with [`ZipWriter::set_default_file_options`]
+    pub fn default_file_options(&self) -> FullFileOptions<'static> {
+        self.default_file_options.clone()
+    }
+
+    /// When `deterministic` is true, every entry started afterward has its last-modified time set
+    /// to [`DateTime::default()`] and no extended timestamp, so that writing the same files with
+    /// the same options always produces the same bytes. The other header fields already don't
+    /// depend on the host: the "version made by" is always Unix, extra fields are written in a
+    /// fixed order, and permissions come from [`FileOptions::unix_permissions`] or fixed defaults
+    /// rather than the umask.
+    ///
+    /// Encrypted entries are still random, and entries copied from another archive keep their
+    /// original headers.
+    ///
+    /// This setting is false by default.
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    /// Write `stub` to the output ahead of the archive, such as the executable part of a
+    /// self-extracting archive
+    ///
+    /// This must be called before any entries are added; calling it more than once writes each
+    /// stub after the last. Offsets in the archive are counted from the start of the output, stub
+    /// included, as self-extractors and other ZIP readers expect.
+    /// [`ZipArchive::prepended_data_len`] returns the stub's length when the result is read back.
+    pub fn set_stub(&mut self, stub: &[u8]) -> ZipResult<()> {
+        if self.inner.is_closed() {
+            return Err(ZipError::Io(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "ZipWriter was already closed",
+            )));
+        }
+        if self.writing_to_file || !self.files.is_empty() || self.appended_directory.is_some() {
+            return Err(ZipError::Io(io::Error::new(
+                io::ErrorKind::Other,
+                "The stub must be written before any files are added",
+            )));
         }
+        self.inner.get_plain().write_all(stub)?;
+        Ok(())
     }
 
     /// Returns true if a file is currently open for writing.
@@ -695,6 +1829,10 @@ impl<W: Write + Seek> ZipWriter<W> {
     }
 
     /// Set ZIP archive comment.
+    ///
+    /// The comment can be at most 65535 bytes long; if it's longer, [`ZipWriter::finish`] fails.
+    /// An archive opened with [`ZipWriter::new_append`] keeps its existing comment unless this is
+    /// called, and setting an empty comment removes it.
     pub fn set_comment<S>(&mut self, comment: S)
     where
         S: Into<Box<str>>,
@@ -705,7 +1843,8 @@ impl<W: Write + Seek> ZipWriter<W> {
     /// Set ZIP archive comment.
     ///
     /// This sets the raw bytes of the comment. The comment
-    /// is typically expected to be encoded in UTF-8
+    /// is typically expected to be encoded in UTF-8. As with [`ZipWriter::set_comment`], it can
+    /// be at most 65535 bytes long.
     pub fn set_raw_comment(&mut self, comment: Box<[u8]>) {
         self.comment = comment;
     }
@@ -727,7 +1866,7 @@ impl<W: Write + Seek> ZipWriter<W> {
     fn start_entry<S, SToOwned, T: FileOptionExtension>(
         &mut self,
         name: S,
-        options: FileOptions<T>,
+        mut options: FileOptions<T>,
         raw_values: Option<ZipRawValues>,
     ) -> ZipResult<()>
     where
@@ -735,11 +1874,18 @@ impl<W: Write + Seek> ZipWriter<W> {
         SToOwned: Into<Box<str>>,
     {
         self.finish_file()?;
+        if self.deterministic {
+            options.last_modified_time = DateTime::default();
+            options.extended_timestamp = None;
+            options.ntfs_times = None;
+        }
 
+        let using_data_descriptor = options.data_descriptor && raw_values.is_none();
         let raw_values = raw_values.unwrap_or(ZipRawValues {
             crc32: 0,
             compressed_size: 0,
             uncompressed_size: 0,
+            compression_flags: 0,
         });
 
         #[allow(unused_mut)]
@@ -754,6 +1900,16 @@ impl<W: Write + Seek> ZipWriter<W> {
                 0x01, 0x99, 0x07, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
             ];
 
+            if options.aes_iterations == 0 {
+                return Err(InvalidArchive("AES iteration count must be nonzero"));
+            }
+            let mut iterations_extra_data = Vec::new();
+            if options.aes_iterations != DEFAULT_ITERATION_COUNT {
+                iterations_extra_data.write_u16_le(AES_ITERATIONS_EXTRA_FIELD_ID)?;
+                iterations_extra_data.write_u16_le(4)?;
+                iterations_extra_data.write_u32_le(options.aes_iterations)?;
+            }
+
             let extra_data = extra_field.get_or_insert_with(Default::default);
             let extra_data = match Arc::get_mut(extra_data) {
                 Some(exclusive) => exclusive,
@@ -763,7 +1919,9 @@ impl<W: Write + Seek> ZipWriter<W> {
                 }
             };
 
-            if extra_data.len() + AES_DUMMY_EXTRA_DATA.len() > u16::MAX as usize {
+            if extra_data.len() + AES_DUMMY_EXTRA_DATA.len() + iterations_extra_data.len()
+                > u16::MAX as usize
+            {
                 let _ = self.abort_file();
                 return Err(InvalidArchive("Extra data field is too large"));
             }
@@ -773,10 +1931,39 @@ impl<W: Write + Seek> ZipWriter<W> {
             // We write zero bytes for now since we need to update the data when finishing the
             // file.
             extra_data.write_all(&AES_DUMMY_EXTRA_DATA)?;
+            extra_data.write_all(&iterations_extra_data)?;
         }
 
+        let EntryFields {
+            name,
+            file_name_raw,
+            file_comment_raw,
+            local_only_extra_field,
+            central_extra_field,
+            extra_fields,
+        } = EntryFields::new(name.into(), &options)?;
+
         {
             let header_start = self.inner.get_plain().stream_position()?;
+            // A size of exactly 0xFFFFFFFF would be taken for the marker that it's in a ZIP64 field
+            let large_file = match options.zip64 {
+                Zip64Mode::Always => true,
+                Zip64Mode::Auto => {
+                    raw_values.compressed_size.max(raw_values.uncompressed_size)
+                        >= spec::ZIP64_BYTES_THR
+                }
+                Zip64Mode::Never => {
+                    if raw_values.compressed_size.max(raw_values.uncompressed_size)
+                        >= spec::ZIP64_BYTES_THR
+                        || header_start >= spec::ZIP64_BYTES_THR
+                    {
+                        return Err(InvalidArchive(
+                            "This file needs ZIP64, but Zip64Mode::Never was chosen",
+                        ));
+                    }
+                    false
+                }
+            };
 
             let permissions = options.permissions.unwrap_or(0o100644);
             let (compression_method, aes_mode) = match options.encrypt_with {
@@ -788,31 +1975,43 @@ impl<W: Write + Seek> ZipWriter<W> {
                 _ => (options.compression_method, None),
             };
             let file = ZipFileData {
-                system: System::Unix,
-                version_made_by: DEFAULT_VERSION,
+                system: options.system,
+                version_made_by: options.version_made_by,
                 encrypted: options.encrypt_with.is_some(),
-                using_data_descriptor: false,
+                using_data_descriptor,
+                is_utf8: options.name_encoding.is_utf8(),
                 compression_method,
+                compression_flags: raw_values.compression_flags,
                 compression_level: options.compression_level,
                 last_modified_time: options.last_modified_time,
                 crc32: raw_values.crc32,
                 compressed_size: raw_values.compressed_size,
                 uncompressed_size: raw_values.uncompressed_size,
-                file_name: name.to_owned().into(), // Never used for saving, but used as map key in insert_file_data()
-                file_name_raw: name.into().bytes().collect(),
+                file_name_raw,
+                file_name: name, // Never used for saving, but used as map key in insert_file_data()
                 extra_field,
-                central_extra_field: options.extended_options.central_extra_data().cloned(),
-                file_comment: String::with_capacity(0).into_boxed_str(),
+                central_extra_field,
+                file_comment: options.comment.into(),
+                file_comment_raw,
                 header_start,
                 extra_data_start: None,
                 data_start: OnceLock::new(),
                 central_header_start: 0,
-                external_attributes: permissions << 16,
-                large_file: options.large_file,
+                external_attributes: options
+                    .external_attributes
+                    .unwrap_or(permissions << 16 | options.dos_attributes.to_external_attributes()),
+                large_file,
                 aes_mode,
                 aes_extra_data_start,
-
-                extra_fields: Vec::new(),
+                #[cfg(feature = "aes-crypto")]
+                aes_iterations: (options.aes_iterations != DEFAULT_ITERATION_COUNT)
+                    .then_some(options.aes_iterations),
+                #[cfg(not(feature = "aes-crypto"))]
+                aes_iterations: None,
+
+                strong_encryption_flag: false,
+                min_version_needed: options.min_version,
+                extra_fields,
             };
             let index = self.insert_file_data(file)?;
             let file = &mut self.files[index];
@@ -822,10 +2021,16 @@ impl<W: Write + Seek> ZipWriter<W> {
             // version needed to extract
             writer.write_u16_le(file.version_needed())?;
             // general purpose bit flag
-            let is_utf8 = std::str::from_utf8(&file.file_name_raw).is_ok();
-            let is_ascii = file.file_name_raw.is_ascii();
-            let flag = if is_utf8 && !is_ascii { 1u16 << 11 } else { 0 }
-                | if file.encrypted { 1u16 << 0 } else { 0 };
+            let utf8_flag =
+                sets_utf8_flag(file.is_utf8, &file.file_name_raw, &file.file_comment_raw);
+            let flag = if utf8_flag { 1u16 << 11 } else { 0 }
+                | if file.encrypted { 1u16 << 0 } else { 0 }
+                | if file.using_data_descriptor {
+                    1u16 << 3
+                } else {
+                    0
+                }
+                | file.compression_flags;
             writer.write_u16_le(flag)?;
             // Compression method
             #[allow(deprecated)]
@@ -846,7 +2051,7 @@ impl<W: Write + Seek> ZipWriter<W> {
             // file name length
             writer.write_u16_le(file.file_name_raw.len() as u16)?;
             // extra field length
-            let mut extra_field_length = file.extra_field_len();
+            let mut extra_field_length = file.extra_field_len() + local_only_extra_field.len();
             if file.large_file {
                 extra_field_length += 20;
             }
@@ -866,6 +2071,7 @@ impl<W: Write + Seek> ZipWriter<W> {
                 file.extra_data_start = Some(writer.stream_position()?);
                 writer.write_all(extra_field)?;
             }
+            writer.write_all(&local_only_extra_field)?;
             let mut header_end = writer.stream_position()?;
             if options.alignment > 1 {
                 let align = options.alignment as u64;
@@ -905,11 +2111,12 @@ impl<W: Write + Seek> ZipWriter<W> {
             }
             match options.encrypt_with {
                 #[cfg(feature = "aes-crypto")]
-                Some(EncryptWith::Aes { mode, password }) => {
+                Some(EncryptWith::Aes { mode, password, .. }) => {
                     let aeswriter = AesWriter::new(
                         mem::replace(&mut self.inner, GenericZipWriter::Closed).unwrap(),
                         mode,
                         password.as_bytes(),
+                        options.aes_iterations,
                     )?;
                     self.inner = GenericZipWriter::Storer(MaybeEncrypted::Aes(aeswriter));
                 }
@@ -933,13 +2140,60 @@ impl<W: Write + Seek> ZipWriter<W> {
             self.writing_to_file = true;
             self.stats.bytes_written = 0;
             self.stats.hasher = Hasher::new();
+            self.stats.compression_method_chosen_late = false;
+            // Encrypted data can't be shared, since each entry may have its own password
+            #[cfg(feature = "sha2")]
+            {
+                self.stats.content_hasher = self
+                    .dedup_index
+                    .as_ref()
+                    .filter(|_| options.encrypt_with.is_none() && !self.flush_on_finish_file)
+                    .map(|_| sha2::Digest::new());
+            }
+            self.stats.aes_vendor_version = match options.encrypt_with {
+                #[cfg(feature = "aes-crypto")]
+                Some(EncryptWith::Aes { vendor_version, .. }) => vendor_version,
+                _ => None,
+            };
         }
         Ok(())
     }
 
+    /// The indices in `files` of the entries that a new entry named `name` would duplicate, which
+    /// includes names that only differ by case if [`ZipWriter::set_duplicate_case_folding`] is on
+    fn duplicates_of(&self, name: &str) -> Vec<usize> {
+        if self.fold_case_for_duplicates {
+            self.files
+                .keys()
+                .enumerate()
+                .filter(|(_, existing)| existing.eq_ignore_ascii_case(name))
+                .map(|(index, _)| index)
+                .collect()
+        } else {
+            self.files.get_index_of(name).into_iter().collect()
+        }
+    }
+
     fn insert_file_data(&mut self, file: ZipFileData) -> ZipResult<usize> {
-        if self.files.contains_key(&file.file_name) {
-            return Err(InvalidArchive("Duplicate filename"));
+        let existing: Vec<usize> = match self.duplicate_policy {
+            // Names that only differ by case can both stay in `files`
+            DuplicatePolicy::Allow => self
+                .files
+                .get_index_of(&file.file_name)
+                .into_iter()
+                .collect(),
+            _ => self.duplicates_of(&file.file_name),
+        };
+        if !existing.is_empty() && self.duplicate_policy == DuplicatePolicy::Error {
+            return Err(ZipError::DuplicateName(file.file_name));
+        }
+        for index in existing.into_iter().rev() {
+            let (_, data) = self.files.shift_remove_index(index).unwrap();
+            self.shadowed_files.push(ShadowedFile {
+                data,
+                keep: self.duplicate_policy == DuplicatePolicy::Allow,
+                replaced_by: file.file_name.clone(),
+            });
         }
         let name = file.file_name.to_owned();
         self.files.insert(name.clone(), file);
@@ -947,6 +2201,8 @@ impl<W: Write + Seek> ZipWriter<W> {
     }
 
     fn finish_file(&mut self) -> ZipResult<()> {
+        self.start_pending_auto_file()?;
+        self.start_pending_sized_file()?;
         if !self.writing_to_file {
             return Ok(());
         }
@@ -956,6 +2212,10 @@ impl<W: Write + Seek> ZipWriter<W> {
             None,
             #[cfg(feature = "deflate-zopfli")]
             None,
+            #[cfg(feature = "zstd")]
+            None,
+            #[cfg(feature = "brotli")]
+            BROTLI_DEFAULT_WINDOW,
         )?;
         self.inner.switch_to(make_plain_writer)?;
         self.switch_to_non_encrypting_writer()?;
@@ -974,25 +2234,46 @@ impl<W: Write + Seek> ZipWriter<W> {
 
             file.crc32 = self.stats.hasher.clone().finalize();
             if let Some(aes_mode) = &mut file.aes_mode {
-                // We prefer using AE-1 which provides an extra CRC check, but for small files we
-                // switch to AE-2 to prevent being able to use the CRC value to to reconstruct the
-                // unencrypted contents.
+                // Unless told otherwise, we prefer using AE-1 which provides an extra CRC check,
+                // but for small files we switch to AE-2 to prevent being able to use the CRC value
+                // to to reconstruct the unencrypted contents.
                 //
                 // C.f. https://www.winzip.com/en/support/aes-encryption/#crc-faq
-                aes_mode.1 = if self.stats.bytes_written < 20 {
+                aes_mode.1 = match self.stats.aes_vendor_version {
+                    Some(vendor_version) => vendor_version,
+                    None if self.stats.bytes_written < 20 => AesVendorVersion::Ae2,
+                    None => AesVendorVersion::Ae1,
+                };
+                if aes_mode.1 == AesVendorVersion::Ae2 {
                     file.crc32 = 0;
-                    AesVendorVersion::Ae2
-                } else {
-                    AesVendorVersion::Ae1
                 }
             }
 
+            if self.stats.compression_method_chosen_late {
+                update_local_compression_method(writer, file)?;
+            }
             update_aes_extra_data(writer, file)?;
-            update_local_file_header(writer, file)?;
-            writer.seek(SeekFrom::Start(file_end))?;
+            #[cfg_attr(not(feature = "sha2"), allow(unused_variables))]
+            let file_end = if file.using_data_descriptor {
+                writer.seek(SeekFrom::Start(file_end))?;
+                write_data_descriptor(writer, file)?;
+                writer.stream_position()?
+            } else {
+                update_local_file_header(writer, file)?;
+                writer.seek(SeekFrom::Start(file_end))?;
+                file_end
+            };
+            #[cfg(feature = "sha2")]
+            self.dedup_last_file(file_end)?;
         }
         if self.flush_on_finish_file {
-            if let Err(e) = writer.flush() {
+            if let Err(e) = self.inner.get_plain().flush() {
+                self.abort_file()?;
+                return Err(e.into());
+            }
+        }
+        if let Some(on_finish_file) = self.on_finish_file {
+            if let Err(e) = on_finish_file(self.inner.get_plain()) {
                 self.abort_file()?;
                 return Err(e.into());
             }
@@ -1003,14 +2284,30 @@ impl<W: Write + Seek> ZipWriter<W> {
     }
 
     fn switch_to_non_encrypting_writer(&mut self) -> Result<(), ZipError> {
+        // If finishing the encryption fails, the writer is kept so `abort` can still return it
         match mem::replace(&mut self.inner, Closed) {
             #[cfg(feature = "aes-crypto")]
-            Storer(MaybeEncrypted::Aes(writer)) => {
-                self.inner = Storer(MaybeEncrypted::Unencrypted(writer.finish()?));
+            Storer(MaybeEncrypted::Aes(mut writer)) => {
+                if let Err(e) = writer.finish() {
+                    self.inner = Broken(MaybeEncrypted::Aes(writer));
+                    return Err(e.into());
+                }
+                self.inner = Storer(MaybeEncrypted::Unencrypted(writer.into_inner()));
             }
-            Storer(MaybeEncrypted::ZipCrypto(writer)) => {
-                let crc32 = self.stats.hasher.clone().finalize();
-                self.inner = Storer(MaybeEncrypted::Unencrypted(writer.finish(crc32)?))
+            Storer(MaybeEncrypted::ZipCrypto(mut writer)) => {
+                // The last byte of the encryption header is checked against the top byte of the
+                // CRC-32, or of the modification time when the CRC-32 comes after the data
+                let check = match self.files.last() {
+                    Some((_, file)) if file.using_data_descriptor => {
+                        (file.last_modified_time.timepart() as u32) << 16
+                    }
+                    _ => self.stats.hasher.clone().finalize(),
+                };
+                if let Err(e) = writer.finish(check) {
+                    self.inner = Broken(MaybeEncrypted::ZipCrypto(writer));
+                    return Err(e.into());
+                }
+                self.inner = Storer(MaybeEncrypted::Unencrypted(writer.writer))
             }
             Storer(MaybeEncrypted::Unencrypted(w)) => {
                 self.inner = Storer(MaybeEncrypted::Unencrypted(w))
@@ -1022,13 +2319,40 @@ impl<W: Write + Seek> ZipWriter<W> {
 
     /// Removes the file currently being written from the archive if there is one, or else removes
     /// the file most recently written.
+    ///
+    /// This is useful when the source of an entry fails partway through. Unless a shallow copy
+    /// still refers to its data, `inner` is rewound to where the entry's local header began, so
+    /// the next file started with [`ZipWriter::start_file`] takes its place. [`Write`] and
+    /// [`Seek`] offer no way to truncate `inner`, so if nothing written afterward covers all of
+    /// the abandoned bytes, [`ZipWriter::finish`] writes the central directory again at the very
+    /// end, and what's left of the entry stays behind as unused bytes that no header refers to.
     pub fn abort_file(&mut self) -> ZipResult<()> {
+        self.pending_sized_file = None;
+        if self.pending_auto_file.take().is_some() {
+            // Nothing has been written for it yet
+            self.writing_to_file = false;
+            return Ok(());
+        }
         let (_, last_file) = self.files.pop().ok_or(ZipError::FileNotFound)?;
+        // Bring back the entry that it replaced, if any
+        while self
+            .shadowed_files
+            .last()
+            .is_some_and(|shadowed| shadowed.replaced_by == last_file.file_name)
+        {
+            let shadowed = self.shadowed_files.pop().unwrap();
+            self.files
+                .insert(shadowed.data.file_name.clone(), shadowed.data);
+        }
         let make_plain_writer = self.inner.prepare_next_writer(
             Stored,
             None,
             #[cfg(feature = "deflate-zopfli")]
             None,
+            #[cfg(feature = "zstd")]
+            None,
+            #[cfg(feature = "brotli")]
+            BROTLI_DEFAULT_WINDOW,
         )?;
         self.inner.switch_to(make_plain_writer)?;
         self.switch_to_non_encrypting_writer()?;
@@ -1052,7 +2376,8 @@ impl<W: Write + Seek> ZipWriter<W> {
     }
 
     /// Create a file in the archive and start writing its' contents. The file must not have the
-    /// same name as a file already in the archive.
+    /// same name as a file already in the archive, unless [`ZipWriter::set_duplicate_policy`]
+    /// allows it.
     ///
     /// The data should be written using the [`Write`] implementation on this [`ZipWriter`]
     pub fn start_file<S, T: FileOptionExtension, SToOwned>(
@@ -1064,13 +2389,29 @@ impl<W: Write + Seek> ZipWriter<W> {
         S: Into<Box<str>> + ToOwned<Owned = SToOwned>,
         SToOwned: Into<Box<str>>,
     {
-        Self::normalize_options(&mut options);
-        let make_new_self = self.inner.prepare_next_writer(
-            options.compression_method,
-            options.compression_level,
-            #[cfg(feature = "deflate-zopfli")]
-            options.zopfli_buffer_size,
-        )?;
+        normalize_options(&mut options);
+        if !self.method_by_size.is_empty()
+            && !options.compression_method_set
+            && options.custom_compressor.is_none()
+        {
+            return self.start_sized_file(name, options);
+        }
+        let make_new_self: SwitchWriterFunction<W> = match options.custom_compressor {
+            Some(CustomCompressor(compressor)) => {
+                let stream = compressor.start(options.compression_level)?;
+                Box::new(move |inner| GenericZipWriter::Custom(CustomEncoder { inner, stream }))
+            }
+            None => self.inner.prepare_next_writer(
+                options.compression_method,
+                options.compression_level,
+                #[cfg(feature = "deflate-zopfli")]
+                options.zopfli_buffer_size,
+                #[cfg(feature = "zstd")]
+                options.zstd_dictionary,
+                #[cfg(feature = "brotli")]
+                options.brotli_window,
+            )?,
+        };
         self.start_entry(name, options, None)?;
         if let Err(e) = self.inner.switch_to(make_new_self) {
             self.abort_file().unwrap();
@@ -1080,95 +2421,452 @@ impl<W: Write + Seek> ZipWriter<W> {
         Ok(())
     }
 
-    /* TODO: link to/use Self::finish_into_readable() from https://github.com/zip-rs/zip/pull/400 in
-     * this docstring. */
-    /// Copy over the entire contents of another archive verbatim.
-    ///
-    /// This method extracts file metadata from the `source` archive, then simply performs a single
-    /// big [`io::copy()`](io::copy) to transfer all the actual file contents without any
-    /// decompression or decryption. This is more performant than the equivalent operation of
-    /// calling [`Self::raw_copy_file()`] for each entry from the `source` archive in sequence.
-    ///
-    ///```
-    /// # fn main() -> Result<(), zip::result::ZipError> {
-    /// use std::io::{Cursor, prelude::*};
-    /// use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
-    ///
-    /// let buf = Cursor::new(Vec::new());
-    /// let mut zip = ZipWriter::new(buf);
-    /// zip.start_file("a.txt", SimpleFileOptions::default())?;
-    /// zip.write_all(b"hello\n")?;
-    /// let src = ZipArchive::new(zip.finish()?)?;
+    /// Create a file in the archive like [`ZipWriter::start_file`], with the options set by
+    /// [`ZipWriter::set_default_file_options`]
     ///
-    /// let buf = Cursor::new(Vec::new());
-    /// let mut zip = ZipWriter::new(buf);
-    /// zip.start_file("b.txt", SimpleFileOptions::default())?;
-    /// zip.write_all(b"hey\n")?;
-    /// let src2 = ZipArchive::new(zip.finish()?)?;
+    /// ```
+    /// # fn main() -> zip::result::ZipResult<()> {
+    /// use std::io::Write;
+    /// use zip::write::SimpleFileOptions;
+    /// use zip::CompressionMethod;
     ///
-    /// let buf = Cursor::new(Vec::new());
-    /// let mut zip = ZipWriter::new(buf);
-    /// zip.merge_archive(src)?;
-    /// zip.merge_archive(src2)?;
-    /// let mut result = ZipArchive::new(zip.finish()?)?;
+    /// let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    /// zip.set_default_file_options(
+    ///     SimpleFileOptions::default()
+    ///         .compression_method(CompressionMethod::Stored)
+    ///         .unix_permissions(0o600),
+    /// );
+    /// zip.start_file_with_defaults("a.txt")?;
+    /// zip.write_all(b"first")?;
+    /// zip.start_file("b.txt", zip.default_file_options().unix_permissions(0o644))?;
+    /// zip.write_all(b"second")?;
     ///
-    /// let mut s: String = String::new();
-    /// result.by_name("a.txt")?.read_to_string(&mut s)?;
-    /// assert_eq!(s, "hello\n");
-    /// s.clear();
-    /// result.by_name("b.txt")?.read_to_string(&mut s)?;
-    /// assert_eq!(s, "hey\n");
+    /// let mut archive = zip.finish_into_readable()?;
+    /// assert_eq!(archive.by_name("a.txt")?.unix_mode(), Some(0o100600));
+    /// assert_eq!(archive.by_name("b.txt")?.unix_mode(), Some(0o100644));
+    /// assert_eq!(archive.by_name("b.txt")?.compression(), CompressionMethod::Stored);
     /// # Ok(())
     /// # }
-    ///```
-    pub fn merge_archive<R>(&mut self, mut source: ZipArchive<R>) -> ZipResult<()>
+    /// ```
+    pub fn start_file_with_defaults<S, SToOwned>(&mut self, name: S) -> ZipResult<()>
     where
-        R: Read + io::Seek,
+        S: Into<Box<str>> + ToOwned<Owned = SToOwned>,
+        SToOwned: Into<Box<str>>,
     {
-        self.finish_file()?;
+        self.start_file(name, self.default_file_options.clone())
+    }
 
-        /* Ensure we accept the file contents on faith (and avoid overwriting the data).
-         * See raw_copy_file_rename(). */
+    /// Create a file in the archive like [`ZipWriter::start_file`], but store it without
+    /// compression if it looks like it's already compressed
+    ///
+    /// The choice is made by the heuristic set with [`ZipWriter::set_compression_heuristic`]. If
+    /// `name` has one of its extensions, the file is stored straight away. Otherwise, the first
+    /// few KiB written are held in memory until the heuristic has sampled them; they're then
+    /// written, stored if they look incompressible and with `options` as given if not. Before the
+    /// file is started, an error such as a duplicate name is returned by the write that fills the
+    /// sample or by whichever call finishes the file, rather than by this method.
+    ///
+    /// Since the options are kept until then, they must not borrow anything, such as a password
+    /// for [`FileOptions::with_aes_encryption`], for less than `'static`.
+    pub fn start_file_auto<S, T>(
+        &mut self,
+        name: S,
+        options: FileOptions<'static, T>,
+    ) -> ZipResult<()>
+    where
+        S: Into<Box<str>>,
+        T: FileOptionExtension + Send + Sync + 'static,
+    {
+        let name: Box<str> = name.into();
+        if self.compression_heuristic.has_stored_extension(&name) {
+            return self.start_file(
+                name,
+                options.compression_method(Stored).compression_level(None),
+            );
+        }
+        self.finish_file()?;
+        if self.inner.is_closed() {
+            return Err(
+                io::Error::new(io::ErrorKind::BrokenPipe, "ZipWriter was already closed").into(),
+            );
+        }
+        self.pending_auto_file = Some(PendingAutoFile {
+            sample: Vec::new(),
+            start: Box::new(move |writer, stored| {
+                let options = if stored {
+                    options.compression_method(Stored).compression_level(None)
+                } else {
+                    options
+                };
+                writer.start_file(name, options)
+            }),
+        });
         self.writing_to_file = true;
-        self.writing_raw = true;
-
-        let writer = self.inner.get_plain();
-        /* Get the file entries from the source archive. */
-        let new_files = source.merge_contents(writer)?;
-
-        /* These file entries are now ours! */
-        self.files.extend(new_files);
-
         Ok(())
     }
 
-    fn normalize_options<T: FileOptionExtension>(options: &mut FileOptions<T>) {
-        if options.permissions.is_none() {
-            options.permissions = Some(0o644);
-        }
-        if !options.last_modified_time.is_valid() {
-            options.last_modified_time = FileOptions::<T>::default().last_modified_time;
+    /// Set whether each file whose contents are the same as an earlier file's shares that file's
+    /// data instead of having its own copy
+    ///
+    /// Once a duplicate has been written, its local header and data are removed again as if by
+    /// [`ZipWriter::abort_file`], so the bytes are reused by whatever's written next, and its
+    /// central directory record refers to the earlier file's local header, like one added with
+    /// [`ZipWriter::shallow_copy_file`]. It keeps its own name, time, permissions and extra
+    /// fields, but gets the earlier file's compression method. This is nonstandard, since the
+    /// local header's name doesn't match, but most readers, including [`ZipArchive`], only use
+    /// the central directory.
+    ///
+    /// Contents are compared by their size, CRC-32 and SHA-256, so files are only shared if
+    /// their contents really are the same. This needs the `sha2` feature. Empty files, encrypted
+    /// files, raw copies and files written while [`ZipWriter::set_flush_on_finish_file`] is on,
+    /// which means `inner` may not be able to seek back over them, are always written normally.
+    /// So is every file written by [`AsyncZipWriter`], which never seeks.
+    ///
+    /// This setting is false by default. Turning it off forgets the files written so far, but
+    /// [`ZipWriter::dedup_stats`] keeps counting.
+    #[cfg(feature = "sha2")]
+    pub fn set_dedup(&mut self, dedup: bool) {
+        if !dedup {
+            self.dedup_index = None;
+        } else if self.dedup_index.is_none() {
+            self.dedup_index = Some(HashMap::new());
         }
-        *options.permissions.as_mut().unwrap() |= ffi::S_IFREG;
     }
 
-    /// Starts a file, taking a Path as argument.
+    /// How many files [`ZipWriter::set_dedup`] has deduplicated, and how many bytes that saved
     ///
-    /// This function ensures that the '/' path separator is used and normalizes `.` and `..`. It
-    /// ignores any `..` or Windows drive letter that would produce a path outside the ZIP file's
-    /// root.
-    pub fn start_file_from_path<E: FileOptionExtension, P: AsRef<Path>>(
-        &mut self,
-        path: P,
-        options: FileOptions<E>,
-    ) -> ZipResult<()> {
-        self.start_file(path_to_string(path), options)
+    /// A file is only checked once it's finished, which is when the next one starts.
+    pub const fn dedup_stats(&self) -> DedupStats {
+        self.dedup_stats
     }
 
-    /// Add a new file using the already compressed data from a ZIP file being read and renames it, this
-    /// allows faster copies of the `ZipFile` since there is no need to decompress and compress it again.
-    /// Any `ZipFile` metadata is copied and not checked, for example the file CRC.
+    /// Remove the file that was just written if an earlier file has the same contents, and refer
+    /// to that file's data instead; otherwise, remember its contents for later files
+    #[cfg(feature = "sha2")]
+    fn dedup_last_file(&mut self, file_end: u64) -> ZipResult<()> {
+        let (Some(dedup_index), Some(content_hasher)) =
+            (&mut self.dedup_index, self.stats.content_hasher.take())
+        else {
+            return Ok(());
+        };
 
+        let Some((name, file)) = self.files.last() else {
+            return Ok(());
+        };
+        if file.uncompressed_size == 0 {
+            return Ok(());
+        }
+        let key = (
+            file.uncompressed_size,
+            file.crc32,
+            sha2::Digest::finalize(content_hasher).into(),
+        );
+        // The earlier file may since have been removed or replaced by one with other contents
+        let original = dedup_index.get(&key).and_then(|original_name| {
+            self.files.get(original_name).filter(|original| {
+                original.uncompressed_size == key.0
+                    && original.crc32 == key.1
+                    && !original.encrypted
+                    && original.header_start < file.header_start
+            })
+        });
+        let Some(original) = original else {
+            dedup_index.insert(key, name.clone());
+            return Ok(());
+        };
+        let original = original.clone();
+        let (_, file) = self.files.last_mut().unwrap();
+        let header_start = file.header_start;
+        file.compression_method = original.compression_method;
+        file.compression_flags = original.compression_flags;
+        file.compressed_size = original.compressed_size;
+        file.crc32 = original.crc32;
+        file.header_start = original.header_start;
+        file.data_start = original.data_start;
+        file.large_file = original.large_file;
+        file.using_data_descriptor = original.using_data_descriptor;
+        self.inner.get_plain().seek(SeekFrom::Start(header_start))?;
+        self.dedup_stats.files_deduplicated += 1;
+        self.dedup_stats.bytes_saved += file_end - header_start;
+        Ok(())
+    }
+
+    /// Set what happens when an entry is added with the same name as an existing one
+    ///
+    /// This applies to every method that adds an entry, including [`ZipWriter::start_file`],
+    /// [`ZipWriter::raw_copy_file`] and [`ZipWriter::shallow_copy_file`], but not to
+    /// [`ZipWriter::merge_archive`], which has its own [`MergeDuplicatePolicy`]. By default, this
+    /// is [`DuplicatePolicy::Error`]. If the new entry is aborted with [`ZipWriter::abort_file`],
+    /// the entry it replaced is restored.
+    pub fn set_duplicate_policy(&mut self, policy: DuplicatePolicy) {
+        self.duplicate_policy = policy;
+    }
+
+    /// Set whether names that only differ in ASCII case count as duplicates for
+    /// [`ZipWriter::set_duplicate_policy`] and [`ZipWriter::merge_archive_with_policy`]
+    ///
+    /// Archives are often extracted onto case-insensitive file systems, where such entries would
+    /// overwrite each other. This has no effect with [`DuplicatePolicy::Allow`], and it makes
+    /// adding each entry take time proportional to the number of entries. It's false by default.
+    pub fn set_duplicate_case_folding(&mut self, fold_case: bool) {
+        self.fold_case_for_duplicates = fold_case;
+    }
+
+    /// Set the heuristic that [`ZipWriter::start_file_auto`] uses to choose which files to store
+    /// without compression
+    ///
+    /// By default, this is [`CompressionHeuristic::default()`].
+    pub fn set_compression_heuristic(&mut self, heuristic: CompressionHeuristic) {
+        self.compression_heuristic = heuristic;
+    }
+
+    /// Choose the compression method of each file started with [`ZipWriter::start_file`] by its
+    /// size, unless its options set one with [`FileOptions::compression_method`] or
+    /// [`FileOptions::with_custom_compressor`]
+    ///
+    /// Each `(size, method)` pair applies to files of at least `size` bytes, up to the next larger
+    /// size given, and a file smaller than every size keeps the method that its options default
+    /// to. So `[(0, CompressionMethod::Stored), (4096, CompressionMethod::Deflated)]` stores files
+    /// under 4 KiB, where compressing rarely saves enough to be worth it, and deflates the rest.
+    /// An empty slice, which is the default, turns this off.
+    ///
+    /// A file's size isn't known until it's finished, so its contents are held in memory until
+    /// then or until they reach the largest size given, and only then compressed and written. A
+    /// file that's streamed in may therefore take as much memory as that largest size. The
+    /// compression level from the options is used with whichever method is chosen, other than
+    /// `Stored`; if it isn't valid for that method, the file is removed as by
+    /// [`ZipWriter::abort_file`] and the error is returned by the write or the call that finishes
+    /// the file.
+    pub fn set_method_by_size(&mut self, thresholds: &[(u64, CompressionMethod)]) {
+        let mut thresholds = thresholds.to_vec();
+        thresholds.sort_by_key(|&(size, _)| size);
+        self.method_by_size = thresholds;
+    }
+
+    /// Start a file for [`ZipWriter::set_method_by_size`] to choose the compression method of.
+    /// The local header gets a placeholder method, which is updated once the file is finished.
+    fn start_sized_file<S, SToOwned, T: FileOptionExtension>(
+        &mut self,
+        name: S,
+        options: FileOptions<T>,
+    ) -> ZipResult<()>
+    where
+        S: Into<Box<str>> + ToOwned<Owned = SToOwned>,
+        SToOwned: Into<Box<str>>,
+    {
+        let pending = PendingSizedFile {
+            buffer: Vec::new(),
+            default_method: options.compression_method,
+            compression_level: options.compression_level,
+            #[cfg(feature = "deflate-zopfli")]
+            zopfli_buffer_size: options.zopfli_buffer_size,
+            #[cfg(feature = "zstd")]
+            zstd_dictionary: options.zstd_dictionary.map(Box::from),
+            #[cfg(feature = "brotli")]
+            brotli_window: options.brotli_window,
+        };
+        self.start_entry(
+            name,
+            options.compression_method(Stored).compression_level(None),
+            None,
+        )?;
+        self.writing_raw = false;
+        self.stats.compression_method_chosen_late = true;
+        self.pending_sized_file = Some(pending);
+        Ok(())
+    }
+
+    /// Choose the compression method of the file being buffered for
+    /// [`ZipWriter::set_method_by_size`], if any, and write what's been buffered
+    fn start_pending_sized_file(&mut self) -> ZipResult<()> {
+        let Some(pending) = self.pending_sized_file.take() else {
+            return Ok(());
+        };
+        let size = pending.buffer.len() as u64;
+        let method = self
+            .method_by_size
+            .iter()
+            .rev()
+            .find(|&&(threshold, _)| threshold <= size)
+            .map_or(pending.default_method, |&(_, method)| method);
+        let level = if method == Stored {
+            None
+        } else {
+            pending.compression_level
+        };
+        let switched = self
+            .inner
+            .prepare_next_writer(
+                method,
+                level,
+                #[cfg(feature = "deflate-zopfli")]
+                pending.zopfli_buffer_size,
+                #[cfg(feature = "zstd")]
+                pending.zstd_dictionary.as_deref(),
+                #[cfg(feature = "brotli")]
+                pending.brotli_window,
+            )
+            .and_then(|make_new_self| self.inner.switch_to(make_new_self));
+        if let Err(e) = switched {
+            self.abort_file()?;
+            return Err(e);
+        }
+        if let Some((_, file)) = self.files.last_mut() {
+            match &mut file.aes_mode {
+                Some((_, _, aes_method)) => *aes_method = method,
+                None => file.compression_method = method,
+            }
+            file.compression_level = level;
+        }
+        self.write_all(&pending.buffer)?;
+        Ok(())
+    }
+
+    /// Start the file that [`ZipWriter::start_file_auto`] is sampling, if any, and write the sample
+    fn start_pending_auto_file(&mut self) -> ZipResult<()> {
+        let Some(pending) = self.pending_auto_file.take() else {
+            return Ok(());
+        };
+        self.writing_to_file = false;
+        let stored = self
+            .compression_heuristic
+            .is_incompressible(&pending.sample);
+        (pending.start)(self, stored)?;
+        self.write_all(&pending.sample)?;
+        Ok(())
+    }
+
+    /* TODO: link to/use Self::finish_into_readable() from https://github.com/zip-rs/zip/pull/400 in
+     * this docstring. */
+    /// Copy over the entire contents of another archive verbatim.
+    ///
+    /// This method extracts file metadata from the `source` archive, then simply performs a single
+    /// big copy to transfer all the actual file contents without any
+    /// decompression or decryption. This is more performant than the equivalent operation of
+    /// calling [`Self::raw_copy_file()`] for each entry from the `source` archive in sequence.
+    ///
+    ///```
+    /// # fn main() -> Result<(), zip::result::ZipError> {
+    /// use std::io::{Cursor, prelude::*};
+    /// use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+    ///
+    /// let buf = Cursor::new(Vec::new());
+    /// let mut zip = ZipWriter::new(buf);
+    /// zip.start_file("a.txt", SimpleFileOptions::default())?;
+    /// zip.write_all(b"hello\n")?;
+    /// let src = ZipArchive::new(zip.finish()?)?;
+    ///
+    /// let buf = Cursor::new(Vec::new());
+    /// let mut zip = ZipWriter::new(buf);
+    /// zip.start_file("b.txt", SimpleFileOptions::default())?;
+    /// zip.write_all(b"hey\n")?;
+    /// let src2 = ZipArchive::new(zip.finish()?)?;
+    ///
+    /// let buf = Cursor::new(Vec::new());
+    /// let mut zip = ZipWriter::new(buf);
+    /// zip.merge_archive(src)?;
+    /// zip.merge_archive(src2)?;
+    /// let mut result = ZipArchive::new(zip.finish()?)?;
+    ///
+    /// let mut s: String = String::new();
+    /// result.by_name("a.txt")?.read_to_string(&mut s)?;
+    /// assert_eq!(s, "hello\n");
+    /// s.clear();
+    /// result.by_name("b.txt")?.read_to_string(&mut s)?;
+    /// assert_eq!(s, "hey\n");
+    /// # Ok(())
+    /// # }
+    ///```
+    pub fn merge_archive<R>(&mut self, source: ZipArchive<R>) -> ZipResult<()>
+    where
+        R: Read + io::Seek,
+    {
+        self.merge_archive_with_policy(source, MergeDuplicatePolicy::Overwrite)
+    }
+
+    /// Like [`Self::merge_archive()`], but lets the caller choose what happens when an entry in
+    /// `source` has the same name as one already in this archive.
+    ///
+    /// Each entry's compressed data, CRC, sizes, compression method, encryption and extra fields
+    /// are copied verbatim, and entries that needed ZIP64 in `source` keep their ZIP64 records.
+    /// With [`MergeDuplicatePolicy::Skip`], the skipped entry's data is still part of the big copy
+    /// but isn't referenced by the central directory. Names are compared as
+    /// [`ZipWriter::set_duplicate_case_folding`] says, so when it's on, two entries in `source`
+    /// whose names only differ by case are duplicates of each other too, and the later one is
+    /// handled as if it were added after the earlier.
+    pub fn merge_archive_with_policy<R>(
+        &mut self,
+        mut source: ZipArchive<R>,
+        policy: MergeDuplicatePolicy,
+    ) -> ZipResult<()>
+    where
+        R: Read + io::Seek,
+    {
+        self.finish_file()?;
+        if policy == MergeDuplicatePolicy::Error {
+            // Every name has to be known before anything is copied
+            source.parse_pending(|_| false)?;
+            let mut folded_names = HashSet::new();
+            if let Some(name) = source.file_names().find(|name| {
+                !self.duplicates_of(name).is_empty()
+                    || (self.fold_case_for_duplicates
+                        && !folded_names.insert(name.to_ascii_lowercase()))
+            }) {
+                return Err(ZipError::DuplicateName(name.into()));
+            }
+        }
+
+        /* Ensure we accept the file contents on faith (and avoid overwriting the data).
+         * See raw_copy_file_rename(). */
+        self.writing_to_file = true;
+        self.writing_raw = true;
+
+        let writer = self.inner.get_plain();
+        /* Get the file entries from the source archive. */
+        let new_files = source.merge_contents(writer, self.raw_copy_buffer_size)?;
+
+        /* These file entries are now ours! */
+        for (name, file) in new_files {
+            let existing = self.duplicates_of(&name);
+            let Some((&first, others)) = existing.split_first() else {
+                self.files.insert(name, file);
+                continue;
+            };
+            if policy == MergeDuplicatePolicy::Skip {
+                continue;
+            }
+            for &index in others.iter().rev() {
+                self.files.shift_remove_index(index);
+            }
+            // The new entry takes the place of the first one it replaces
+            if self.files.insert(name, file).is_none() {
+                self.files.swap_remove_index(first);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Starts a file, taking a Path as argument.
+    ///
+    /// This function ensures that the '/' path separator is used and normalizes `.` and `..`. It
+    /// ignores any `..` or Windows drive letter that would produce a path outside the ZIP file's
+    /// root.
+    pub fn start_file_from_path<E: FileOptionExtension, P: AsRef<Path>>(
+        &mut self,
+        path: P,
+        options: FileOptions<E>,
+    ) -> ZipResult<()> {
+        self.start_file(path_to_string(path, options.normalize_separators), options)
+    }
+
+    /// Add a new file using the already compressed data from a ZIP file being read and renames it, this
+    /// allows faster copies of the `ZipFile` since there is no need to decompress and compress it again.
+    /// Any `ZipFile` metadata is copied and not checked, for example the file CRC.
+    ///
+    /// The local header is written for the new name, so its length and the UTF-8 flag may differ
+    /// from the original entry's. The compressed data is copied unchanged.
+    ///
     /// ```no_run
     /// use std::fs::File;
     /// use std::io::{Read, Seek, Write};
@@ -1197,29 +2895,115 @@ impl<W: Write + Seek> ZipWriter<W> {
         SToOwned: Into<Box<str>>,
     {
         let mut options = SimpleFileOptions::default()
-            .large_file(file.compressed_size().max(file.size()) > spec::ZIP64_BYTES_THR)
+            .large_file(file.compressed_size().max(file.size()) >= spec::ZIP64_BYTES_THR)
             .last_modified_time(file.last_modified())
-            .compression_method(file.compression());
+            .compression_method(file.compression())
+            .with_comment(file.comment());
         if let Some(perms) = file.unix_mode() {
             options = options.unix_permissions(perms);
         }
-        Self::normalize_options(&mut options);
+        normalize_options(&mut options);
 
         let raw_values = ZipRawValues {
             crc32: file.crc32(),
             compressed_size: file.compressed_size(),
             uncompressed_size: file.size(),
+            compression_flags: file.data.compression_flags,
         };
 
         self.start_entry(name, options, Some(raw_values))?;
         self.writing_to_file = true;
         self.writing_raw = true;
 
-        io::copy(file.get_raw_reader(), self)?;
+        let mut buf = vec![0; self.raw_copy_buffer_size];
+        copy_with_progress(file.get_raw_reader(), self, &mut buf, |_| {})?;
 
         Ok(())
     }
 
+    /// Add a copy of a file from a ZIP file being read, encrypted with the password and algorithm
+    /// set in `options` instead of its own. The compressed data is decrypted and encrypted again,
+    /// but not decompressed, so this is much faster than extracting and adding the file again.
+    ///
+    /// An encrypted `file` must have been opened with its current password, using
+    /// [`ZipArchive::by_index_decrypt`] or [`ZipArchive::by_name_decrypt`]. Only the encryption is
+    /// taken from `options`, as set by [`FileOptions::with_aes_encryption`], and the copy isn't
+    /// encrypted if it wasn't set. Everything else, including the name and compression method, is
+    /// copied from `file` as [`ZipWriter::raw_copy_file`] does. The copy gets a new salt and
+    /// authentication code, so this can also convert a ZipCrypto-encrypted file to AES.
+    ///
+    /// AE-2 doesn't record the CRC-32 of the file, so a file that uses it can only be copied with
+    /// AES encryption, and the copy also uses AE-2. If AE-1 was chosen with
+    /// [`FileOptions::with_aes_encryption_version`], or AES wasn't chosen at all, this fails with
+    /// [`ZipError::UnsupportedArchive`] before writing anything.
+    pub fn raw_copy_file_reencrypt<T: FileOptionExtension>(
+        &mut self,
+        mut file: ZipFile,
+        options: FileOptions<T>,
+    ) -> ZipResult<()> {
+        let (compression_method, crc32) = match file.data.aes_mode {
+            Some((_, vendor_version, compression_method)) => (
+                compression_method,
+                matches!(vendor_version, AesVendorVersion::Ae1).then_some(file.crc32()),
+            ),
+            None => (file.compression(), Some(file.crc32())),
+        };
+        let can_use_ae2 = match options.encrypt_with {
+            #[cfg(feature = "aes-crypto")]
+            Some(EncryptWith::Aes { vendor_version, .. }) => {
+                vendor_version != Some(AesVendorVersion::Ae1)
+            }
+            _ => false,
+        };
+        if crc32.is_none() && !can_use_ae2 {
+            return Err(ZipError::UnsupportedArchive(
+                "An AE-2 encrypted file can only be re-encrypted with AE-2",
+            ));
+        }
+        // Fail now if the file can't be decrypted
+        file.get_decrypted_raw_reader()?;
+
+        let mut new_options = FileOptions::<()>::default()
+            .large_file(file.compressed_size().max(file.size()) >= spec::ZIP64_BYTES_THR)
+            .last_modified_time(file.last_modified())
+            .compression_method(compression_method)
+            .with_comment(file.comment());
+        if let Some(perms) = file.unix_mode() {
+            new_options = new_options.unix_permissions(perms);
+        }
+        new_options.encrypt_with = options.encrypt_with;
+        #[cfg(feature = "aes-crypto")]
+        {
+            new_options.aes_iterations = options.aes_iterations;
+        }
+        normalize_options(&mut new_options);
+
+        let raw_values = ZipRawValues {
+            crc32: crc32.unwrap_or(0),
+            compressed_size: file.compressed_size(),
+            uncompressed_size: file.size(),
+            compression_flags: file.data.compression_flags,
+        };
+        self.start_entry(file.name().to_owned(), new_options, Some(raw_values))?;
+        self.writing_to_file = true;
+        self.writing_raw = false;
+
+        let mut buf = vec![0; self.raw_copy_buffer_size];
+        if let Err(e) = copy_with_progress(file.get_decrypted_raw_reader()?, self, &mut buf, |_| {})
+        {
+            let _ = self.abort_file();
+            return Err(e.into());
+        }
+        // The sizes and CRC-32 are set when the file is finished, from what's been written, so
+        // make them describe the decompressed data rather than the compressed data
+        self.stats.bytes_written = file.size();
+        self.stats.hasher = Hasher::new_with_initial(crc32.unwrap_or(0));
+        if crc32.is_none() {
+            self.stats.aes_vendor_version = Some(AesVendorVersion::Ae2);
+        }
+        self.finish_file()
+    }
+
     /// Like `raw_copy_file_to_path`, but uses Path arguments.
     ///
     /// This function ensures that the '/' path separator is used and normalizes `.` and `..`. It
@@ -1230,7 +3014,7 @@ impl<W: Write + Seek> ZipWriter<W> {
         file: ZipFile,
         path: P,
     ) -> ZipResult<()> {
-        self.raw_copy_file_rename(file, path_to_string(path))
+        self.raw_copy_file_rename(file, path_to_string(path, false))
     }
 
     /// Add a new file using the already compressed data from a ZIP file being read, this allows faster
@@ -1261,6 +3045,70 @@ impl<W: Write + Seek> ZipWriter<W> {
         self.raw_copy_file_rename(file, name)
     }
 
+    /// Add a new file from data that has already been compressed with Deflate, such as by an
+    /// earlier stage of a pipeline, so that it doesn't have to be compressed again.
+    ///
+    /// `compressed` must be a raw Deflate stream, without a zlib or gzip header, that decompresses
+    /// to `uncompressed_size` bytes whose CRC-32 is `crc32`. Like [`ZipWriter::raw_copy_file`],
+    /// this writes the data and metadata as they are without checking them, so a mistake only
+    /// shows up when the file is read. The compression method is always Deflate, even if this crate
+    /// was built without a Deflate feature; the compression level and any encryption set in
+    /// `options` are ignored.
+    ///
+    /// ```
+    /// # fn main() -> zip::result::ZipResult<()> {
+    /// use std::io::Read;
+    /// use zip::write::SimpleFileOptions;
+    ///
+    /// // "hello" as a single stored Deflate block
+    /// let compressed = [0x01, 0x05, 0x00, 0xfa, 0xff, b'h', b'e', b'l', b'l', b'o'];
+    /// let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    /// zip.write_raw_deflate("hello.txt", &compressed, 5, 0x3610a686, SimpleFileOptions::default())?;
+    /// let mut archive = zip.finish_into_readable()?;
+    /// # #[cfg(feature = "_deflate-any")]
+    /// # {
+    /// let mut file = archive.by_name("hello.txt")?;
+    /// assert_eq!(file.crc32(), 0x3610a686);
+    /// let mut contents = String::new();
+    /// file.read_to_string(&mut contents)?;
+    /// assert_eq!(contents, "hello");
+    /// # }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn write_raw_deflate<S, SToOwned, T: FileOptionExtension>(
+        &mut self,
+        name: S,
+        compressed: &[u8],
+        uncompressed_size: u64,
+        crc32: u32,
+        mut options: FileOptions<T>,
+    ) -> ZipResult<()>
+    where
+        S: Into<Box<str>> + ToOwned<Owned = SToOwned>,
+        SToOwned: Into<Box<str>>,
+    {
+        let compressed_size = compressed.len() as u64;
+        options.compression_method = CompressionMethod::DEFLATE;
+        options.compression_level = None;
+        options.encrypt_with = None;
+        normalize_options(&mut options);
+
+        let raw_values = ZipRawValues {
+            crc32,
+            compressed_size,
+            uncompressed_size,
+            compression_flags: 0,
+        };
+
+        self.start_entry(name, options, Some(raw_values))?;
+        self.writing_to_file = true;
+        self.writing_raw = true;
+        self.write_all(compressed)?;
+
+        Ok(())
+    }
+
     /// Add a directory entry.
     ///
     /// As directories have no content, you must not call [`ZipWriter::write`] before adding a new file.
@@ -1292,6 +3140,32 @@ impl<W: Write + Seek> ZipWriter<W> {
         Ok(())
     }
 
+    /// Add a directory entry like [`ZipWriter::add_directory`], which also sets the MS-DOS
+    /// directory attribute in its external file attributes
+    ///
+    /// The entry gets the mode from [`FileOptions::unix_permissions`] (0o755 by default) and the
+    /// time from [`FileOptions::last_modified_time`], which
+    /// [`ZipArchive::extract_with_options`](crate::ZipArchive::extract_with_options) restores on
+    /// the extracted directory when [`ExtractOptions::preserve_permissions`] and
+    /// [`ExtractOptions::preserve_mtime`] are set. The directory attribute lets tools that only
+    /// look at the MS-DOS attributes, as many on Windows do, recognize it as a directory too.
+    ///
+    /// [`ExtractOptions::preserve_permissions`]: crate::read::ExtractOptions::preserve_permissions
+    /// [`ExtractOptions::preserve_mtime`]: crate::read::ExtractOptions::preserve_mtime
+    pub fn add_directory_with_options<S, T: FileOptionExtension>(
+        &mut self,
+        name: S,
+        options: FileOptions<T>,
+    ) -> ZipResult<()>
+    where
+        S: Into<String>,
+    {
+        self.add_directory(name, options)?;
+        let (_, file) = self.files.last_mut().unwrap();
+        file.external_attributes |= DosAttributes::DIRECTORY;
+        Ok(())
+    }
+
     /// Add a directory entry, taking a Path as argument.
     ///
     /// This function ensures that the '/' path separator is used and normalizes `.` and `..`. It
@@ -1302,7 +3176,111 @@ impl<W: Write + Seek> ZipWriter<W> {
         path: P,
         options: FileOptions<T>,
     ) -> ZipResult<()> {
-        self.add_directory(path_to_string(path), options)
+        self.add_directory(path_to_string(path, options.normalize_separators), options)
+    }
+
+    /// Add every file, directory and symlink under `root`, each named by its path relative to
+    /// `root`, which isn't added itself.
+    ///
+    /// `options_for` is called with each relative path and returns the options to add it with,
+    /// such as a compression method that suits the file, or `None` to skip it along with
+    /// everything in it. The entries in each directory are added in order of their names, after an
+    /// entry for the directory, so empty directories are kept too. On Unix, entries whose options
+    /// don't set [`FileOptions::unix_permissions`] get the permissions of the file they come from.
+    ///
+    /// With `preserve_symlinks`, a symlink is added as by [`ZipWriter::add_symlink`] with its
+    /// target path unchanged. Otherwise, the file or directory it points to is added in its place,
+    /// and a symlink to a directory that contains it fails with an [`io::ErrorKind::InvalidInput`]
+    /// error rather than being followed forever.
+    pub fn add_directory_recursive<'k, P, E, F>(
+        &mut self,
+        root: P,
+        preserve_symlinks: bool,
+        mut options_for: F,
+    ) -> ZipResult<()>
+    where
+        P: AsRef<Path>,
+        E: FileOptionExtension,
+        F: FnMut(&Path) -> Option<FileOptions<'k, E>>,
+    {
+        let root = root.as_ref();
+        let mut ancestors = vec![directory_id(root, &fs::metadata(root)?)?];
+        self.add_directory_contents(
+            root,
+            Path::new(""),
+            preserve_symlinks,
+            &mut options_for,
+            &mut ancestors,
+        )
+    }
+
+    /// Add what's in `directory`, under `root`, for [`ZipWriter::add_directory_recursive`].
+    /// `ancestors` identifies each directory from `root` down to this one.
+    fn add_directory_contents<'k, E, F>(
+        &mut self,
+        root: &Path,
+        directory: &Path,
+        preserve_symlinks: bool,
+        options_for: &mut F,
+        ancestors: &mut Vec<DirectoryId>,
+    ) -> ZipResult<()>
+    where
+        E: FileOptionExtension,
+        F: FnMut(&Path) -> Option<FileOptions<'k, E>>,
+    {
+        let mut file_names = fs::read_dir(root.join(directory))?
+            .map(|entry| entry.map(|entry| entry.file_name()))
+            .collect::<io::Result<Vec<_>>>()?;
+        file_names.sort();
+        for file_name in file_names {
+            let relative = directory.join(file_name);
+            #[allow(unused_mut)]
+            let Some(mut options) = options_for(&relative) else {
+                continue;
+            };
+            let path = root.join(&relative);
+            let metadata = if preserve_symlinks {
+                fs::symlink_metadata(&path)?
+            } else {
+                fs::metadata(&path)?
+            };
+            #[cfg(unix)]
+            if options.permissions.is_none() {
+                use std::os::unix::fs::PermissionsExt;
+                options.permissions = Some(metadata.permissions().mode() & 0o7777);
+            }
+            let name = path_to_string(&relative, options.normalize_separators);
+            if metadata.file_type().is_symlink() {
+                let target = fs::read_link(&path)?;
+                let target = target.to_string_lossy().replace(MAIN_SEPARATOR, "/");
+                self.add_symlink(name, target, options)?;
+            } else if metadata.is_dir() {
+                let id = directory_id(&path, &metadata)?;
+                if ancestors.contains(&id) {
+                    return Err(ZipError::Io(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "{} is a symlink to a directory that contains it",
+                            path.display()
+                        ),
+                    )));
+                }
+                self.add_directory(name, options)?;
+                ancestors.push(id);
+                self.add_directory_contents(
+                    root,
+                    &relative,
+                    preserve_symlinks,
+                    options_for,
+                    ancestors,
+                )?;
+                ancestors.pop();
+            } else {
+                self.start_file(name, options)?;
+                io::copy(&mut File::open(&path)?, self)?;
+            }
+        }
+        Ok(())
     }
 
     /// Finish the last file and write all other zip-structures
@@ -1315,7 +3293,49 @@ impl<W: Write + Seek> ZipWriter<W> {
         Ok(inner.unwrap())
     }
 
-    /// Add a symlink entry.
+    /// Give up on the archive and return the underlying writer, without writing a central
+    /// directory, unlike [`ZipWriter::finish`] and dropping the [`ZipWriter`]
+    ///
+    /// Any file in progress is abandoned, but whatever its compressor or encryption still holds is
+    /// flushed first, so `inner` is left positioned just after the last byte written for it, or
+    /// just after the last file finished if there's none. If that fails, whatever the compressor or
+    /// encryption hasn't written yet is lost, but `inner` is still returned along with the error.
+    /// No further bytes are written either way, so `inner` can be truncated there or to wherever
+    /// the archive started.
+    ///
+    /// ```
+    /// # fn main() -> zip::result::ZipResult<()> {
+    /// use std::io::{Seek, Write};
+    /// use zip::write::SimpleFileOptions;
+    ///
+    /// let mut zip = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    /// zip.start_file("partial.txt", SimpleFileOptions::default())?;
+    /// zip.write_all(b"never mind")?;
+    /// let (mut inner, result) = zip.abort();
+    /// result?;
+    /// assert_eq!(inner.stream_position()?, inner.get_ref().len() as u64);
+    /// assert!(zip::ZipArchive::new(inner).is_err());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn abort(mut self) -> (W, ZipResult<()>) {
+        self.pending_auto_file = None;
+        self.pending_sized_file = None;
+        let result = self
+            .inner
+            .finish_detached()
+            .and_then(|()| self.switch_to_non_encrypting_writer());
+        // Once `inner` is closed, dropping `self` doesn't write a central directory
+        let inner = match mem::replace(&mut self.inner, Closed) {
+            Storer(w) | Broken(w) => w,
+            mut compressor => compressor
+                .detach()
+                .expect("ZipWriter is only closed once it's consumed"),
+        };
+        (inner.into_inner(), result)
+    }
+
+    /// Add a symlink entry.
     ///
     /// The zip archive will contain an entry for path `name` which is a symlink to `target`.
     ///
@@ -1323,6 +3343,12 @@ impl<W: Write + Seek> ZipWriter<W> {
     /// callers should normalize `\` to `/` and ensure symlinks are relative to other
     /// paths within the zip archive.
     ///
+    /// The entry gets the Unix mode `S_IFLNK` along with the permissions set on `options`, or
+    /// `0o777` if there are none, and is marked as made on [`System::Unix`] so that its mode is
+    /// read as one, whichever system this runs on. So the host system set with
+    /// [`FileOptions::with_version_made_by`], and any [`FileOptions::with_external_attributes`],
+    /// are ignored.
+    ///
     /// WARNING: not all zip implementations preserve symlinks on extract. Some zip
     /// implementations may materialize a symlink as a regular file, possibly with the
     /// content incorrectly set to the symlink target. For maximum portability, consider
@@ -1338,10 +3364,10 @@ impl<W: Write + Seek> ZipWriter<W> {
         NToOwned: Into<Box<str>>,
         T: Into<Box<str>>,
     {
-        if options.permissions.is_none() {
-            options.permissions = Some(0o777);
-        }
-        *options.permissions.as_mut().unwrap() |= 0o120000;
+        let permissions = options.permissions.unwrap_or(0o777) & 0o7777;
+        options.permissions = Some(ffi::S_IFLNK | permissions);
+        options.system = System::Unix;
+        options.external_attributes = None;
         // The symlink target is stored as file content. And compressing the target path
         // likely wastes space. So always store.
         options.compression_method = Stored;
@@ -1369,11 +3395,39 @@ impl<W: Write + Seek> ZipWriter<W> {
         target: T,
         options: FileOptions<E>,
     ) -> ZipResult<()> {
-        self.add_symlink(path_to_string(path), path_to_string(target), options)
+        let normalize = options.normalize_separators;
+        self.add_symlink(
+            path_to_string(path, normalize),
+            path_to_string(target, normalize),
+            options,
+        )
+    }
+
+    /// Add an empty entry that's a hard link to the entry named `target`, which it names in a
+    /// PKWARE Unix extra field as Info-ZIP does. It can be read back with
+    /// [`ZipFile::hardlink_target`](crate::read::ZipFile::hardlink_target) and extracted as a link
+    /// with [`ExtractOptions::hard_links`](crate::read::ExtractOptions::hard_links).
+    pub fn add_hardlink<'k, N, NToOwned, E: FileOptionExtension>(
+        &mut self,
+        name: N,
+        target: &'k str,
+        mut options: FileOptions<'k, E>,
+    ) -> ZipResult<()>
+    where
+        N: Into<Box<str>> + ToOwned<Owned = NToOwned>,
+        NToOwned: Into<Box<str>>,
+    {
+        options.hardlink_target = Some(target);
+        options.compression_method = Stored;
+        self.start_entry(name, options, None)?;
+        self.writing_to_file = true;
+        self.writing_raw = false;
+        self.finish_file()
     }
 
     fn finalize(&mut self) -> ZipResult<u64> {
         self.finish_file()?;
+        check_archive_comment(&self.comment)?;
 
         let central_start = {
             let central_start = self.write_central_and_footer()?;
@@ -1397,48 +3451,33 @@ impl<W: Write + Seek> ZipWriter<W> {
         let writer = self.inner.get_plain();
 
         let central_start = writer.stream_position()?;
-        for file in self.files.values() {
+        // Entries kept under `DuplicatePolicy::Allow` come first, so that readers which only keep
+        // one entry per name see the one written last
+        let kept_files: Vec<_> = self
+            .shadowed_files
+            .iter()
+            .filter(|shadowed| shadowed.keep)
+            .map(|shadowed| &shadowed.data)
+            .collect();
+        let appended_count = match &self.appended_directory {
+            Some(appended) => {
+                writer.write_all(&appended.records)?;
+                appended.count
+            }
+            None => 0,
+        };
+        for file in kept_files.iter().copied().chain(self.files.values()) {
             write_central_directory_header(writer, file)?;
         }
         let central_size = writer.stream_position()? - central_start;
 
-        if self.files.len() > spec::ZIP64_ENTRY_THR
-            || central_size.max(central_start) > spec::ZIP64_BYTES_THR
-        {
-            let zip64_footer = spec::Zip64CentralDirectoryEnd {
-                version_made_by: DEFAULT_VERSION as u16,
-                version_needed_to_extract: DEFAULT_VERSION as u16,
-                disk_number: 0,
-                disk_with_central_directory: 0,
-                number_of_files_on_this_disk: self.files.len() as u64,
-                number_of_files: self.files.len() as u64,
-                central_directory_size: central_size,
-                central_directory_offset: central_start,
-            };
-
-            zip64_footer.write(writer)?;
-
-            let zip64_footer = spec::Zip64CentralDirectoryEndLocator {
-                disk_with_central_directory: 0,
-                end_of_central_directory_offset: central_start + central_size,
-                number_of_disks: 1,
-            };
-
-            zip64_footer.write(writer)?;
-        }
-
-        let number_of_files = self.files.len().min(spec::ZIP64_ENTRY_THR) as u16;
-        let footer = spec::CentralDirectoryEnd {
-            disk_number: 0,
-            disk_with_central_directory: 0,
-            zip_file_comment: self.comment.clone(),
-            number_of_files_on_this_disk: number_of_files,
-            number_of_files,
-            central_directory_size: central_size.min(spec::ZIP64_BYTES_THR) as u32,
-            central_directory_offset: central_start.min(spec::ZIP64_BYTES_THR) as u32,
-        };
-
-        footer.write(writer)?;
+        write_central_directory_end(
+            writer,
+            appended_count + kept_files.len() + self.files.len(),
+            central_start,
+            central_size,
+            &self.comment,
+        )?;
         Ok(central_start)
     }
 
@@ -1457,6 +3496,7 @@ impl<W: Write + Seek> ZipWriter<W> {
         let mut dest_data = self.files[src_index].to_owned();
         dest_data.file_name = dest_name.to_string().into();
         dest_data.file_name_raw = dest_name.to_string().into_bytes().into();
+        dest_data.is_utf8 = true;
         self.insert_file_data(dest_data)?;
         Ok(())
     }
@@ -1471,7 +3511,26 @@ impl<W: Write + Seek> ZipWriter<W> {
         src_path: T,
         dest_path: U,
     ) -> ZipResult<()> {
-        self.shallow_copy_file(&path_to_string(src_path), &path_to_string(dest_path))
+        self.shallow_copy_file(
+            &path_to_string(src_path, false),
+            &path_to_string(dest_path, false),
+        )
+    }
+}
+
+impl ZipWriter<File> {
+    /// Choose whether [`File::sync_data`] is called once each file has been written, so that the
+    /// file's header and data reach the disk before the next file starts
+    ///
+    /// This makes a partly written archive more likely to survive a crash, and makes writing
+    /// slower. Writes to a [`File`] aren't buffered, so this doesn't also need
+    /// [`ZipWriter::set_flush_on_finish_file`], and the central directory is only written by
+    /// [`ZipWriter::finish`] as usual. If syncing fails, the file that was just written is
+    /// removed as if by [`ZipWriter::abort_file`].
+    ///
+    /// This setting is false by default.
+    pub fn set_sync_data_on_finish_file(&mut self, sync_data: bool) {
+        self.on_finish_file = sync_data.then_some(|file| file.sync_data());
     }
 }
 
@@ -1493,8 +3552,10 @@ impl<W: Write + Seek> GenericZipWriter<W> {
         compression: CompressionMethod,
         compression_level: Option<i64>,
         #[cfg(feature = "deflate-zopfli")] zopfli_buffer_size: Option<usize>,
+        #[cfg(feature = "zstd")] zstd_dictionary: Option<&[u8]>,
+        #[cfg(feature = "brotli")] brotli_window: u32,
     ) -> ZipResult<SwitchWriterFunction<W>> {
-        if let Closed = self {
+        if self.is_closed() {
             return Err(
                 io::Error::new(io::ErrorKind::BrokenPipe, "ZipWriter was already closed").into(),
             );
@@ -1515,18 +3576,12 @@ impl<W: Write + Seek> GenericZipWriter<W> {
                 }
                 #[cfg(feature = "_deflate-any")]
                 CompressionMethod::Deflated => {
-                    let default = if cfg!(feature = "deflate-zopfli") {
-                        24
-                    } else {
-                        Compression::default().level() as i64
-                    };
-
                     let level = clamp_opt(
-                        compression_level.unwrap_or(default),
+                        compression_level.unwrap_or(DEFLATE_DEFAULT_LEVEL),
                         deflate_compression_level_range(),
                     )
                     .ok_or(ZipError::UnsupportedArchive(
-                        "Unsupported compression level",
+                        "Unsupported compression level for Deflated",
                     ))? as u32;
 
                     #[cfg(feature = "deflate-zopfli")]
@@ -1579,11 +3634,11 @@ impl<W: Write + Seek> GenericZipWriter<W> {
                 #[cfg(feature = "bzip2")]
                 CompressionMethod::Bzip2 => {
                     let level = clamp_opt(
-                        compression_level.unwrap_or(bzip2::Compression::default().level() as i64),
+                        compression_level.unwrap_or(BZIP2_DEFAULT_LEVEL),
                         bzip2_compression_level_range(),
                     )
                     .ok_or(ZipError::UnsupportedArchive(
-                        "Unsupported compression level",
+                        "Unsupported compression level for Bzip2; the range is 1 to 9",
                     ))? as u32;
                     Ok(Box::new(move |bare| {
                         GenericZipWriter::Bzip2(BzEncoder::new(
@@ -1598,20 +3653,68 @@ impl<W: Write + Seek> GenericZipWriter<W> {
                 #[cfg(feature = "zstd")]
                 CompressionMethod::Zstd => {
                     let level = clamp_opt(
-                        compression_level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL as i64),
-                        zstd::compression_level_range(),
+                        compression_level.unwrap_or(ZSTD_DEFAULT_LEVEL),
+                        zstd_compression_level_range(),
                     )
                     .ok_or(ZipError::UnsupportedArchive(
-                        "Unsupported compression level",
+                        "Unsupported compression level for Zstd; the range is -7 to 22",
                     ))?;
-                    Ok(Box::new(move |bare| {
-                        GenericZipWriter::Zstd(ZstdEncoder::new(bare, level as i32).unwrap())
-                    }))
+                    match zstd_dictionary {
+                        Some(dictionary) => {
+                            // Loading the dictionary is what can fail, so it's done before the
+                            // writer is switched over
+                            let encoder = zstd::stream::raw::Encoder::with_dictionary(
+                                level as i32,
+                                dictionary,
+                            )?;
+                            Ok(Box::new(move |bare| {
+                                GenericZipWriter::Zstd(ZstdEncoder::with_encoder(bare, encoder))
+                            }))
+                        }
+                        None => Ok(Box::new(move |bare| {
+                            GenericZipWriter::Zstd(ZstdEncoder::new(bare, level as i32).unwrap())
+                        })),
+                    }
                 }
                 #[cfg(feature = "lzma")]
                 CompressionMethod::Lzma => {
                     Err(UnsupportedArchive("LZMA isn't supported for compression"))
                 }
+                #[cfg(feature = "legacy")]
+                CompressionMethod::Shrink | CompressionMethod::Implode => {
+                    Err(ZipError::UnsupportedArchive(
+                        "Shrink and Implode aren't supported for compression",
+                    ))
+                }
+                #[cfg(feature = "ppmd")]
+                CompressionMethod::Ppmd => Err(ZipError::UnsupportedArchive(
+                    "PPMd isn't supported for compression",
+                )),
+                #[cfg(feature = "xz")]
+                CompressionMethod::Xz => Err(ZipError::UnsupportedArchive(
+                    "XZ isn't supported for compression",
+                )),
+                #[cfg(feature = "brotli")]
+                CompressionMethod::Brotli => {
+                    let quality =
+                        clamp_opt(compression_level.unwrap_or(BROTLI_DEFAULT_QUALITY), 0..=11)
+                            .ok_or(ZipError::UnsupportedArchive(
+                                "Unsupported compression level for Brotli; the range is 0 to 11",
+                            ))? as u32;
+                    if !(10..=24).contains(&brotli_window) {
+                        return Err(ZipError::UnsupportedArchive(
+                            "Unsupported Brotli window size",
+                        ));
+                    }
+                    Ok(Box::new(move |bare| {
+                        GenericZipWriter::Brotli(Box::new(brotli::CompressorWriter::new(
+                            bare,
+                            BROTLI_BUFFER_SIZE,
+                            quality,
+                            brotli_window,
+                        )))
+                    }))
+                }
                 CompressionMethod::Unsupported(..) => {
                     Err(ZipError::UnsupportedArchive("Unsupported compression"))
                 }
@@ -1620,6 +3723,23 @@ impl<W: Write + Seek> GenericZipWriter<W> {
     }
 
     fn switch_to(&mut self, make_new_self: SwitchWriterFunction<W>) -> ZipResult<()> {
+        // Finishing in place keeps the writer if it fails, so that `ZipWriter::abort` can still
+        // return it
+        if let Err(e) = self.finish_in_place() {
+            if let Some(bare) = self.detach() {
+                *self = Broken(bare);
+            }
+            return Err(e.into());
+        }
+        // Zopfli can only be finished by consuming it, and finishes again as it's dropped if that
+        // fails, so it finishes into a buffer instead. This only holds its last chunk.
+        #[cfg(feature = "deflate-zopfli")]
+        if matches!(
+            self,
+            GenericZipWriter::ZopfliDeflater(_) | GenericZipWriter::BufferedZopfliDeflater(_)
+        ) {
+            self.finish_detached()?;
+        }
         let bare = match mem::replace(self, Closed) {
             Storer(w) => w,
             #[cfg(any(
@@ -1629,17 +3749,23 @@ impl<W: Write + Seek> GenericZipWriter<W> {
             ))]
             GenericZipWriter::Deflater(w) => w.finish()?,
             #[cfg(feature = "deflate-zopfli")]
-            GenericZipWriter::ZopfliDeflater(w) => w.finish()?,
-            #[cfg(feature = "deflate-zopfli")]
-            GenericZipWriter::BufferedZopfliDeflater(w) => w
-                .into_inner()
-                .map_err(|e| ZipError::Io(e.into_error()))?
-                .finish()?,
+            GenericZipWriter::ZopfliDeflater(_) | GenericZipWriter::BufferedZopfliDeflater(_) => {
+                unreachable!("Zopfli was finished into a buffer")
+            }
             #[cfg(feature = "bzip2")]
             GenericZipWriter::Bzip2(w) => w.finish()?,
             #[cfg(feature = "zstd")]
             GenericZipWriter::Zstd(w) => w.finish()?,
-            Closed => {
+            #[cfg(feature = "brotli")]
+            GenericZipWriter::Brotli(w) => w.into_inner(),
+            GenericZipWriter::Custom(CustomEncoder { mut inner, stream }) => {
+                if let Err(e) = stream.finish(&mut inner) {
+                    *self = Broken(inner);
+                    return Err(e.into());
+                }
+                inner
+            }
+            Closed | Broken(_) => {
                 return Err(io::Error::new(
                     io::ErrorKind::BrokenPipe,
                     "ZipWriter was already closed",
@@ -1651,6 +3777,108 @@ impl<W: Write + Seek> GenericZipWriter<W> {
         Ok(())
     }
 
+    /// Writes out whatever the compressor still holds, where that can be done without taking the
+    /// writer out of it
+    fn finish_in_place(&mut self) -> io::Result<()> {
+        match self {
+            #[cfg(any(
+                feature = "deflate",
+                feature = "deflate-zlib",
+                feature = "deflate-zlib-ng"
+            ))]
+            GenericZipWriter::Deflater(w) => w.try_finish(),
+            #[cfg(feature = "deflate-zopfli")]
+            GenericZipWriter::BufferedZopfliDeflater(w) => w.flush(),
+            #[cfg(feature = "bzip2")]
+            GenericZipWriter::Bzip2(w) => w.try_finish(),
+            #[cfg(feature = "zstd")]
+            GenericZipWriter::Zstd(w) => w.do_finish(),
+            // into_inner ignores errors while finishing the stream, so write out everything but
+            // the last few bytes first
+            #[cfg(feature = "brotli")]
+            GenericZipWriter::Brotli(w) => w.flush(),
+            _ => Ok(()),
+        }
+    }
+
+    /// Finishes the compressor, leaving a [`Storer`]. The compressor finishes into a buffer
+    /// instead of the real writer, which it would take down with it if it failed, so the writer
+    /// is kept as [`Broken`] if this fails.
+    fn finish_detached(&mut self) -> ZipResult<()> {
+        let Some(mut bare) = self.detach() else {
+            return Err(
+                io::Error::new(io::ErrorKind::BrokenPipe, "ZipWriter was already closed").into(),
+            );
+        };
+        let tail = match mem::replace(self, Closed) {
+            Storer(w) => Ok(w),
+            #[cfg(any(
+                feature = "deflate",
+                feature = "deflate-zlib",
+                feature = "deflate-zlib-ng"
+            ))]
+            GenericZipWriter::Deflater(w) => w.finish(),
+            #[cfg(feature = "deflate-zopfli")]
+            GenericZipWriter::ZopfliDeflater(w) => w.finish(),
+            #[cfg(feature = "deflate-zopfli")]
+            GenericZipWriter::BufferedZopfliDeflater(w) => w
+                .into_inner()
+                .map_err(|e| e.into_error())
+                .and_then(|w| w.finish()),
+            #[cfg(feature = "bzip2")]
+            GenericZipWriter::Bzip2(w) => w.finish(),
+            #[cfg(feature = "zstd")]
+            GenericZipWriter::Zstd(w) => w.finish(),
+            // into_inner ignores errors while finishing the stream, but writing to a buffer can't
+            // fail
+            #[cfg(feature = "brotli")]
+            GenericZipWriter::Brotli(w) => Ok(w.into_inner()),
+            GenericZipWriter::Custom(w) => w.finish(),
+            Closed | Broken(_) => unreachable!("detach() returned a writer"),
+        };
+        let result = tail.and_then(|tail| match tail {
+            MaybeEncrypted::Detached(bytes) => bare.write_all(&bytes),
+            _ => unreachable!("The compressor was given a detached writer"),
+        });
+        match result {
+            Ok(()) => {
+                *self = Storer(bare);
+                Ok(())
+            }
+            Err(e) => {
+                *self = Broken(bare);
+                Err(e.into())
+            }
+        }
+    }
+
+    /// Takes the writer that the current compressor writes to, leaving it writing to a buffer
+    /// instead, or returns `None` if there's no writer any more
+    fn detach(&mut self) -> Option<MaybeEncrypted<W>> {
+        let w = match self {
+            Storer(w) => w,
+            #[cfg(any(
+                feature = "deflate",
+                feature = "deflate-zlib",
+                feature = "deflate-zlib-ng"
+            ))]
+            GenericZipWriter::Deflater(w) => w.get_mut(),
+            #[cfg(feature = "deflate-zopfli")]
+            GenericZipWriter::ZopfliDeflater(w) => w.get_mut(),
+            #[cfg(feature = "deflate-zopfli")]
+            GenericZipWriter::BufferedZopfliDeflater(w) => w.get_mut().get_mut(),
+            #[cfg(feature = "bzip2")]
+            GenericZipWriter::Bzip2(w) => w.get_mut(),
+            #[cfg(feature = "zstd")]
+            GenericZipWriter::Zstd(w) => w.get_mut(),
+            #[cfg(feature = "brotli")]
+            GenericZipWriter::Brotli(w) => w.get_mut(),
+            GenericZipWriter::Custom(w) => &mut w.inner,
+            Closed | Broken(_) => return None,
+        };
+        Some(mem::replace(w, MaybeEncrypted::Detached(Vec::new())))
+    }
+
     fn ref_mut(&mut self) -> Option<&mut dyn Write> {
         match self {
             Storer(ref mut w) => Some(w as &mut dyn Write),
@@ -1668,12 +3896,15 @@ impl<W: Write + Seek> GenericZipWriter<W> {
             GenericZipWriter::Bzip2(ref mut w) => Some(w as &mut dyn Write),
             #[cfg(feature = "zstd")]
             GenericZipWriter::Zstd(ref mut w) => Some(w as &mut dyn Write),
-            Closed => None,
+            #[cfg(feature = "brotli")]
+            GenericZipWriter::Brotli(ref mut w) => Some(w as &mut dyn Write),
+            GenericZipWriter::Custom(ref mut w) => Some(w as &mut dyn Write),
+            Closed | Broken(_) => None,
         }
     }
 
     const fn is_closed(&self) -> bool {
-        matches!(*self, GenericZipWriter::Closed)
+        matches!(*self, Closed | Broken(_))
     }
 
     fn get_plain(&mut self) -> &mut W {
@@ -1691,6 +3922,24 @@ impl<W: Write + Seek> GenericZipWriter<W> {
     }
 }
 
+/// What identifies a directory for [`ZipWriter::add_directory_recursive`] however it's reached:
+/// its device and inode on Unix, and its canonical path elsewhere
+#[cfg(unix)]
+type DirectoryId = (u64, u64);
+#[cfg(not(unix))]
+type DirectoryId = std::path::PathBuf;
+
+#[cfg(unix)]
+fn directory_id(_path: &Path, metadata: &fs::Metadata) -> io::Result<DirectoryId> {
+    use std::os::unix::fs::MetadataExt;
+    Ok((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn directory_id(path: &Path, _metadata: &fs::Metadata) -> io::Result<DirectoryId> {
+    path.canonicalize()
+}
+
 #[cfg(feature = "_deflate-any")]
 fn deflate_compression_level_range() -> std::ops::RangeInclusive<i64> {
     let min = if cfg!(feature = "deflate")
@@ -1712,6 +3961,32 @@ fn deflate_compression_level_range() -> std::ops::RangeInclusive<i64> {
     min..=max
 }
 
+/// Default level for [`CompressionMethod::Deflated`]: 24, which is Zopfli with 15 iterations, if
+/// the `deflate-zopfli` feature is enabled, or 6 otherwise
+#[cfg(feature = "_deflate-any")]
+const DEFLATE_DEFAULT_LEVEL: i64 = if cfg!(feature = "deflate-zopfli") {
+    24
+} else {
+    6
+};
+/// Default level for [`CompressionMethod::Bzip2`]
+#[cfg(feature = "bzip2")]
+const BZIP2_DEFAULT_LEVEL: i64 = 6;
+/// Default level for [`CompressionMethod::Zstd`], the same as `zstd`'s own default
+#[cfg(feature = "zstd")]
+const ZSTD_DEFAULT_LEVEL: i64 = zstd::DEFAULT_COMPRESSION_LEVEL as i64;
+/// Default quality for [`CompressionMethod::Brotli`], which is also the best
+#[cfg(feature = "brotli")]
+const BROTLI_DEFAULT_QUALITY: i64 = 11;
+/// Default window size for [`CompressionMethod::Brotli`], as the base-2 logarithm of its size
+#[cfg(feature = "brotli")]
+const BROTLI_DEFAULT_WINDOW: u32 = 22;
+/// Size of the buffer between the Brotli encoder and the writer
+#[cfg(feature = "brotli")]
+const BROTLI_BUFFER_SIZE: usize = 4096;
+/// Highest version of the ZIP specification, which is 6.3, times 10
+const MAX_VERSION_NEEDED: u16 = 63;
+
 #[cfg(feature = "bzip2")]
 fn bzip2_compression_level_range() -> std::ops::RangeInclusive<i64> {
     let min = bzip2::Compression::fast().level() as i64;
@@ -1719,7 +3994,18 @@ fn bzip2_compression_level_range() -> std::ops::RangeInclusive<i64> {
     min..=max
 }
 
-#[cfg(any(feature = "_deflate-any", feature = "bzip2", feature = "zstd"))]
+/// The documented `-7..=22` range, even though `libzstd` accepts much lower (faster) levels
+#[cfg(feature = "zstd")]
+fn zstd_compression_level_range() -> std::ops::RangeInclusive<i64> {
+    -7..=*zstd::compression_level_range().end() as i64
+}
+
+#[cfg(any(
+    feature = "_deflate-any",
+    feature = "bzip2",
+    feature = "zstd",
+    feature = "brotli"
+))]
 fn clamp_opt<T: Ord + Copy, U: Ord + Copy + TryFrom<T>>(
     value: T,
     range: std::ops::RangeInclusive<U>,
@@ -1775,6 +4061,22 @@ fn update_aes_extra_data<W: Write + io::Seek>(
     Ok(())
 }
 
+/// Rewrites the version needed to extract and the compression method in a local header that was
+/// written before [`ZipWriter::set_method_by_size`] chose the method
+fn update_local_compression_method<T: Write + Seek>(
+    writer: &mut T,
+    file: &ZipFileData,
+) -> ZipResult<()> {
+    const VERSION_NEEDED_OFFSET: u64 = 4;
+    writer.seek(SeekFrom::Start(file.header_start + VERSION_NEEDED_OFFSET))?;
+    writer.write_u16_le(file.version_needed())?;
+    // skip the general purpose bit flag
+    writer.seek(SeekFrom::Current(2))?;
+    #[allow(deprecated)]
+    writer.write_u16_le(file.compression_method.to_u16())?;
+    Ok(())
+}
+
 fn update_local_file_header<T: Write + Seek>(writer: &mut T, file: &ZipFileData) -> ZipResult<()> {
     const CRC32_OFFSET: u64 = 14;
     writer.seek(SeekFrom::Start(file.header_start + CRC32_OFFSET))?;
@@ -1783,7 +4085,7 @@ fn update_local_file_header<T: Write + Seek>(writer: &mut T, file: &ZipFileData)
         update_local_zip64_extra_field(writer, file)?;
     } else {
         // check compressed size as well as it can also be slightly larger than uncompressed size
-        if file.compressed_size > spec::ZIP64_BYTES_THR {
+        if file.compressed_size >= spec::ZIP64_BYTES_THR {
             return Err(ZipError::Io(io::Error::new(
                 io::ErrorKind::Other,
                 "Large file option has not been set",
@@ -1796,6 +4098,217 @@ fn update_local_file_header<T: Write + Seek>(writer: &mut T, file: &ZipFileData)
     Ok(())
 }
 
+/// Writes a signed data descriptor holding the CRC-32 and sizes of a file that's just been written
+pub(crate) fn write_data_descriptor<T: Write>(writer: &mut T, file: &ZipFileData) -> ZipResult<()> {
+    if !file.large_file && file.compressed_size.max(file.uncompressed_size) >= spec::ZIP64_BYTES_THR
+    {
+        return Err(ZipError::Io(io::Error::new(
+            io::ErrorKind::Other,
+            "Large file option has not been set",
+        )));
+    }
+    writer.write_u32_le(spec::DATA_DESCRIPTOR_SIGNATURE)?;
+    writer.write_u32_le(file.crc32)?;
+    if file.large_file {
+        writer.write_u64_le(file.compressed_size)?;
+        writer.write_u64_le(file.uncompressed_size)?;
+    } else {
+        writer.write_u32_le(file.compressed_size as u32)?;
+        writer.write_u32_le(file.uncompressed_size as u32)?;
+    }
+    Ok(())
+}
+
+fn normalize_options<T: FileOptionExtension>(options: &mut FileOptions<T>) {
+    if options.permissions.is_none() {
+        options.permissions = Some(0o644);
+    }
+    if !options.last_modified_time.is_valid() {
+        options.last_modified_time = FileOptions::<T>::default().last_modified_time;
+    }
+    *options.permissions.as_mut().unwrap() |= ffi::S_IFREG;
+}
+
+/// A new entry's name, comment and extra fields as its [`FileOptions`] ask for them, apart from
+/// the extra data for AES encryption
+struct EntryFields {
+    name: Box<str>,
+    file_name_raw: Box<[u8]>,
+    file_comment_raw: Box<[u8]>,
+    /// Fields that belong in the local header but not the central directory, which writes
+    /// `extra_field` as well as `central_extra_field`
+    local_only_extra_field: Vec<u8>,
+    central_extra_field: Option<Arc<Vec<u8>>>,
+    extra_fields: Vec<ExtraField>,
+}
+
+impl EntryFields {
+    fn new<T: FileOptionExtension>(
+        mut name: Box<str>,
+        options: &FileOptions<T>,
+    ) -> ZipResult<Self> {
+        let mut local_only_extra_field = Vec::new();
+        let mut central_extra_field = options.extended_options.central_extra_data().cloned();
+        let mut extra_fields = Vec::new();
+        if let Some(extended_timestamp) = options.extended_timestamp {
+            local_only_extra_field.extend(extended_timestamp.local_header_bytes());
+            Arc::make_mut(central_extra_field.get_or_insert_with(Default::default))
+                .extend(extended_timestamp.central_header_bytes());
+            extra_fields.push(ExtraField::ExtendedTimestamp(extended_timestamp));
+        }
+        if let Some(ntfs) = options.ntfs_times {
+            local_only_extra_field.extend(ntfs.to_bytes());
+            Arc::make_mut(central_extra_field.get_or_insert_with(Default::default))
+                .extend(ntfs.to_bytes());
+            extra_fields.push(ExtraField::Ntfs(ntfs));
+        }
+        if let Some(ownership) = options.unix_ownership {
+            local_only_extra_field.extend(ownership.to_bytes());
+            Arc::make_mut(central_extra_field.get_or_insert_with(Default::default))
+                .extend(ownership.to_bytes());
+            extra_fields.push(ExtraField::UnixOwnership(ownership));
+        }
+        if let Some(target) = options.hardlink_target {
+            // The times and IDs are left as zeros, since other fields hold them
+            let mut field = Vec::with_capacity(16 + target.len());
+            field.write_u16_le(PKWARE_UNIX_EXTRA_FIELD_ID)?;
+            field.write_u16_le(
+                u16::try_from(12 + target.len())
+                    .map_err(|_| InvalidArchive("Hard link target is too long"))?,
+            )?;
+            field.write_all(&[0; 12])?;
+            field.write_all(target.as_bytes())?;
+            local_only_extra_field.extend_from_slice(&field);
+            Arc::make_mut(central_extra_field.get_or_insert_with(Default::default)).extend(field);
+        }
+        if options.normalize_separators {
+            if let Cow::Owned(normalized) = normalize_separators(&name) {
+                name = normalized.into();
+            }
+        }
+        let file_name_raw = options.name_encoding.encode(&name).ok_or(InvalidArchive(
+            "File name can't be represented in the chosen encoding",
+        ))?;
+        let file_comment_raw =
+            options
+                .name_encoding
+                .encode(options.comment)
+                .ok_or(InvalidArchive(
+                    "File comment can't be represented in the chosen encoding",
+                ))?;
+        if file_comment_raw.len() > u16::MAX as usize {
+            return Err(InvalidArchive("File comment is longer than 65535 bytes"));
+        }
+        if options.min_version > MAX_VERSION_NEEDED {
+            return Err(InvalidArchive(
+                "Minimum version needed to extract is above the latest ZIP version",
+            ));
+        }
+        if options.unicode_path_extra_field && !name.is_ascii() {
+            // Info-ZIP Unicode Path: version 1, then the CRC-32 of the name in the header, which
+            // lets readers tell whether the field is still up to date, and then the UTF-8 name
+            let mut unicode_path = Vec::with_capacity(9 + name.len());
+            unicode_path.write_u16_le(0x7075)?;
+            let len = u16::try_from(5 + name.len())
+                .map_err(|_| InvalidArchive("Extra data field is too large"))?;
+            unicode_path.write_u16_le(len)?;
+            unicode_path.push(1);
+            unicode_path.write_u32_le(crc32fast::hash(&file_name_raw))?;
+            unicode_path.extend_from_slice(name.as_bytes());
+            local_only_extra_field.extend_from_slice(&unicode_path);
+            Arc::make_mut(central_extra_field.get_or_insert_with(Default::default))
+                .extend(unicode_path);
+        }
+        if options.unicode_comment_extra_field && !options.comment.is_ascii() {
+            // Info-ZIP Unicode Comment, laid out the same way as the Unicode Path field above
+            let mut unicode_comment = Vec::with_capacity(9 + options.comment.len());
+            unicode_comment.write_u16_le(0x6375)?;
+            let len = u16::try_from(5 + options.comment.len())
+                .map_err(|_| InvalidArchive("Extra data field is too large"))?;
+            unicode_comment.write_u16_le(len)?;
+            unicode_comment.push(1);
+            unicode_comment.write_u32_le(crc32fast::hash(&file_comment_raw))?;
+            unicode_comment.extend_from_slice(options.comment.as_bytes());
+            Arc::make_mut(central_extra_field.get_or_insert_with(Default::default))
+                .extend(unicode_comment);
+        }
+        Ok(EntryFields {
+            name,
+            file_name_raw,
+            file_comment_raw,
+            local_only_extra_field,
+            central_extra_field,
+            extra_fields,
+        })
+    }
+}
+
+/// Whether an entry's headers set the language encoding flag (bit 11), which says that its name
+/// and comment are UTF-8. It's left clear when both are ASCII, which reads the same either way.
+fn sets_utf8_flag(is_utf8: bool, file_name_raw: &[u8], file_comment_raw: &[u8]) -> bool {
+    is_utf8
+        && from_utf8(file_name_raw).is_ok()
+        && from_utf8(file_comment_raw).is_ok()
+        && !(file_name_raw.is_ascii() && file_comment_raw.is_ascii())
+}
+
+/// Check that an archive comment fits in the end-of-central-directory record
+fn check_archive_comment(comment: &[u8]) -> ZipResult<()> {
+    if comment.len() > u16::MAX as usize {
+        return Err(InvalidArchive("Archive comment is longer than 65535 bytes"));
+    }
+    Ok(())
+}
+
+/// Writes the records that follow the central directory: the ZIP64 end of central directory record
+/// and locator if they're needed, then the end of central directory record.
+fn write_central_directory_end<T: Write>(
+    writer: &mut T,
+    number_of_files: usize,
+    central_start: u64,
+    central_size: u64,
+    comment: &[u8],
+) -> ZipResult<()> {
+    if number_of_files > spec::ZIP64_ENTRY_THR
+        || central_size.max(central_start) > spec::ZIP64_BYTES_THR
+    {
+        let zip64_footer = spec::Zip64CentralDirectoryEnd {
+            version_made_by: DEFAULT_VERSION as u16,
+            version_needed_to_extract: DEFAULT_VERSION as u16,
+            disk_number: 0,
+            disk_with_central_directory: 0,
+            number_of_files_on_this_disk: number_of_files as u64,
+            number_of_files: number_of_files as u64,
+            central_directory_size: central_size,
+            central_directory_offset: central_start,
+        };
+
+        zip64_footer.write(writer)?;
+
+        let zip64_footer = spec::Zip64CentralDirectoryEndLocator {
+            disk_with_central_directory: 0,
+            end_of_central_directory_offset: central_start + central_size,
+            number_of_disks: 1,
+        };
+
+        zip64_footer.write(writer)?;
+    }
+
+    let footer_number_of_files = number_of_files.min(spec::ZIP64_ENTRY_THR) as u16;
+    let footer = spec::CentralDirectoryEnd {
+        disk_number: 0,
+        disk_with_central_directory: 0,
+        zip_file_comment: comment.into(),
+        number_of_files_on_this_disk: footer_number_of_files,
+        number_of_files: footer_number_of_files,
+        central_directory_size: central_size.min(spec::ZIP64_BYTES_THR) as u32,
+        central_directory_offset: central_start.min(spec::ZIP64_BYTES_THR) as u32,
+    };
+
+    footer.write(writer)?;
+    Ok(())
+}
+
 fn write_central_directory_header<T: Write>(writer: &mut T, file: &ZipFileData) -> ZipResult<()> {
     // buffer zip64 extra field to determine its variable length
     let mut zip64_extra_field = [0; 28];
@@ -1810,10 +4323,15 @@ fn write_central_directory_header<T: Write>(writer: &mut T, file: &ZipFileData)
     // version needed to extract
     writer.write_u16_le(file.version_needed())?;
     // general puprose bit flag
-    let is_utf8 = std::str::from_utf8(&file.file_name_raw).is_ok();
-    let is_ascii = file.file_name_raw.is_ascii();
-    let flag = if is_utf8 && !is_ascii { 1u16 << 11 } else { 0 }
-        | if file.encrypted { 1u16 << 0 } else { 0 };
+    let utf8_flag = sets_utf8_flag(file.is_utf8, &file.file_name_raw, &file.file_comment_raw);
+    let flag = if utf8_flag { 1u16 << 11 } else { 0 }
+        | if file.encrypted { 1u16 << 0 } else { 0 }
+        | if file.using_data_descriptor {
+            1u16 << 3
+        } else {
+            0
+        }
+        | file.compression_flags;
     writer.write_u16_le(flag)?;
     // compression method
     #[allow(deprecated)]
@@ -1824,19 +4342,24 @@ fn write_central_directory_header<T: Write>(writer: &mut T, file: &ZipFileData)
     // crc-32
     writer.write_u32_le(file.crc32)?;
     // compressed size
-    writer.write_u32_le(file.compressed_size.min(spec::ZIP64_BYTES_THR) as u32)?;
+    writer.write_u32_le(zip64_size_field(file, file.compressed_size))?;
     // uncompressed size
-    writer.write_u32_le(file.uncompressed_size.min(spec::ZIP64_BYTES_THR) as u32)?;
+    writer.write_u32_le(zip64_size_field(file, file.uncompressed_size))?;
     // file name length
     writer.write_u16_le(file.file_name_raw.len() as u16)?;
+    // A ZIP64 field copied from another archive may be stale, so we write our own instead
+    let extra_field = file
+        .extra_field
+        .as_deref()
+        .map(|e| without_zip64_extra_field(e));
     // extra field length
     writer.write_u16_le(
         zip64_extra_field_length
-            + file.extra_field_len() as u16
+            + extra_field.as_ref().map_or(0, |e| e.len()) as u16
             + file.central_extra_field_len() as u16,
     )?;
     // file comment length
-    writer.write_u16_le(0)?;
+    writer.write_u16_le(file.file_comment_raw.len() as u16)?;
     // disk number start
     writer.write_u16_le(0)?;
     // internal file attributes
@@ -1850,18 +4373,52 @@ fn write_central_directory_header<T: Write>(writer: &mut T, file: &ZipFileData)
     // zip64 extra field
     writer.write_all(&zip64_extra_field[..zip64_extra_field_length as usize])?;
     // extra field
-    if let Some(extra_field) = &file.extra_field {
+    if let Some(extra_field) = &extra_field {
         writer.write_all(extra_field)?;
     }
     if let Some(central_extra_field) = &file.central_extra_field {
         writer.write_all(central_extra_field)?;
     }
     // file comment
-    // <none>
+    writer.write_all(&file.file_comment_raw)?;
 
     Ok(())
 }
 
+/// The value of a 32-bit size field in the central directory, which is all ones when the real
+/// size is in the ZIP64 extra field.
+fn zip64_size_field(file: &ZipFileData, size: u64) -> u32 {
+    if file.large_file {
+        spec::ZIP64_BYTES_THR as u32
+    } else {
+        size.min(spec::ZIP64_BYTES_THR) as u32
+    }
+}
+
+/// Returns `extra_field` without any ZIP64 extended information records.
+fn without_zip64_extra_field(extra_field: &[u8]) -> Cow<'_, [u8]> {
+    let mut kept: Option<Vec<u8>> = None;
+    let mut rest = extra_field;
+    while rest.len() >= 4 {
+        let header_id = u16::from_le_bytes([rest[0], rest[1]]);
+        let len = 4 + u16::from_le_bytes([rest[2], rest[3]]) as usize;
+        let (field, remaining) = rest.split_at(len.min(rest.len()));
+        if header_id == 0x0001 {
+            kept.get_or_insert_with(|| extra_field[..extra_field.len() - rest.len()].to_vec());
+        } else if let Some(kept) = kept.as_mut() {
+            kept.extend_from_slice(field);
+        }
+        rest = remaining;
+    }
+    match kept {
+        Some(mut kept) => {
+            kept.extend_from_slice(rest);
+            Cow::Owned(kept)
+        }
+        None => Cow::Borrowed(extra_field),
+    }
+}
+
 fn validate_extra_data(header_id: u16, data: &[u8]) -> ZipResult<()> {
     if data.len() > u16::MAX as usize {
         return Err(ZipError::Io(io::Error::new(
@@ -1907,6 +4464,86 @@ fn write_local_zip64_extra_field<T: Write>(writer: &mut T, file: &ZipFileData) -
     Ok(())
 }
 
+/// A part of what [`splice`] writes
+enum Splice {
+    Bytes(Vec<u8>),
+    /// The bytes that were in this range before anything was written
+    Copy(std::ops::Range<u64>),
+}
+
+/// Write `pieces` one after another from `start`, where the ranges copied are in order and
+/// between `start` and `end`, and return where the last one ends
+///
+/// The bytes of a range are read before anything overwrites them, so the pieces can be longer in
+/// total than what they're made from. Only as many bytes as that have grown by are held in memory
+/// at once, along with one buffer.
+fn splice<T: Read + Write + Seek>(
+    writer: &mut T,
+    start: u64,
+    end: u64,
+    pieces: Vec<Splice>,
+) -> io::Result<u64> {
+    const CHUNK_SIZE: u64 = 1 << 16;
+    let mut write_position = start;
+    // The bytes that were read but not written yet end at `read_position`
+    let mut read_position = start;
+    let mut pending = VecDeque::new();
+    // Read ahead so the bytes before `until` are safe to overwrite
+    let read_to = |writer: &mut T,
+                   pending: &mut VecDeque<u8>,
+                   read_position: &mut u64,
+                   until: u64|
+     -> io::Result<()> {
+        let until = until.min(end);
+        if *read_position < until {
+            let mut buffer = vec![0; (until - *read_position) as usize];
+            writer.seek(SeekFrom::Start(*read_position))?;
+            writer.read_exact(&mut buffer)?;
+            pending.extend(buffer);
+            *read_position = until;
+        }
+        Ok(())
+    };
+    for piece in pieces {
+        match piece {
+            Splice::Bytes(bytes) => {
+                read_to(
+                    writer,
+                    &mut pending,
+                    &mut read_position,
+                    write_position + bytes.len() as u64,
+                )?;
+                writer.seek(SeekFrom::Start(write_position))?;
+                writer.write_all(&bytes)?;
+                write_position += bytes.len() as u64;
+            }
+            Splice::Copy(range) => {
+                // Drop what was read of the bytes before the range
+                let pending_start = read_position - pending.len() as u64;
+                let skipped = range.start.saturating_sub(pending_start) as usize;
+                pending.drain(..skipped.min(pending.len()));
+                read_position = read_position.max(range.start);
+                let mut position = range.start;
+                while position < range.end {
+                    let len = CHUNK_SIZE.min(range.end - position);
+                    read_to(
+                        writer,
+                        &mut pending,
+                        &mut read_position,
+                        (position + len).max(write_position + len),
+                    )?;
+                    let chunk: Vec<u8> = pending.drain(..len as usize).collect();
+                    writer.seek(SeekFrom::Start(write_position))?;
+                    writer.write_all(&chunk)?;
+                    write_position += len;
+                    position += len;
+                }
+            }
+        }
+    }
+    Ok(write_position)
+}
+
 fn update_local_zip64_extra_field<T: Write + Seek>(
     writer: &mut T,
     file: &ZipFileData,
@@ -1926,9 +4563,9 @@ fn write_central_zip64_extra_field<T: Write>(writer: &mut T, file: &ZipFileData)
     // only appear if the corresponding Local or Central
     // directory record field is set to 0xFFFF or 0xFFFFFFFF.
     let mut size = 0;
-    let uncompressed_size = file.uncompressed_size > spec::ZIP64_BYTES_THR;
-    let compressed_size = file.compressed_size > spec::ZIP64_BYTES_THR;
-    let header_start = file.header_start > spec::ZIP64_BYTES_THR;
+    let uncompressed_size = file.large_file || file.uncompressed_size >= spec::ZIP64_BYTES_THR;
+    let compressed_size = file.large_file || file.compressed_size >= spec::ZIP64_BYTES_THR;
+    let header_start = file.header_start >= spec::ZIP64_BYTES_THR;
     if uncompressed_size {
         size += 8;
     }
@@ -1969,11 +4606,11 @@ const EXTRA_FIELD_MAPPING: [u16; 49] = [
 
 #[cfg(test)]
 mod test {
-    use super::{FileOptions, ZipWriter};
+    use super::{FileOptions, MergeDuplicatePolicy, Zip64Mode, ZipRawValues, ZipWriter};
     use crate::compression::CompressionMethod;
-    use crate::result::ZipResult;
-    use crate::types::DateTime;
-    use crate::write::SimpleFileOptions;
+    use crate::result::{ZipError, ZipResult};
+    use crate::types::{DateTime, DosAttributes, NameEncoding, System, DEFAULT_VERSION};
+    use crate::write::{FullFileOptions, SimpleFileOptions};
     use crate::CompressionMethod::Stored;
     use crate::ZipArchive;
     use std::io;
@@ -2056,6 +4693,30 @@ mod test {
         );
     }
 
+    #[test]
+    fn write_symlink_mode() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default()
+            .with_version_made_by(System::Dos, 20)
+            .with_external_attributes(0x20)
+            .unix_permissions(0o755);
+        writer.add_symlink("link", "../target", options)?;
+        writer.add_symlink("default", "target", SimpleFileOptions::default())?;
+        let mut archive = writer.finish_into_readable()?;
+
+        let mut link = archive.by_name("link")?;
+        assert_eq!(link.system(), System::Unix);
+        assert_eq!(link.host_version(), 20);
+        assert_eq!(link.unix_mode(), Some(0o120755));
+        assert!(link.is_symlink());
+        let mut target = String::new();
+        link.read_to_string(&mut target)?;
+        assert_eq!(target, "../target");
+        drop(link);
+        assert_eq!(archive.by_name("default")?.unix_mode(), Some(0o120777));
+        Ok(())
+    }
+
     #[test]
     fn test_path_normalization() {
         let mut path = PathBuf::new();
@@ -2112,12 +4773,35 @@ mod test {
             compression_level: None,
             last_modified_time: DateTime::default(),
             permissions: Some(33188),
-            large_file: false,
+            zip64: Zip64Mode::Auto,
             encrypt_with: None,
             extended_options: (),
             alignment: 1,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: None,
+            #[cfg(feature = "zstd")]
+            zstd_dictionary: None,
+            #[cfg(feature = "brotli")]
+            brotli_window: super::BROTLI_DEFAULT_WINDOW,
+            #[cfg(feature = "aes-crypto")]
+            aes_iterations: crate::aes::DEFAULT_ITERATION_COUNT,
+            extended_timestamp: None,
+            ntfs_times: None,
+            unix_ownership: None,
+            hardlink_target: None,
+            compression_method_set: false,
+            name_encoding: NameEncoding::Utf8,
+            dos_attributes: DosAttributes::default(),
+            unicode_path_extra_field: false,
+            unicode_comment_extra_field: false,
+            external_attributes: None,
+            system: System::Unix,
+            version_made_by: DEFAULT_VERSION,
+            data_descriptor: false,
+            normalize_separators: false,
+            comment: "",
+            min_version: 0,
+            custom_compressor: None,
         };
         writer.start_file("mimetype", options).unwrap();
         writer
@@ -2149,12 +4833,35 @@ mod test {
             compression_level: None,
             last_modified_time: DateTime::default(),
             permissions: Some(33188),
-            large_file: false,
+            zip64: Zip64Mode::Auto,
             encrypt_with: None,
             extended_options: (),
             alignment: 1,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: None,
+            #[cfg(feature = "zstd")]
+            zstd_dictionary: None,
+            #[cfg(feature = "brotli")]
+            brotli_window: super::BROTLI_DEFAULT_WINDOW,
+            #[cfg(feature = "aes-crypto")]
+            aes_iterations: crate::aes::DEFAULT_ITERATION_COUNT,
+            extended_timestamp: None,
+            ntfs_times: None,
+            unix_ownership: None,
+            hardlink_target: None,
+            compression_method_set: false,
+            name_encoding: NameEncoding::Utf8,
+            dos_attributes: DosAttributes::default(),
+            unicode_path_extra_field: false,
+            unicode_comment_extra_field: false,
+            external_attributes: None,
+            system: System::Unix,
+            version_made_by: DEFAULT_VERSION,
+            data_descriptor: false,
+            normalize_separators: false,
+            comment: "",
+            min_version: 0,
+            custom_compressor: None,
         };
 
         // GB18030
@@ -2189,24 +4896,364 @@ mod test {
         path.push("..");
         path.push(".");
         path.push("system32");
-        let path_str = super::path_to_string(&path);
+        let path_str = super::path_to_string(&path, false);
         assert_eq!(&*path_str, "system32");
     }
 
     #[test]
-    fn test_shallow_copy() {
+    fn windows_separators() -> ZipResult<()> {
         let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
-        let options = FileOptions {
-            compression_method: CompressionMethod::default(),
-            compression_level: None,
-            last_modified_time: DateTime::default(),
-            permissions: Some(33188),
-            large_file: false,
-            encrypt_with: None,
-            extended_options: (),
+        let normalize = SimpleFileOptions::default().normalize_separators(true);
+        writer.start_file_from_path(r"C:\Users\me\..\notes.txt", normalize)?;
+        writer.add_directory_from_path(r"\photos\2024", normalize)?;
+        writer.start_file(r"d:docs\a.txt", normalize)?;
+        writer.add_directory(r"docs\b\", normalize)?;
+        writer.start_file(r"kept\as is", SimpleFileOptions::default())?;
+        let archive = writer.finish_into_readable()?;
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            [
+                "Users/notes.txt",
+                "photos/2024/",
+                "docs/a.txt",
+                "docs/b/",
+                r"kept\as is"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "deflate")]
+    fn write_raw_deflate() -> ZipResult<()> {
+        use flate2::{write::DeflateEncoder, Compression};
+
+        let data = b"precompressed contents ".repeat(1000);
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&data)?;
+        let compressed = encoder.finish()?;
+        let crc32 = crc32fast::hash(&data);
+
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        // The method, level and encryption in the options don't apply
+        let options = SimpleFileOptions::default()
+            .compression_method(Stored)
+            .compression_level(Some(1))
+            .with_deprecated_encryption(b"password");
+        writer.write_raw_deflate("raw.txt", &compressed, data.len() as u64, crc32, options)?;
+        writer.start_file("after.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"after")?;
+        // The data doesn't have to be read back to check how the size is stored
+        writer.write_raw_deflate(
+            "boundary.txt",
+            &compressed,
+            crate::spec::ZIP64_BYTES_THR,
+            0,
+            SimpleFileOptions::default(),
+        )?;
+
+        let mut archive = writer.finish_into_readable()?;
+        let boundary = archive.by_name("boundary.txt")?;
+        assert_eq!(boundary.size(), crate::spec::ZIP64_BYTES_THR);
+        assert!(boundary.uses_zip64());
+        drop(boundary);
+        let mut file = archive.by_name("raw.txt")?;
+        assert_eq!(file.compression(), CompressionMethod::Deflated);
+        assert_eq!(file.compressed_size(), compressed.len() as u64);
+        assert_eq!(file.size(), data.len() as u64);
+        assert_eq!(file.crc32(), crc32);
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        assert_eq!(contents, data);
+        drop(file);
+        let mut contents = String::new();
+        archive
+            .by_name("after.txt")?
+            .read_to_string(&mut contents)?;
+        assert_eq!(contents, "after");
+        Ok(())
+    }
+
+    #[test]
+    fn raw_copy_at_zip64_boundary() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.write_raw_deflate(
+            "boundary.txt",
+            &[0x03, 0x00],
+            crate::spec::ZIP64_BYTES_THR,
+            0,
+            SimpleFileOptions::default(),
+        )?;
+        let mut source = writer.finish_into_readable()?;
+
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.raw_copy_file(source.by_index_raw(0)?)?;
+        writer.raw_copy_file_rename(source.by_index_raw(0)?, "renamed.txt")?;
+        let mut archive = writer.finish_into_readable()?;
+        for index in 0..2 {
+            let file = archive.by_index_raw(index)?;
+            assert_eq!(file.size(), crate::spec::ZIP64_BYTES_THR);
+            assert!(file.uses_zip64());
+            drop(file);
+            // The local header needs the ZIP64 field too, since its size would be the marker
+            assert!(archive
+                .local_extra_fields(index)?
+                .iter()
+                .any(|field| field.header_id() == 0x0001));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn file_comments() -> ZipResult<()> {
+        let comment = "First line\nSecond line, with ümlauts\r\n\n";
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.start_file("a.txt", SimpleFileOptions::default().with_comment(comment))?;
+        writer.write_all(b"a")?;
+        writer.start_file("b.txt", SimpleFileOptions::default())?;
+        let long = "x".repeat(u16::MAX as usize + 1);
+        assert!(matches!(
+            writer.start_file("c.txt", SimpleFileOptions::default().with_comment(&long)),
+            Err(ZipError::InvalidArchive(
+                "File comment is longer than 65535 bytes"
+            ))
+        ));
+        let long = &long[1..];
+        writer.start_file("c.txt", SimpleFileOptions::default().with_comment(long))?;
+        let cp437 = SimpleFileOptions::default().with_name_encoding(NameEncoding::Cp437);
+        assert!(writer
+            .start_file("d.txt", cp437.with_comment("日本語"))
+            .is_err());
+        let mut archive = writer.finish_into_readable()?;
+        assert_eq!(archive.by_name("a.txt")?.comment(), comment);
+        assert_eq!(archive.by_name("b.txt")?.comment(), "");
+        assert_eq!(archive.by_name("c.txt")?.comment(), long);
+
+        // Copies keep the comment
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        writer.raw_copy_file_rename(archive.by_name("a.txt")?, "raw.txt")?;
+        writer.merge_archive(archive)?;
+        writer.deep_copy_file("a.txt", "deep.txt")?;
+        let mut archive = writer.finish_into_readable()?;
+        assert_eq!(archive.len(), 5);
+        for name in ["raw.txt", "a.txt", "deep.txt"] {
+            assert_eq!(archive.by_name(name)?.comment(), comment);
+        }
+        assert_eq!(archive.by_name("c.txt")?.comment(), long);
+        Ok(())
+    }
+
+    #[test]
+    fn default_file_options() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file_with_defaults("plain.txt")?;
+        let mut options = FullFileOptions::default()
+            .compression_method(Stored)
+            .with_comment("default");
+        options.add_extra_data(0xCAFE, b"extra", false)?;
+        writer.set_default_file_options(options);
+        writer.start_file_with_defaults("default.txt")?;
+        writer.write_all(b"default")?;
+        let overridden = writer.default_file_options().with_comment("overridden");
+        writer.start_file("overridden.txt", overridden)?;
+        writer.set_default_file_options(SimpleFileOptions::default().unix_permissions(0o600));
+        writer.start_file_with_defaults("simple.txt")?;
+        let mut archive = writer.finish_into_readable()?;
+
+        assert_eq!(archive.by_name("plain.txt")?.unix_mode(), Some(0o100644));
+        for (name, comment) in [("default.txt", "default"), ("overridden.txt", "overridden")] {
+            let file = archive.by_name(name)?;
+            assert_eq!(file.compression(), Stored, "{name}");
+            assert_eq!(file.comment(), comment);
+            assert_eq!(file.extra_data(), Some(&b"\xfe\xca\x05\x00extra"[..]));
+        }
+        let file = archive.by_name("simple.txt")?;
+        assert_eq!(file.unix_mode(), Some(0o100600));
+        assert_eq!(file.comment(), "");
+        assert_eq!(file.extra_data(), Some(&[][..]));
+        Ok(())
+    }
+
+    #[test]
+    fn min_version() -> ZipResult<()> {
+        fn versions_needed(archive: &[u8]) -> (u16, u16) {
+            let central = archive
+                .windows(4)
+                .position(|window| window == b"PK\x01\x02")
+                .unwrap();
+            (
+                u16::from_le_bytes([archive[4], archive[5]]),
+                u16::from_le_bytes([archive[central + 6], archive[central + 7]]),
+            )
+        }
+
+        for (options, expected) in [
+            (SimpleFileOptions::default(), 10),
+            (SimpleFileOptions::default().min_version(45), 45),
+            (SimpleFileOptions::default().min_version(63), 63),
+            // ZIP64 needs 4.5 however low the minimum is
+            (
+                SimpleFileOptions::default()
+                    .min_version(20)
+                    .large_file(true),
+                45,
+            ),
+        ] {
+            let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+            writer.start_file("file", options.compression_method(Stored))?;
+            writer.write_all(b"contents")?;
+            let bytes = writer.finish()?.into_inner();
+            assert_eq!(versions_needed(&bytes), (expected, expected));
+            let mut archive = ZipArchive::new(io::Cursor::new(bytes))?;
+            let mut contents = String::new();
+            archive.by_index(0)?.read_to_string(&mut contents)?;
+            assert_eq!(contents, "contents");
+        }
+
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        assert!(matches!(
+            writer.start_file("file", SimpleFileOptions::default().min_version(64)),
+            Err(ZipError::InvalidArchive(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn add_directory_recursive() -> ZipResult<()> {
+        use std::fs;
+
+        let source = tempdir::TempDir::new("add_directory_recursive")?;
+        let root = source.path();
+        fs::create_dir_all(root.join("sub/deeper"))?;
+        fs::create_dir(root.join("empty"))?;
+        fs::create_dir(root.join("build"))?;
+        fs::write(root.join("a.txt"), b"first file")?;
+        fs::write(root.join("sub/b.txt"), b"second file")?;
+        fs::write(root.join("sub/deeper/c.bin"), [0, 1, 2, 3])?;
+        fs::write(root.join("build/output"), b"skipped")?;
+        fs::write(root.join("skipped.log"), b"skipped")?;
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("../a.txt", root.join("sub/link"))?;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_directory_recursive(root, true, |path| {
+            if path.starts_with("build") || path.extension().is_some_and(|e| e == "log") {
+                None
+            } else if path.extension().is_some_and(|e| e == "bin") {
+                Some(SimpleFileOptions::default().compression_method(Stored))
+            } else {
+                Some(SimpleFileOptions::default())
+            }
+        })?;
+        let mut archive = writer.finish_into_readable()?;
+        let mut expected = vec![
+            "a.txt",
+            "empty/",
+            "sub/",
+            "sub/b.txt",
+            "sub/deeper/",
+            "sub/deeper/c.bin",
+        ];
+        #[cfg(unix)]
+        expected.push("sub/link");
+        assert_eq!(archive.file_names().collect::<Vec<_>>(), expected);
+        assert_eq!(archive.by_name("sub/deeper/c.bin")?.compression(), Stored);
+
+        let destination = tempdir::TempDir::new("add_directory_recursive")?;
+        archive.extract(destination.path())?;
+        let extracted = destination.path();
+        assert_eq!(fs::read(extracted.join("a.txt"))?, b"first file");
+        assert_eq!(fs::read(extracted.join("sub/b.txt"))?, b"second file");
+        assert_eq!(fs::read(extracted.join("sub/deeper/c.bin"))?, [0, 1, 2, 3]);
+        assert_eq!(fs::read_dir(extracted.join("empty"))?.count(), 0);
+        assert!(!extracted.join("build").exists());
+        #[cfg(unix)]
+        {
+            assert_eq!(
+                fs::read_link(extracted.join("sub/link"))?,
+                PathBuf::from("../a.txt")
+            );
+            assert_eq!(fs::read(extracted.join("sub/link"))?, b"first file");
+        }
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn add_directory_recursive_symlink_cycle() -> ZipResult<()> {
+        use std::fs;
+
+        let source = tempdir::TempDir::new("add_directory_recursive_symlink_cycle")?;
+        let root = source.path();
+        fs::create_dir(root.join("sub"))?;
+        fs::write(root.join("sub/file"), b"contents")?;
+        std::os::unix::fs::symlink("..", root.join("sub/parent"))?;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        match writer.add_directory_recursive(root, false, |_| Some(SimpleFileOptions::default())) {
+            Err(ZipError::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidInput),
+            other => panic!("expected an error, got {other:?}"),
+        }
+
+        // A symlink to a directory elsewhere is still followed
+        fs::remove_file(root.join("sub/parent"))?;
+        fs::create_dir(root.join("other"))?;
+        fs::write(root.join("other/file"), b"other")?;
+        std::os::unix::fs::symlink("../other", root.join("sub/other"))?;
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.add_directory_recursive(root, false, |_| Some(SimpleFileOptions::default()))?;
+        let archive = writer.finish_into_readable()?;
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            [
+                "other/",
+                "other/file",
+                "sub/",
+                "sub/file",
+                "sub/other/",
+                "sub/other/file"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_shallow_copy() {
+        let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+        let options = FileOptions {
+            compression_method: CompressionMethod::default(),
+            compression_level: None,
+            last_modified_time: DateTime::default(),
+            permissions: Some(33188),
+            zip64: Zip64Mode::Auto,
+            encrypt_with: None,
+            extended_options: (),
             alignment: 0,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: None,
+            #[cfg(feature = "zstd")]
+            zstd_dictionary: None,
+            #[cfg(feature = "brotli")]
+            brotli_window: super::BROTLI_DEFAULT_WINDOW,
+            #[cfg(feature = "aes-crypto")]
+            aes_iterations: crate::aes::DEFAULT_ITERATION_COUNT,
+            extended_timestamp: None,
+            ntfs_times: None,
+            unix_ownership: None,
+            hardlink_target: None,
+            compression_method_set: false,
+            name_encoding: NameEncoding::Utf8,
+            dos_attributes: DosAttributes::default(),
+            unicode_path_extra_field: false,
+            unicode_comment_extra_field: false,
+            external_attributes: None,
+            system: System::Unix,
+            version_made_by: DEFAULT_VERSION,
+            data_descriptor: false,
+            normalize_separators: false,
+            comment: "",
+            min_version: 0,
+            custom_compressor: None,
         };
         writer.start_file(RT_TEST_FILENAME, options).unwrap();
         writer.write_all(RT_TEST_TEXT.as_ref()).unwrap();
@@ -2252,12 +5299,35 @@ mod test {
             compression_level: None,
             last_modified_time: DateTime::default(),
             permissions: Some(33188),
-            large_file: false,
+            zip64: Zip64Mode::Auto,
             encrypt_with: None,
             extended_options: (),
             alignment: 0,
             #[cfg(feature = "deflate-zopfli")]
             zopfli_buffer_size: None,
+            #[cfg(feature = "zstd")]
+            zstd_dictionary: None,
+            #[cfg(feature = "brotli")]
+            brotli_window: super::BROTLI_DEFAULT_WINDOW,
+            #[cfg(feature = "aes-crypto")]
+            aes_iterations: crate::aes::DEFAULT_ITERATION_COUNT,
+            extended_timestamp: None,
+            ntfs_times: None,
+            unix_ownership: None,
+            hardlink_target: None,
+            compression_method_set: false,
+            name_encoding: NameEncoding::Utf8,
+            dos_attributes: DosAttributes::default(),
+            unicode_path_extra_field: false,
+            unicode_comment_extra_field: false,
+            external_attributes: None,
+            system: System::Unix,
+            version_made_by: DEFAULT_VERSION,
+            data_descriptor: false,
+            normalize_separators: false,
+            comment: "",
+            min_version: 0,
+            custom_compressor: None,
         };
         writer.start_file(RT_TEST_FILENAME, options).unwrap();
         writer.write_all(RT_TEST_TEXT.as_ref()).unwrap();
@@ -2556,4 +5626,1625 @@ mod test {
         let _ = ZipArchive::new(first_writer.finish()?)?;
         Ok(())
     }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_dictionary_round_trip() -> ZipResult<()> {
+        let dictionary =
+            br#"{"id": 0, "name": "example", "tags": ["alpha", "beta", "gamma"], "enabled": true}"#;
+        let entry = |i: usize| {
+            format!(
+                r#"{{"id": {i}, "name": "example", "tags": ["alpha", "beta", "gamma"], "enabled": true}}"#
+            )
+        };
+        let write_entries = |options: SimpleFileOptions| -> ZipResult<_> {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            for i in 0..10 {
+                writer.start_file(format!("{i}.json"), options)?;
+                writer.write_all(entry(i).as_bytes())?;
+            }
+            writer.finish_into_readable()
+        };
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Zstd);
+        let mut plain = write_entries(options)?;
+        let mut with_dictionary = write_entries(options.with_zstd_dictionary(dictionary))?;
+        for i in 0..10 {
+            let plain_size = plain.by_index(i)?.compressed_size();
+            let mut file = with_dictionary.by_index_with_zstd_dictionary(i, dictionary)?;
+            assert!(file.compressed_size() < plain_size);
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)?;
+            assert_eq!(contents, entry(i));
+        }
+        let mut contents = String::new();
+        with_dictionary
+            .by_name_with_zstd_dictionary("3.json", dictionary)?
+            .read_to_string(&mut contents)?;
+        assert_eq!(contents, entry(3));
+        Ok(())
+    }
+
+    #[cfg(feature = "brotli")]
+    #[test]
+    fn brotli_round_trip() -> ZipResult<()> {
+        let contents = "Brotli is good at compressing text. ".repeat(1000);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Brotli);
+        for (name, options) in [
+            ("default.txt", options),
+            ("fast.txt", options.compression_level(Some(0))),
+            ("small_window.txt", options.with_brotli_window(10)),
+        ] {
+            writer.start_file(name, options)?;
+            writer.write_all(contents.as_bytes())?;
+        }
+        for options in [
+            options.compression_level(Some(12)),
+            options.with_brotli_window(9),
+            options.with_brotli_window(25),
+        ] {
+            assert!(matches!(
+                writer.start_file("invalid.txt", options),
+                Err(ZipError::UnsupportedArchive(_))
+            ));
+        }
+        let bytes = writer.finish()?.into_inner();
+        // The method ID in the first local header
+        assert_eq!(bytes[8..10], 121u16.to_le_bytes());
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert_eq!(archive.len(), 3);
+        for i in 0..archive.len() {
+            let mut file = archive.by_index(i)?;
+            assert_eq!(file.compression(), CompressionMethod::Brotli);
+            assert!(file.compressed_size() < contents.len() as u64 / 10);
+            let mut read_back = String::new();
+            file.read_to_string(&mut read_back)?;
+            assert_eq!(read_back, contents);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn remove_file() -> ZipResult<()> {
+        let options = SimpleFileOptions::default();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents) in [("a.txt", "first"), ("b.txt", "second"), ("c.txt", "third")] {
+            writer.start_file(name, options)?;
+            writer.write_all(contents.as_bytes())?;
+        }
+        writer.shallow_copy_file("c.txt", "d.txt")?;
+        let mut writer = ZipWriter::new_append(writer.finish()?)?;
+        assert!(matches!(
+            writer.remove_file("missing.txt"),
+            Err(ZipError::FileNotFound)
+        ));
+        writer.remove_file("b.txt")?;
+        writer.remove_file("c.txt")?;
+        writer.start_file("e.txt", options)?;
+        writer.write_all(b"fifth")?;
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            ["a.txt", "d.txt", "e.txt"]
+        );
+        for (name, expected) in [("a.txt", "first"), ("d.txt", "third"), ("e.txt", "fifth")] {
+            let mut contents = String::new();
+            archive.by_name(name)?.read_to_string(&mut contents)?;
+            assert_eq!(contents, expected);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn rename_entries() -> ZipResult<()> {
+        use crate::read::{read_zipfile_from_stream, Strictness};
+
+        let large: Vec<u8> = (0..100_000u32).flat_map(u32::to_le_bytes).collect();
+        let entries: [(&str, SimpleFileOptions, &[u8]); 5] = [
+            ("a.txt", SimpleFileOptions::default(), b"first"),
+            (
+                "dir/large.bin",
+                SimpleFileOptions::default().compression_method(Stored),
+                &large,
+            ),
+            (
+                "descriptor.txt",
+                SimpleFileOptions::default()
+                    .compression_method(Stored)
+                    .with_data_descriptor(true),
+                b"third",
+            ),
+            ("kept.txt", SimpleFileOptions::default(), b"fourth"),
+            ("b.txt", SimpleFileOptions::default(), b"fifth"),
+        ];
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, options, contents) in entries {
+            writer.start_file(name, options)?;
+            writer.write_all(contents)?;
+        }
+        writer.shallow_copy_file("b.txt", "copy.txt")?;
+        let mut writer = ZipWriter::new_append(writer.finish()?)?;
+
+        assert!(matches!(
+            writer.rename_entries(|name| (name == "a.txt").then(|| "kept.txt".to_string())),
+            Err(ZipError::DuplicateName(name)) if &*name == "kept.txt"
+        ));
+        writer.rename_entries(|name| match name {
+            "a.txt" => Some("a much longer name.txt".to_string()),
+            "dir/large.bin" => Some("l".to_string()),
+            "descriptor.txt" => Some("descripteur-ôté.txt".to_string()),
+            "kept.txt" => Some(name.to_string()),
+            "copy.txt" => Some("renamed copy.txt".to_string()),
+            _ => None,
+        })?;
+        writer.start_file("new.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"added after renaming")?;
+        let bytes = writer.finish()?.into_inner();
+
+        let expected = [
+            ("a much longer name.txt", &b"first"[..]),
+            ("l", &large),
+            ("descripteur-ôté.txt", b"third"),
+            ("kept.txt", b"fourth"),
+            ("b.txt", b"fifth"),
+            ("renamed copy.txt", b"fifth"),
+            ("new.txt", b"added after renaming"),
+        ];
+        let mut archive = ZipArchive::new(Cursor::new(bytes.clone()))?;
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            expected.map(|(name, _)| name)
+        );
+        for (name, contents) in expected {
+            // The local headers have the new names too, except for the shallow copy's
+            archive.set_strictness(if name == "renamed copy.txt" {
+                Strictness::Lenient
+            } else {
+                Strictness::Strict
+            });
+            let mut read = Vec::new();
+            archive.by_name(name)?.read_to_end(&mut read)?;
+            assert_eq!(read, contents, "{name}");
+        }
+        let mut stream = Cursor::new(bytes);
+        let mut names = Vec::new();
+        while let Some(file) = read_zipfile_from_stream(&mut stream)? {
+            names.push(file.name().to_string());
+        }
+        assert_eq!(
+            names,
+            [
+                "a much longer name.txt",
+                "l",
+                "descripteur-ôté.txt",
+                "kept.txt",
+                "b.txt",
+                "new.txt"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn forced_data_descriptor() -> ZipResult<()> {
+        use crate::read::{read_zipfile_from_stream, read_zipfile_from_stream_with_password};
+
+        let options = SimpleFileOptions::default()
+            .compression_method(Stored)
+            .with_data_descriptor(true);
+        // Holds data descriptor signatures that don't end the data
+        let tricky = b"xxPK\x07\x08\x01\x02\x03\x04\x10\0\0\0\x10\0\0\0PK\x07".repeat(3);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("plain.txt", options)?;
+        writer.write_all(b"stored with a data descriptor")?;
+        writer.start_file("tricky.bin", options)?;
+        writer.write_all(&tricky)?;
+        writer.start_file("empty.txt", options)?;
+        writer.start_file("large.txt", options.large_file(true))?;
+        writer.write_all(b"with 8-byte sizes")?;
+        writer.start_file(
+            "header.txt",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(b"sizes in the local header")?;
+        writer.start_file(
+            "secret.txt",
+            options.with_deprecated_encryption(b"password"),
+        )?;
+        writer.write_all(b"encrypted")?;
+        let bytes = writer.finish()?.into_inner();
+
+        let expected: [(&str, &[u8], bool); 6] = [
+            ("plain.txt", b"stored with a data descriptor", true),
+            ("tricky.bin", &tricky, true),
+            ("empty.txt", b"", true),
+            ("large.txt", b"with 8-byte sizes", true),
+            ("header.txt", b"sizes in the local header", false),
+            ("secret.txt", b"encrypted", true),
+        ];
+        let mut archive = ZipArchive::new(Cursor::new(bytes.clone()))?;
+        for (name, contents, data_descriptor) in expected {
+            let mut file = if name == "secret.txt" {
+                archive.by_name_decrypt(name, b"password")?
+            } else {
+                archive.by_name(name)?
+            };
+            let local_flags = u16::from_le_bytes(
+                bytes[file.header_start() as usize + 6..][..2]
+                    .try_into()
+                    .unwrap(),
+            );
+            assert_eq!(local_flags & (1 << 3) != 0, data_descriptor, "{name}");
+            let mut read = Vec::new();
+            file.read_to_end(&mut read)?;
+            assert_eq!(read, contents, "{name}");
+        }
+
+        let mut stream = Cursor::new(bytes);
+        for (name, contents, _) in expected {
+            let mut file = if name == "secret.txt" {
+                read_zipfile_from_stream_with_password(&mut stream, b"password")?
+            } else {
+                read_zipfile_from_stream(&mut stream)?
+            }
+            .unwrap();
+            assert_eq!(file.name(), name);
+            assert_eq!(file.size(), contents.len() as u64);
+            let mut read = Vec::new();
+            file.read_to_end(&mut read)?;
+            assert_eq!(read, contents, "{name}");
+        }
+        assert!(read_zipfile_from_stream(&mut stream)?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn remove_file_with_data_descriptor() -> ZipResult<()> {
+        let mut v = Vec::new();
+        v.extend_from_slice(include_bytes!("../tests/data/data_descriptor.zip"));
+        let mut writer = ZipWriter::new_append(Cursor::new(v))?;
+        let name = writer.files.keys().next().unwrap().to_string();
+        assert!(writer.files[0].using_data_descriptor);
+        writer.start_file("after.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"after")?;
+        writer.remove_file(&name)?;
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+        assert_eq!(archive.len(), 1);
+        let file = archive.by_index(0)?;
+        assert_eq!(file.header_start(), 0);
+        drop(file);
+        let mut contents = String::new();
+        archive
+            .by_name("after.txt")?
+            .read_to_string(&mut contents)?;
+        assert_eq!(contents, "after");
+        Ok(())
+    }
+
+    #[test]
+    fn merge_archive_duplicate_policies() -> ZipResult<()> {
+        let archive = |contents: &[(&str, &str)]| -> ZipResult<_> {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            for (name, data) in contents {
+                writer.start_file(*name, SimpleFileOptions::default())?;
+                writer.write_all(data.as_bytes())?;
+            }
+            writer.finish_into_readable()
+        };
+        let read = |archive: &mut ZipArchive<_>, name| -> ZipResult<String> {
+            let mut contents = String::new();
+            archive.by_name(name)?.read_to_string(&mut contents)?;
+            Ok(contents)
+        };
+        for (policy, expected) in [
+            (MergeDuplicatePolicy::Skip, "old"),
+            (MergeDuplicatePolicy::Overwrite, "new"),
+        ] {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            writer.merge_archive(archive(&[("a.txt", "old"), ("b.txt", "b")])?)?;
+            writer
+                .merge_archive_with_policy(archive(&[("a.txt", "new"), ("c.txt", "c")])?, policy)?;
+            let mut merged = writer.finish_into_readable()?;
+            assert_eq!(merged.len(), 3);
+            assert_eq!(read(&mut merged, "a.txt")?, expected);
+            assert_eq!(read(&mut merged, "b.txt")?, "b");
+            assert_eq!(read(&mut merged, "c.txt")?, "c");
+            // A replaced entry keeps its place
+            assert!(merged.file_names().eq(["a.txt", "b.txt", "c.txt"]));
+        }
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.merge_archive(archive(&[("a.txt", "old")])?)?;
+        assert!(writer
+            .merge_archive_with_policy(
+                archive(&[("c.txt", "c"), ("a.txt", "new")])?,
+                MergeDuplicatePolicy::Error,
+            )
+            .is_err());
+        let mut merged = writer.finish_into_readable()?;
+        assert_eq!(merged.len(), 1);
+        assert_eq!(read(&mut merged, "a.txt")?, "old");
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_duplicate_case_folding(true);
+        writer.merge_archive(archive(&[("a.txt", "old"), ("b.txt", "b")])?)?;
+        writer.merge_archive(archive(&[("A.TXT", "new")])?)?;
+        // The source's own names can clash once case is folded
+        assert!(matches!(
+            writer.merge_archive_with_policy(
+                archive(&[("c.txt", "c"), ("C.txt", "C")])?,
+                MergeDuplicatePolicy::Error,
+            ),
+            Err(ZipError::DuplicateName(name)) if &*name == "C.txt"
+        ));
+        let mut merged = writer.finish_into_readable()?;
+        assert!(merged.file_names().eq(["A.TXT", "b.txt"]));
+        assert_eq!(read(&mut merged, "A.TXT")?, "new");
+        Ok(())
+    }
+
+    #[test]
+    fn central_directory_zip64_field() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("large.txt", SimpleFileOptions::default().large_file(true))?;
+        writer.write_all(b"large")?;
+        writer.start_file(
+            "small.txt",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(b"small")?;
+        let bytes = writer.finish()?.into_inner();
+        // Appending reads each ZIP64 field into the entry's extra field, which mustn't be written
+        // again next to the new one
+        let mut writer = ZipWriter::new_append(Cursor::new(bytes))?;
+        writer.start_file("after.txt", SimpleFileOptions::default())?;
+        let bytes = writer.finish()?.into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes.as_slice()))?;
+        let central = &bytes[archive.by_name("large.txt")?.central_header_start() as usize..];
+        // Like the local header, the central one has both sizes in a single ZIP64 field
+        assert_eq!(central[20..28], [0xFF; 8]);
+        assert_eq!(central[30..32], [20, 0]);
+        assert_eq!(central[46 + 9..46 + 13], [1, 0, 16, 0]);
+        let central = &bytes[archive.by_name("small.txt")?.central_header_start() as usize..];
+        assert_eq!(central[20..28], [5, 0, 0, 0, 5, 0, 0, 0]);
+        assert_eq!(central[30..32], [0, 0]);
+        let mut contents = String::new();
+        archive
+            .by_name("large.txt")?
+            .read_to_string(&mut contents)?;
+        assert_eq!(contents, "large");
+        Ok(())
+    }
+
+    #[test]
+    fn merge_archive_keeps_zip64() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("first.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"first")?;
+        let mut source = ZipWriter::new(Cursor::new(Vec::new()));
+        source.start_file("large.txt", SimpleFileOptions::default().large_file(true))?;
+        source.write_all(b"large")?;
+        writer.merge_archive(source.finish_into_readable()?)?;
+        let bytes = writer.finish()?.into_inner();
+
+        let mut merged = ZipArchive::new(Cursor::new(bytes.as_slice()))?;
+        let mut large = merged.by_name("large.txt")?;
+        assert_ne!(large.header_start(), 0);
+        // The central directory record has both sizes in a single ZIP64 field
+        let central = &bytes[large.central_header_start() as usize..];
+        assert_eq!(central[20..28], [0xFF; 8]);
+        assert_eq!(central[30..32], [20, 0]);
+        assert_eq!(central[46 + 9..46 + 13], [1, 0, 16, 0]);
+        let mut contents = String::new();
+        large.read_to_string(&mut contents)?;
+        assert_eq!(contents, "large");
+        Ok(())
+    }
+
+    #[test]
+    fn legacy_name_encodings() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let cp437 = SimpleFileOptions::default().with_name_encoding(NameEncoding::Cp437);
+        writer.start_file("café.txt", cp437)?;
+        writer.start_file("naïve.txt", SimpleFileOptions::default())?;
+        assert!(writer.start_file("日本語.txt", cp437).is_err());
+        #[cfg(feature = "encoding_rs")]
+        writer.start_file(
+            "日本語.txt",
+            SimpleFileOptions::default().with_name_encoding(NameEncoding::ShiftJis),
+        )?;
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+        assert_eq!(archive.by_index(0)?.name_raw(), b"caf\x82.txt");
+        assert_eq!(archive.by_index(0)?.name(), "café.txt");
+        assert_eq!(archive.by_index(1)?.name_raw(), "naïve.txt".as_bytes());
+        #[cfg(feature = "encoding_rs")]
+        {
+            assert_eq!(
+                archive.by_index(2)?.name_raw(),
+                b"\x93\xfa\x96\x7b\x8c\xea.txt"
+            );
+            assert_ne!(archive.by_index(2)?.name(), "日本語.txt");
+            archive.set_name_encoding(NameEncoding::ShiftJis)?;
+            assert_eq!(archive.by_index(2)?.name(), "日本語.txt");
+            assert!(archive.by_name("日本語.txt").is_ok());
+        }
+        // Names with the UTF-8 flag are never redecoded
+        archive.set_name_encoding(NameEncoding::Cp437)?;
+        assert_eq!(archive.by_index(1)?.name(), "naïve.txt");
+        assert_eq!(archive.by_index(0)?.name(), "café.txt");
+        Ok(())
+    }
+
+    #[test]
+    fn name_encoding_collision() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let cp437 = SimpleFileOptions::default().with_name_encoding(NameEncoding::Cp437);
+        writer.start_file("café", cp437)?;
+        writer.start_file("cafâ", cp437)?;
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+        // Both names end in a byte that isn't valid UTF-8 on its own
+        assert!(matches!(
+            archive.set_name_encoding(NameEncoding::Utf8),
+            Err(ZipError::DuplicateName(name)) if &*name == "caf\u{fffd}"
+        ));
+        assert_eq!(archive.file_names().collect::<Vec<_>>(), ["café", "cafâ"]);
+        assert_eq!(archive.by_index(1)?.name(), "cafâ");
+        Ok(())
+    }
+
+    #[test]
+    fn deterministic_output() -> ZipResult<()> {
+        let write = || -> ZipResult<Vec<u8>> {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            writer.set_deterministic(true);
+            writer.add_directory("dir/", SimpleFileOptions::default())?;
+            writer.start_file(
+                "dir/file.txt",
+                SimpleFileOptions::default().with_extended_timestamp(1714635025, None, None),
+            )?;
+            writer.write_all(b"some contents")?;
+            writer.add_symlink("link", "dir/file.txt", SimpleFileOptions::default())?;
+            Ok(writer.finish()?.into_inner())
+        };
+        let first = write()?;
+        std::thread::sleep(std::time::Duration::from_secs(2));
+        assert_eq!(first, write()?);
+        let mut archive = ZipArchive::new(Cursor::new(first))?;
+        let file = archive.by_name("dir/file.txt")?;
+        assert_eq!(
+            file.last_modified().datepart(),
+            DateTime::default().datepart()
+        );
+        assert_eq!(
+            file.last_modified().timepart(),
+            DateTime::default().timepart()
+        );
+        assert!(file.extra_data_fields().next().is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn raw_copy_file_rename_updates_utf8_flag() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("ascii.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"ascii")?;
+        writer.start_file("\u{1F600}.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"emoji")?;
+        let mut source = writer.finish_into_readable()?;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.raw_copy_file_rename(source.by_name("ascii.txt")?, "\u{00fc}ber.txt")?;
+        writer.raw_copy_file_rename(source.by_name("\u{1F600}.txt")?, "plain.txt")?;
+        let bytes = writer.finish()?.into_inner();
+
+        let local_flags = |name: &[u8]| {
+            let name_start = bytes.windows(name.len()).position(|w| w == name).unwrap();
+            let header_start = name_start - 30;
+            assert_eq!(
+                bytes[header_start..header_start + 4],
+                crate::spec::LOCAL_FILE_HEADER_SIGNATURE.to_le_bytes()
+            );
+            u16::from_le_bytes([bytes[header_start + 6], bytes[header_start + 7]])
+        };
+        assert_ne!(local_flags("\u{00fc}ber.txt".as_bytes()) & (1 << 11), 0);
+        assert_eq!(local_flags(b"plain.txt") & (1 << 11), 0);
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        let mut contents = String::new();
+        archive
+            .by_name("\u{00fc}ber.txt")?
+            .read_to_string(&mut contents)?;
+        assert_eq!(contents, "ascii");
+        contents.clear();
+        archive
+            .by_name("plain.txt")?
+            .read_to_string(&mut contents)?;
+        assert_eq!(contents, "emoji");
+        Ok(())
+    }
+
+    #[test]
+    fn custom_extra_data_round_trip() -> ZipResult<()> {
+        let mut options = FullFileOptions::default();
+        options.add_extra_data(0x7a7a, b"everywhere", false)?;
+        options.add_extra_data(0x7b7b, b"central only", true)?;
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("file", options)?;
+        writer.write_all(b"contents")?;
+        let bytes = writer.finish()?.into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        let central: Vec<_> = archive
+            .by_index(0)?
+            .raw_extra_fields()
+            .map(|field| (field.header_id(), field.data().to_vec()))
+            .collect();
+        assert_eq!(
+            central,
+            [
+                (0x7a7a, b"everywhere".to_vec()),
+                (0x7b7b, b"central only".to_vec())
+            ]
+        );
+        let local: Vec<_> = archive
+            .local_extra_fields(0)?
+            .into_iter()
+            .map(|field| (field.header_id(), field.data().to_vec()))
+            .collect();
+        assert_eq!(local, [(0x7a7a, b"everywhere".to_vec())]);
+
+        let mut options = FullFileOptions::default();
+        options.add_extra_data(0x7a7a, &[0; 40000], false)?;
+        assert!(options.add_extra_data(0x7b7b, &[0; 30000], true).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn ntfs_times_round_trip() -> ZipResult<()> {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let mtime = UNIX_EPOCH + Duration::new(4_102_444_800, 500_000_000);
+        let atime = UNIX_EPOCH + Duration::from_secs(1_714_635_025);
+        let ctime = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "ntfs.txt",
+            SimpleFileOptions::default()
+                .with_extended_timestamp(1, None, None)
+                .with_ntfs_times(mtime, atime, ctime),
+        )?;
+        writer.start_file("dos.txt", SimpleFileOptions::default())?;
+        let bytes = writer.finish()?.into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        let file = archive.by_name("ntfs.txt")?;
+        assert_eq!(file.last_modified_precise(), Some(mtime));
+        assert_eq!(file.last_accessed(), Some(atime));
+        assert_eq!(file.created(), Some(ctime));
+        drop(file);
+        assert_eq!(
+            archive.local_extra_fields(0)?[1],
+            archive.by_index(0)?.raw_extra_fields().nth(1).unwrap()
+        );
+        let file = archive.by_name("dos.txt")?;
+        assert!(file.last_modified_precise().is_some());
+        assert_eq!(file.last_accessed(), None);
+        assert_eq!(file.created(), None);
+        Ok(())
+    }
+
+    #[test]
+    fn write_with_stub() -> ZipResult<()> {
+        let stub = b"MZ\x90\x00 not really an executable";
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_stub(stub)?;
+        writer.start_file("file.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"after the stub")?;
+        assert!(writer.set_stub(stub).is_err());
+        let bytes = writer.finish()?.into_inner();
+        assert!(bytes.starts_with(stub));
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert_eq!(archive.prepended_data_len(), stub.len() as u64);
+        assert_eq!(archive.offset(), 0);
+        let mut contents = String::new();
+        archive.by_name("file.txt")?.read_to_string(&mut contents)?;
+        assert_eq!(contents, "after the stub");
+        Ok(())
+    }
+
+    #[test]
+    fn start_file_auto() -> ZipResult<()> {
+        use super::CompressionHeuristic;
+
+        let text = "The quick brown fox jumps over the lazy dog.\n".repeat(1000);
+        let noise: Vec<u8> = (0..20000u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+            .collect();
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::DEFLATE)
+            .compression_level(Some(1));
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file_auto("text.txt", options)?;
+        assert!(writer.is_writing_file());
+        writer.write_all(text.as_bytes())?;
+        writer.start_file_auto("noise.bin", options)?;
+        // Shorter than the sample, so the method is only chosen when the file is finished
+        writer.write_all(&noise[..100])?;
+        writer.write_all(&noise[100..1000])?;
+        writer.start_file_auto("photo.JPG", options)?;
+        writer.write_all(text.as_bytes())?;
+        writer.start_file_auto("more_noise.bin", options)?;
+        writer.write_all(&noise)?;
+        writer.start_file_auto("aborted.txt", options)?;
+        writer.write_all(b"never written")?;
+        writer.abort_file()?;
+        writer.start_file_auto("empty.txt", options)?;
+        writer.set_compression_heuristic(CompressionHeuristic::default().sample_size(0));
+        writer.start_file_auto("unsampled.bin", options)?;
+        writer.write_all(&noise)?;
+
+        let mut archive = writer.finish_into_readable()?;
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            [
+                "text.txt",
+                "noise.bin",
+                "photo.JPG",
+                "more_noise.bin",
+                "empty.txt",
+                "unsampled.bin"
+            ]
+        );
+        for (name, method, contents) in [
+            ("text.txt", CompressionMethod::DEFLATE, text.as_bytes()),
+            ("noise.bin", Stored, &noise[..1000]),
+            ("photo.JPG", Stored, text.as_bytes()),
+            ("more_noise.bin", Stored, &noise[..]),
+            ("empty.txt", CompressionMethod::DEFLATE, &[]),
+            ("unsampled.bin", CompressionMethod::DEFLATE, &noise[..]),
+        ] {
+            let mut file = archive.by_name(name)?;
+            assert_eq!(file.compression(), method, "{name}");
+            let mut read = Vec::new();
+            file.read_to_end(&mut read)?;
+            assert_eq!(read, contents, "{name}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn start_file_auto_duplicate_name() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("file.txt", SimpleFileOptions::default())?;
+        writer.start_file_auto("file.txt", SimpleFileOptions::default())?;
+        assert!(writer.write_all(&[b'a'; 5000]).is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn try_last_modified_time() -> ZipResult<()> {
+        use time::macros::datetime;
+
+        assert!(SimpleFileOptions::default()
+            .try_last_modified_time(datetime!(1979-12-31 23:59:59 UTC))
+            .is_err());
+        let options = SimpleFileOptions::default()
+            .try_last_modified_time(datetime!(2024-05-01 12:34:57.789 UTC))
+            .unwrap();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("file.txt", options)?;
+        writer.start_file(
+            "dos.txt",
+            SimpleFileOptions::default()
+                .try_last_modified_time(DateTime::from_date_and_time(2000, 1, 2, 3, 4, 6).unwrap())
+                .unwrap(),
+        )?;
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+        assert_eq!(
+            archive
+                .by_name("file.txt")?
+                .last_modified()
+                .to_time()
+                .unwrap(),
+            datetime!(2024-05-01 12:34:56 UTC)
+        );
+        assert_eq!(
+            archive
+                .by_name("dos.txt")?
+                .last_modified()
+                .to_time()
+                .unwrap(),
+            datetime!(2000-01-02 03:04:06 UTC)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn last_modified_time_from_system() -> ZipResult<()> {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        assert!(SimpleFileOptions::default()
+            .last_modified_time_from_system(UNIX_EPOCH)
+            .is_err());
+        let options = SimpleFileOptions::default()
+            .last_modified_time_from_system(UNIX_EPOCH + Duration::from_secs(1_714_566_897))
+            .unwrap();
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("file.txt", options)?;
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+        // MS-DOS times only have two-second precision
+        assert_eq!(
+            archive
+                .by_name("file.txt")?
+                .last_modified()
+                .to_system_time(),
+            Some(UNIX_EPOCH + Duration::from_secs(1_714_566_896))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_policy() -> ZipResult<()> {
+        use super::DuplicatePolicy;
+        use crate::spec::CentralDirectoryEnd;
+
+        fn contents(archive: &mut ZipArchive<Cursor<Vec<u8>>>, name: &str) -> String {
+            let mut contents = String::new();
+            archive
+                .by_name(name)
+                .unwrap()
+                .read_to_string(&mut contents)
+                .unwrap();
+            contents
+        }
+
+        let options = SimpleFileOptions::default().compression_method(Stored);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("a.txt", options)?;
+        writer.write_all(b"first")?;
+        assert!(matches!(
+            writer.start_file("a.txt", options),
+            Err(ZipError::DuplicateName(name)) if &*name == "a.txt"
+        ));
+        // Names are case-sensitive unless case folding is enabled
+        writer.start_file("A.TXT", options)?;
+        writer.set_duplicate_case_folding(true);
+        assert!(matches!(
+            writer.start_file("a.TXT", options),
+            Err(ZipError::DuplicateName(_))
+        ));
+
+        writer.set_duplicate_policy(DuplicatePolicy::Allow);
+        writer.start_file("a.txt", options)?;
+        writer.write_all(b"second")?;
+        let mut bytes = writer.finish()?;
+        let (footer, _) = CentralDirectoryEnd::find_and_parse(&mut bytes)?;
+        assert_eq!(footer.number_of_files, 3);
+        let mut archive = ZipArchive::new(bytes)?;
+        assert_eq!(archive.len(), 2);
+        assert_eq!(contents(&mut archive, "a.txt"), "second");
+
+        let mut writer = ZipWriter::new_append(archive.into_inner())?;
+        writer.set_duplicate_policy(DuplicatePolicy::Overwrite);
+        writer.start_file("a.txt", options)?;
+        writer.write_all(b"third")?;
+        writer.set_duplicate_case_folding(true);
+        writer.start_file("a.Txt", options)?;
+        writer.write_all(b"fourth")?;
+        // Aborting the replacement brings back the entry it replaced
+        writer.start_file("A.txt", options)?;
+        writer.abort_file()?;
+        let mut bytes = writer.finish()?;
+        let (footer, _) = CentralDirectoryEnd::find_and_parse(&mut bytes)?;
+        assert_eq!(footer.number_of_files, 1);
+        let mut archive = ZipArchive::new(bytes)?;
+        assert_eq!(archive.file_names().collect::<Vec<_>>(), ["a.Txt"]);
+        assert_eq!(contents(&mut archive, "a.Txt"), "fourth");
+        Ok(())
+    }
+
+    #[cfg(all(feature = "aes-crypto", feature = "_deflate-any"))]
+    #[test]
+    fn raw_copy_file_reencrypt() -> ZipResult<()> {
+        use crate::AesMode;
+
+        let contents = "Rotate me. ".repeat(100);
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .compression_level(Some(1));
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("zipcrypto.txt", options.with_deprecated_encryption(b"old"))?;
+        writer.write_all(contents.as_bytes())?;
+        writer.start_file(
+            "aes.txt",
+            options.with_aes_encryption(AesMode::Aes128, "old"),
+        )?;
+        writer.write_all(contents.as_bytes())?;
+        // AE-2 is used for files this short
+        writer.start_file(
+            "short.txt",
+            options.with_aes_encryption(AesMode::Aes128, "old"),
+        )?;
+        writer.write_all(b"short")?;
+        let mut source = ZipArchive::new(writer.finish()?)?;
+        let compressed_len = source.entry_metadata(0)?.compressed_size - 12;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let new_options = SimpleFileOptions::default();
+        assert!(matches!(
+            writer.raw_copy_file_reencrypt(source.by_index_raw(1)?, new_options),
+            Err(ZipError::PasswordRequired)
+        ));
+        writer.raw_copy_file_reencrypt(
+            source.by_name_decrypt("zipcrypto.txt", b"old")?,
+            new_options.with_aes_encryption(AesMode::Aes256, "new"),
+        )?;
+        writer.raw_copy_file_reencrypt(
+            source.by_name_decrypt("aes.txt", b"old")?,
+            new_options.with_deprecated_encryption(b"new"),
+        )?;
+        writer.raw_copy_file_reencrypt(
+            source.by_name_decrypt("short.txt", b"old")?,
+            new_options.with_aes_encryption(AesMode::Aes192, "new"),
+        )?;
+        assert!(matches!(
+            writer.raw_copy_file_reencrypt(
+                source.by_name_decrypt("short.txt", b"old")?,
+                new_options.with_deprecated_encryption(b"new"),
+            ),
+            Err(ZipError::UnsupportedArchive(_))
+        ));
+        let mut archive = ZipArchive::new(writer.finish()?)?;
+        assert_eq!(archive.len(), 3);
+
+        for (name, expected, overhead) in [
+            ("zipcrypto.txt", contents.as_str(), 16 + 2 + 10),
+            ("aes.txt", contents.as_str(), 12),
+            ("short.txt", "short", 24 + 2 + 10),
+        ] {
+            assert!(archive.by_name_decrypt(name, b"old").is_err(), "{name}");
+            let mut file = archive.by_name_decrypt(name, b"new")?;
+            if name != "short.txt" {
+                assert_eq!(file.compressed_size(), compressed_len + overhead, "{name}");
+            }
+            let mut actual = String::new();
+            file.read_to_string(&mut actual)?;
+            assert_eq!(actual, expected, "{name}");
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "aes-crypto")]
+    #[test]
+    fn aes_vendor_version() -> ZipResult<()> {
+        use crate::{AesMode, AesVendorVersion};
+
+        let long = b"Long enough to use AE-1 by default";
+        let options = SimpleFileOptions::default().compression_method(Stored);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for (name, contents, vendor_version) in [
+            ("long-ae2", &long[..], Some(AesVendorVersion::Ae2)),
+            ("short-ae1", b"short", Some(AesVendorVersion::Ae1)),
+            ("long-auto", long, None),
+            ("short-auto", b"short", None),
+        ] {
+            let options = match vendor_version {
+                Some(vendor_version) => {
+                    options.with_aes_encryption_version(AesMode::Aes256, vendor_version, "password")
+                }
+                None => options.with_aes_encryption(AesMode::Aes256, "password"),
+            };
+            writer.start_file(name, options)?;
+            writer.write_all(contents)?;
+        }
+        let bytes = writer.finish()?.into_inner();
+        let mut archive = ZipArchive::new(Cursor::new(&bytes))?;
+
+        for (index, (version, crc32)) in [
+            (2, 0),
+            (1, crc32fast::hash(b"short")),
+            (1, crc32fast::hash(long)),
+            (2, 0),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            let metadata = archive.entry_metadata(index)?;
+            let header =
+                &bytes[metadata.local_header_offset as usize..metadata.data_start as usize];
+            // The local header's CRC-32 field
+            assert_eq!(header[14..18], crc32.to_le_bytes(), "{}", metadata.name);
+            // AES extra field: ID, size, version, vendor ID, strength and actual method
+            let mut expected = vec![0x01, 0x99, 0x07, 0x00, version, 0x00, b'A', b'E', 0x03];
+            expected.extend_from_slice(&0u16.to_le_bytes());
+            assert_eq!(header[header.len() - 11..], expected, "{}", metadata.name);
+
+            let mut file = archive.by_index_decrypt(index, b"password")?;
+            assert_eq!(file.crc32(), crc32, "{}", file.name());
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents)?;
+            assert!(contents == b"short" || contents == long);
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "aes-crypto")]
+    #[test]
+    fn aes_iterations() -> ZipResult<()> {
+        use crate::AesMode;
+
+        let options = SimpleFileOptions::default()
+            .compression_method(Stored)
+            .with_aes_encryption(AesMode::Aes128, "password");
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("default", options)?;
+        writer.write_all(b"standard")?;
+        writer.start_file("hardened", options.with_aes_iterations(5000))?;
+        writer.write_all(b"nonstandard")?;
+        assert!(matches!(
+            writer.start_file("zero", options.with_aes_iterations(0)),
+            Err(ZipError::InvalidArchive(_))
+        ));
+        let bytes = writer.finish()?.into_inner();
+        let mut archive = ZipArchive::new(Cursor::new(&bytes))?;
+
+        // Left unset, only the standard AES extra field is written, so other readers can open it
+        let metadata = archive.entry_metadata(0)?;
+        let header = &bytes[metadata.local_header_offset as usize..metadata.data_start as usize];
+        assert_eq!(header[28..30], 11u16.to_le_bytes());
+        let mut contents = Vec::new();
+        archive
+            .by_index_decrypt(0, b"password")?
+            .read_to_end(&mut contents)?;
+        assert_eq!(contents, b"standard");
+
+        let metadata = archive.entry_metadata(1)?;
+        let header = &bytes[metadata.local_header_offset as usize..metadata.data_start as usize];
+        let mut expected = vec![0x7a, 0x69, 0x04, 0x00];
+        expected.extend_from_slice(&5000u32.to_le_bytes());
+        assert_eq!(header[header.len() - 8..], expected);
+        contents.clear();
+        archive
+            .by_index_decrypt(1, b"password")?
+            .read_to_end(&mut contents)?;
+        assert_eq!(contents, b"nonstandard");
+
+        // Supplying the count overrides the recorded one
+        archive.set_aes_iterations(5000);
+        contents.clear();
+        archive
+            .by_index_decrypt(1, b"password")?
+            .read_to_end(&mut contents)?;
+        assert_eq!(contents, b"nonstandard");
+        archive.set_aes_iterations(1000);
+        assert!(archive
+            .by_index_decrypt(1, b"password")
+            .and_then(|mut file| Ok(file.read_to_end(&mut Vec::new())?))
+            .is_err());
+
+        // A recorded count that's too high isn't honoured unless the caller supplies it
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("slow", options.with_aes_iterations(1_000_001))?;
+        let mut archive = writer.finish_into_readable()?;
+        assert!(matches!(
+            archive.by_index_decrypt(0, b"password"),
+            Err(ZipError::UnsupportedArchive(_))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn abort_file_then_restart() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file(
+            "first",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(&[b'x'; 10000])?;
+        writer.abort_file()?;
+        assert!(!writer.is_writing_file());
+        writer.start_file(
+            "second",
+            SimpleFileOptions::default().compression_method(Stored),
+        )?;
+        writer.write_all(b"second")?;
+        let bytes = writer.finish()?.into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(&bytes))?;
+        assert_eq!(archive.file_names().collect::<Vec<_>>(), ["second"]);
+        assert_eq!(archive.entry_metadata(0)?.local_header_offset, 0);
+        let mut contents = String::new();
+        archive.by_name("second")?.read_to_string(&mut contents)?;
+        assert_eq!(contents, "second");
+        assert!(archive.by_name("first").is_err());
+        Ok(())
+    }
+
+    #[test]
+    #[cfg(feature = "deflate")]
+    fn method_by_size() -> ZipResult<()> {
+        use crate::CompressionMethod::Deflated;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_method_by_size(&[(100, Deflated), (10, Stored), (1000, Stored)]);
+        let options = SimpleFileOptions::default();
+        let mut expected = Vec::new();
+        for (size, method) in [
+            (0, Deflated),
+            (9, Deflated),
+            (10, Stored),
+            (11, Stored),
+            (99, Stored),
+            (100, Deflated),
+            (101, Deflated),
+            (999, Deflated),
+            (1000, Stored),
+            (1001, Stored),
+            (5000, Stored),
+        ] {
+            let name = format!("{size}.txt");
+            writer.start_file(name.as_str(), options.compression_level(Some(9)))?;
+            // Streamed in small pieces, so the method is chosen partway through the larger files
+            for chunk in vec![b'a'; size].chunks(7) {
+                writer.write_all(chunk)?;
+            }
+            expected.push((name, size, method));
+        }
+        writer.start_file("explicit.txt", options.compression_method(Deflated))?;
+        writer.write_all(&[b'a'; 5000])?;
+        expected.push(("explicit.txt".to_string(), 5000, Deflated));
+        writer.start_file(
+            "encrypted.txt",
+            options.with_deprecated_encryption(b"password"),
+        )?;
+        writer.write_all(&[b'a'; 500])?;
+        #[cfg(feature = "aes-crypto")]
+        {
+            let aes = options.with_aes_encryption(crate::AesMode::Aes256, "password");
+            writer.start_file("aes.txt", aes)?;
+            writer.write_all(&[b'a'; 50])?;
+        }
+        writer.start_file("aborted.txt", options)?;
+        writer.write_all(&[b'a'; 500])?;
+        writer.abort_file()?;
+        let bytes = writer.finish()?.into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(&bytes))?;
+        for (name, size, method) in expected {
+            let header = archive.entry_metadata(archive.index_for_name(&name).unwrap())?;
+            let offset = header.local_header_offset as usize;
+            let version = u16::from_le_bytes([bytes[offset + 4], bytes[offset + 5]]);
+            let local_method = u16::from_le_bytes([bytes[offset + 8], bytes[offset + 9]]);
+            let mut file = archive.by_name(&name)?;
+            assert_eq!(file.compression(), method, "{name}");
+            #[allow(deprecated)]
+            let method_id = method.to_u16();
+            assert_eq!(local_method, method_id, "{name}");
+            assert_eq!(version, if method == Stored { 10 } else { 20 }, "{name}");
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents)?;
+            assert_eq!(contents, vec![b'a'; size], "{name}");
+        }
+        let mut file = archive.by_name_decrypt("encrypted.txt", b"password")?;
+        assert_eq!(file.compression(), Deflated);
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        assert_eq!(contents, [b'a'; 500]);
+        drop(file);
+        #[cfg(feature = "aes-crypto")]
+        {
+            let mut file = archive.by_name_decrypt("aes.txt", b"password")?;
+            assert_eq!(file.compression(), Stored);
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents)?;
+            assert_eq!(contents, [b'a'; 50]);
+        }
+        assert!(archive.by_name("aborted.txt").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn abort() -> ZipResult<()> {
+        let options = SimpleFileOptions::default().compression_method(Stored);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("first", options)?;
+        writer.write_all(b"first")?;
+        writer.start_file("second", options)?;
+        writer.write_all(b"second")?;
+        let (inner, result) = writer.abort();
+        result?;
+        let bytes = inner.get_ref().clone();
+        assert_eq!(inner.position(), bytes.len() as u64);
+        assert!(bytes.ends_with(b"second"));
+        assert!(!bytes.windows(4).any(|window| window == b"PK\x05\x06"));
+        assert!(ZipArchive::new(inner).is_err());
+
+        let (inner, result) = ZipWriter::new(Cursor::new(Vec::new())).abort();
+        result?;
+        assert!(inner.into_inner().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn abort_returns_writer_when_finishing_fails() -> ZipResult<()> {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        /// Fails every write once `fail` is set
+        struct FailingWriter {
+            inner: Cursor<Vec<u8>>,
+            fail: Rc<Cell<bool>>,
+        }
+        impl Write for FailingWriter {
+            fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+                if self.fail.get() {
+                    return Err(io::Error::new(io::ErrorKind::Other, "disk full"));
+                }
+                self.inner.write(buf)
+            }
+            fn flush(&mut self) -> io::Result<()> {
+                self.inner.flush()
+            }
+        }
+        impl io::Seek for FailingWriter {
+            fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+                self.inner.seek(pos)
+            }
+        }
+
+        // The encryption holds back everything written until the file is finished
+        let options = [
+            SimpleFileOptions::default()
+                .compression_method(Stored)
+                .with_deprecated_encryption(b"password"),
+            #[cfg(feature = "deflate")]
+            SimpleFileOptions::default().compression_method(CompressionMethod::Deflated),
+        ];
+        for options in options {
+            let fail = Rc::new(Cell::new(false));
+            let mut writer = ZipWriter::new(FailingWriter {
+                inner: Cursor::new(Vec::new()),
+                fail: fail.clone(),
+            });
+            writer.start_file("lost.txt", options)?;
+            writer.write_all(b"never written")?;
+            fail.set(true);
+            let (inner, result) = writer.abort();
+            assert!(result.is_err());
+            let bytes = inner.inner.into_inner();
+            assert!(bytes.starts_with(b"PK\x03\x04"));
+            assert!(!bytes.windows(4).any(|window| window == b"PK\x05\x06"));
+
+            // Nor is the writer lost when finishing the file fails before the next one starts
+            let fail = Rc::new(Cell::new(false));
+            let mut writer = ZipWriter::new(FailingWriter {
+                inner: Cursor::new(Vec::new()),
+                fail: fail.clone(),
+            });
+            writer.start_file("lost.txt", options)?;
+            writer.write_all(b"never written")?;
+            fail.set(true);
+            assert!(writer.start_file("next.txt", options).is_err());
+            let (inner, result) = writer.abort();
+            assert!(result.is_err());
+            assert!(inner.inner.into_inner().starts_with(b"PK\x03\x04"));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn sync_data_on_finish_file() -> ZipResult<()> {
+        let dir = tempdir::TempDir::new("sync_data_on_finish_file")?;
+        let path = dir.path().join("archive.zip");
+        let mut writer = ZipWriter::new(std::fs::File::create(&path)?);
+        writer.set_flush_on_finish_file(true);
+        writer.set_sync_data_on_finish_file(true);
+        let options = SimpleFileOptions::default().compression_method(Stored);
+        for name in ["first", "second"] {
+            writer.start_file(name, options)?;
+            writer.write_all(RT_TEST_TEXT.as_bytes())?;
+        }
+        writer.finish()?;
+
+        let mut archive = ZipArchive::new(std::fs::File::open(&path)?)?;
+        for name in ["first", "second"] {
+            let mut contents = String::new();
+            archive.by_name(name)?.read_to_string(&mut contents)?;
+            assert_eq!(contents, RT_TEST_TEXT);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn unicode_path_extra_field() -> ZipResult<()> {
+        let options = SimpleFileOptions::default()
+            .compression_method(Stored)
+            .with_name_encoding(NameEncoding::Cp437)
+            .with_unicode_path_extra(true);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("naïve.txt", options)?;
+        writer.start_file("ascii.txt", options)?;
+        let mut bytes = writer.finish()?.into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes.clone()))?;
+        // Decoding the header's name as UTF-8 would fail, so the name must come from the field
+        archive.set_name_encoding(NameEncoding::Utf8)?;
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            ["naïve.txt", "ascii.txt"]
+        );
+        assert!(archive
+            .by_name("ascii.txt")?
+            .raw_extra_fields()
+            .all(|field| field.header_id() != 0x7075));
+
+        // A field whose CRC-32 doesn't match the name in the header is ignored
+        let field_starts: Vec<_> = bytes
+            .windows(5)
+            .enumerate()
+            .filter(|(_, window)| *window == [0x75, 0x70, 15, 0, 1])
+            .map(|(start, _)| start)
+            .collect();
+        assert_eq!(field_starts.len(), 2);
+        for start in field_starts {
+            bytes[start + 5] ^= 0xff;
+        }
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        archive.set_name_encoding(NameEncoding::Utf8)?;
+        assert_eq!(archive.name_for_index(0), Some("na\u{fffd}ve.txt"));
+        Ok(())
+    }
+
+    #[test]
+    fn unicode_comment_extra_field() -> ZipResult<()> {
+        let options = SimpleFileOptions::default()
+            .compression_method(Stored)
+            .with_name_encoding(NameEncoding::Cp437)
+            .with_unicode_comment_extra(true);
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.start_file("accented", options.with_comment("café"))?;
+        writer.start_file("plain", options.with_comment("cafe"))?;
+        let bytes = writer.finish()?.into_inner();
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        // Decoding the header's comment as UTF-8 would fail, so it must come from the field
+        archive.set_name_encoding(NameEncoding::Utf8)?;
+        let accented = archive.by_name("accented")?;
+        assert_eq!(accented.comment(), "café");
+        assert!(accented
+            .raw_extra_fields()
+            .any(|field| field.header_id() == 0x6375));
+        drop(accented);
+        assert!(archive.local_extra_fields(0)?.is_empty());
+        let plain = archive.by_name("plain")?;
+        assert_eq!(plain.comment(), "cafe");
+        assert!(plain
+            .raw_extra_fields()
+            .all(|field| field.header_id() != 0x6375));
+        Ok(())
+    }
+
+    /// Length of the local header's extra field and its 32-bit compressed and uncompressed sizes
+    fn local_zip64_fields(archive: &[u8]) -> (u16, u32, u32) {
+        (
+            u16::from_le_bytes([archive[28], archive[29]]),
+            u32::from_le_bytes(archive[18..22].try_into().unwrap()),
+            u32::from_le_bytes(archive[22..26].try_into().unwrap()),
+        )
+    }
+
+    #[test]
+    fn zip64_modes() -> ZipResult<()> {
+        for (mode, expected) in [
+            (Zip64Mode::Auto, (0, 5, 5)),
+            (Zip64Mode::Never, (0, 5, 5)),
+            (Zip64Mode::Always, (20, u32::MAX, u32::MAX)),
+        ] {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            let options = SimpleFileOptions::default()
+                .compression_method(Stored)
+                .with_zip64(mode);
+            writer.start_file("small.txt", options)?;
+            writer.write_all(b"small")?;
+            let archive = writer.finish()?.into_inner();
+            assert_eq!(local_zip64_fields(&archive), expected, "{mode:?}");
+
+            let mut archive = ZipArchive::new(Cursor::new(archive))?;
+            let mut contents = String::new();
+            archive.by_index(0)?.read_to_string(&mut contents)?;
+            assert_eq!(contents, "small", "{mode:?}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn zip64_never_rejects_large_entry() {
+        // 0xFFFFFFFF itself is the marker for a size in the ZIP64 field
+        for size in [crate::spec::ZIP64_BYTES_THR, 1 << 32] {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            let raw_values = ZipRawValues {
+                crc32: 0,
+                compressed_size: size,
+                uncompressed_size: size,
+                compression_flags: 0,
+            };
+            let options = SimpleFileOptions::default().with_zip64(Zip64Mode::Never);
+            assert!(matches!(
+                writer.start_entry("large.bin", options, Some(raw_values)),
+                Err(ZipError::InvalidArchive(_))
+            ));
+            let options = SimpleFileOptions::default().with_zip64(Zip64Mode::Auto);
+            writer
+                .start_entry("large.bin", options, Some(raw_values))
+                .unwrap();
+            assert!(writer.files.last().unwrap().1.large_file);
+        }
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let raw_values = ZipRawValues {
+            crc32: 0,
+            compressed_size: crate::spec::ZIP64_BYTES_THR - 1,
+            uncompressed_size: crate::spec::ZIP64_BYTES_THR - 1,
+            compression_flags: 0,
+        };
+        let options = SimpleFileOptions::default().with_zip64(Zip64Mode::Never);
+        writer
+            .start_entry("small.bin", options, Some(raw_values))
+            .unwrap();
+        assert!(!writer.files.last().unwrap().1.large_file);
+    }
+
+    #[test]
+    fn archive_comment_on_append() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_comment("first");
+        writer.start_file("a.txt", SimpleFileOptions::default())?;
+        let writer = ZipWriter::new_append(writer.finish()?)?;
+        assert_eq!(writer.get_raw_comment(), b"first");
+        let archive = ZipWriter::new_append(writer.finish()?)?.finish_into_readable()?;
+        assert_eq!(archive.comment(), b"first");
+        assert_eq!(archive.len(), 1);
+
+        let mut writer = ZipWriter::new_append(archive.into_inner())?;
+        writer.set_raw_comment(Box::new(*b"second"));
+        let archive = writer.finish_into_readable()?;
+        assert_eq!(archive.comment(), b"second");
+
+        let mut writer = ZipWriter::new_append(archive.into_inner())?;
+        writer.set_comment("");
+        let archive = writer.finish_into_readable()?;
+        assert_eq!(archive.comment(), b"");
+        let archive = ZipWriter::new_append(archive.into_inner())?.finish_into_readable()?;
+        assert_eq!(archive.comment(), b"");
+        Ok(())
+    }
+
+    #[test]
+    fn archive_comment_too_long() {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_raw_comment(vec![b'a'; u16::MAX as usize].into_boxed_slice());
+        let archive = writer.finish_into_readable().unwrap();
+        assert_eq!(archive.comment().len(), u16::MAX as usize);
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_raw_comment(vec![b'a'; u16::MAX as usize + 1].into_boxed_slice());
+        assert!(matches!(
+            writer.finish(),
+            Err(ZipError::InvalidArchive(
+                "Archive comment is longer than 65535 bytes"
+            ))
+        ));
+    }
+
+    #[test]
+    fn raw_copy_buffer_size() -> ZipResult<()> {
+        let contents = b"Raw copies go through a buffer. ".repeat(100);
+        let mut source = ZipWriter::new(Cursor::new(Vec::new()));
+        for name in ["a.txt", "b.txt"] {
+            source.start_file(name, SimpleFileOptions::default())?;
+            source.write_all(&contents)?;
+        }
+        let mut source = source.finish_into_readable()?;
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        assert!(writer.set_raw_copy_buffer_size(0).is_err());
+        writer.set_raw_copy_buffer_size(1)?;
+        writer.raw_copy_file(source.by_name("a.txt")?)?;
+        writer.set_raw_copy_buffer_size(3)?;
+        writer.merge_archive(source.clone())?;
+        let mut archive = writer.finish_into_readable()?;
+        assert_eq!(archive.len(), 2);
+        for name in ["a.txt", "b.txt"] {
+            let mut read_back = Vec::new();
+            archive.by_name(name)?.read_to_end(&mut read_back)?;
+            assert_eq!(read_back, contents, "{name}");
+        }
+        Ok(())
+    }
+
+    /// Text that compresses well, with words that repeat every 60 KB so that compressors with
+    /// bigger blocks or windows do noticeably better
+    #[cfg(any(feature = "bzip2", feature = "zstd"))]
+    fn compression_level_fixture() -> Vec<u8> {
+        const WORDS: [&str; 8] = [
+            "alpha ", "beta ", "gamma ", "delta ", "zip ", "level ", "x ", "!\n",
+        ];
+        let mut state = 1u32;
+        let chunk: Vec<u8> = (0..10_000)
+            .flat_map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                WORDS[(state >> 16) as usize % WORDS.len()].bytes()
+            })
+            .collect();
+        chunk.repeat(8)
+    }
+
+    #[cfg(any(feature = "bzip2", feature = "zstd"))]
+    fn compressed_size_at_level(method: CompressionMethod, level: i64, contents: &[u8]) -> u64 {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default()
+            .compression_method(method)
+            .compression_level(Some(level));
+        writer.start_file("fixture.txt", options).unwrap();
+        writer.write_all(contents).unwrap();
+        let mut archive = writer.finish_into_readable().unwrap();
+        let mut file = archive.by_index(0).unwrap();
+        let mut read_back = Vec::new();
+        file.read_to_end(&mut read_back).unwrap();
+        assert_eq!(read_back, contents);
+        file.compressed_size()
+    }
+
+    #[test]
+    #[cfg(feature = "bzip2")]
+    fn bzip2_compression_levels() {
+        let contents = compression_level_fixture();
+        let fast = compressed_size_at_level(CompressionMethod::Bzip2, 1, &contents);
+        let best = compressed_size_at_level(CompressionMethod::Bzip2, 9, &contents);
+        assert!(
+            best < fast,
+            "level 9 gave {best} bytes, level 1 gave {fast}"
+        );
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for level in [0, 10] {
+            let options = SimpleFileOptions::default()
+                .compression_method(CompressionMethod::Bzip2)
+                .compression_level(Some(level));
+            assert!(matches!(
+                writer.start_file("invalid.txt", options),
+                Err(ZipError::UnsupportedArchive(_))
+            ));
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn zstd_compression_levels() {
+        let contents = compression_level_fixture();
+        let fast = compressed_size_at_level(CompressionMethod::Zstd, 1, &contents);
+        let best = compressed_size_at_level(CompressionMethod::Zstd, 22, &contents);
+        assert!(
+            best < fast,
+            "level 22 gave {best} bytes, level 1 gave {fast}"
+        );
+
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        for level in [-8, 23] {
+            let options = SimpleFileOptions::default()
+                .compression_method(CompressionMethod::Zstd)
+                .compression_level(Some(level));
+            assert!(matches!(
+                writer.start_file("invalid.txt", options),
+                Err(ZipError::UnsupportedArchive(_))
+            ));
+        }
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn dedup() -> ZipResult<()> {
+        use super::DedupStats;
+
+        let shared = b"The same contents in several files. ".repeat(50);
+        let write_archive = |dedup: bool| -> ZipResult<(DedupStats, Vec<u8>)> {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            writer.set_dedup(dedup);
+            let options = SimpleFileOptions::default();
+            for (name, options, contents) in [
+                ("a.txt", options, &shared[..]),
+                ("c.txt", options.unix_permissions(0o600), &shared[..]),
+                ("empty1", options, b""),
+                ("empty2", options, b""),
+                ("d.txt", options.compression_method(Stored), &shared[..]),
+                ("b.txt", options, b"Something else"),
+            ] {
+                writer.start_file(name, options)?;
+                writer.write_all(contents)?;
+            }
+            Ok((writer.dedup_stats(), writer.finish()?.into_inner()))
+        };
+
+        let (stats, plain) = write_archive(false)?;
+        assert_eq!(stats, DedupStats::default());
+        let (stats, deduplicated) = write_archive(true)?;
+        assert_eq!(stats.files_deduplicated, 2);
+        // At least the stored duplicate's data and header
+        assert!(stats.bytes_saved > shared.len() as u64);
+        assert!(deduplicated.len() < plain.len());
+
+        let mut archive = ZipArchive::new(Cursor::new(deduplicated))?;
+        assert_eq!(archive.len(), 6);
+        let a_start = archive.by_name("a.txt")?.header_start();
+        for (name, contents) in [
+            ("a.txt", &shared[..]),
+            ("b.txt", b"Something else"),
+            ("c.txt", &shared[..]),
+            ("empty1", b""),
+            ("empty2", b""),
+            ("d.txt", &shared[..]),
+        ] {
+            let mut file = archive.by_name(name)?;
+            let mut read_back = Vec::new();
+            file.read_to_end(&mut read_back)?;
+            assert_eq!(read_back, contents, "{name}");
+            if contents == &shared[..] {
+                assert_eq!(file.header_start(), a_start, "{name}");
+            }
+        }
+        assert_eq!(archive.by_name("c.txt")?.unix_mode(), Some(0o100600));
+        assert_eq!(
+            archive.by_name("d.txt")?.compression(),
+            CompressionMethod::DEFLATE
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn lazy_append_matches_eager_append() -> ZipResult<()> {
+        let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+        writer.set_comment("comment");
+        writer.add_directory("dir/", SimpleFileOptions::default())?;
+        writer.start_file("dir/a.txt", SimpleFileOptions::default())?;
+        writer.write_all(b"first")?;
+        writer.start_file("big.bin", SimpleFileOptions::default().large_file(true))?;
+        writer.write_all(&[7; 1000])?;
+        let original = writer.finish()?.into_inner();
+
+        let append = |mut writer: ZipWriter<Cursor<Vec<u8>>>| -> ZipResult<Vec<u8>> {
+            writer.start_file("b.txt", SimpleFileOptions::default())?;
+            writer.write_all(b"second")?;
+            Ok(writer.finish()?.into_inner())
+        };
+        let eager = append(ZipWriter::new_append(Cursor::new(original.clone()))?)?;
+        let lazy = append(ZipWriter::new_append_lazy(Cursor::new(original.clone()))?)?;
+        assert_eq!(lazy, eager);
+
+        let mut archive = ZipArchive::new(Cursor::new(lazy))?;
+        assert_eq!(archive.comment(), b"comment");
+        assert_eq!(
+            archive.file_names().collect::<Vec<_>>(),
+            ["dir/", "dir/a.txt", "big.bin", "b.txt"]
+        );
+        let mut contents = String::new();
+        archive.by_name("b.txt")?.read_to_string(&mut contents)?;
+        assert_eq!(contents, "second");
+
+        // Nothing appended
+        let archive =
+            ZipWriter::new_append_lazy(Cursor::new(original.clone()))?.finish_into_readable()?;
+        assert_eq!(archive.len(), 3);
+        assert_eq!(archive.into_inner().into_inner(), original);
+
+        // Prepended data falls back to parsing the central directory
+        let mut prepended = b"#!/bin/sh\n".to_vec();
+        prepended.extend_from_slice(&original);
+        let eager = append(ZipWriter::new_append(Cursor::new(prepended.clone()))?)?;
+        let lazy = append(ZipWriter::new_append_lazy(Cursor::new(prepended))?)?;
+        assert_eq!(lazy, eager);
+        Ok(())
+    }
 }
@@ -0,0 +1,505 @@
+//! Types for writing ZIP archives asynchronously with tokio
+
+use super::{
+    check_archive_comment, normalize_options, sets_utf8_flag, write_central_directory_end,
+    write_central_directory_header, write_data_descriptor, write_local_zip64_extra_field,
+    EntryFields, FileOptionExtension, FileOptions, Zip64Mode,
+};
+use crate::compression::CompressionMethod;
+use crate::result::{ZipError, ZipResult};
+use crate::spec;
+use crate::types::ZipFileData;
+use crate::unstable::LittleEndianWriteExt;
+use crc32fast::Hasher;
+use indexmap::IndexMap;
+use std::io::Write;
+use std::sync::OnceLock;
+use tokio::io::AsyncWrite;
+
+#[cfg(any(
+    feature = "deflate",
+    feature = "deflate-zlib",
+    feature = "deflate-zlib-ng"
+))]
+use {
+    flate2::{write::DeflateEncoder, Compression},
+    std::mem,
+};
+
+/// Asynchronous ZIP archive generator
+///
+/// This mirrors [`ZipWriter`](crate::ZipWriter), but performs all I/O through tokio's
+/// [`AsyncWrite`]. Since it never seeks, each entry's CRC and sizes are written in a data
+/// descriptor after its contents, as if [`FileOptions::with_data_descriptor`] were always set.
+/// Only unencrypted entries using [`CompressionMethod::Stored`] or [`CompressionMethod::Deflated`]
+/// can be written, so [`AsyncZipWriter::start_file`] fails with [`ZipError::UnsupportedArchive`]
+/// for encryption, other methods and [`FileOptions::with_custom_compressor`], and
+/// [`FileOptions::with_alignment`] is ignored. Every other option is applied as
+/// [`ZipWriter::start_file`](crate::ZipWriter::start_file) applies it.
+///
+/// ```
+/// # async fn doit() -> zip::result::ZipResult<Vec<u8>> {
+/// use zip::write::{AsyncZipWriter, SimpleFileOptions};
+///
+/// let mut zip = AsyncZipWriter::new(Vec::new());
+/// zip.start_file("hello_world.txt", SimpleFileOptions::default())
+///     .await?;
+/// zip.write_all(b"Hello, World!").await?;
+/// zip.finish().await
+/// # }
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(doit()).unwrap();
+/// ```
+pub struct AsyncZipWriter<W> {
+    inner: W,
+    files: IndexMap<Box<str>, ZipFileData>,
+    current: Option<CurrentFile>,
+    bytes_written: u64,
+    comment: Box<[u8]>,
+}
+
+struct CurrentFile {
+    index: usize,
+    compressor: Compressor,
+    hasher: Hasher,
+    uncompressed_size: u64,
+    compressed_size: u64,
+}
+
+enum Compressor {
+    Stored,
+    #[cfg(any(
+        feature = "deflate",
+        feature = "deflate-zlib",
+        feature = "deflate-zlib-ng"
+    ))]
+    Deflated(DeflateEncoder<Vec<u8>>),
+}
+
+impl Compressor {
+    /// Flushes the compressor, returning any compressed bytes it was still holding.
+    fn finish(self) -> std::io::Result<Vec<u8>> {
+        match self {
+            Compressor::Stored => Ok(Vec::new()),
+            #[cfg(any(
+                feature = "deflate",
+                feature = "deflate-zlib",
+                feature = "deflate-zlib-ng"
+            ))]
+            Compressor::Deflated(encoder) => encoder.finish(),
+        }
+    }
+}
+
+impl<W: AsyncWrite + Unpin> AsyncZipWriter<W> {
+    /// Initializes the archive.
+    ///
+    /// Before writing to this object, the [`AsyncZipWriter::start_file`] function should be
+    /// called. After a successful write, the file remains open for writing. After a failed write,
+    /// call [`AsyncZipWriter::finish`] to write what's been accepted so far.
+    pub fn new(inner: W) -> AsyncZipWriter<W> {
+        AsyncZipWriter {
+            inner,
+            files: IndexMap::new(),
+            current: None,
+            bytes_written: 0,
+            comment: Box::new([]),
+        }
+    }
+
+    /// Set ZIP archive comment.
+    pub fn set_comment<S>(&mut self, comment: S)
+    where
+        S: Into<Box<str>>,
+    {
+        self.comment = comment.into().into_boxed_bytes();
+    }
+
+    /// Returns true if a file is currently open for writing.
+    pub const fn is_writing_file(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// Create a file in the archive and start writing its contents. The file must not have the
+    /// same name as a file already in the archive, or this fails with [`ZipError::DuplicateName`].
+    ///
+    /// The data should be written using [`AsyncZipWriter::write_all`].
+    pub async fn start_file<S, T: FileOptionExtension>(
+        &mut self,
+        name: S,
+        mut options: FileOptions<'_, T>,
+    ) -> ZipResult<()>
+    where
+        S: Into<Box<str>>,
+    {
+        self.finish_file().await?;
+        if options.encrypt_with.is_some() {
+            return Err(ZipError::UnsupportedArchive(
+                "AsyncZipWriter doesn't support encryption",
+            ));
+        }
+        if options.custom_compressor.is_some() {
+            return Err(ZipError::UnsupportedArchive(
+                "AsyncZipWriter doesn't support custom compressors",
+            ));
+        }
+        let compressor = match options.compression_method {
+            CompressionMethod::Stored => Compressor::Stored,
+            #[cfg(any(
+                feature = "deflate",
+                feature = "deflate-zlib",
+                feature = "deflate-zlib-ng"
+            ))]
+            CompressionMethod::Deflated => {
+                let level = options
+                    .compression_level
+                    .unwrap_or(Compression::default().level() as i64);
+                if !(0..=9).contains(&level) {
+                    return Err(ZipError::UnsupportedArchive(
+                        "Unsupported compression level",
+                    ));
+                }
+                Compressor::Deflated(DeflateEncoder::new(
+                    Vec::new(),
+                    Compression::new(level as u32),
+                ))
+            }
+            _ => {
+                return Err(ZipError::UnsupportedArchive(
+                    "AsyncZipWriter only supports the Stored and Deflated methods",
+                ))
+            }
+        };
+        normalize_options(&mut options);
+
+        let EntryFields {
+            name,
+            file_name_raw,
+            file_comment_raw,
+            local_only_extra_field,
+            central_extra_field,
+            extra_fields,
+        } = EntryFields::new(name.into(), &options)?;
+        if self.files.contains_key(&name) {
+            return Err(ZipError::DuplicateName(name));
+        }
+        let extra_field = options.extended_options.extra_data().cloned();
+        let file = ZipFileData {
+            system: options.system,
+            version_made_by: options.version_made_by,
+            encrypted: false,
+            using_data_descriptor: true,
+            is_utf8: options.name_encoding.is_utf8(),
+            compression_method: options.compression_method,
+            compression_flags: 0,
+            compression_level: options.compression_level,
+            last_modified_time: options.last_modified_time,
+            crc32: 0,
+            compressed_size: 0,
+            uncompressed_size: 0,
+            file_name_raw,
+            file_name: name.clone(),
+            extra_field,
+            central_extra_field,
+            file_comment: options.comment.into(),
+            file_comment_raw,
+            header_start: self.bytes_written,
+            extra_data_start: None,
+            data_start: OnceLock::new(),
+            central_header_start: 0,
+            external_attributes: options.external_attributes.unwrap_or(
+                options.permissions.unwrap() << 16
+                    | options.dos_attributes.to_external_attributes(),
+            ),
+            large_file: options.zip64 == Zip64Mode::Always,
+            aes_mode: None,
+            aes_extra_data_start: 0,
+            aes_iterations: None,
+            strong_encryption_flag: false,
+            min_version_needed: options.min_version,
+            extra_fields,
+        };
+
+        let mut header = Vec::new();
+        write_local_file_header(&mut header, &file, &local_only_extra_field)?;
+        file.data_start
+            .get_or_init(|| file.header_start + header.len() as u64);
+        self.write_raw(&header).await?;
+        let (index, _) = self.files.insert_full(name, file);
+        self.current = Some(CurrentFile {
+            index,
+            compressor,
+            hasher: Hasher::new(),
+            uncompressed_size: 0,
+            compressed_size: 0,
+        });
+        Ok(())
+    }
+
+    /// Write the whole of `buf` to the file currently being written.
+    pub async fn write_all(&mut self, buf: &[u8]) -> ZipResult<()> {
+        let Some(current) = self.current.as_mut() else {
+            return Err(ZipError::Io(std::io::Error::new(
+                std::io::ErrorKind::Other,
+                "No file has been started",
+            )));
+        };
+        current.hasher.update(buf);
+        current.uncompressed_size += buf.len() as u64;
+        match &mut current.compressor {
+            Compressor::Stored => {
+                current.compressed_size += buf.len() as u64;
+                self.write_raw(buf).await
+            }
+            #[cfg(any(
+                feature = "deflate",
+                feature = "deflate-zlib",
+                feature = "deflate-zlib-ng"
+            ))]
+            Compressor::Deflated(encoder) => {
+                encoder.write_all(buf)?;
+                let compressed = mem::take(encoder.get_mut());
+                current.compressed_size += compressed.len() as u64;
+                self.write_raw(&compressed).await
+            }
+        }
+    }
+
+    /// Finish the last file and write all other zip-structures, then flush and return the inner
+    /// writer.
+    pub async fn finish(mut self) -> ZipResult<W> {
+        self.finish_file().await?;
+        check_archive_comment(&self.comment)?;
+        let central_start = self.bytes_written;
+        let mut central_directory = Vec::new();
+        for file in self.files.values() {
+            write_central_directory_header(&mut central_directory, file)?;
+        }
+        let central_size = central_directory.len() as u64;
+        write_central_directory_end(
+            &mut central_directory,
+            self.files.len(),
+            central_start,
+            central_size,
+            &self.comment,
+        )?;
+        self.write_raw(&central_directory).await?;
+        tokio::io::AsyncWriteExt::flush(&mut self.inner).await?;
+        Ok(self.inner)
+    }
+
+    async fn finish_file(&mut self) -> ZipResult<()> {
+        let Some(current) = self.current.take() else {
+            return Ok(());
+        };
+        let trailer = current.compressor.finish()?;
+        self.write_raw(&trailer).await?;
+        let file = &mut self.files[current.index];
+        file.crc32 = current.hasher.finalize();
+        file.compressed_size = current.compressed_size + trailer.len() as u64;
+        file.uncompressed_size = current.uncompressed_size;
+        let mut descriptor = Vec::with_capacity(24);
+        write_data_descriptor(&mut descriptor, file)?;
+        self.write_raw(&descriptor).await
+    }
+
+    async fn write_raw(&mut self, buf: &[u8]) -> ZipResult<()> {
+        tokio::io::AsyncWriteExt::write_all(&mut self.inner, buf).await?;
+        self.bytes_written += buf.len() as u64;
+        Ok(())
+    }
+}
+
+/// Writes a local file header whose CRC and sizes are left for the data descriptor.
+fn write_local_file_header<T: Write>(
+    writer: &mut T,
+    file: &ZipFileData,
+    local_only_extra_field: &[u8],
+) -> ZipResult<()> {
+    // local file header signature
+    writer.write_u32_le(spec::LOCAL_FILE_HEADER_SIGNATURE)?;
+    // version needed to extract
+    writer.write_u16_le(file.version_needed())?;
+    // general purpose bit flag
+    let utf8_flag = sets_utf8_flag(file.is_utf8, &file.file_name_raw, &file.file_comment_raw);
+    let flag = if utf8_flag { 1u16 << 11 } else { 0 } | 1u16 << 3;
+    writer.write_u16_le(flag)?;
+    // Compression method
+    #[allow(deprecated)]
+    writer.write_u16_le(file.compression_method.to_u16())?;
+    // last mod file time and last mod file date
+    writer.write_u16_le(file.last_modified_time.timepart())?;
+    writer.write_u16_le(file.last_modified_time.datepart())?;
+    // crc-32
+    writer.write_u32_le(0)?;
+    // compressed size and uncompressed size
+    let size = if file.large_file {
+        spec::ZIP64_BYTES_THR as u32
+    } else {
+        0
+    };
+    writer.write_u32_le(size)?;
+    writer.write_u32_le(size)?;
+    // file name length
+    writer.write_u16_le(file.file_name_raw.len() as u16)?;
+    // extra field length
+    let mut extra_field_length = file.extra_field_len() + local_only_extra_field.len();
+    if file.large_file {
+        extra_field_length += 20;
+    }
+    if extra_field_length + file.central_extra_field_len() > u16::MAX as usize {
+        return Err(ZipError::InvalidArchive("Extra data field is too large"));
+    }
+    writer.write_u16_le(extra_field_length as u16)?;
+    // file name
+    writer.write_all(&file.file_name_raw)?;
+    // zip64 extra field
+    if file.large_file {
+        write_local_zip64_extra_field(writer, file)?;
+    }
+    if let Some(extra_field) = &file.extra_field {
+        writer.write_all(extra_field)?;
+    }
+    writer.write_all(local_only_extra_field)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::AsyncZipWriter;
+    use crate::compression::CompressionMethod;
+    use crate::result::{ZipError, ZipResult};
+    use crate::write::SimpleFileOptions;
+    use crate::ZipArchive;
+    use std::io::{Cursor, Read};
+
+    #[tokio::test]
+    async fn write_and_read_with_sync_archive() -> ZipResult<()> {
+        let methods = [
+            CompressionMethod::Stored,
+            #[cfg(any(
+                feature = "deflate",
+                feature = "deflate-zlib",
+                feature = "deflate-zlib-ng"
+            ))]
+            CompressionMethod::Deflated,
+        ];
+
+        let mut writer = AsyncZipWriter::new(Vec::new());
+        writer.set_comment("async");
+        for method in methods {
+            let options = SimpleFileOptions::default().compression_method(method);
+            writer.start_file(format!("{method}.txt"), options).await?;
+            for _ in 0..100 {
+                writer.write_all(b"Hello, World!\n").await?;
+            }
+        }
+        writer
+            .start_file("large.txt", SimpleFileOptions::default().large_file(true))
+            .await?;
+        writer.write_all(b"not really large").await?;
+        writer
+            .start_file(
+                "empty.txt",
+                SimpleFileOptions::default().with_comment("nothing here"),
+            )
+            .await?;
+        assert!(matches!(
+            writer
+                .start_file("empty.txt", SimpleFileOptions::default())
+                .await,
+            Err(ZipError::DuplicateName(_))
+        ));
+        let bytes = writer.finish().await?;
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        assert_eq!(archive.comment(), b"async");
+        assert_eq!(archive.len(), methods.len() + 2);
+        for method in methods {
+            let mut file = archive.by_name(&format!("{method}.txt"))?;
+            assert_eq!(file.compression(), method);
+            let mut contents = String::new();
+            file.read_to_string(&mut contents)?;
+            assert_eq!(contents, "Hello, World!\n".repeat(100));
+        }
+        let mut contents = String::new();
+        archive
+            .by_name("large.txt")?
+            .read_to_string(&mut contents)?;
+        assert_eq!(contents, "not really large");
+        let empty = archive.by_name("empty.txt")?;
+        assert_eq!(empty.size(), 0);
+        assert_eq!(empty.comment(), "nothing here");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn options_match_sync_writer() -> ZipResult<()> {
+        use crate::types::{DosAttributes, NameEncoding};
+
+        let dos_attributes = DosAttributes {
+            hidden: true,
+            ..DosAttributes::default()
+        };
+        let options = SimpleFileOptions::default()
+            .with_unix_ownership(1000, 100)
+            .with_dos_attributes(dos_attributes)
+            .with_name_encoding(NameEncoding::Cp437)
+            .with_unicode_path_extra(true)
+            .with_unicode_comment_extra(true)
+            .with_comment("café")
+            .min_version(45);
+        let mut writer = AsyncZipWriter::new(Vec::new());
+        writer.start_file("naïve.txt", options).await?;
+        writer.write_all(b"contents").await?;
+        let bytes = writer.finish().await?;
+        let mut sync_writer = crate::ZipWriter::new(Cursor::new(Vec::new()));
+        sync_writer.start_file("naïve.txt", options)?;
+        std::io::Write::write_all(&mut sync_writer, b"contents")?;
+        let sync_bytes = sync_writer.finish()?.into_inner();
+
+        // Version needed to extract
+        assert_eq!(bytes[4..6], [45, 0]);
+        for bytes in [bytes, sync_bytes] {
+            let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+            archive.set_name_encoding(NameEncoding::Utf8)?;
+            let file = archive.by_name("naïve.txt")?;
+            assert_eq!(file.unix_ownership(), Some((1000, 100)));
+            assert_eq!(file.dos_attributes(), dos_attributes);
+            assert_eq!(file.comment(), "café");
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn utf8_flag_covers_comment() -> ZipResult<()> {
+        let mut writer = AsyncZipWriter::new(Vec::new());
+        let options = SimpleFileOptions::default().with_comment("café");
+        writer.start_file("ascii.txt", options).await?;
+        let bytes = writer.finish().await?;
+
+        let central_start = bytes
+            .windows(4)
+            .position(|window| window == b"PK\x01\x02")
+            .unwrap();
+        let local_flags = u16::from_le_bytes([bytes[6], bytes[7]]);
+        let central_flags =
+            u16::from_le_bytes([bytes[central_start + 8], bytes[central_start + 9]]);
+        assert_ne!(local_flags & 1 << 11, 0);
+        assert_eq!(local_flags, central_flags);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unsupported_options() {
+        let mut writer = AsyncZipWriter::new(Vec::new());
+        assert!(writer
+            .start_file(
+                "encrypted.txt",
+                SimpleFileOptions::default().with_deprecated_encryption(b"password"),
+            )
+            .await
+            .is_err());
+        assert!(!writer.is_writing_file());
+        assert!(writer.write_all(b"no file").await.is_err());
+    }
+}
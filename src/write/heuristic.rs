@@ -0,0 +1,153 @@
+//! Choosing whether an entry is worth compressing
+
+use std::collections::HashSet;
+
+/// Extensions of formats that are already compressed, so compressing them again rarely helps
+const PRECOMPRESSED_EXTENSIONS: &[&str] = &[
+    "7z", "aac", "apk", "avi", "avif", "br", "bz2", "docx", "epub", "flac", "gif", "gz", "heic",
+    "jar", "jpeg", "jpg", "lz4", "lzma", "m4a", "m4v", "mkv", "mov", "mp3", "mp4", "odp", "ods",
+    "odt", "ogg", "opus", "png", "pptx", "rar", "tgz", "txz", "webm", "webp", "whl", "woff",
+    "woff2", "xlsx", "xz", "zip", "zst",
+];
+
+/// Decides which entries [`ZipWriter::start_file_auto`](crate::ZipWriter::start_file_auto)
+/// stores without compression
+///
+/// An entry is stored if its name ends in one of the configured extensions (compared without
+/// regard to ASCII case), such as `jpg`, `png`, `mp4` or `zip`. Otherwise, the first
+/// [`sample_size`](Self::sample_size) bytes written to it are buffered, and it's stored if their
+/// Shannon entropy is at least [`entropy_threshold`](Self::entropy_threshold) bits per byte, which
+/// is typical of compressed or encrypted data. Plain text is usually around 4 to 5 bits per byte.
+#[derive(Clone, Debug)]
+pub struct CompressionHeuristic {
+    sample_size: usize,
+    entropy_threshold: f64,
+    stored_extensions: HashSet<Box<str>>,
+}
+
+impl Default for CompressionHeuristic {
+    /// Sample 4 KiB, store data with at least 7.5 bits of entropy per byte, and store files with
+    /// the extensions of common compressed image, audio, video, document and archive formats
+    fn default() -> Self {
+        Self {
+            sample_size: 4096,
+            entropy_threshold: 7.5,
+            stored_extensions: PRECOMPRESSED_EXTENSIONS
+                .iter()
+                .map(|&extension| extension.into())
+                .collect(),
+        }
+    }
+}
+
+impl CompressionHeuristic {
+    /// Set how many bytes are buffered and sampled before choosing a compression method
+    ///
+    /// An entry that's shorter than this is sampled in full when it's finished.
+    pub fn sample_size(mut self, sample_size: usize) -> Self {
+        self.sample_size = sample_size;
+        self
+    }
+
+    /// Set the entropy, in bits per byte from 0.0 to 8.0, at or above which a sample is
+    /// considered incompressible
+    pub fn entropy_threshold(mut self, entropy_threshold: f64) -> Self {
+        self.entropy_threshold = entropy_threshold;
+        self
+    }
+
+    /// Store files whose names end in `.extension` without sampling them
+    pub fn add_stored_extension(mut self, extension: &str) -> Self {
+        self.stored_extensions
+            .insert(extension.to_ascii_lowercase().into());
+        self
+    }
+
+    /// Sample files whose names end in `.extension`, rather than always storing them
+    pub fn remove_stored_extension(mut self, extension: &str) -> Self {
+        self.stored_extensions
+            .remove(extension.to_ascii_lowercase().as_str());
+        self
+    }
+
+    /// The extensions of files that are stored without sampling, in lowercase and in no
+    /// particular order
+    pub fn stored_extensions(&self) -> impl Iterator<Item = &str> {
+        self.stored_extensions.iter().map(|extension| &**extension)
+    }
+
+    pub(crate) const fn get_sample_size(&self) -> usize {
+        self.sample_size
+    }
+
+    /// Whether `name` has one of the extensions that are always stored
+    pub(crate) fn has_stored_extension(&self, name: &str) -> bool {
+        let file_name = name.rsplit('/').next().unwrap_or(name);
+        match file_name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => self
+                .stored_extensions
+                .contains(extension.to_ascii_lowercase().as_str()),
+            _ => false,
+        }
+    }
+
+    /// Whether `sample` looks like it's already compressed
+    pub(crate) fn is_incompressible(&self, sample: &[u8]) -> bool {
+        !sample.is_empty() && entropy(sample) >= self.entropy_threshold
+    }
+}
+
+/// Shannon entropy of `data`, in bits per byte
+fn entropy(data: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for &byte in data {
+        counts[byte as usize] += 1;
+    }
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|&&count| count > 0)
+        .map(|&count| {
+            let p = count as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::CompressionHeuristic;
+
+    #[test]
+    fn extensions() {
+        let heuristic = CompressionHeuristic::default();
+        assert!(heuristic.has_stored_extension("photos/IMG_0001.JPG"));
+        assert!(heuristic.has_stored_extension("archive.tar.gz"));
+        assert!(!heuristic.has_stored_extension("notes.txt"));
+        assert!(!heuristic.has_stored_extension("zip"));
+        assert!(!heuristic.has_stored_extension(".png"));
+        assert!(!heuristic.has_stored_extension("png.d/readme"));
+
+        let heuristic = heuristic
+            .add_stored_extension("DAT")
+            .remove_stored_extension("png");
+        assert!(heuristic.has_stored_extension("blob.dat"));
+        assert!(!heuristic.has_stored_extension("image.png"));
+        assert!(heuristic
+            .stored_extensions()
+            .any(|extension| extension == "dat"));
+    }
+
+    #[test]
+    fn entropy() {
+        let heuristic = CompressionHeuristic::default();
+        let text = "The quick brown fox jumps over the lazy dog. ".repeat(100);
+        assert!(!heuristic.is_incompressible(text.as_bytes()));
+        let noise: Vec<u8> = (0..4096u32)
+            .map(|i| (i.wrapping_mul(2654435761) >> 13) as u8)
+            .collect();
+        assert!(heuristic.is_incompressible(&noise));
+        assert!(!heuristic.is_incompressible(&[]));
+        assert!(!heuristic.entropy_threshold(8.1).is_incompressible(&noise));
+    }
+}
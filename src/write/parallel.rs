@@ -0,0 +1,240 @@
+//! Compressing entries on several threads at once
+
+use super::{FileOptionExtension, FileOptions, ZipWriter};
+use crate::read::ZipArchive;
+use crate::result::{ZipError, ZipResult};
+use std::collections::{BTreeMap, HashSet};
+use std::io::{Cursor, Seek, Write};
+use std::num::NonZeroUsize;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+type Job = (usize, Box<dyn FnOnce() -> ZipResult<Vec<u8>> + Send>);
+
+/// Writes a ZIP archive whose entries are compressed by a pool of worker threads
+///
+/// Each entry passed to [`ParallelZipWriter::add_file`] is compressed, along with its CRC-32 and
+/// sizes, by whichever worker is free, into an archive of its own in memory. Those are copied into
+/// the output in the order the entries were added as soon as each one and all entries before it
+/// are ready, so the result is the same as writing every entry with [`ZipWriter::start_file`]
+/// and [`Write::write_all`] in turn. Since a whole entry's contents are passed at once and held
+/// until they're written, this suits many large entries that fit in memory.
+///
+/// ```
+/// # fn main() -> zip::result::ZipResult<()> {
+/// use zip::write::{ParallelZipWriter, SimpleFileOptions};
+///
+/// let mut zip = ParallelZipWriter::new(std::io::Cursor::new(Vec::new()));
+/// zip.add_file("a.txt", b"first".to_vec(), SimpleFileOptions::default())?;
+/// zip.add_file("b.txt", b"second".to_vec(), SimpleFileOptions::default())?;
+/// let archive = zip::ZipArchive::new(zip.finish()?)?;
+/// assert_eq!(archive.file_names().collect::<Vec<_>>(), ["a.txt", "b.txt"]);
+/// # Ok(())
+/// # }
+/// ```
+pub struct ParallelZipWriter<W: Write + Seek> {
+    inner: ZipWriter<W>,
+    jobs: Option<mpsc::Sender<Job>>,
+    results: mpsc::Receiver<(usize, ZipResult<Vec<u8>>)>,
+    workers: Vec<thread::JoinHandle<()>>,
+    names: HashSet<Box<str>>,
+    /// Compressed entries that finished before an entry added earlier
+    finished: BTreeMap<usize, ZipResult<Vec<u8>>>,
+    added: usize,
+    written: usize,
+}
+
+impl<W: Write + Seek> ParallelZipWriter<W> {
+    /// Initializes the archive, with one worker thread for each CPU that
+    /// [`std::thread::available_parallelism`] reports
+    pub fn new(inner: W) -> Self {
+        let threads = thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        Self::with_threads(inner, threads)
+    }
+
+    /// Initializes the archive with the given number of worker threads, or one if it's 0
+    pub fn with_threads(inner: W, threads: usize) -> Self {
+        let (jobs, job_receiver) = mpsc::channel::<Job>();
+        let (result_sender, results) = mpsc::channel();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let workers = (0..threads.max(1))
+            .map(|_| {
+                let job_receiver = Arc::clone(&job_receiver);
+                let result_sender = result_sender.clone();
+                thread::spawn(move || loop {
+                    // The lock is released before the job runs, so other workers can take jobs
+                    let job = job_receiver.lock().unwrap().recv();
+                    let Ok((index, compress)) = job else {
+                        return;
+                    };
+                    if result_sender.send((index, compress())).is_err() {
+                        return;
+                    }
+                })
+            })
+            .collect();
+        ParallelZipWriter {
+            inner: ZipWriter::new(inner),
+            jobs: Some(jobs),
+            results,
+            workers,
+            names: HashSet::new(),
+            finished: BTreeMap::new(),
+            added: 0,
+            written: 0,
+        }
+    }
+
+    /// Set the ZIP archive comment, as [`ZipWriter::set_comment`] does
+    pub fn set_comment<S>(&mut self, comment: S)
+    where
+        S: Into<Box<str>>,
+    {
+        self.inner.set_comment(comment);
+    }
+
+    /// Add a file with the given contents, which a worker thread compresses with `options`
+    ///
+    /// Returns [`ZipError::DuplicateName`] if a file with this name was already added. An error
+    /// while compressing is returned from a later call, or from [`ParallelZipWriter::finish`],
+    /// once the entries before it have been written.
+    pub fn add_file<S, E>(
+        &mut self,
+        name: S,
+        data: Vec<u8>,
+        options: FileOptions<'static, E>,
+    ) -> ZipResult<()>
+    where
+        S: Into<Box<str>>,
+        E: FileOptionExtension + Send + 'static,
+    {
+        let name = name.into();
+        if !self.names.insert(name.clone()) {
+            return Err(ZipError::DuplicateName(name));
+        }
+        let compress = move || {
+            let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+            writer.start_file(name, options)?;
+            writer.write_all(&data)?;
+            Ok(writer.finish()?.into_inner())
+        };
+        // A worker only stops once `jobs` is dropped, unless it panicked
+        self.jobs
+            .as_ref()
+            .unwrap()
+            .send((self.added, Box::new(compress)))
+            .map_err(|_| worker_stopped())?;
+        self.added += 1;
+        while let Ok((index, result)) = self.results.try_recv() {
+            self.finished.insert(index, result);
+        }
+        self.write_finished()
+    }
+
+    /// Wait for every file to be compressed, then write them and the central directory as
+    /// [`ZipWriter::finish`] does
+    pub fn finish(mut self) -> ZipResult<W> {
+        drop(self.jobs.take());
+        while self.written + self.finished.len() < self.added {
+            let (index, result) = self.results.recv().map_err(|_| worker_stopped())?;
+            self.finished.insert(index, result);
+        }
+        self.write_finished()?;
+        for worker in self.workers.drain(..) {
+            worker.join().map_err(|_| worker_stopped())?;
+        }
+        self.inner.finish()
+    }
+
+    /// Copy the compressed entries that are next in order into the output
+    fn write_finished(&mut self) -> ZipResult<()> {
+        while let Some(result) = self.finished.remove(&self.written) {
+            self.inner
+                .merge_archive(ZipArchive::new(Cursor::new(result?))?)?;
+            self.written += 1;
+        }
+        Ok(())
+    }
+}
+
+fn worker_stopped() -> ZipError {
+    ZipError::Io(std::io::Error::new(
+        std::io::ErrorKind::Other,
+        "A compression thread panicked",
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::ParallelZipWriter;
+    use crate::result::ZipError;
+    use crate::write::SimpleFileOptions;
+    use crate::CompressionMethod;
+    use std::io::Cursor;
+
+    #[test]
+    #[cfg(feature = "deflate")]
+    fn same_as_serial() {
+        use crate::{ZipArchive, ZipWriter};
+        use std::io::{Read, Write};
+
+        let files: Vec<(String, Vec<u8>, SimpleFileOptions)> = (0..24)
+            .map(|i| {
+                // The fastest level, since the default may mean Zopfli, and this is about order
+                let (method, level) = if i % 3 == 0 {
+                    (CompressionMethod::Stored, None)
+                } else {
+                    (CompressionMethod::Deflated, Some(1))
+                };
+                let options = SimpleFileOptions::default()
+                    .compression_method(method)
+                    .compression_level(level)
+                    .large_file(i % 5 == 0);
+                // Later files are smaller, so they tend to be done first
+                let data = format!("file {i} ").repeat(10_000 - i * 400).into_bytes();
+                (format!("{i}.txt"), data, options)
+            })
+            .collect();
+
+        let mut serial = ZipWriter::new(Cursor::new(Vec::new()));
+        serial.set_comment("comment");
+        let mut parallel = ParallelZipWriter::with_threads(Cursor::new(Vec::new()), 4);
+        parallel.set_comment("comment");
+        for (name, data, options) in &files {
+            serial.start_file(name.as_str(), *options).unwrap();
+            serial.write_all(data).unwrap();
+            parallel
+                .add_file(name.as_str(), data.clone(), *options)
+                .unwrap();
+        }
+        let serial = serial.finish().unwrap().into_inner();
+        let parallel = parallel.finish().unwrap().into_inner();
+        assert_eq!(parallel, serial);
+
+        let mut archive = ZipArchive::new(Cursor::new(parallel)).unwrap();
+        for (index, (name, data, _)) in files.iter().enumerate() {
+            let mut file = archive.by_index(index).unwrap();
+            assert_eq!(file.name(), name);
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents).unwrap();
+            assert_eq!(&contents, data);
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let mut writer = ParallelZipWriter::with_threads(Cursor::new(Vec::new()), 0);
+        writer
+            .add_file("a", Vec::new(), SimpleFileOptions::default())
+            .unwrap();
+        assert!(matches!(
+            writer.add_file("a", Vec::new(), SimpleFileOptions::default()),
+            Err(ZipError::DuplicateName(_))
+        ));
+        let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        writer
+            .add_file("b", Vec::new(), stored.compression_level(Some(1)))
+            .unwrap();
+        assert!(writer.finish().is_err());
+    }
+}
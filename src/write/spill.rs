@@ -0,0 +1,405 @@
+//! Writing an archive to a writer that can't seek, by holding back each entry until it's finished
+
+use super::ZipWriter;
+use crate::result::{ZipError, ZipResult};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// How many bytes of an entry [`SpillWriter`] keeps in memory, unless told otherwise
+const DEFAULT_SPILL_THRESHOLD: usize = 1 << 20;
+
+/// Distinguishes the temporary files of one process
+static SPILL_FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Lets a [`ZipWriter`] seek within the entry it's writing, on top of a writer that can't seek
+///
+/// Everything written is held back, in memory up to the spill threshold and then in a temporary
+/// file, until the entry is finished and its local header has been filled in; only then is it
+/// written to the inner writer. Seeking back to before that is an error. A `SpillWriter` is
+/// made by [`ZipWriter::new_buffered`], whose documentation has an example.
+///
+/// Whatever is still held back is written out when the `SpillWriter` is dropped, as well as by
+/// [`SpillWriter::into_inner`], which is the way to find out whether that succeeded.
+#[derive(Debug)]
+pub struct SpillWriter<W: Write> {
+    /// Only `None` once [`SpillWriter::into_inner`] has taken it
+    inner: Option<W>,
+    /// How many bytes have already been written to `inner`
+    committed: u64,
+    /// The bytes held back, while there are no more than `threshold` of them
+    buffer: Cursor<Vec<u8>>,
+    /// The bytes held back, once there were too many for `buffer`
+    spilled: Option<SpillFile>,
+    /// How many bytes are held back
+    len: u64,
+    /// The position within the held back bytes
+    position: u64,
+    threshold: usize,
+    /// Where temporary files are made, if not in [`std::env::temp_dir`]
+    temp_dir: Option<PathBuf>,
+}
+
+/// A temporary file, which is deleted when it's dropped
+#[derive(Debug)]
+struct SpillFile {
+    file: File,
+    path: PathBuf,
+}
+
+impl SpillFile {
+    fn create(dir: &Path) -> io::Result<Self> {
+        loop {
+            let count = SPILL_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+            let path = dir.join(format!("zip-spill-{}-{count}", process::id()));
+            let mut options = OpenOptions::new();
+            options.read(true).write(true).create_new(true);
+            // The temporary directory may be shared with other users
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.mode(0o600);
+            }
+            match options.open(&path) {
+                Ok(file) => return Ok(SpillFile { file, path }),
+                // Left behind by an earlier process with the same ID
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for SpillFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+impl<W: Write> SpillWriter<W> {
+    fn new(inner: W) -> Self {
+        SpillWriter {
+            inner: Some(inner),
+            committed: 0,
+            buffer: Cursor::new(Vec::new()),
+            spilled: None,
+            len: 0,
+            position: 0,
+            threshold: DEFAULT_SPILL_THRESHOLD,
+            temp_dir: None,
+        }
+    }
+
+    /// Write everything that's held back to the inner writer and flush it
+    fn commit(&mut self) -> io::Result<()> {
+        let Some(inner) = &mut self.inner else {
+            return Ok(());
+        };
+        match &mut self.spilled {
+            Some(spilled) => {
+                spilled.file.seek(SeekFrom::Start(0))?;
+                let copied = io::copy(&mut (&mut spilled.file).take(self.len), inner)?;
+                if copied < self.len {
+                    return Err(io::ErrorKind::UnexpectedEof.into());
+                }
+            }
+            None => inner.write_all(self.buffer.get_ref())?,
+        }
+        self.committed += self.len;
+        self.len = 0;
+        self.position = 0;
+        self.spilled = None;
+        self.buffer.get_mut().clear();
+        self.buffer.set_position(0);
+        inner.flush()
+    }
+
+    /// Move the held back bytes from memory to a temporary file
+    fn spill(&mut self) -> io::Result<()> {
+        let mut spilled = match &self.temp_dir {
+            Some(dir) => SpillFile::create(dir)?,
+            None => SpillFile::create(&std::env::temp_dir())?,
+        };
+        spilled.file.write_all(self.buffer.get_ref())?;
+        spilled.file.seek(SeekFrom::Start(self.position))?;
+        self.buffer = Cursor::new(Vec::new());
+        self.spilled = Some(spilled);
+        Ok(())
+    }
+
+    /// Get a reference to the inner writer
+    pub const fn get_ref(&self) -> &W {
+        match &self.inner {
+            Some(inner) => inner,
+            None => unreachable!(),
+        }
+    }
+
+    /// Write out whatever is still held back, and return the inner writer
+    ///
+    /// After [`ZipWriter::finish`], that's the central directory.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        self.commit()?;
+        Ok(self.inner.take().unwrap())
+    }
+}
+
+impl<W: Write> Drop for SpillWriter<W> {
+    fn drop(&mut self) {
+        if let Err(e) = self.commit() {
+            let _ = write!(io::stderr(), "SpillWriter drop failed: {:?}", e);
+        }
+    }
+}
+
+impl<W: Write> Write for SpillWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.spilled.is_none()
+            && self.position.saturating_add(buf.len() as u64) > self.threshold as u64
+        {
+            self.spill()?;
+        }
+        let count = match &mut self.spilled {
+            Some(spilled) => spilled.file.write(buf)?,
+            None => self.buffer.write(buf)?,
+        };
+        self.position += count as u64;
+        self.len = self.len.max(self.position);
+        Ok(count)
+    }
+
+    /// Flushes the inner writer, but doesn't write out what's held back
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.as_mut().unwrap().flush()
+    }
+}
+
+impl<W: Write> Seek for SpillWriter<W> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => (self.committed + self.len).checked_add_signed(offset),
+            SeekFrom::Current(offset) => {
+                (self.committed + self.position).checked_add_signed(offset)
+            }
+        };
+        let position = target
+            .and_then(|target| target.checked_sub(self.committed))
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "Can't seek back to data that was already written out",
+                )
+            })?;
+        match &mut self.spilled {
+            Some(spilled) => {
+                spilled.file.seek(SeekFrom::Start(position))?;
+            }
+            None => self.buffer.set_position(position),
+        }
+        self.position = position;
+        Ok(self.committed + position)
+    }
+}
+
+impl<W: Write> ZipWriter<SpillWriter<W>> {
+    /// Initializes an archive that's written to `inner`, which doesn't need to be able to seek
+    ///
+    /// Each entry is held back, in memory up to the threshold set with
+    /// [`ZipWriter::set_spill_threshold`] and then in a temporary file, until it's finished. Then
+    /// its local header, with the real CRC-32 and sizes, and its data are written to `inner`, which
+    /// is flushed. So no data descriptors are needed, which some readers don't support, and the
+    /// archive is still written as it goes, for instance to a network socket. [`ZipWriter::finish`]
+    /// returns the [`SpillWriter`], whose [`SpillWriter::into_inner`] writes the central directory.
+    /// Like a [`ZipWriter`], which finishes the archive when it's dropped, the [`SpillWriter`]
+    /// writes out what it holds when it's dropped, but errors are only reported by `into_inner`.
+    ///
+    /// As with [`ZipWriter::set_flush_on_finish_file`], [`ZipWriter::abort_file`] can only remove
+    /// the entry being written.
+    ///
+    /// ```
+    /// # fn main() -> zip::result::ZipResult<()> {
+    /// use std::io::Write;
+    /// use zip::write::SimpleFileOptions;
+    ///
+    /// // A `Vec<u8>` can't seek
+    /// let mut zip = zip::ZipWriter::new_buffered(Vec::new());
+    /// zip.start_file("hello.txt", SimpleFileOptions::default())?;
+    /// zip.write_all(b"Hello, World!")?;
+    /// let bytes = zip.finish()?.into_inner()?;
+    ///
+    /// let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes))?;
+    /// assert!(!archive.by_index(0)?.has_data_descriptor());
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn new_buffered(inner: W) -> Self {
+        let mut writer = ZipWriter::new(SpillWriter::new(inner));
+        writer.flush_on_finish_file = true;
+        writer.on_finish_file = Some(SpillWriter::commit);
+        writer
+    }
+
+    /// Set how many bytes of an entry are kept in memory before the rest of it is moved to a
+    /// temporary file. The default is 1 MiB.
+    ///
+    /// This finishes the file being written, if any, and applies to the files after it.
+    pub fn set_spill_threshold(&mut self, threshold: usize) -> ZipResult<()> {
+        self.spill_writer()?.threshold = threshold;
+        Ok(())
+    }
+
+    /// Set the directory that temporary files are made in, instead of [`std::env::temp_dir`],
+    /// such as when that's read-only in a sandbox
+    ///
+    /// Each temporary file is deleted once its entry has been written out, or when the writer is
+    /// dropped, whether or not [`ZipWriter::finish`] was called. Like
+    /// [`ZipWriter::set_spill_threshold`], this finishes the file being written, if any, and
+    /// applies to the files after it.
+    pub fn set_temp_dir(&mut self, dir: PathBuf) -> ZipResult<()> {
+        self.spill_writer()?.temp_dir = Some(dir);
+        Ok(())
+    }
+
+    /// Finish the file being written, if any, to get to the [`SpillWriter`] underneath
+    fn spill_writer(&mut self) -> ZipResult<&mut SpillWriter<W>> {
+        self.finish_file()?;
+        if self.inner.is_closed() {
+            return Err(ZipError::Io(io::Error::new(
+                io::ErrorKind::BrokenPipe,
+                "ZipWriter was already closed",
+            )));
+        }
+        Ok(self.inner.get_plain())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::SpillWriter;
+    use crate::result::ZipResult;
+    use crate::write::SimpleFileOptions;
+    use crate::{CompressionMethod, ZipArchive, ZipWriter};
+    use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+
+    #[test]
+    fn same_as_seekable() -> ZipResult<()> {
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let small = b"small".to_vec();
+        let large: Vec<u8> = (0..10_000u32).flat_map(u32::to_le_bytes).collect();
+
+        let mut seekable = ZipWriter::new(Cursor::new(Vec::new()));
+        let mut buffered = ZipWriter::new_buffered(Vec::new());
+        buffered.set_spill_threshold(1000)?;
+        for (name, data) in [("small", &small), ("large", &large), ("small2", &small)] {
+            seekable.start_file(name, options)?;
+            seekable.write_all(data)?;
+            buffered.start_file(name, options)?;
+            buffered.write_all(data)?;
+        }
+        seekable.add_directory("dir/", options)?;
+        buffered.add_directory("dir/", options)?;
+        let expected = seekable.finish()?.into_inner();
+        let spill = buffered.finish()?;
+        // Everything but the directory and the central directory has been written out
+        assert_eq!(spill.get_ref()[..], expected[..spill.get_ref().len()]);
+        assert!(spill.get_ref().len() > large.len());
+        let bytes = spill.into_inner()?;
+        assert_eq!(bytes, expected);
+
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        let mut file = archive.by_name("large")?;
+        assert!(!file.has_data_descriptor());
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        assert_eq!(contents, large);
+        Ok(())
+    }
+
+    #[test]
+    fn seek_before_written_out() {
+        let mut writer = SpillWriter::new(Vec::new());
+        writer.threshold = 4;
+        writer.write_all(b"abcdef").unwrap();
+        assert!(writer.spilled.is_some());
+        writer.seek(SeekFrom::Start(1)).unwrap();
+        writer.write_all(b"B").unwrap();
+        assert_eq!(writer.seek(SeekFrom::End(0)).unwrap(), 6);
+        writer.commit().unwrap();
+        assert!(writer.spilled.is_none());
+        assert_eq!(writer.get_ref(), b"aBcdef");
+
+        assert!(writer.seek(SeekFrom::Start(5)).is_err());
+        assert!(writer.seek(SeekFrom::Current(-1)).is_err());
+        writer.write_all(b"g").unwrap();
+        assert_eq!(writer.stream_position().unwrap(), 7);
+        assert_eq!(writer.into_inner().unwrap(), b"aBcdefg");
+    }
+
+    #[test]
+    fn custom_temp_dir() -> ZipResult<()> {
+        let dir = tempdir::TempDir::new("spill")?;
+        let files = || std::fs::read_dir(dir.path()).unwrap().count();
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+        let mut writer = ZipWriter::new_buffered(Vec::new());
+        writer.set_spill_threshold(1000)?;
+        writer.set_temp_dir(dir.path().to_path_buf())?;
+        writer.start_file("spilled", options)?;
+        writer.write_all(&[1; 2000])?;
+        assert_eq!(files(), 1);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let spilled = std::fs::read_dir(dir.path()).unwrap().next().unwrap()?;
+            assert_eq!(spilled.metadata()?.permissions().mode() & 0o777, 0o600);
+        }
+        writer.start_file("next", options)?;
+        assert_eq!(files(), 0);
+        writer.write_all(&[2; 2000])?;
+        assert_eq!(files(), 1);
+        let spill = writer.finish()?;
+        assert_eq!(files(), 0);
+        let bytes = spill.into_inner()?;
+        let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+        let mut contents = Vec::new();
+        archive.by_name("next")?.read_to_end(&mut contents)?;
+        assert_eq!(contents, [2; 2000]);
+
+        // Nothing is left behind by a writer that's dropped without being finished
+        let mut writer = ZipWriter::new_buffered(io::sink());
+        writer.set_spill_threshold(1000)?;
+        writer.set_temp_dir(dir.path().to_path_buf())?;
+        writer.start_file("spilled", options)?;
+        writer.write_all(&[1; 2000])?;
+        assert_eq!(files(), 1);
+        drop(writer);
+        assert_eq!(files(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn written_out_on_drop() -> ZipResult<()> {
+        let options = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+        let mut dropped = Vec::new();
+        let mut writer = ZipWriter::new_buffered(&mut dropped);
+        writer.start_file("dropped", options)?;
+        writer.write_all(b"contents")?;
+        drop(writer);
+        let mut finished = Vec::new();
+        let mut writer = ZipWriter::new_buffered(&mut finished);
+        writer.start_file("finished", options)?;
+        writer.write_all(b"contents")?;
+        drop(writer.finish()?);
+
+        for (bytes, name) in [(dropped, "dropped"), (finished, "finished")] {
+            let mut archive = ZipArchive::new(Cursor::new(bytes))?;
+            let mut contents = String::new();
+            archive.by_name(name)?.read_to_string(&mut contents)?;
+            assert_eq!(contents, "contents");
+        }
+        Ok(())
+    }
+}
@@ -95,40 +95,30 @@ pub enum ZipCryptoValidator {
 }
 
 impl<R: std::io::Read> ZipCryptoReader<R> {
+    /// Read the ZipCrypto header bytes and validate each of `passwords` against them, keeping the
+    /// first that passes, or fail with [`ZipError::InvalidPassword`] if none does.
+    ///
     /// Note: The password is `&[u8]` and not `&str` because the
     /// [zip specification](https://pkware.cachefly.net/webdocs/APPNOTE/APPNOTE-6.3.3.TXT)
     /// does not specify password encoding (see function `update_keys` in the specification).
     /// Therefore, if `&str` was used, the password would be UTF-8 and it
     /// would be impossible to decrypt files that were encrypted with a
     /// password byte sequence that is unrepresentable in UTF-8.
-    pub fn new(file: R, password: &[u8]) -> ZipCryptoReader<R> {
-        ZipCryptoReader {
-            file,
-            keys: ZipCryptoKeys::derive(password),
-        }
-    }
-
-    /// Read the ZipCrypto header bytes and validate the password.
     pub fn validate(
-        mut self,
+        mut file: R,
+        passwords: &[&[u8]],
         validator: ZipCryptoValidator,
     ) -> Result<ZipCryptoReaderValid<R>, ZipError> {
         // ZipCrypto prefixes a file with a 12 byte header
-        let mut header_buf = [0u8; 12];
-        self.file.read_exact(&mut header_buf)?;
-        for byte in header_buf.iter_mut() {
-            *byte = self.keys.decrypt_byte(*byte);
-        }
+        let mut encrypted_header = [0u8; 12];
+        file.read_exact(&mut encrypted_header)?;
 
-        match validator {
+        let check_byte = match validator {
             ZipCryptoValidator::PkzipCrc32(crc32_plaintext) => {
                 // PKZIP before 2.0 used 2 byte CRC check.
                 // PKZIP 2.0+ used 1 byte CRC check. It's more secure.
                 // We also use 1 byte CRC.
-
-                if (crc32_plaintext >> 24) as u8 != header_buf[11] {
-                    return Err(ZipError::InvalidPassword);
-                }
+                (crc32_plaintext >> 24) as u8
             }
             ZipCryptoValidator::InfoZipMsdosTime(last_mod_time) => {
                 // Info-ZIP modification to ZipCrypto format:
@@ -137,14 +127,23 @@ impl<R: std::io::Read> ZipCryptoReader<R> {
                 // it uses high byte of 16-bit File Time.
                 // Info-ZIP code probably writes 2 bytes of File Time.
                 // We check only 1 byte.
+                (last_mod_time >> 8) as u8
+            }
+        };
 
-                if (last_mod_time >> 8) as u8 != header_buf[11] {
-                    return Err(ZipError::InvalidPassword);
-                }
+        for password in passwords {
+            let mut keys = ZipCryptoKeys::derive(password);
+            let mut header_buf = encrypted_header;
+            for byte in header_buf.iter_mut() {
+                *byte = keys.decrypt_byte(*byte);
+            }
+            if header_buf[11] == check_byte {
+                return Ok(ZipCryptoReaderValid {
+                    reader: ZipCryptoReader { file, keys },
+                });
             }
         }
-
-        Ok(ZipCryptoReaderValid { reader: self })
+        Err(ZipError::InvalidPassword)
     }
 }
 #[allow(unused)]
@@ -155,14 +154,15 @@ pub(crate) struct ZipCryptoWriter<W> {
 }
 impl<W: std::io::Write> ZipCryptoWriter<W> {
     #[allow(unused)]
-    pub(crate) fn finish(mut self, crc32: u32) -> std::io::Result<W> {
+    /// Encrypts and writes everything buffered. The writer is kept rather than returned, so that
+    /// it isn't lost if this fails.
+    pub(crate) fn finish(&mut self, crc32: u32) -> std::io::Result<()> {
         self.buffer[11] = (crc32 >> 24) as u8;
         for byte in self.buffer.iter_mut() {
             *byte = self.keys.encrypt_byte(*byte);
         }
         self.writer.write_all(&self.buffer)?;
-        self.writer.flush()?;
-        Ok(self.writer)
+        self.writer.flush()
     }
 }
 impl<W: std::io::Write> std::io::Write for ZipCryptoWriter<W> {
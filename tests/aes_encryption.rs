@@ -122,15 +122,15 @@ fn test_extract_encrypted_file<R: io::Read + io::Seek>(
     incorrect_password: &str,
 ) {
     {
-        let file = archive.by_name(file_name).map(|_| ());
-        match file {
-            Err(ZipError::UnsupportedArchive("Password required to decrypt file")) => {}
-            Err(err) => {
-                panic!("Failed to read file for unknown reason: {err:?}");
-            }
-            Ok(_) => {
-                panic!("Was able to successfully read encrypted file without password");
-            }
+        let mut file = archive
+            .by_name(file_name)
+            .expect("couldn't open encrypted file without password");
+        let err = file
+            .read_to_end(&mut Vec::new())
+            .expect_err("Was able to successfully read encrypted file without password");
+        match err.into_inner().map(|err| err.downcast::<ZipError>()) {
+            Some(Ok(err)) if matches!(*err, ZipError::PasswordRequired) => {}
+            err => panic!("Failed to read file for unknown reason: {err:?}"),
         }
     }
 
@@ -0,0 +1,142 @@
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use zip::result::ZipError;
+use zip::unstable::write::FileOptionsExt;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, CompressionStream, Compressor, Decompressor, ZipArchive, ZipWriter};
+
+/// Run-length encoding as pairs of a count and a byte, which buffers a run between writes
+#[derive(Default)]
+struct RunLength {
+    started: AtomicUsize,
+}
+
+struct RunLengthStream {
+    run: Option<(u8, u8)>,
+}
+
+impl Compressor for RunLength {
+    fn start(&self, level: Option<i64>) -> io::Result<Box<dyn CompressionStream>> {
+        if level.is_some() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "no levels"));
+        }
+        self.started.fetch_add(1, Ordering::Relaxed);
+        Ok(Box::new(RunLengthStream { run: None }))
+    }
+}
+
+impl CompressionStream for RunLengthStream {
+    fn compress(&mut self, input: &[u8], output: &mut dyn Write) -> io::Result<()> {
+        for &byte in input {
+            self.run = match self.run {
+                Some((count, run_byte)) if run_byte == byte && count < u8::MAX => {
+                    Some((count + 1, byte))
+                }
+                Some((count, run_byte)) => {
+                    output.write_all(&[count, run_byte])?;
+                    Some((1, byte))
+                }
+                None => Some((1, byte)),
+            };
+        }
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>, output: &mut dyn Write) -> io::Result<()> {
+        match self.run {
+            Some((count, byte)) => output.write_all(&[count, byte]),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Decompressor for RunLength {
+    fn decompress<'a>(&self, mut compressed: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+        let mut pairs = Vec::new();
+        compressed.read_to_end(&mut pairs)?;
+        let data: Vec<u8> = pairs
+            .chunks(2)
+            .flat_map(|pair| std::iter::repeat(pair[1]).take(pair[0] as usize))
+            .collect();
+        Ok(Box::new(io::Cursor::new(data)))
+    }
+}
+
+/// Decompresses to the wrong data, to check that the CRC-32 is still verified
+struct Garbage;
+
+impl Decompressor for Garbage {
+    fn decompress<'a>(&self, _compressed: Box<dyn Read + 'a>) -> io::Result<Box<dyn Read + 'a>> {
+        Ok(Box::new(io::Cursor::new(b"garbage")))
+    }
+}
+
+const METHOD: u16 = 0xBEEF;
+
+#[test]
+fn custom_compression_round_trip() -> zip::result::ZipResult<()> {
+    let compressor = RunLength::default();
+    let options = SimpleFileOptions::default().with_custom_compressor(METHOD, &compressor);
+    let data: Vec<u8> = [b'a'; 300]
+        .into_iter()
+        .chain(*b"bcd")
+        .chain([b'e'; 10])
+        .collect();
+
+    let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+    writer.start_file("runs.txt", options)?;
+    // Runs carry on across writes
+    for chunk in data.chunks(7) {
+        writer.write_all(chunk)?;
+    }
+    writer.start_file(
+        "encrypted.txt",
+        options.with_deprecated_encryption(b"password"),
+    )?;
+    writer.write_all(&data)?;
+    writer.start_file("empty.txt", options)?;
+    writer.start_file(
+        "stored.txt",
+        options.compression_method(CompressionMethod::Stored),
+    )?;
+    writer.write_all(&data)?;
+    assert!(writer
+        .start_file("level.txt", options.compression_level(Some(1)))
+        .is_err());
+    let bytes = writer.finish()?.into_inner();
+    assert_eq!(compressor.started.load(Ordering::Relaxed), 3);
+
+    let mut archive = ZipArchive::new(io::Cursor::new(bytes))?;
+    assert!(matches!(
+        archive.by_name("runs.txt"),
+        Err(ZipError::UnsupportedArchive(_))
+    ));
+    archive.register_decompressor(METHOD, Garbage);
+    let mut contents = Vec::new();
+    assert!(archive
+        .by_name("runs.txt")?
+        .read_to_end(&mut contents)
+        .is_err());
+
+    archive.register_decompressor(METHOD, RunLength::default());
+    let file = archive.by_name("runs.txt")?;
+    // "a" is in two runs, since a count fits in a byte
+    assert_eq!(file.compressed_size(), 12);
+    assert_eq!(file.size(), data.len() as u64);
+    drop(file);
+    for (name, expected) in [
+        ("runs.txt", &data[..]),
+        ("empty.txt", &[]),
+        ("stored.txt", &data[..]),
+    ] {
+        let mut contents = Vec::new();
+        archive.by_name(name)?.read_to_end(&mut contents)?;
+        assert_eq!(contents, expected, "{name}");
+    }
+    let mut contents = Vec::new();
+    archive
+        .by_name_decrypt("encrypted.txt", b"password")?
+        .read_to_end(&mut contents)?;
+    assert_eq!(contents, data);
+    Ok(())
+}
@@ -1,7 +1,7 @@
 #![cfg(feature = "deflate64")]
 
 use std::io::{self, Read};
-use zip::ZipArchive;
+use zip::{CompressionMethod, ZipArchive};
 
 #[test]
 fn decompress_deflate64() {
@@ -13,9 +13,16 @@ fn decompress_deflate64() {
         .by_name("binary.wmv")
         .expect("couldn't find file in archive");
     assert_eq!("binary.wmv", file.name());
+    assert_eq!(file.compression(), CompressionMethod::Deflate64);
 
     let mut content = Vec::new();
     file.read_to_end(&mut content)
         .expect("couldn't read encrypted and compressed file");
     assert_eq!(include_bytes!("data/folder/binary.wmv"), &content[..]);
+    assert_eq!(crc32fast::hash(&content), file.crc32());
+    drop(file);
+
+    archive
+        .verify()
+        .expect("Deflate64 entries should pass their CRC check");
 }
@@ -0,0 +1,74 @@
+#![cfg(any(
+    feature = "deflate",
+    feature = "deflate-zlib",
+    feature = "deflate-zlib-ng"
+))]
+
+use std::io::{Cursor, Read, Write};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+// Both fixtures hold `lines.txt`, with the contents below, compressed at level 9: one by zlib
+// (through Python's zipfile module) and one by this crate with the miniz_oxide backend. Whichever
+// backend this build uses must be able to read both.
+const FIXTURES: [(&str, &[u8]); 2] = [
+    ("zlib", include_bytes!("data/deflate_zlib.zip")),
+    (
+        "miniz_oxide",
+        include_bytes!("data/deflate_miniz_oxide.zip"),
+    ),
+];
+
+fn lines() -> Vec<u8> {
+    (0..2000u64)
+        .flat_map(|i| format!("line {i}: {}\n", i * i % 9973).into_bytes())
+        .collect()
+}
+
+#[test]
+fn read_archives_from_other_backends() {
+    for (backend, fixture) in FIXTURES {
+        let mut archive = ZipArchive::new(Cursor::new(fixture)).unwrap();
+        let mut file = archive.by_name("lines.txt").unwrap();
+        assert_eq!(file.compression(), CompressionMethod::Deflated, "{backend}");
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents).unwrap();
+        assert_eq!(contents, lines(), "{backend}");
+    }
+}
+
+#[test]
+fn round_trip_with_current_backend() {
+    let contents = lines();
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for level in [1, 6, 9] {
+        writer
+            .start_file(
+                format!("level{level}.txt"),
+                SimpleFileOptions::default()
+                    .compression_method(CompressionMethod::Deflated)
+                    .compression_level(Some(level)),
+            )
+            .unwrap();
+        writer.write_all(&contents).unwrap();
+    }
+    // Mix in entries compressed by the other backends without recompressing them
+    for (backend, fixture) in FIXTURES {
+        let mut source = ZipArchive::new(Cursor::new(fixture)).unwrap();
+        writer
+            .raw_copy_file_rename(
+                source.by_name("lines.txt").unwrap(),
+                format!("{backend}.txt"),
+            )
+            .unwrap();
+    }
+    let mut archive = writer.finish_into_readable().unwrap();
+    assert_eq!(archive.len(), 5);
+    archive.verify().unwrap();
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).unwrap();
+        let mut read = Vec::new();
+        file.read_to_end(&mut read).unwrap();
+        assert_eq!(read, contents, "{}", file.name());
+    }
+}
@@ -20,5 +20,13 @@ const BUF: &[u8] = &[
 
 #[test]
 fn invalid_date() {
-    let _archive = ZipArchive::new(Cursor::new(BUF)).unwrap();
+    let mut archive = ZipArchive::new(Cursor::new(BUF)).unwrap();
+    let file = archive.by_index(0).unwrap();
+    let modified = file.last_modified();
+    assert!(!modified.is_valid());
+    assert_eq!(
+        (modified.year(), modified.month(), modified.day()),
+        (1980, 0, 0)
+    );
+    assert_eq!(modified.datepart(), 0);
 }
@@ -0,0 +1,94 @@
+#![cfg(feature = "legacy")]
+
+use std::io::{self, Read};
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+fn lorem() -> Vec<u8> {
+    (0..2000)
+        .flat_map(|i| format!("{i:05}: the quick brown fox jumps over the lazy dog\n").into_bytes())
+        .collect()
+}
+
+fn runs() -> Vec<u8> {
+    (1..200)
+        .zip(b"abcab".iter().cycle())
+        .flat_map(|(count, &byte)| std::iter::repeat(byte).take(count))
+        .collect()
+}
+
+fn read_all(archive: &mut ZipArchive<io::Cursor<Vec<u8>>>, name: &str) -> Vec<u8> {
+    let mut content = Vec::new();
+    archive
+        .by_name(name)
+        .expect("couldn't find file in archive")
+        .read_to_end(&mut content)
+        .expect("couldn't decompress file");
+    content
+}
+
+#[test]
+fn decompress_shrink() {
+    let v = include_bytes!("data/shrink.zip").to_vec();
+    let mut archive = ZipArchive::new(io::Cursor::new(v)).expect("couldn't open test zip file");
+    for i in 0..archive.len() {
+        assert_eq!(
+            archive.by_index(i).unwrap().compression(),
+            CompressionMethod::Shrink
+        );
+    }
+    assert_eq!(
+        read_all(&mut archive, "hello.txt"),
+        b"Hello from 1995!\n".repeat(3)
+    );
+    assert_eq!(read_all(&mut archive, "lorem.txt"), lorem());
+    assert_eq!(read_all(&mut archive, "runs.txt"), runs());
+    // Reading to the end checks the CRC-32
+    assert_eq!(read_all(&mut archive, "noise.bin").len(), 24000);
+}
+
+#[test]
+fn decompress_implode() {
+    let v = include_bytes!("data/implode.zip").to_vec();
+    let mut archive = ZipArchive::new(io::Cursor::new(v)).expect("couldn't open test zip file");
+    assert_eq!(
+        read_all(&mut archive, "hello.txt"),
+        b"Hello from 1995!\n".repeat(3)
+    );
+    // 4 or 8 KiB windows, each with and without a literal tree
+    for name in [
+        "lorem.txt",
+        "lorem_4k.txt",
+        "lorem_8k_2.txt",
+        "lorem_4k_3.txt",
+    ] {
+        let file = archive.by_name(name).unwrap();
+        assert_eq!(file.compression(), CompressionMethod::Implode);
+        drop(file);
+        assert_eq!(read_all(&mut archive, name), lorem(), "{name}");
+    }
+    assert_eq!(read_all(&mut archive, "runs.txt"), runs());
+}
+
+#[test]
+fn raw_copy_keeps_implode_flags() {
+    let v = include_bytes!("data/implode.zip").to_vec();
+    let mut source = ZipArchive::new(io::Cursor::new(v)).unwrap();
+    let mut writer = ZipWriter::new(io::Cursor::new(Vec::new()));
+    for i in 0..source.len() {
+        writer.raw_copy_file(source.by_index(i).unwrap()).unwrap();
+    }
+    let mut copy = writer.finish_into_readable().unwrap();
+    for name in [
+        "lorem.txt",
+        "lorem_4k.txt",
+        "lorem_8k_2.txt",
+        "lorem_4k_3.txt",
+    ] {
+        let mut content = Vec::new();
+        copy.by_name(name)
+            .unwrap()
+            .read_to_end(&mut content)
+            .unwrap();
+        assert_eq!(content, lorem(), "{name}");
+    }
+}
@@ -1,7 +1,7 @@
 #![cfg(feature = "lzma")]
 
 use std::io::{self, Read};
-use zip::ZipArchive;
+use zip::{CompressionMethod, ZipArchive};
 
 #[test]
 fn decompress_lzma() {
@@ -19,3 +19,31 @@ fn decompress_lzma() {
         .expect("couldn't read encrypted and compressed file");
     assert_eq!("Hello world\n", String::from_utf8(content).unwrap());
 }
+
+#[test]
+fn decompress_lzma_method_14() {
+    let mut v = Vec::new();
+    v.extend_from_slice(include_bytes!("data/lzma_method14.zip"));
+    let mut archive = ZipArchive::new(io::Cursor::new(v)).expect("couldn't open test zip file");
+
+    let mut file = archive
+        .by_name("hello.txt")
+        .expect("couldn't find file in archive");
+    assert_eq!(file.compression(), CompressionMethod::Lzma);
+    let mut content = String::new();
+    file.read_to_string(&mut content)
+        .expect("couldn't read LZMA-compressed file");
+    assert_eq!(content, "Hello world\n");
+    drop(file);
+
+    let mut content = Vec::new();
+    archive
+        .by_name("lorem.txt")
+        .expect("couldn't find file in archive")
+        .read_to_end(&mut content)
+        .expect("couldn't read LZMA-compressed file");
+    let expected: Vec<u8> = (0..2000)
+        .flat_map(|i| format!("{i:05}: the quick brown fox jumps over the lazy dog\n").into_bytes())
+        .collect();
+    assert_eq!(content, expected);
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "ppmd")]
+
+use std::io::{self, Read};
+use zip::{CompressionMethod, ZipArchive};
+
+#[test]
+fn decompress_ppmd() {
+    let v = include_bytes!("data/ppmd.zip").to_vec();
+    let mut archive = ZipArchive::new(io::Cursor::new(v)).expect("couldn't open test zip file");
+
+    let mut file = archive
+        .by_name("hello.txt")
+        .expect("couldn't find file in archive");
+    assert_eq!(file.compression(), CompressionMethod::Ppmd);
+    let mut content = String::new();
+    file.read_to_string(&mut content)
+        .expect("couldn't read PPMd-compressed file");
+    assert_eq!(content, "Hello from WinRAR!\n".repeat(3));
+    drop(file);
+
+    let mut content = Vec::new();
+    archive
+        .by_name("lorem.txt")
+        .expect("couldn't find file in archive")
+        .read_to_end(&mut content)
+        .expect("couldn't read PPMd-compressed file");
+    let expected: Vec<u8> = (0..2000)
+        .flat_map(|i| format!("{i:05}: the quick brown fox jumps over the lazy dog\n").into_bytes())
+        .collect();
+    assert_eq!(content, expected);
+
+    // These fill the model's memory, which is restored by restarting or cutting it off. Reading
+    // to the end checks the CRC-32.
+    let mut restarted = Vec::new();
+    archive
+        .by_name("words_restart.txt")
+        .unwrap()
+        .read_to_end(&mut restarted)
+        .expect("couldn't read PPMd-compressed file");
+    let mut cut_off = Vec::new();
+    archive
+        .by_name("words_cut_off.txt")
+        .unwrap()
+        .read_to_end(&mut cut_off)
+        .expect("couldn't read PPMd-compressed file");
+    assert_eq!(restarted.len(), 183774);
+    assert_eq!(restarted, cut_off);
+}
+
+#[test]
+fn ppmd_bad_crc() {
+    let mut v = include_bytes!("data/ppmd.zip").to_vec();
+    // Flip a bit in the middle of lorem.txt's compressed data
+    let lorem = v.windows(9).position(|w| w == b"lorem.txt").unwrap();
+    v[lorem + 9 + 1000] ^= 0x10;
+    let mut archive = ZipArchive::new(io::Cursor::new(v)).unwrap();
+    let mut content = Vec::new();
+    assert!(archive
+        .by_name("lorem.txt")
+        .unwrap()
+        .read_to_end(&mut content)
+        .is_err());
+}
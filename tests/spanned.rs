@@ -0,0 +1,73 @@
+#![cfg(any(
+    feature = "deflate",
+    feature = "deflate-zlib",
+    feature = "deflate-zlib-ng"
+))]
+
+use std::fs::File;
+use std::io::Read;
+use zip::read::SpannedReader;
+use zip::ZipArchive;
+
+// Created with Info-ZIP's `zip -s 64k -X split.zip random.bin hello.txt more.bin`, where the two
+// .bin files are 100000 and 40000 bytes of random data. random.bin spans all three segments and
+// more.bin starts on the second.
+const SEGMENTS: [&str; 3] = [
+    "tests/data/split/split.z01",
+    "tests/data/split/split.z02",
+    "tests/data/split/split.zip",
+];
+
+fn check_archive(archive: &mut ZipArchive<SpannedReader<File>>) {
+    assert_eq!(
+        archive.file_names().collect::<Vec<_>>(),
+        ["random.bin", "hello.txt", "more.bin"]
+    );
+    archive
+        .verify()
+        .expect("every entry should pass its CRC check");
+
+    let mut contents = String::new();
+    archive
+        .by_name("hello.txt")
+        .unwrap()
+        .read_to_string(&mut contents)
+        .unwrap();
+    assert_eq!(contents, "Hello from a split archive!\n".repeat(100));
+
+    for (name, size) in [("random.bin", 100000), ("more.bin", 40000)] {
+        let mut data = Vec::new();
+        archive
+            .by_name(name)
+            .unwrap()
+            .read_to_end(&mut data)
+            .unwrap();
+        assert_eq!(data.len(), size, "{name}");
+    }
+}
+
+#[test]
+fn open_split_archive() {
+    let reader = SpannedReader::open(SEGMENTS[2]).unwrap();
+    let mut archive = ZipArchive::new_spanned(reader).unwrap();
+    check_archive(&mut archive);
+}
+
+#[test]
+fn split_archive_from_segments() {
+    let segments = SEGMENTS
+        .iter()
+        .map(|path| File::open(path).unwrap())
+        .collect();
+    let mut archive = ZipArchive::new_spanned(SpannedReader::new(segments).unwrap()).unwrap();
+    check_archive(&mut archive);
+}
+
+#[test]
+fn split_archive_missing_segment() {
+    let segments = SEGMENTS[1..]
+        .iter()
+        .map(|path| File::open(path).unwrap())
+        .collect();
+    assert!(ZipArchive::new_spanned(SpannedReader::new(segments).unwrap()).is_err());
+}
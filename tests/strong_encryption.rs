@@ -0,0 +1,91 @@
+use std::io::{self, Read};
+use zip::extra_fields::{ExtraField, StrongEncryptionAlgorithm};
+use zip::read::read_zipfile_from_stream;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+#[test]
+fn strong_encryption_is_reported() {
+    let v = include_bytes!("data/strong_encryption.zip").to_vec();
+    let mut archive = ZipArchive::new(io::Cursor::new(v)).expect("couldn't open test zip file");
+
+    let mut content = String::new();
+    archive
+        .by_name("readme.txt")
+        .expect("couldn't find file in archive")
+        .read_to_string(&mut content)
+        .expect("couldn't read unencrypted file");
+    assert_eq!(content, "This file isn't encrypted.\n");
+
+    for (name, algorithm) in [
+        ("secret.txt", StrongEncryptionAlgorithm::Aes256),
+        ("flag_only.txt", StrongEncryptionAlgorithm::Unknown(0xFFFF)),
+    ] {
+        match archive.by_name(name) {
+            Err(ZipError::UnsupportedEncryption(found)) => assert_eq!(found, algorithm, "{name}"),
+            Err(e) => panic!("unexpected error for {name}: {e}"),
+            Ok(_) => panic!("{name} shouldn't be readable"),
+        }
+        assert!(matches!(
+            archive.by_name_decrypt(name, b"password"),
+            Err(ZipError::UnsupportedEncryption(found)) if found == algorithm
+        ));
+    }
+
+    // The header is still there to look at, and the data can be copied as it is
+    let mut file = archive.by_index_raw(1).unwrap();
+    let header = file
+        .extra_data_fields()
+        .find_map(|field| match field {
+            ExtraField::StrongEncryption(header) => Some(header.to_owned()),
+            _ => None,
+        })
+        .expect("no Strong Encryption Header");
+    assert_eq!(header.algorithm(), StrongEncryptionAlgorithm::Aes256);
+    assert_eq!(header.bit_length(), 256);
+    assert_eq!(header.flags(), 1);
+    let mut raw = Vec::new();
+    file.read_to_end(&mut raw).unwrap();
+    assert_eq!(raw.len(), 64);
+
+    let error = ZipError::UnsupportedEncryption(StrongEncryptionAlgorithm::Aes256);
+    assert_eq!(
+        error.to_string(),
+        "unsupported encryption: PKWARE strong encryption with AES-256"
+    );
+}
+
+#[test]
+fn strong_encryption_in_stream() {
+    let mut stream = io::Cursor::new(include_bytes!("data/strong_encryption.zip"));
+    let mut content = String::new();
+    read_zipfile_from_stream(&mut stream)
+        .unwrap()
+        .unwrap()
+        .read_to_string(&mut content)
+        .unwrap();
+    assert_eq!(content, "This file isn't encrypted.\n");
+    assert!(matches!(
+        read_zipfile_from_stream(&mut stream),
+        Err(ZipError::UnsupportedEncryption(
+            StrongEncryptionAlgorithm::Aes256
+        ))
+    ));
+}
+
+#[test]
+fn encrypted_central_directory() {
+    let v = include_bytes!("data/encrypted_directory.zip").to_vec();
+    assert!(matches!(
+        ZipArchive::new(io::Cursor::new(v.clone())),
+        Err(ZipError::UnsupportedEncryption(
+            StrongEncryptionAlgorithm::Aes192
+        ))
+    ));
+    assert!(matches!(
+        ZipArchive::new_lazy(io::Cursor::new(v)),
+        Err(ZipError::UnsupportedEncryption(
+            StrongEncryptionAlgorithm::Aes192
+        ))
+    ));
+}
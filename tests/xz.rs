@@ -0,0 +1,61 @@
+#![cfg(feature = "xz")]
+
+use std::io::{self, Read};
+use zip::{CompressionMethod, ZipArchive};
+
+#[test]
+fn decompress_xz() {
+    let v = include_bytes!("data/xz.zip").to_vec();
+    let mut archive = ZipArchive::new(io::Cursor::new(v)).expect("couldn't open test zip file");
+
+    let mut file = archive
+        .by_name("hello.txt")
+        .expect("couldn't find file in archive");
+    assert_eq!(file.compression(), CompressionMethod::Xz);
+    let mut content = String::new();
+    file.read_to_string(&mut content)
+        .expect("couldn't read XZ-compressed file");
+    assert_eq!(content, "Hello from xz!\n".repeat(3));
+    drop(file);
+
+    let expected: Vec<u8> = (0..2000)
+        .flat_map(|i| format!("{i:05}: the quick brown fox jumps over the lazy dog\n").into_bytes())
+        .collect();
+    for name in ["lorem.txt", "lorem_no_check.txt"] {
+        let mut content = Vec::new();
+        archive
+            .by_name(name)
+            .expect("couldn't find file in archive")
+            .read_to_end(&mut content)
+            .expect("couldn't read XZ-compressed file");
+        assert_eq!(content, expected);
+    }
+}
+
+#[test]
+fn xz_wrong_uncompressed_size() {
+    let mut v = include_bytes!("data/xz.zip").to_vec();
+    // Claim that hello.txt is a byte shorter than it is, in both the local and central headers
+    let mut patched = 0;
+    for at in 0..v.len() - 4 {
+        let header_len = match &v[at..at + 4] {
+            [0x50, 0x4b, 0x03, 0x04] => 30,
+            [0x50, 0x4b, 0x01, 0x02] => 46,
+            _ => continue,
+        };
+        if v.get(at + header_len..at + header_len + 9) == Some(b"hello.txt") {
+            let size_at = if header_len == 30 { at + 22 } else { at + 24 };
+            v[size_at] -= 1;
+            patched += 1;
+        }
+    }
+    assert_eq!(patched, 2);
+    let mut archive = ZipArchive::new(io::Cursor::new(v)).unwrap();
+    let mut content = Vec::new();
+    let error = archive
+        .by_name("hello.txt")
+        .unwrap()
+        .read_to_end(&mut content)
+        .unwrap_err();
+    assert!(error.to_string().contains("uncompressed size"), "{error}");
+}
@@ -65,17 +65,21 @@ fn encrypted_file() {
     assert_eq!(archive.len(), 1); //Only one file inside archive: `test.txt`
 
     {
-        // No password
-        let file = archive.by_index(0);
-        match file {
-            Err(zip::result::ZipError::UnsupportedArchive(
-                zip::result::ZipError::PASSWORD_REQUIRED,
-            )) => (),
-            Err(_) => panic!(
-                "Expected PasswordRequired error when opening encrypted file without password"
-            ),
-            Ok(_) => panic!("Error: Successfully opened encrypted file without password?!"),
-        }
+        // No password: the metadata is there, but reading the data fails
+        assert_eq!(archive.file_names().collect::<Vec<_>>(), ["test.txt"]);
+        assert!(archive.entry_metadata(0).unwrap().encrypted);
+        let mut file = archive.by_index(0).unwrap();
+        assert_eq!(file.name(), "test.txt");
+        assert_eq!(file.size(), 35);
+        let error = file.read_to_end(&mut Vec::new()).unwrap_err();
+        assert!(matches!(
+            error
+                .into_inner()
+                .unwrap()
+                .downcast::<ZipError>()
+                .as_deref(),
+            Ok(ZipError::PasswordRequired)
+        ));
     }
 
     {
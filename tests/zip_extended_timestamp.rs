@@ -14,6 +14,61 @@ fn test_extended_timestamp() {
                 assert!(ts.cr_time().is_none());
                 assert_eq!(*ts.mod_time().unwrap(), 1714635025);
             }
+            zip::ExtraField::UnixOwnership(ownership) => {
+                assert_eq!((ownership.uid(), ownership.gid()), (1000, 1000));
+            }
+            other => panic!("unexpected extra field {other:?}"),
+        }
+    }
+}
+
+#[test]
+fn test_write_extended_timestamp() {
+    use std::io::Write;
+    use std::time::{Duration, UNIX_EPOCH};
+    use zip::write::SimpleFileOptions;
+
+    let mut writer = zip::ZipWriter::new(io::Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().with_extended_timestamp(
+        1714635025,
+        Some(1714635026),
+        Some(1714635027),
+    );
+    writer.start_file("test.txt", options).unwrap();
+    writer.write_all(b"hello").unwrap();
+    let options = SimpleFileOptions::default()
+        .last_modified_time(zip::DateTime::from_date_and_time(2020, 1, 2, 3, 4, 6).unwrap());
+    writer.start_file("no_timestamp.txt", options).unwrap();
+    let bytes = writer.finish().unwrap().into_inner();
+
+    // The local header holds all three timestamps; the central directory only the mtime.
+    let local = [
+        0x55, 0x54, 13, 0, 0b111, 0x11, 0x41, 0x33, 0x66, 0x12, 0x41, 0x33, 0x66, 0x13, 0x41, 0x33,
+        0x66,
+    ];
+    let central = [0x55, 0x54, 5, 0, 0b111, 0x11, 0x41, 0x33, 0x66];
+    assert!(bytes.windows(local.len()).any(|w| w == local));
+    assert!(bytes.windows(central.len()).any(|w| w == central));
+
+    let mut archive = ZipArchive::new(io::Cursor::new(bytes)).unwrap();
+    let file = archive.by_name("test.txt").unwrap();
+    assert_eq!(
+        file.last_modified_precise(),
+        Some(UNIX_EPOCH + Duration::from_secs(1714635025))
+    );
+    for field in file.extra_data_fields() {
+        match field {
+            zip::ExtraField::ExtendedTimestamp(ts) => {
+                assert_eq!(*ts.mod_time().unwrap(), 1714635025);
+                assert!(ts.ac_time().is_none());
+            }
+            other => panic!("unexpected extra field {other:?}"),
         }
     }
+    drop(file);
+    let file = archive.by_name("no_timestamp.txt").unwrap();
+    assert_eq!(
+        file.last_modified_precise(),
+        Some(UNIX_EPOCH + Duration::from_secs(1577934246))
+    );
 }